@@ -29,7 +29,7 @@ pub mod informant;
 use client::ExecutionStrategies;
 use service::{
 	config::Configuration,
-	ServiceBuilderExport, ServiceBuilderImport, ServiceBuilderRevert,
+	ServiceBuilderExport, ServiceBuilderImport, ServiceBuilderRevert, ServiceBuilderState,
 	RuntimeGenesis, PruningMode, ChainSpec,
 };
 use network::{
@@ -37,6 +37,7 @@ use network::{
 	config::{NetworkConfiguration, TransportConfig, NonReservedPeerMode, NodeKeyConfig, build_multiaddr},
 };
 use primitives::H256;
+use sr_primitives::traits::Block as BlockT;
 
 use std::{
 	io::{Write, Read, Seek, Cursor, stdin, stdout, ErrorKind}, iter, fs::{self, File},
@@ -49,7 +50,8 @@ use structopt::{StructOpt, clap::AppSettings};
 #[doc(hidden)]
 pub use structopt::clap::App;
 use params::{
-	RunCmd, PurgeChainCmd, RevertCmd, ImportBlocksCmd, ExportBlocksCmd, BuildSpecCmd,
+	RunCmd, PurgeChainCmd, RevertCmd, ImportBlocksCmd, ExportBlocksCmd, ImportStateCmd,
+	ExportStateCmd, CheckDbCmd, BuildSpecCmd,
 	NetworkConfigurationParams, MergeParameters, TransactionPoolParams,
 	NodeKeyParams, NodeKeyType, Cors,
 };
@@ -225,6 +227,15 @@ where
 		params::CoreParams::ImportBlocks(params) => ParseAndPrepare::ImportBlocks(
 			ParseAndPrepareImport { params, version }
 		),
+		params::CoreParams::ExportState(params) => ParseAndPrepare::ExportState(
+			ParseAndPrepareExportState { params, version }
+		),
+		params::CoreParams::ImportState(params) => ParseAndPrepare::ImportState(
+			ParseAndPrepareImportState { params, version }
+		),
+		params::CoreParams::CheckDb(params) => ParseAndPrepare::CheckDb(
+			ParseAndPrepareCheckDb { params, version }
+		),
 		params::CoreParams::PurgeChain(params) => ParseAndPrepare::PurgeChain(
 			ParseAndPreparePurge { params, version }
 		),
@@ -246,6 +257,12 @@ pub enum ParseAndPrepare<'a, CC, RP> {
 	ExportBlocks(ParseAndPrepareExport<'a>),
 	/// Command ready to import the chain.
 	ImportBlocks(ParseAndPrepareImport<'a>),
+	/// Command ready to export the state at a finalized block.
+	ExportState(ParseAndPrepareExportState<'a>),
+	/// Command ready to initialize the database from a state snapshot.
+	ImportState(ParseAndPrepareImportState<'a>),
+	/// Command ready to check the integrity of the database.
+	CheckDb(ParseAndPrepareCheckDb<'a>),
 	/// Command ready to purge the chain.
 	PurgeChain(ParseAndPreparePurge<'a>),
 	/// Command ready to revert the chain.
@@ -374,6 +391,8 @@ impl<'a> ParseAndPrepareImport<'a> {
 			other: self.params.execution.into(),
 			..Default::default()
 		};
+		config.wasm_method = self.params.wasm_method.into_service().map_err(error::Error::Input)?;
+		enable_wasm_profiling(self.params.wasm_profile.as_ref())?;
 
 		let file: Box<dyn ReadPlusSeek> = match self.params.input {
 			Some(filename) => Box::new(File::open(filename)?),
@@ -390,6 +409,100 @@ impl<'a> ParseAndPrepareImport<'a> {
 	}
 }
 
+/// Command ready to export the state at a finalized block.
+pub struct ParseAndPrepareExportState<'a> {
+	params: ExportStateCmd,
+	version: &'a VersionInfo,
+}
+
+impl<'a> ParseAndPrepareExportState<'a> {
+	/// Runs the command and exports the state.
+	pub fn run_with_builder<C, G, F, B, S>(
+		self,
+		builder: F,
+		spec_factory: S,
+	) -> error::Result<()>
+	where S: FnOnce(&str) -> Result<Option<ChainSpec<G>>, String>,
+		F: FnOnce(Configuration<C, G>) -> Result<B, error::Error>,
+		B: ServiceBuilderState,
+		C: Default,
+		G: RuntimeGenesis,
+	{
+		let config = create_config_with_db_path(spec_factory, &self.params.shared_params, self.version)?;
+
+		info!("DB path: {}", config.database_path.display());
+		let file: Box<dyn Write> = match self.params.output {
+			Some(filename) => Box::new(File::create(filename)?),
+			None => Box::new(stdout()),
+		};
+
+		builder(config)?.export_state(file, self.params.block.map(Into::into))?;
+		Ok(())
+	}
+}
+
+/// Command ready to initialize the database from a state snapshot.
+pub struct ParseAndPrepareImportState<'a> {
+	params: ImportStateCmd,
+	version: &'a VersionInfo,
+}
+
+impl<'a> ParseAndPrepareImportState<'a> {
+	/// Runs the command and imports the state snapshot into an empty database.
+	pub fn run_with_builder<C, G, F, B, S>(
+		self,
+		builder: F,
+		spec_factory: S,
+	) -> error::Result<()>
+	where S: FnOnce(&str) -> Result<Option<ChainSpec<G>>, String>,
+		F: FnOnce(Configuration<C, G>) -> Result<B, error::Error>,
+		B: ServiceBuilderState,
+		C: Default,
+		G: RuntimeGenesis,
+	{
+		let mut config = create_config_with_db_path(spec_factory, &self.params.shared_params, self.version)?;
+		config.pruning = pruning_mode(self.params.pruning, self.params.pruning_max_mem)?;
+
+		info!("DB path: {}", config.database_path.display());
+		let file: Box<dyn Read> = match self.params.input {
+			Some(filename) => Box::new(File::open(filename)?),
+			None => Box::new(stdin()),
+		};
+
+		builder(config)?.import_state(file)?;
+		Ok(())
+	}
+}
+
+/// Command ready to check the integrity of the database.
+pub struct ParseAndPrepareCheckDb<'a> {
+	params: CheckDbCmd,
+	version: &'a VersionInfo,
+}
+
+impl<'a> ParseAndPrepareCheckDb<'a> {
+	/// Runs the command and checks the database, repairing it if requested.
+	pub fn run<Block, G, S>(
+		self,
+		spec_factory: S,
+	) -> error::Result<()>
+	where S: FnOnce(&str) -> Result<Option<ChainSpec<G>>, String>,
+		Block: BlockT<Hash = H256>,
+		G: RuntimeGenesis,
+	{
+		let mut config = create_config_with_db_path::<(), _, _>(
+			spec_factory,
+			&self.params.shared_params,
+			self.version,
+		)?;
+		config.pruning = pruning_mode(self.params.pruning, self.params.pruning_max_mem)?;
+
+		info!("DB path: {}", config.database_path.display());
+		service::chain_ops::check_db::<Block, _, _>(&config, self.params.repair)?;
+		Ok(())
+	}
+}
+
 /// Command ready to purge the chain.
 pub struct ParseAndPreparePurge<'a> {
 	params: PurgeChainCmd,
@@ -579,6 +692,7 @@ fn fill_network_configuration(
 
 	config.in_peers = cli.in_peers;
 	config.out_peers = cli.out_peers;
+	config.sync_mode = cli.sync.into();
 
 	config.transport = TransportConfig::Normal {
 		enable_mdns: !is_dev && !cli.no_mdns,
@@ -651,15 +765,12 @@ where
 	);
 
 	config.database_path = db_path(&base_path, config.chain_spec.id());
+	config.database = cli.shared_params.database.into();
+	config.wasm_cache_path = Some(wasm_cache_path(&base_path));
+	config.consensus_failure_path = Some(consensus_failure_path(&base_path, config.chain_spec.id()));
 	config.database_cache_size = cli.database_cache_size;
 	config.state_cache_size = cli.state_cache_size;
-	config.pruning = match cli.pruning {
-		Some(ref s) if s == "archive" => PruningMode::ArchiveAll,
-		None => PruningMode::default(),
-		Some(s) => PruningMode::keep_blocks(
-			s.parse().map_err(|_| error::Error::Input("Invalid pruning mode specified".to_string()))?
-		),
-	};
+	config.pruning = pruning_mode(cli.pruning, cli.pruning_max_mem)?;
 
 	let is_dev = cli.shared_params.dev;
 
@@ -681,6 +792,8 @@ where
 		offchain_worker: exec_all_or(exec.execution_offchain_worker),
 		other: exec_all_or(exec.execution_other),
 	};
+	config.wasm_method = exec.wasm_method.into_service().map_err(error::Error::Input)?;
+	enable_wasm_profiling(exec.wasm_profile.as_ref())?;
 
 	config.offchain_worker = match (cli.offchain_worker, role) {
 		(params::OffchainWorkerEnabled::WhenValidating, service::Roles::AUTHORITY) => true,
@@ -688,6 +801,11 @@ where
 		(params::OffchainWorkerEnabled::Never, _) => false,
 		(params::OffchainWorkerEnabled::WhenValidating, _) => false,
 	};
+	config.offchain_worker_config = service::OffchainWorkerConfig {
+		max_concurrent_workers: cli.offchain_worker_threads,
+		skip_non_best: cli.offchain_worker_skip_non_best,
+		skip_superseded: cli.offchain_worker_skip_superseded,
+	};
 
 	config.roles = role;
 	config.disable_grandpa = cli.no_grandpa;
@@ -720,6 +838,8 @@ where
 	config.rpc_ws = Some(parse_address(&format!("{}:{}", ws_interface, 9944), cli.ws_port)?);
 
 	config.rpc_ws_max_connections = cli.ws_max_connections;
+	config.rpc_methods = cli.rpc_methods.into();
+	config.rpc_rate_limit = cli.rpc_rate_limit;
 	config.rpc_cors = cli.rpc_cors.unwrap_or_else(|| if is_dev {
 		log::warn!("Running in --dev mode, RPC CORS has been disabled.");
 		Cors::All
@@ -793,6 +913,9 @@ where
 
 	let mut config = service::Configuration::default_with_spec(spec.clone());
 	config.database_path = db_path(&base_path, spec.id());
+	config.database = cli.database.into();
+	config.wasm_cache_path = Some(wasm_cache_path(&base_path));
+	config.consensus_failure_path = Some(consensus_failure_path(&base_path, spec.id()));
 
 	Ok(config)
 }
@@ -832,6 +955,49 @@ fn db_path(base_path: &Path, chain_id: &str) -> PathBuf {
 	path
 }
 
+fn pruning_mode(pruning: Option<String>, max_mem_mib: Option<usize>) -> error::Result<PruningMode> {
+	let max_blocks = match pruning {
+		Some(ref s) if s == "archive" => {
+			if max_mem_mib.is_some() {
+				return Err(error::Error::Input("Memory limit can't be used with archive pruning".to_string()));
+			}
+			return Ok(PruningMode::ArchiveAll);
+		},
+		None => None,
+		Some(s) => Some(
+			s.parse().map_err(|_| error::Error::Input("Invalid pruning mode specified".to_string()))?
+		),
+	};
+	Ok(match (max_blocks, max_mem_mib) {
+		(max_blocks, Some(mib)) => PruningMode::keep_mem(max_blocks, mib.saturating_mul(1024 * 1024)),
+		(Some(n), None) => PruningMode::keep_blocks(n),
+		(None, None) => PruningMode::default(),
+	})
+}
+
+fn consensus_failure_path(base_path: &Path, chain_id: &str) -> PathBuf {
+	let mut path = base_path.to_owned();
+	path.push("chains");
+	path.push(chain_id);
+	path.push("consensus-failures");
+	path
+}
+
+fn enable_wasm_profiling(output: Option<&PathBuf>) -> error::Result<()> {
+	if let Some(output) = output {
+		service::wasm_profiling::enable(true, Some(output)).map_err(|e| error::Error::Input(
+			format!("Cannot open Wasm profile output {}: {}", output.display(), e)
+		))?;
+	}
+	Ok(())
+}
+
+fn wasm_cache_path(base_path: &Path) -> PathBuf {
+	let mut path = base_path.to_owned();
+	path.push("wasm-cache");
+	path
+}
+
 fn network_path(base_path: &Path, chain_id: &str) -> PathBuf {
 	let mut path = base_path.to_owned();
 	path.push("chains");
@@ -44,6 +44,35 @@ impl Into<client::ExecutionStrategy> for ExecutionStrategy {
 	}
 }
 
+arg_enum! {
+	/// How to execute Wasm runtime code.
+	#[allow(missing_docs)]
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub enum WasmExecutionMethod {
+		// Interpret the runtime with `wasmi`.
+		Interpreted,
+		// Compile the runtime to native code with `wasmtime`.
+		Compiled,
+	}
+}
+
+impl WasmExecutionMethod {
+	/// Convert into the execution method of the service.
+	///
+	/// Fails if compiled execution was requested but is not enabled in this build.
+	pub fn into_service(self) -> Result<service::WasmExecutionMethod, String> {
+		match self {
+			WasmExecutionMethod::Interpreted => Ok(service::WasmExecutionMethod::Interpreted),
+			#[cfg(feature = "wasmtime")]
+			WasmExecutionMethod::Compiled => Ok(service::WasmExecutionMethod::Compiled),
+			#[cfg(not(feature = "wasmtime"))]
+			WasmExecutionMethod::Compiled => Err(
+				"Compiled Wasm execution is not enabled in this build, see the `wasmtime` feature".into()
+			),
+		}
+	}
+}
+
 arg_enum! {
 	/// Whether off-chain workers are enabled.
 	#[allow(missing_docs)]
@@ -55,6 +84,31 @@ arg_enum! {
 	}
 }
 
+arg_enum! {
+	/// Available RPC methods.
+	#[allow(missing_docs)]
+	#[derive(Debug, Copy, Clone, PartialEq)]
+	pub enum RpcMethods {
+		// Expose every RPC method only when RPC is listening on `localhost`,
+		// otherwise serve only safe RPC methods.
+		Auto,
+		// Allow only a safe subset of RPC methods.
+		Safe,
+		// Expose every RPC method (even potentially unsafe ones).
+		Unsafe,
+	}
+}
+
+impl Into<service::RpcMethods> for RpcMethods {
+	fn into(self) -> service::RpcMethods {
+		match self {
+			RpcMethods::Auto => service::RpcMethods::Auto,
+			RpcMethods::Safe => service::RpcMethods::Safe,
+			RpcMethods::Unsafe => service::RpcMethods::Unsafe,
+		}
+	}
+}
+
 /// Shared parameters used by all `CoreParams`.
 #[derive(Debug, StructOpt, Clone)]
 pub struct SharedParams {
@@ -73,6 +127,40 @@ pub struct SharedParams {
 	/// Sets a custom logging filter
 	#[structopt(short = "l", long = "log", value_name = "LOG_PATTERN")]
 	pub log: Option<String>,
+
+	/// Select the key-value store backing the database.
+	///
+	/// `LogDb` is an embedded log-structured store keeping an index of all the keys in memory.
+	/// A database can only be reopened with the engine which created it.
+	#[structopt(
+		long = "database",
+		value_name = "DB",
+		raw(
+			possible_values = "&Database::variants()",
+			case_insensitive = "true",
+			default_value = r#""RocksDb""#
+		)
+	)]
+	pub database: Database,
+}
+
+arg_enum! {
+	/// Key-value store backing the database.
+	#[allow(missing_docs)]
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub enum Database {
+		RocksDb,
+		LogDb,
+	}
+}
+
+impl Into<service::DatabaseEngine> for Database {
+	fn into(self) -> service::DatabaseEngine {
+		match self {
+			Database::RocksDb => service::DatabaseEngine::RocksDb,
+			Database::LogDb => service::DatabaseEngine::LogDb,
+		}
+	}
 }
 
 impl GetLogFilter for SharedParams {
@@ -113,11 +201,48 @@ pub struct NetworkConfigurationParams {
 	#[structopt(long = "no-mdns")]
 	pub no_mdns: bool,
 
+	/// Select how the chain is synced.
+	///
+	/// `Fast` downloads the state of a recent finalized block instead of importing all the
+	/// blocks from genesis. It only applies to an empty database and falls back to `Full` if
+	/// the node does not support it.
+	#[structopt(
+		long = "sync",
+		value_name = "MODE",
+		raw(
+			possible_values = "&SyncMode::variants()",
+			case_insensitive = "true",
+			default_value = r#""Full""#
+		)
+	)]
+	pub sync: SyncMode,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub node_key_params: NodeKeyParams
 }
 
+arg_enum! {
+	/// How the chain is synced.
+	#[allow(missing_docs)]
+	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+	pub enum SyncMode {
+		// Import all the blocks from genesis.
+		Full,
+		// Download the state of a recent finalized block, then import the blocks after it.
+		Fast,
+	}
+}
+
+impl Into<network::config::SyncMode> for SyncMode {
+	fn into(self) -> network::config::SyncMode {
+		match self {
+			SyncMode::Full => network::config::SyncMode::Full,
+			SyncMode::Fast => network::config::SyncMode::Fast,
+		}
+	}
+}
+
 arg_enum! {
 	#[allow(missing_docs)]
 	#[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -293,6 +418,23 @@ pub struct ExecutionStrategies {
 		)
 	)]
 	pub execution: Option<ExecutionStrategy>,
+
+	/// Method for executing Wasm runtime code.
+	#[structopt(
+		long = "wasm-execution",
+		value_name = "METHOD",
+		raw(
+			possible_values = "&WasmExecutionMethod::variants()",
+			case_insensitive = "true",
+			default_value = r#""Interpreted""#
+		)
+	)]
+	pub wasm_method: WasmExecutionMethod,
+
+	/// Profile host function calls of Wasm execution and append a JSON summary of every
+	/// runtime call to the given file. Native execution is not profiled.
+	#[structopt(long = "wasm-profile", value_name = "PATH", parse(from_os_str))]
+	pub wasm_profile: Option<PathBuf>,
 }
 
 /// The `run` command used to run a node.
@@ -326,6 +468,23 @@ pub struct RunCmd {
 	#[structopt(long = "ws-external")]
 	pub ws_external: bool,
 
+	/// RPC methods to expose.
+	///
+	/// - `Unsafe`: Exposes every RPC method.
+	/// - `Safe`: Exposes only a safe subset of RPC methods, denying unsafe RPC methods.
+	/// - `Auto`: Acts as `Safe` if RPC is served externally, e.g. when `--{rpc,ws}-external` is passed,
+	///   otherwise acts as `Unsafe`.
+	#[structopt(
+		long = "rpc-methods",
+		value_name = "METHOD SET",
+		raw(
+			possible_values = "&RpcMethods::variants()",
+			case_insensitive = "true",
+			default_value = r#""Auto""#
+		)
+	)]
+	pub rpc_methods: RpcMethods,
+
 	/// Specify HTTP RPC server TCP port
 	#[structopt(long = "rpc-port", value_name = "PORT")]
 	pub rpc_port: Option<u16>,
@@ -338,6 +497,13 @@ pub struct RunCmd {
 	#[structopt(long = "ws-max-connections", value_name = "COUNT")]
 	pub ws_max_connections: Option<usize>,
 
+	/// Maximum number of RPC calls per second a single WS RPC connection can make.
+	/// Calls over the limit are rejected. Unlimited by default.
+	///
+	/// Calls made over HTTP are never limited.
+	#[structopt(long = "rpc-rate-limit", value_name = "CALLS")]
+	pub rpc_rate_limit: Option<u32>,
+
 	/// Specify browser Origins allowed to access the HTTP & WS RPC servers.
 	/// It's a comma-separated list of origins (protocol://domain or special `null` value).
 	/// Value of `all` will disable origin validation.
@@ -350,6 +516,13 @@ pub struct RunCmd {
 	#[structopt(long = "pruning", value_name = "PRUNING_MODE")]
 	pub pruning: Option<String>,
 
+	/// Limit the memory used to track recent state changes, in MiB. Old states are pruned to
+	/// stay within the limit, and a warning is logged if the changes of the blocks which are not
+	/// finalized yet exceed it on their own. When given without `--pruning` the number of blocks
+	/// kept is limited by memory only.
+	#[structopt(long = "pruning-max-mem", value_name = "MiB")]
+	pub pruning_max_mem: Option<usize>,
+
 	/// The human-readable name for this node, as reported to the telemetry server, if enabled
 	#[structopt(long = "name", value_name = "NAME")]
 	pub name: Option<String>,
@@ -377,6 +550,19 @@ pub struct RunCmd {
 	)]
 	pub offchain_worker: OffchainWorkerEnabled,
 
+	/// Maximal number of offchain workers running concurrently.
+	#[structopt(long = "offchain-worker-threads", value_name = "COUNT", default_value = "4")]
+	pub offchain_worker_threads: usize,
+
+	/// Skip offchain workers for blocks that are not the new best block when imported.
+	#[structopt(long = "offchain-worker-skip-non-best")]
+	pub offchain_worker_skip_non_best: bool,
+
+	/// Skip queued offchain workers of blocks superseded by a higher block before their
+	/// worker started.
+	#[structopt(long = "offchain-worker-skip-superseded")]
+	pub offchain_worker_skip_superseded: bool,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub execution_strategies: ExecutionStrategies,
@@ -629,10 +815,91 @@ pub struct ImportBlocksCmd {
 		)
 	)]
 	pub execution: ExecutionStrategy,
+
+	/// Method for executing Wasm runtime code.
+	#[structopt(
+		long = "wasm-execution",
+		value_name = "METHOD",
+		raw(
+			possible_values = "&WasmExecutionMethod::variants()",
+			case_insensitive = "true",
+			default_value = r#""Interpreted""#
+		)
+	)]
+	pub wasm_method: WasmExecutionMethod,
+
+	/// Profile host function calls of Wasm execution and append a JSON summary of every
+	/// runtime call to the given file. Native execution is not profiled.
+	#[structopt(long = "wasm-profile", value_name = "PATH", parse(from_os_str))]
+	pub wasm_profile: Option<PathBuf>,
 }
 
 impl_get_log_filter!(ImportBlocksCmd);
 
+/// The `export-state` command used to export the state at a finalized block.
+#[derive(Debug, StructOpt, Clone)]
+pub struct ExportStateCmd {
+	/// Number of the block to export the state at. Last finalized block by default.
+	#[structopt(value_name = "BLOCK")]
+	pub block: Option<u32>,
+
+	/// Output file name or stdout if unspecified.
+	#[structopt(long = "output", short = "o", value_name = "PATH", parse(from_os_str))]
+	pub output: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl_get_log_filter!(ExportStateCmd);
+
+/// The `import-state` command used to initialize an empty database from a state snapshot.
+#[derive(Debug, StructOpt, Clone)]
+pub struct ImportStateCmd {
+	/// Input file or stdin if unspecified.
+	#[structopt(parse(from_os_str))]
+	pub input: Option<PathBuf>,
+
+	/// Specify the pruning mode the node will be run with, a number of blocks to keep or
+	/// 'archive'. Default is 256.
+	#[structopt(long = "pruning", value_name = "PRUNING_MODE")]
+	pub pruning: Option<String>,
+
+	/// Specify the memory limit for recent state changes the node will be run with, in MiB.
+	#[structopt(long = "pruning-max-mem", value_name = "MiB")]
+	pub pruning_max_mem: Option<usize>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl_get_log_filter!(ImportStateCmd);
+
+/// The `check-db` command used to check the integrity of the database.
+#[derive(Debug, StructOpt, Clone)]
+pub struct CheckDbCmd {
+	/// Remove the lookup, body and justification entries which no block refers to.
+	#[structopt(long = "repair")]
+	pub repair: bool,
+
+	/// Specify the pruning mode the node has been run with, a number of blocks to keep or
+	/// 'archive'. Default is 256.
+	#[structopt(long = "pruning", value_name = "PRUNING_MODE")]
+	pub pruning: Option<String>,
+
+	/// Specify the memory limit for recent state changes the node has been run with, in MiB.
+	#[structopt(long = "pruning-max-mem", value_name = "MiB")]
+	pub pruning_max_mem: Option<usize>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+impl_get_log_filter!(CheckDbCmd);
+
 /// The `revert` command used revert the chain to a previous state.
 #[derive(Debug, StructOpt, Clone)]
 pub struct RevertCmd {
@@ -680,6 +947,15 @@ pub enum CoreParams<CC, RP> {
 	/// Import blocks from file.
 	ImportBlocks(ImportBlocksCmd),
 
+	/// Export the state at a finalized block to a file.
+	ExportState(ExportStateCmd),
+
+	/// Initialize an empty database from a state snapshot.
+	ImportState(ImportStateCmd),
+
+	/// Check the integrity of the database.
+	CheckDb(CheckDbCmd),
+
 	/// Revert chain to the previous state.
 	Revert(RevertCmd),
 
@@ -711,6 +987,18 @@ impl<CC, RP> StructOpt for CoreParams<CC, RP> where
 			ImportBlocksCmd::augment_clap(SubCommand::with_name("import-blocks"))
 				.about("Import blocks from file.")
 		)
+		.subcommand(
+			ExportStateCmd::augment_clap(SubCommand::with_name("export-state"))
+				.about("Export the state at a finalized block to a file.")
+		)
+		.subcommand(
+			ImportStateCmd::augment_clap(SubCommand::with_name("import-state"))
+				.about("Initialize an empty database from a state snapshot.")
+		)
+		.subcommand(
+			CheckDbCmd::augment_clap(SubCommand::with_name("check-db"))
+				.about("Check the integrity of the database.")
+		)
 		.subcommand(
 			RevertCmd::augment_clap(SubCommand::with_name("revert"))
 				.about("Revert chain to the previous state.")
@@ -729,6 +1017,12 @@ impl<CC, RP> StructOpt for CoreParams<CC, RP> where
 				CoreParams::ExportBlocks(ExportBlocksCmd::from_clap(matches)),
 			("import-blocks", Some(matches)) =>
 				CoreParams::ImportBlocks(ImportBlocksCmd::from_clap(matches)),
+			("export-state", Some(matches)) =>
+				CoreParams::ExportState(ExportStateCmd::from_clap(matches)),
+			("import-state", Some(matches)) =>
+				CoreParams::ImportState(ImportStateCmd::from_clap(matches)),
+			("check-db", Some(matches)) =>
+				CoreParams::CheckDb(CheckDbCmd::from_clap(matches)),
 			("revert", Some(matches)) => CoreParams::Revert(RevertCmd::from_clap(matches)),
 			("purge-chain", Some(matches)) =>
 				CoreParams::PurgeChain(PurgeChainCmd::from_clap(matches)),
@@ -745,6 +1039,9 @@ impl<CC, RP> GetLogFilter for CoreParams<CC, RP> where CC: GetLogFilter {
 			CoreParams::BuildSpec(c) => c.get_log_filter(),
 			CoreParams::ExportBlocks(c) => c.get_log_filter(),
 			CoreParams::ImportBlocks(c) => c.get_log_filter(),
+			CoreParams::ExportState(c) => c.get_log_filter(),
+			CoreParams::ImportState(c) => c.get_log_filter(),
+			CoreParams::CheckDb(c) => c.get_log_filter(),
 			CoreParams::PurgeChain(c) => c.get_log_filter(),
 			CoreParams::Revert(c) => c.get_log_filter(),
 			CoreParams::Custom(c) => c.get_log_filter(),
@@ -0,0 +1,122 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use criterion::{Criterion, Bencher, Fun, black_box, criterion_group, criterion_main};
+use kvdb::{DBTransaction, KeyValueDB};
+use kvdb_rocksdb::{Database, DatabaseConfig};
+use primitives::blake2_256;
+use substrate_client_db::LogDb;
+use tempdir::TempDir;
+
+const NUM_COLUMNS: u32 = 1;
+const COLUMN: Option<u32> = Some(0);
+/// Size of the values, close to the one of trie nodes.
+const VALUE_SIZE: usize = 128;
+/// Number of values written per transaction.
+const BATCH_SIZE: u64 = 1000;
+/// Number of values in the database read from.
+const NUM_VALUES: u64 = 100_000;
+
+type Open = fn(&Path) -> Arc<dyn KeyValueDB>;
+
+fn open_logdb(path: &Path) -> Arc<dyn KeyValueDB> {
+	Arc::new(LogDb::open(path, NUM_COLUMNS).unwrap())
+}
+
+fn open_rocksdb(path: &Path) -> Arc<dyn KeyValueDB> {
+	let config = DatabaseConfig::with_columns(Some(NUM_COLUMNS));
+	Arc::new(Database::open(&config, path.to_str().unwrap()).unwrap())
+}
+
+/// Keys are hashes, as the ones of trie nodes.
+fn key(i: u64) -> [u8; 32] {
+	blake2_256(&i.to_le_bytes())
+}
+
+fn insert(db: &dyn KeyValueDB, from: u64, count: u64) {
+	let mut transaction = DBTransaction::new();
+	for i in from..from + count {
+		transaction.put(COLUMN, &key(i), &[i as u8; VALUE_SIZE]);
+	}
+	db.write(transaction).unwrap();
+}
+
+fn bench_write(b: &mut Bencher, open: Open, batch_size: u64) {
+	let dir = TempDir::new("bench-db").unwrap();
+	let db = open(dir.path());
+	let mut next = 0;
+	b.iter(|| {
+		insert(&*db, next, batch_size);
+		next += batch_size;
+	});
+}
+
+fn bench_read(b: &mut Bencher, open: Open, num_values: u64) {
+	let dir = TempDir::new("bench-db").unwrap();
+	let db = open(dir.path());
+	for from in (0..num_values).step_by(BATCH_SIZE as usize) {
+		insert(&*db, from, BATCH_SIZE);
+	}
+	let mut i = 0;
+	b.iter(|| {
+		i = (i + 7919) % num_values;
+		black_box(db.get(COLUMN, &key(i)).unwrap())
+	});
+}
+
+fn bench_iter(b: &mut Bencher, open: Open, num_values: u64) {
+	let dir = TempDir::new("bench-db").unwrap();
+	let db = open(dir.path());
+	for from in (0..num_values).step_by(BATCH_SIZE as usize) {
+		insert(&*db, from, BATCH_SIZE);
+	}
+	b.iter(|| black_box(db.iter(COLUMN).count()));
+}
+
+fn bench_writes(c: &mut Criterion) {
+	let fns = vec![
+		Fun::new("logdb", |b, batch_size: &u64| bench_write(b, open_logdb, *batch_size)),
+		Fun::new("rocksdb", |b, batch_size: &u64| bench_write(b, open_rocksdb, *batch_size)),
+	];
+	c.bench_functions("write transaction", fns, BATCH_SIZE);
+}
+
+fn bench_reads(c: &mut Criterion) {
+	let fns = vec![
+		Fun::new("logdb", |b, num_values: &u64| bench_read(b, open_logdb, *num_values)),
+		Fun::new("rocksdb", |b, num_values: &u64| bench_read(b, open_rocksdb, *num_values)),
+	];
+	c.bench_functions("read value", fns, NUM_VALUES);
+}
+
+fn bench_iters(c: &mut Criterion) {
+	let fns = vec![
+		Fun::new("logdb", |b, num_values: &u64| bench_iter(b, open_logdb, *num_values)),
+		Fun::new("rocksdb", |b, num_values: &u64| bench_iter(b, open_rocksdb, *num_values)),
+	];
+	c.bench_functions("iterate column", fns, NUM_VALUES);
+}
+
+criterion_group!{
+	name = benches;
+	config = Criterion::default().warm_up_time(Duration::from_millis(500)).without_plots();
+	targets = bench_writes, bench_reads, bench_iters
+}
+criterion_main!(benches);
@@ -0,0 +1,566 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Database integrity check.
+//!
+//! Walks the canonical chain and the block columns of a full database, checking that headers,
+//! bodies, justifications and lookup entries reference each other, and that the state of every
+//! block which has not been pruned is fully present. Entries which no header refers to are
+//! reported as dangling, and may be removed.
+//!
+//! The columns are read directly rather than through a `Backend`, so that checking a database
+//! never writes to it unless asked to repair it. The states of consecutive blocks share most of
+//! their trie nodes, and the nodes visited for a state are not visited again for the next one.
+//! Only the nodes of the last two states checked are remembered, which bounds the memory used.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use codec::{Decode, Encode};
+use kvdb::{DBTransaction, KeyValueDB};
+use primitives::{H256, Blake2Hasher, HexDisplay};
+use primitives::storage::well_known_keys;
+use sr_primitives::Justification;
+use sr_primitives::traits::{
+	Block as BlockT, Header as HeaderT, Hash as HashT, SaturatedConversion,
+};
+use state_db::{PruningMode, StateDb};
+use trie::{NibbleSlice, Node, NodeCodec, NodeCodecT, default_child_trie_root, prefixed_key};
+use trie::trie_types::Layout;
+use log::info;
+
+use crate::{StateMetaDb, StorageDb, columns};
+use crate::utils::{db_err, meta_keys, number_index_key, number_and_hash_to_lookup_key, read_meta};
+
+/// Outcome of a database integrity check.
+#[derive(Debug, Default)]
+pub struct CheckReport {
+	/// Number of canonical blocks checked.
+	pub blocks: u64,
+	/// Ranges of canonical block numbers missing from the database, as left by state snapshot
+	/// imports and warp sync.
+	pub gaps: Vec<(u64, u64)>,
+	/// Number of distinct states checked.
+	pub states: u64,
+	/// Number of trie nodes read while checking the states.
+	pub nodes: u64,
+	/// Inconsistencies which can't be repaired.
+	pub errors: Vec<String>,
+	/// Entries which no header refers to, as column name and key.
+	pub dangling: Vec<(&'static str, Vec<u8>)>,
+	/// Whether the dangling entries have been removed.
+	pub repaired: bool,
+}
+
+/// Check the integrity of a full database, removing the dangling entries if `repair` is set.
+///
+/// `pruning` must be the pruning mode the database is used with, which tells the states that
+/// are expected to be present.
+pub fn check_database<Block: BlockT<Hash=H256>>(
+	db: Arc<dyn KeyValueDB>,
+	pruning: PruningMode,
+	repair: bool,
+) -> Result<CheckReport, client::error::Error> {
+	let meta = read_meta::<Block>(&*db, columns::META, columns::HEADER)?;
+	if meta.genesis_hash == Default::default() {
+		return Err(client::error::Error::Backend("The database is empty".into()));
+	}
+	let state_db = StateDb::new(pruning, &StateMetaDb(&*db))
+		.map_err(|e| client::error::Error::from(format!("State database error: {:?}", e)))?;
+
+	let mut checker = Checker::<Block> {
+		storage: StorageDb { db, state_db },
+		best_number: meta.best_number.saturated_into(),
+		visited: HashSet::new(),
+		previously_visited: HashSet::new(),
+	};
+	let mut report = CheckReport::default();
+	let mut transaction = DBTransaction::new();
+	checker.check_meta(&mut report)?;
+	checker.check_canonical_chain(&mut report)?;
+	checker.check_headers(&mut report)?;
+	checker.check_dangling(&mut report, &mut transaction)?;
+
+	if repair && !report.dangling.is_empty() {
+		checker.storage.db.write(transaction).map_err(db_err)?;
+		report.repaired = true;
+	}
+	Ok(report)
+}
+
+struct Checker<Block: BlockT> {
+	storage: StorageDb<Block>,
+	best_number: u64,
+	/// Database keys of the trie nodes visited while checking the current state, all the nodes
+	/// below them having been visited as well.
+	visited: HashSet<Vec<u8>>,
+	/// Same as `visited`, for the state checked before the current one.
+	previously_visited: HashSet<Vec<u8>>,
+}
+
+impl<Block: BlockT<Hash=H256>> Checker<Block> {
+	fn check_meta(&self, report: &mut CheckReport) -> Result<(), client::error::Error> {
+		let db = &*self.storage.db;
+		for (name, key) in &[("best", &meta_keys::BEST_BLOCK[..]), ("finalized", &meta_keys::FINALIZED_BLOCK[..])] {
+			let header = match db.get(columns::META, key).map_err(db_err)? {
+				Some(lookup_key) => db.get(columns::HEADER, &lookup_key).map_err(db_err)?,
+				None => None,
+			};
+			if header.is_none() {
+				report.errors.push(format!("Header of the {} block is missing", name));
+			}
+		}
+		Ok(())
+	}
+
+	fn check_canonical_chain(&mut self, report: &mut CheckReport) -> Result<(), client::error::Error> {
+		let db = self.storage.db.clone();
+		let mut checked_states = HashSet::new();
+		let mut parent_hash = None;
+		let mut gap_start = None;
+
+		for number in 0..=self.best_number {
+			let lookup_key = match db.get(columns::KEY_LOOKUP, &number_index_key(number)?).map_err(db_err)? {
+				Some(lookup_key) => lookup_key.into_vec(),
+				None => {
+					if number == 0 {
+						report.errors.push("Genesis block is not canonical".into());
+					}
+					gap_start.get_or_insert(number);
+					parent_hash = None;
+					continue;
+				},
+			};
+			if let Some(start) = gap_start.take() {
+				report.gaps.push((start, number - 1));
+			}
+			report.blocks += 1;
+			if number % 10_000 == 0 && number != 0 {
+				info!("#{} blocks checked", number);
+			}
+
+			let header = match db.get(columns::HEADER, &lookup_key).map_err(db_err)? {
+				Some(header) => Block::Header::decode(&mut &header[..]).ok(),
+				None => None,
+			};
+			let header = match header {
+				Some(header) => header,
+				None => {
+					report.errors.push(format!("Header of canonical block #{} is missing or invalid", number));
+					parent_hash = None;
+					continue;
+				},
+			};
+			let hash = header.hash();
+			if lookup_key != number_and_hash_to_lookup_key(number, hash)? {
+				report.errors.push(format!("Canonical block #{} is stored under the key of another block", number));
+			}
+			if parent_hash.map_or(false, |parent_hash| parent_hash != *header.parent_hash()) {
+				report.errors.push(format!("Parent of canonical block #{} ({}) is not canonical", number, hash));
+			}
+			parent_hash = Some(hash);
+
+			if let Some(body) = db.get(columns::BODY, &lookup_key).map_err(db_err)? {
+				let extrinsics_root = Vec::<Block::Extrinsic>::decode(&mut &body[..]).ok().map(|body|
+					<<Block::Header as HeaderT>::Hashing as HashT>::ordered_trie_root(
+						body.iter().map(|extrinsic| extrinsic.encode())
+					)
+				);
+				if extrinsics_root.as_ref() != Some(header.extrinsics_root()) {
+					report.errors.push(format!(
+						"Body of canonical block #{} ({}) does not match its extrinsics root", number, hash,
+					));
+				}
+			}
+			if let Some(justification) = db.get(columns::JUSTIFICATION, &lookup_key).map_err(db_err)? {
+				if Justification::decode(&mut &justification[..]).is_err() {
+					report.errors.push(format!("Justification of canonical block #{} ({}) is invalid", number, hash));
+				}
+			}
+
+			let state_root = *header.state_root();
+			if !self.storage.state_db.is_pruned(&hash, number) && checked_states.insert(state_root) {
+				if let Err(e) = self.check_state(&state_root, &mut report.nodes) {
+					report.errors.push(format!("State of canonical block #{} ({}) is incomplete: {}", number, hash, e));
+				}
+				report.states += 1;
+			}
+		}
+
+		if let Some(start) = gap_start {
+			report.gaps.push((start, self.best_number));
+		}
+		Ok(())
+	}
+
+	/// Check that every header, canonical or not, is stored under its own key and can be looked
+	/// up by hash.
+	fn check_headers(&self, report: &mut CheckReport) -> Result<(), client::error::Error> {
+		let db = &*self.storage.db;
+		for (key, header) in db.iter(columns::HEADER) {
+			let header = match Block::Header::decode(&mut &header[..]) {
+				Ok(header) => header,
+				Err(_) => {
+					report.errors.push(format!("Header stored under {} is invalid", HexDisplay::from(&&*key)));
+					continue;
+				},
+			};
+			let hash = header.hash();
+			if *key != number_and_hash_to_lookup_key(*header.number(), hash)?[..] {
+				report.errors.push(format!("Header of #{} ({}) is stored under a wrong key", header.number(), hash));
+			}
+			match db.get(columns::KEY_LOOKUP, hash.as_ref()).map_err(db_err)? {
+				Some(ref lookup_key) if **lookup_key == *key => {},
+				_ => report.errors.push(format!("Lookup entry of #{} ({}) is missing", header.number(), hash)),
+			}
+		}
+		Ok(())
+	}
+
+	/// Note the lookup, body and justification entries referring to a missing header, and
+	/// add their removal to `transaction`.
+	fn check_dangling(
+		&self,
+		report: &mut CheckReport,
+		transaction: &mut DBTransaction,
+	) -> Result<(), client::error::Error> {
+		let db = &*self.storage.db;
+		let has_header = |lookup_key: &[u8]| db.get(columns::HEADER, lookup_key)
+			.map(|header| header.is_some())
+			.map_err(db_err);
+
+		for (key, lookup_key) in db.iter(columns::KEY_LOOKUP) {
+			if !has_header(&lookup_key)? {
+				transaction.delete(columns::KEY_LOOKUP, &key);
+				report.dangling.push(("KEY_LOOKUP", key.into_vec()));
+			}
+		}
+		for &(name, column) in &[("BODY", columns::BODY), ("JUSTIFICATION", columns::JUSTIFICATION)] {
+			for (key, _) in db.iter(column) {
+				if !has_header(&key)? {
+					transaction.delete(column, &key);
+					report.dangling.push((name, key.into_vec()));
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Check that all the nodes of the state with the given root, child tries included, are
+	/// present, adding the number of nodes read to `nodes`.
+	fn check_state(&mut self, root: &H256, nodes: &mut u64) -> Result<(), String> {
+		self.previously_visited = std::mem::replace(&mut self.visited, HashSet::new());
+
+		let mut child_roots = Vec::new();
+		self.walk_trie(root, nodes, |key, value| if well_known_keys::is_child_storage_key(key) {
+			child_roots.push((key.to_vec(), value.to_vec()));
+		})?;
+
+		for (storage_key, child_root) in child_roots {
+			if child_root.len() != H256::len_bytes() {
+				return Err(format!("invalid root of child trie {}", HexDisplay::from(&storage_key)));
+			}
+			self.walk_trie(&H256::from_slice(&child_root), nodes, |_, _| ())
+				.map_err(|e| format!("child trie {}: {}", HexDisplay::from(&storage_key), e))?;
+		}
+		Ok(())
+	}
+
+	/// Visit the nodes of a trie which have not been visited yet, calling `f` with the entries
+	/// they hold and adding the number of nodes read to `nodes`, and fail if any of them is
+	/// missing.
+	///
+	/// The whole trie is walked even if a node is missing, and a node is only marked as visited
+	/// once all the nodes below it have been visited without error, so that a missing node is
+	/// reported for every state it belongs to.
+	fn walk_trie(&mut self, root: &H256, nodes: &mut u64, mut f: impl FnMut(&[u8], &[u8])) -> Result<(), String> {
+		// The empty trie has no node in the database.
+		if root.as_ref() == &default_child_trie_root::<Layout<Blake2Hasher>>(&[])[..] {
+			return Ok(());
+		}
+
+		let mut first_error = None;
+		let mut errors = 0;
+		let mut pending = vec![Walk::Node(Vec::new(), NodeRef::Hash(*root))];
+		while let Some(next) = pending.pop() {
+			let (path, node) = match next {
+				Walk::Node(path, node) => (path, node),
+				Walk::Visited(key, errors_before) => {
+					if errors == errors_before {
+						self.visited.insert(key);
+					}
+					continue;
+				},
+			};
+			let encoded = match node {
+				NodeRef::Hash(hash) => {
+					let (packed, last) = pack_path(&path);
+					let prefix = (&packed[..], last);
+					let key = prefixed_key::<Blake2Hasher>(&hash, prefix);
+					if self.visited.contains(&key) {
+						continue;
+					}
+					if self.previously_visited.contains(&key) {
+						self.visited.insert(key);
+						continue;
+					}
+					match state_machine::Storage::<Blake2Hasher>::get(&self.storage, &hash, prefix) {
+						Ok(Some(encoded)) => {
+							*nodes += 1;
+							// popped once all the nodes below this one have been visited.
+							pending.push(Walk::Visited(key, errors));
+							encoded.to_vec()
+						},
+						Ok(None) => {
+							errors += 1;
+							first_error.get_or_insert_with(|| format!("missing trie node {}", hash));
+							continue;
+						},
+						Err(e) => {
+							errors += 1;
+							first_error.get_or_insert_with(|| format!("error reading trie node {}: {}", hash, e));
+							continue;
+						},
+					}
+				},
+				NodeRef::Inline(encoded) => encoded,
+			};
+
+			let node = match <NodeCodec<Blake2Hasher> as NodeCodecT<Blake2Hasher>>::decode(&encoded) {
+				Ok(node) => node,
+				Err(e) => {
+					errors += 1;
+					first_error.get_or_insert_with(|| format!("invalid trie node: {:?}", e));
+					continue;
+				},
+			};
+			let (partial, children, value) = match node {
+				Node::Empty => continue,
+				Node::Leaf(partial, value) => (partial, None, Some(value)),
+				Node::Extension(partial, child) => {
+					let path = extend_path(&path, &partial);
+					pending.push(Walk::Node(path, NodeRef::new(child)));
+					continue;
+				},
+				Node::Branch(children, value) => (NibbleSlice::new(&[]), Some(children), value),
+				Node::NibbledBranch(partial, children, value) => (partial, Some(children), value),
+			};
+
+			let path = extend_path(&path, &partial);
+			if let Some(value) = value {
+				if path.len() % 2 == 0 {
+					let key = path.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect::<Vec<_>>();
+					f(&key, value);
+				}
+			}
+			for (index, child) in children.iter().flat_map(|children| children.iter()).enumerate() {
+				if let Some(child) = child {
+					let mut child_path = path.clone();
+					child_path.push(index as u8);
+					pending.push(Walk::Node(child_path, NodeRef::new(child)));
+				}
+			}
+		}
+
+		match first_error {
+			Some(e) => Err(e),
+			None => Ok(()),
+		}
+	}
+}
+
+/// Step of the walk of a trie.
+enum Walk {
+	/// Node to visit, along with the nibbles of the path leading to it.
+	Node(Vec<u8>, NodeRef),
+	/// All the nodes below the one with the given database key have been visited, and the given
+	/// number of errors had been found before visiting them.
+	Visited(Vec<u8>, usize),
+}
+
+/// Reference to a trie node from its parent.
+enum NodeRef {
+	/// Hash of a node stored in the database.
+	Hash(H256),
+	/// Encoding of a node too small to be stored on its own.
+	Inline(Vec<u8>),
+}
+
+impl NodeRef {
+	fn new(data: &[u8]) -> Self {
+		match <NodeCodec<Blake2Hasher> as NodeCodecT<Blake2Hasher>>::try_decode_hash(data) {
+			Some(hash) => NodeRef::Hash(hash),
+			None => NodeRef::Inline(data.to_vec()),
+		}
+	}
+}
+
+/// Append the nibbles of a partial key to a path.
+fn extend_path(path: &[u8], partial: &NibbleSlice) -> Vec<u8> {
+	let mut extended = Vec::with_capacity(path.len() + partial.len());
+	extended.extend_from_slice(path);
+	extended.extend((0..partial.len()).map(|i| partial.at(i)));
+	extended
+}
+
+/// Pack the nibbles of a path into the prefix of the database keys of the nodes at its end:
+/// the full bytes, and the last nibble padded on the right if the path has an odd length.
+fn pack_path(path: &[u8]) -> (Vec<u8>, Option<u8>) {
+	let mut packed = path.chunks(2)
+		.map(|pair| pair[0] << 4 | pair.get(1).cloned().unwrap_or(0))
+		.collect::<Vec<_>>();
+	let last = if path.len() % 2 == 1 { packed.pop() } else { None };
+	(packed, last)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Backend;
+	use client::backend::{Backend as BTrait, BlockImportOperation as Op, NewBlockState};
+	use sr_primitives::generic::BlockId;
+	use sr_primitives::testing::{Header, Block as RawBlock, ExtrinsicWrapper};
+	use sr_primitives::traits::BlakeTwo256;
+	use state_machine::backend::Backend as StateBackend;
+
+	type Block = RawBlock<ExtrinsicWrapper<u64>>;
+
+	fn insert_block(
+		backend: &Backend<Block>,
+		number: u64,
+		parent_hash: H256,
+		storage: Vec<(Vec<u8>, Vec<u8>)>,
+	) -> H256 {
+		let mut op = backend.begin_operation().unwrap();
+		let parent = if number == 0 { BlockId::Hash(Default::default()) } else { BlockId::Number(number - 1) };
+		backend.begin_state_operation(&mut op, parent).unwrap();
+
+		let (state_root, overlay) = op.old_state.storage_root(
+			storage.into_iter().map(|(key, value)| (key, Some(value)))
+		);
+		op.update_db_storage(overlay).unwrap();
+		let header = Header {
+			number,
+			parent_hash,
+			state_root,
+			digest: Default::default(),
+			extrinsics_root: BlakeTwo256::ordered_trie_root(Vec::<Vec<u8>>::new()),
+		};
+		let hash = header.hash();
+		op.set_block_data(header, Some(vec![]), None, NewBlockState::Best).unwrap();
+		backend.commit_operation(op).unwrap();
+		hash
+	}
+
+	fn check(backend: &Backend<Block>, repair: bool) -> CheckReport {
+		check_database::<Block>(backend.storage.db.clone(), PruningMode::keep_blocks(16), repair).unwrap()
+	}
+
+	fn prepare_chain() -> Backend<Block> {
+		let backend = Backend::<Block>::new_test(16, 0);
+		let mut hash = Default::default();
+		for number in 0..4 {
+			hash = insert_block(&backend, number, hash, vec![(vec![number as u8], vec![42; 64])]);
+		}
+		backend
+	}
+
+	#[test]
+	fn consistent_database_passes() {
+		let backend = prepare_chain();
+		let report = check(&backend, false);
+		assert_eq!(report.blocks, 4);
+		assert_eq!(report.states, 4);
+		assert!(report.gaps.is_empty());
+		assert!(report.errors.is_empty(), "{:?}", report.errors);
+		assert!(report.dangling.is_empty());
+	}
+
+	#[test]
+	fn dangling_entries_are_repaired() {
+		let backend = prepare_chain();
+		let unknown = number_and_hash_to_lookup_key(2u64, H256::repeat_byte(1)).unwrap();
+		let mut transaction = DBTransaction::new();
+		transaction.put(columns::KEY_LOOKUP, H256::repeat_byte(1).as_ref(), &unknown);
+		transaction.put(columns::JUSTIFICATION, &unknown, &[1, 2, 3]);
+		backend.storage.db.write(transaction).unwrap();
+
+		let report = check(&backend, false);
+		assert!(report.errors.is_empty(), "{:?}", report.errors);
+		assert_eq!(report.dangling.len(), 2);
+		assert!(!report.repaired);
+
+		assert!(check(&backend, true).repaired);
+		let report = check(&backend, false);
+		assert!(report.dangling.is_empty());
+		assert!(backend.storage.db.get(columns::JUSTIFICATION, &unknown).unwrap().is_none());
+	}
+
+	#[test]
+	fn missing_state_is_detected() {
+		let backend = prepare_chain();
+		let mut transaction = DBTransaction::new();
+		for (key, _) in backend.storage.db.iter(columns::STATE) {
+			transaction.delete(columns::STATE, &key);
+		}
+		backend.storage.db.write(transaction).unwrap();
+
+		let report = check(&backend, true);
+		assert!(!report.errors.is_empty());
+		assert!(report.errors.iter().all(|e| e.starts_with("State of canonical block")), "{:?}", report.errors);
+	}
+
+	#[test]
+	fn trie_nodes_are_visited_once() {
+		let backend = prepare_chain();
+		let db = backend.storage.db.clone();
+		let state_db = StateDb::new(PruningMode::keep_blocks(16), &StateMetaDb(&*db)).unwrap();
+		let mut checker = Checker::<Block> {
+			storage: StorageDb { db: db.clone(), state_db },
+			best_number: 3,
+			visited: HashSet::new(),
+			previously_visited: HashSet::new(),
+		};
+
+		let mut report = CheckReport::default();
+		checker.check_canonical_chain(&mut report).unwrap();
+		assert_eq!(report.states, 4);
+		assert_eq!(report.nodes as usize, db.iter(columns::STATE).count());
+	}
+
+	#[test]
+	fn missing_node_is_reported_for_every_state() {
+		let backend = Backend::<Block>::new_test(16, 0);
+		let genesis_storage = vec![(vec![0x00], vec![42; 64]), (vec![0x01], vec![42; 64])];
+		let mut hash = insert_block(&backend, 0, Default::default(), genesis_storage);
+		hash = insert_block(&backend, 1, hash, vec![(vec![0x10], vec![42; 64])]);
+		insert_block(&backend, 2, hash, vec![(vec![0x20], vec![42; 64])]);
+
+		// the leaf of the key 0x01 is shared by all the states, and so is its parent from block #1.
+		let db = backend.storage.db.clone();
+		let leaf = db.iter(columns::STATE)
+			.map(|(key, _)| key)
+			.find(|key| key.len() == 33 && key[0] == 0x01)
+			.unwrap();
+		let mut transaction = DBTransaction::new();
+		transaction.delete(columns::STATE, &leaf);
+		db.write(transaction).unwrap();
+
+		let report = check(&backend, false);
+		assert_eq!(report.errors.len(), 3, "{:?}", report.errors);
+		assert!(report.errors.iter().all(|e| e.starts_with("State of canonical block")), "{:?}", report.errors);
+	}
+}
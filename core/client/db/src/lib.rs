@@ -14,7 +14,8 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-//! Client backend that uses RocksDB database as storage.
+//! Client backend that uses a key-value database as storage, either RocksDB or the embedded
+//! log-structured store of the `logdb` module.
 //!
 //! # Canonicality vs. Finality
 //!
@@ -30,15 +31,19 @@ pub mod light;
 pub mod offchain;
 
 mod cache;
+mod check;
+mod logdb;
+mod state_import;
 mod storage_cache;
 mod utils;
 
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::path::PathBuf;
 use std::io;
 use std::collections::HashMap;
 
-use client::backend::NewBlockState;
+use client::backend::{NewBlockState, StateEntry};
 use client::blockchain::HeaderBackend;
 use client::ExecutionStrategies;
 use client::backend::{StorageCollection, ChildStorageCollection};
@@ -67,6 +72,8 @@ use consensus_common::well_known_cache_keys;
 use crate::storage_cache::{CachingState, SharedCache, new_shared_cache};
 use log::{trace, debug, warn};
 pub use state_db::PruningMode;
+pub use crate::check::CheckReport;
+pub use crate::logdb::LogDb;
 
 #[cfg(feature = "test-helpers")]
 use client::in_mem::Backend as InMemoryBackend;
@@ -133,6 +140,14 @@ impl<B: BlockT> StateBackend<Blake2Hasher> for RefTrackingState<B> {
 		self.state.exists_child_storage(storage_key, key)
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.state.next_storage_key(key)
+	}
+
+	fn next_child_storage_key(&self, storage_key: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.state.next_child_storage_key(storage_key, key)
+	}
+
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
 		self.state.for_keys_with_prefix(prefix, f)
 	}
@@ -188,6 +203,33 @@ pub struct DatabaseSettings {
 	pub path: PathBuf,
 	/// Pruning mode.
 	pub pruning: PruningMode,
+	/// Key-value store to use.
+	pub engine: DatabaseEngine,
+}
+
+/// Key-value store backing the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseEngine {
+	/// RocksDB.
+	RocksDb,
+	/// Embedded log-structured store, keeping an index of all the keys in memory.
+	LogDb,
+}
+
+impl DatabaseEngine {
+	/// Name of the engine, as recorded in the database directory.
+	pub fn name(&self) -> &'static str {
+		match self {
+			DatabaseEngine::RocksDb => "rocksdb",
+			DatabaseEngine::LogDb => "logdb",
+		}
+	}
+}
+
+impl Default for DatabaseEngine {
+	fn default() -> Self {
+		DatabaseEngine::RocksDb
+	}
 }
 
 /// Create an instance of db-backed client.
@@ -197,6 +239,7 @@ pub fn new_client<E, S, Block, RA>(
 	genesis_storage: S,
 	execution_strategies: ExecutionStrategies,
 	keystore: Option<primitives::traits::BareCryptoStorePtr>,
+	consensus_failure_dir: Option<std::path::PathBuf>,
 ) -> Result<
 	client::Client<Backend<Block>,
 	client::LocalCallExecutor<Backend<Block>, E>, Block, RA>, client::error::Error
@@ -207,10 +250,33 @@ pub fn new_client<E, S, Block, RA>(
 		S: BuildStorage,
 {
 	let backend = Arc::new(Backend::new(settings, CANONICALIZATION_DELAY)?);
-	let executor = client::LocalCallExecutor::new(backend.clone(), executor, keystore);
+	let executor = client::LocalCallExecutor::new(backend.clone(), executor, keystore)
+		.with_consensus_failure_dir(consensus_failure_dir);
 	Ok(client::Client::new(backend, executor, genesis_storage, execution_strategies)?)
 }
 
+/// Check the integrity of the database at the given location, removing the entries no block
+/// refers to if `repair` is set.
+///
+/// Unless `repair` is set, the database is opened without recording its engine and type.
+pub fn check_database<Block>(
+	settings: DatabaseSettings,
+	repair: bool,
+) -> Result<CheckReport, client::error::Error>
+	where
+		Block: BlockT<Hash=H256>,
+{
+	if !settings.path.exists() {
+		return Err(client::error::Error::Backend(format!("No database at {}", settings.path.display())));
+	}
+	let db = if repair {
+		crate::utils::open_database(&settings, columns::META, "full")?
+	} else {
+		crate::utils::open_database_read_only(&settings, columns::META, "full")?
+	};
+	check::check_database::<Block>(db, settings.pruning, repair)
+}
+
 pub(crate) mod columns {
 	pub const META: Option<u32> = crate::utils::COLUMN_META;
 	pub const STATE: Option<u32> = Some(1);
@@ -391,6 +457,7 @@ pub struct BlockImportOperation<Block: BlockT, H: Hasher> {
 	storage_updates: StorageCollection,
 	child_storage_updates: ChildStorageCollection,
 	changes_trie_updates: MemoryDB<H>,
+	offchain_storage_updates: StorageCollection,
 	pending_block: Option<PendingBlock<Block>>,
 	aux_ops: Vec<(Vec<u8>, Option<Vec<u8>>)>,
 	finalized_blocks: Vec<(BlockId<Block>, Option<Justification>)>,
@@ -495,6 +562,11 @@ where Block: BlockT<Hash=H256>,
 		Ok(())
 	}
 
+	fn update_offchain_storage(&mut self, update: StorageCollection) -> Result<(), client::error::Error> {
+		self.offchain_storage_updates = update;
+		Ok(())
+	}
+
 	fn mark_finalized(&mut self, block: BlockId<Block>, justification: Option<Justification>) -> Result<(), client::error::Error> {
 		self.finalized_blocks.push((block, justification));
 		Ok(())
@@ -700,6 +772,9 @@ pub struct Backend<Block: BlockT> {
 	canonicalization_delay: u64,
 	shared_cache: SharedCache<Block, Blake2Hasher>,
 	import_lock: Mutex<()>,
+	/// Whether the non-canonical overlay exceeded the pruning memory budget at the last import,
+	/// so that a warning is only logged when it starts exceeding it.
+	non_canonical_mem_exceeded: AtomicBool,
 }
 
 impl<Block: BlockT<Hash=H256>> Backend<Block> {
@@ -711,14 +786,8 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 	}
 
 	fn new_inner(config: DatabaseSettings, canonicalization_delay: u64) -> Result<Self, client::error::Error> {
-		#[cfg(feature = "kvdb-rocksdb")]
 		let db = crate::utils::open_database(&config, columns::META, "full")?;
-		#[cfg(not(feature = "kvdb-rocksdb"))]
-		let db = {
-			log::warn!("Running without the RocksDB feature. The database will NOT be saved.");
-			Arc::new(kvdb_memorydb::create(crate::utils::NUM_COLUMNS))
-		};
-		Self::from_kvdb(db as Arc<_>, canonicalization_delay, &config)
+		Self::from_kvdb(db, canonicalization_delay, &config)
 	}
 
 	/// Create new memory-backed client backend for tests.
@@ -738,6 +807,7 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			state_cache_child_ratio: Some((50, 100)),
 			path: Default::default(),
 			pruning: PruningMode::keep_blocks(keep_blocks),
+			engine: DatabaseEngine::RocksDb,
 		};
 		Self::from_kvdb(
 			db,
@@ -780,6 +850,7 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 				config.state_cache_child_ratio.unwrap_or(DEFAULT_CHILD_RATIO),
 			),
 			import_lock: Default::default(),
+			non_canonical_mem_exceeded: AtomicBool::new(false),
 		})
 	}
 
@@ -960,6 +1031,9 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 	}
 
 	// performs forced canonicaliziation with a delay after importing a non-finalized block.
+	// The non-canonical overlay may exceed the pruning memory budget on its own, which pruning
+	// can't help with: blocks are never canonicalized ahead of the delay to make up for it, as
+	// that would rule out reorgs of blocks which are not finalized. A warning is logged instead.
 	fn force_delayed_canonicalize(
 		&self,
 		transaction: &mut DBTransaction,
@@ -968,6 +1042,17 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 	)
 		-> Result<(), client::error::Error>
 	{
+		let mem_exceeded = self.storage.state_db.non_canonical_mem_exceeded();
+		if mem_exceeded && !self.non_canonical_mem_exceeded.swap(true, Ordering::Relaxed) {
+			warn!(
+				target: "db",
+				"State changes of the blocks which are not finalized exceed the pruning memory limit, \
+				finality may be lagging behind",
+			);
+		} else if !mem_exceeded {
+			self.non_canonical_mem_exceeded.store(false, Ordering::Relaxed);
+		}
+
 		let number_u64 = number.saturated_into::<u64>();
 		if number_u64 > self.canonicalization_delay {
 			let new_canonical = number_u64 - self.canonicalization_delay;
@@ -993,6 +1078,71 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 		Ok(())
 	}
 
+	/// Add the block whose state was imported by `import_state`, making it the best and last
+	/// finalized block.
+	fn insert_imported_state_block(
+		&self,
+		header: Block::Header,
+		body: Option<Vec<Block::Extrinsic>>,
+		justification: Option<Justification>,
+	) -> Result<(), client::error::Error> {
+		let hash = header.hash();
+		let number = *header.number();
+		let parent_hash = *header.parent_hash();
+
+		let mut transaction = DBTransaction::new();
+
+		let lookup_key = utils::number_and_hash_to_lookup_key(number, hash)?;
+		utils::insert_hash_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash)?;
+		utils::insert_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash)?;
+		transaction.put(columns::HEADER, &lookup_key, &header.encode());
+		if let Some(body) = body {
+			transaction.put(columns::BODY, &lookup_key, &body.encode());
+		}
+		if let Some(justification) = justification {
+			transaction.put(columns::JUSTIFICATION, &lookup_key, &justification.encode());
+		}
+		transaction.put(columns::META, meta_keys::BEST_BLOCK, &lookup_key);
+		transaction.put(columns::META, meta_keys::FINALIZED_BLOCK, &lookup_key);
+
+		// the blocks between genesis and the imported one are never known to the state database,
+		// which only tracks the changes of the descendants of the imported block.
+		let number_u64 = number.saturated_into::<u64>();
+		let state_commit = self.storage.state_db
+			.insert_detached_block(&hash, number_u64, &parent_hash, state_db::ChangeSet::default())
+			.and_then(|insert| self.storage.state_db.canonicalize_block(&hash).map(|finalize| (insert, finalize)));
+		let (insert, finalize) = match state_commit {
+			Ok(commits) => commits,
+			Err(e) => {
+				self.storage.state_db.revert_pending();
+				let e: state_db::Error<io::Error> = e;
+				return Err(client::error::Error::from(format!("State database error: {:?}", e)));
+			}
+		};
+		apply_state_commit(&mut transaction, insert);
+		apply_state_commit(&mut transaction, finalize);
+		state_import::commit(&*self.storage.db, &mut transaction);
+
+		// the genesis leaf is displaced by the imported block.
+		let mut leaves = self.blockchain.leaves.read().clone();
+		let _ = leaves.finalize_height(number);
+		let _ = leaves.import(hash, number, parent_hash);
+		leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
+		children::write_children(&mut transaction, columns::META, meta_keys::CHILDREN_PREFIX, parent_hash, vec![hash]);
+
+		if let Err(e) = self.storage.db.write(transaction).map_err(db_err) {
+			self.storage.state_db.revert_pending();
+			return Err(e);
+		}
+		self.storage.state_db.apply_pending();
+		*self.blockchain.leaves.write() = leaves;
+
+		// nothing is known about the changes between genesis and the imported block.
+		self.shared_cache.lock().sync(&[hash], &[]);
+		self.blockchain.update_meta(hash, number, true, true);
+		Ok(())
+	}
+
 	fn try_commit_operation(&self, mut operation: BlockImportOperation<Block, Blake2Hasher>)
 		-> Result<(), client::error::Error>
 	{
@@ -1079,6 +1229,19 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			let cache = operation.old_state.release(); // release state reference so that it can be finalized
 
 
+			let offchain_changes = ::std::mem::replace(&mut operation.offchain_storage_updates, Vec::new());
+			if finalized {
+				offchain::apply_indexed_changes(&mut transaction, offchain_changes);
+			} else if number > self.blockchain.meta.read().finalized_number {
+				offchain::note_indexed_changes(
+					&*self.storage.db,
+					&mut transaction,
+					number_u64,
+					hash.as_ref(),
+					offchain_changes,
+				)?;
+			}
+
 			if finalized {
 				// TODO: ensure best chain contains this block.
 				self.ensure_sequential_finalization(header, Some(last_finalized_hash))?;
@@ -1203,6 +1366,13 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			}
 		}
 
+		offchain::finalize_indexed_changes(
+			&*self.storage.db,
+			transaction,
+			f_num.saturated_into::<u64>(),
+			f_hash.as_ref(),
+		)?;
+
 		let new_displaced = self.blockchain.leaves.write().finalize_height(f_num);
 		match displaced {
 			x @ &mut None => *x = Some(new_displaced),
@@ -1268,6 +1438,7 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 			storage_updates: Default::default(),
 			child_storage_updates: Default::default(),
 			changes_trie_updates: MemoryDB::default(),
+			offchain_storage_updates: Default::default(),
 			aux_ops: Vec::new(),
 			finalized_blocks: Vec::new(),
 			set_head: None,
@@ -1371,6 +1542,43 @@ impl<Block> client::backend::Backend<Block, Blake2Hasher> for Backend<Block> whe
 		Ok(n)
 	}
 
+	fn import_state<I>(
+		&self,
+		header: Block::Header,
+		body: Option<Vec<Block::Extrinsic>>,
+		justification: Option<Justification>,
+		state: I,
+	) -> Result<(), client::error::Error> where
+		I: IntoIterator<Item=Result<StateEntry, client::error::Error>>,
+	{
+		let info = self.blockchain.info();
+		if !info.best_number.is_zero() || !info.finalized_number.is_zero() {
+			return Err(client::error::Error::Backend(
+				"Cannot import state when blocks other than genesis are known".into()
+			));
+		}
+
+		if header.number().is_zero() {
+			return Err(client::error::Error::Backend("Cannot import state of the genesis block".into()));
+		}
+
+		// the trie nodes are written as the state is imported. Until the block is added below, they
+		// are removed when the import fails, also by the next import if the node was interrupted.
+		state_import::revert(&*self.storage.db)?;
+		let root = state_import::StateImport::new(&*self.storage.db).import(state)?;
+		let result = if root != *header.state_root() {
+			Err(client::error::Error::InvalidStateRoot)
+		} else {
+			self.insert_imported_state_block(header, body, justification)
+		};
+		if result.is_err() {
+			if let Err(e) = state_import::revert(&*self.storage.db) {
+				warn!("Failed to remove the nodes of a failed state import: {:?}", e);
+			}
+		}
+		result
+	}
+
 	fn blockchain(&self) -> &BlockchainDb<Block> {
 		&self.blockchain
 	}
@@ -2136,6 +2344,65 @@ mod tests {
 		assert_eq!(backend.blockchain().leaves().unwrap(), vec![block2_a, block2_b, block2_c]);
 	}
 
+	#[test]
+	fn import_state_works() {
+		let backend = Backend::<Block>::new_test(2, 0);
+		let genesis = insert_header(&backend, 0, Default::default(), Vec::new(), Default::default());
+
+		let child_key = b":child_storage:default:child".to_vec();
+		let child_root = BlakeTwo256::trie_root::<_, &[u8], &[u8]>(vec![(&b"key"[..], &b"child"[..])]);
+		let header = Header {
+			number: 10,
+			parent_hash: H256::repeat_byte(1),
+			state_root: BlakeTwo256::trie_root::<_, &[u8], &[u8]>(vec![
+				(&child_key[..], child_root.as_ref()),
+				(&b"key"[..], &b"value"[..]),
+			]),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let hash = header.hash();
+		let state = || vec![
+			Ok(StateEntry::Child(child_key.clone(), b"key".to_vec(), b"child".to_vec())),
+			Ok(StateEntry::Top(b"key".to_vec(), b"value".to_vec())),
+		];
+
+		// the state root is checked, the nodes of a failed import are removed.
+		let state_nodes = || backend.storage.db.iter(columns::STATE).count();
+		let genesis_nodes = state_nodes();
+		assert!(backend.import_state(header.clone(), None, None, state().into_iter().skip(1)).is_err());
+		assert_eq!(state_nodes(), genesis_nodes);
+		assert!(backend.import_state(
+			header.clone(),
+			None,
+			None,
+			state().into_iter().chain(Some(Err(client::error::Error::Backend("interrupted".into())))),
+		).is_err());
+		assert_eq!(state_nodes(), genesis_nodes);
+
+		backend.import_state(header.clone(), Some(Vec::new()), Some(vec![1, 2, 3]), state()).unwrap();
+
+		let info = backend.blockchain().info();
+		assert_eq!(info.genesis_hash, genesis);
+		assert_eq!((info.best_number, info.best_hash), (10, hash));
+		assert_eq!((info.finalized_number, info.finalized_hash), (10, hash));
+		assert_eq!(backend.blockchain().body(BlockId::Hash(hash)).unwrap(), Some(Vec::new()));
+		assert_eq!(backend.blockchain().justification(BlockId::Hash(hash)).unwrap(), Some(vec![1, 2, 3]));
+		let state_at = backend.state_at(BlockId::Hash(hash)).unwrap();
+		assert_eq!(state_at.storage(b"key").unwrap(), Some(b"value".to_vec()));
+		assert_eq!(state_at.child_storage(&child_key, b"key").unwrap(), Some(b"child".to_vec()));
+		assert_eq!(backend.blockchain().leaves().unwrap(), vec![hash]);
+		// the nodes of the committed import are kept.
+		state_import::revert(&*backend.storage.db).unwrap();
+		assert_eq!(backend.state_at(BlockId::Hash(hash)).unwrap().storage(b"key").unwrap(), Some(b"value".to_vec()));
+
+		let child = insert_header(&backend, 11, hash, Vec::new(), Default::default());
+		assert_eq!(backend.blockchain().info().best_hash, child);
+
+		// state is only imported on top of genesis.
+		assert!(backend.import_state(header, None, None, state()).is_err());
+	}
+
 	#[test]
 	fn test_aux() {
 		let backend: Backend<test_client::runtime::Block> = Backend::new_test(0, 0);
@@ -2194,4 +2461,132 @@ mod tests {
 			backend.commit_operation(op).unwrap_err();
 		}
 	}
+
+	#[test]
+	fn offchain_storage_changes_are_written_on_finalization() {
+		use client::backend::OffchainStorage;
+		use primitives::offchain::STORAGE_PREFIX;
+
+		let backend = Backend::<Block>::new_test(10, 10);
+		let import = |number, parent_hash, extrinsics_root, changes| {
+			let header = Header {
+				number,
+				parent_hash,
+				state_root: BlakeTwo256::trie_root::<_, &[u8], &[u8]>(Vec::new()),
+				digest: Default::default(),
+				extrinsics_root,
+			};
+			let hash = header.hash();
+			let mut op = backend.begin_operation().unwrap();
+			backend.begin_state_operation(&mut op, BlockId::Hash(parent_hash)).unwrap();
+			op.set_block_data(header, None, None, NewBlockState::Normal).unwrap();
+			op.update_offchain_storage(changes).unwrap();
+			backend.commit_operation(op).unwrap();
+			hash
+		};
+
+		let block0 = insert_header(&backend, 0, Default::default(), Default::default(), Default::default());
+		let mut offchain = backend.offchain_storage().unwrap();
+		offchain.set(STORAGE_PREFIX, b"removed", b"value");
+
+		let block1_a = import(1, block0, Default::default(), vec![
+			(b"indexed".to_vec(), Some(b"a".to_vec())),
+			(b"removed".to_vec(), None),
+		]);
+		import(1, block0, [1; 32].into(), vec![
+			(b"indexed".to_vec(), Some(b"b".to_vec())),
+			(b"fork".to_vec(), Some(b"b".to_vec())),
+		]);
+
+		// nothing is visible until finalization
+		assert_eq!(offchain.get(STORAGE_PREFIX, b"indexed"), None);
+		assert_eq!(offchain.get(STORAGE_PREFIX, b"removed"), Some(b"value".to_vec()));
+
+		backend.finalize_block(BlockId::hash(block1_a), None).unwrap();
+
+		assert_eq!(offchain.get(STORAGE_PREFIX, b"indexed"), Some(b"a".to_vec()));
+		assert_eq!(offchain.get(STORAGE_PREFIX, b"removed"), None);
+		// changes of the abandoned fork are discarded
+		assert_eq!(offchain.get(STORAGE_PREFIX, b"fork"), None);
+
+		// changes of blocks imported on top of a finalized one are applied right away
+		let header = Header {
+			number: 2,
+			parent_hash: block1_a,
+			state_root: BlakeTwo256::trie_root::<_, &[u8], &[u8]>(Vec::new()),
+			digest: Default::default(),
+			extrinsics_root: Default::default(),
+		};
+		let mut op = backend.begin_operation().unwrap();
+		backend.begin_state_operation(&mut op, BlockId::Hash(block1_a)).unwrap();
+		op.set_block_data(header, None, None, NewBlockState::Final).unwrap();
+		op.update_offchain_storage(vec![(b"final".to_vec(), Some(b"value".to_vec()))]).unwrap();
+		backend.commit_operation(op).unwrap();
+		assert_eq!(offchain.get(STORAGE_PREFIX, b"final"), Some(b"value".to_vec()));
+	}
+
+	#[test]
+	fn database_engines_store_the_same_data() {
+		fn import_blocks(db: Arc<dyn KeyValueDB>) -> Vec<Vec<(Box<[u8]>, Box<[u8]>)>> {
+			let backend = Backend::<Block>::new_test_db(2, 10, db.clone());
+			let import = |number, parent_hash, extrinsics_root, storage: Vec<(Vec<u8>, Option<Vec<u8>>)>| {
+				let mut op = backend.begin_operation().unwrap();
+				backend.begin_state_operation(&mut op, BlockId::Hash(parent_hash)).unwrap();
+				let (root, overlay) = op.old_state.storage_root(storage.iter().cloned());
+				if number == 0 {
+					let storage = storage.into_iter().filter_map(|(k, v)| v.map(|v| (k, v))).collect();
+					op.reset_storage(storage, Default::default()).unwrap();
+				} else {
+					op.update_db_storage(overlay).unwrap();
+				}
+				let header = Header {
+					number,
+					parent_hash,
+					state_root: root.into(),
+					digest: Default::default(),
+					extrinsics_root,
+				};
+				let hash = header.hash();
+				op.set_block_data(header, Some(vec![]), None, NewBlockState::Best).unwrap();
+				backend.commit_operation(op).unwrap();
+				hash
+			};
+
+			let block0 = import(0, Default::default(), Default::default(), vec![
+				(vec![1], Some(vec![1])),
+				(vec![2], Some(vec![2])),
+			]);
+			let block1_a = import(1, block0, Default::default(), vec![
+				(vec![1], None),
+				(vec![3], Some(vec![3])),
+			]);
+			let block1_b = import(1, block0, [1; 32].into(), vec![(vec![2], Some(vec![4]))]);
+			let block2 = import(2, block1_a, Default::default(), vec![(vec![3], Some(vec![5]))]);
+			backend.finalize_block(BlockId::hash(block1_a), Some(vec![1, 2, 3])).unwrap();
+			backend.insert_aux(&[(&b"aux"[..], &b"value"[..])], &[]).unwrap();
+
+			assert_eq!(backend.blockchain().info().best_hash, block2);
+			assert_eq!(backend.blockchain().info().finalized_hash, block1_a);
+			assert!(backend.blockchain().header(BlockId::hash(block1_b)).unwrap().is_some());
+			let state = backend.state_at(BlockId::hash(block2)).unwrap();
+			assert_eq!(state.storage(&[1]).unwrap(), None);
+			assert_eq!(state.storage(&[2]).unwrap(), Some(vec![2]));
+			assert_eq!(state.storage(&[3]).unwrap(), Some(vec![5]));
+			drop(state);
+			drop(backend);
+
+			(0..crate::utils::NUM_COLUMNS).map(|col| {
+				let mut entries: Vec<_> = db.iter(Some(col)).collect();
+				entries.sort();
+				entries
+			}).collect()
+		}
+
+		let memory = import_blocks(Arc::new(kvdb_memorydb::create(crate::utils::NUM_COLUMNS)));
+		let dir = tempdir::TempDir::new("client-db").unwrap();
+		let log = import_blocks(Arc::new(
+			crate::logdb::LogDb::open(dir.path(), crate::utils::NUM_COLUMNS).unwrap()
+		));
+		assert_eq!(memory, log);
+	}
 }
@@ -0,0 +1,945 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Embedded log-structured key-value store.
+//!
+//! Every transaction is appended to a single log file as one record, and an in-memory index
+//! maps each key to the location of its latest value in the log. Reading a value costs a single
+//! positional read, so readers never wait for each other. The space used by overwritten and
+//! deleted values is reclaimed by compacting the log, which happens in the background whenever
+//! the garbage outgrows the live data, starting from when the database is opened: the live values
+//! of a snapshot of the index are copied to a new log, the records appended in the meantime are
+//! copied after them, and the new log replaces the current one.
+//!
+//! The index keeps every key in memory, which suits the state of the chain: trie nodes are
+//! keyed by their hash and never updated in place.
+//!
+//! A record is laid out as follows, integers being little-endian:
+//! - `u32`: length of the body;
+//! - body: the operations, each made of a `u8` tag (0 for insert, 1 for delete), a `u32` column
+//!   index (0 for no column, `n + 1` for column `n`), a `u32` key length and the key, followed
+//!   for inserts by a `u32` value length and the value;
+//! - `[u8; 8]`: twox-64 hash of the body.
+//!
+//! A truncated or corrupted record at the end of the log, left by a crash, is discarded when
+//! opening the database. A corrupted record followed by other records can't have been left by a
+//! crash, so the database then fails to open rather than discarding the records after it.
+
+use std::collections::BTreeMap;
+use std::ops::Bound;
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use kvdb::{DBOp, DBTransaction, DBValue, KeyValueDB};
+use log::{debug, warn};
+use parking_lot::{Mutex, RwLock};
+use primitives::twox_64;
+
+/// Name of the log file in the database directory.
+pub const LOG_FILE: &str = "log.db";
+/// Name of the log file being written while compacting.
+const COMPACTION_FILE: &str = "log.db.compaction";
+/// Tag of an insert operation.
+const INSERT: u8 = 0;
+/// Tag of a delete operation.
+const DELETE: u8 = 1;
+/// Size of the record header (length) and trailer (checksum).
+const RECORD_OVERHEAD: u64 = 4 + 8;
+/// Size of an insert operation, besides its key and value.
+const INSERT_OVERHEAD: u64 = 1 + 4 + 4 + 4;
+/// Garbage below which the log is never compacted.
+const MIN_COMPACTION_GARBAGE: u64 = 64 * 1024 * 1024;
+/// Size of the body above which a new record is started while compacting.
+const COMPACTION_RECORD_SIZE: usize = 16 * 1024 * 1024;
+/// Size of the chunks in which the records appended during a compaction are copied.
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Location of a value in the log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Location {
+	offset: u64,
+	len: u32,
+}
+
+/// In-memory index of the log.
+struct Index {
+	/// Location of the values of each column, the first one standing for no column.
+	columns: Vec<BTreeMap<Vec<u8>, Location>>,
+	/// Length of the valid part of the log.
+	len: u64,
+	/// Size the log would have once compacted, ignoring the records overhead.
+	live: u64,
+}
+
+impl Index {
+	fn new(num_columns: usize) -> Self {
+		Index {
+			columns: vec![BTreeMap::new(); num_columns],
+			len: 0,
+			live: 0,
+		}
+	}
+
+	fn column(&self, col: Option<u32>) -> io::Result<usize> {
+		let column = col.map_or(0, |col| col as usize + 1);
+		if column < self.columns.len() {
+			Ok(column)
+		} else {
+			Err(io::Error::new(io::ErrorKind::InvalidInput, format!("No such column family: {:?}", col)))
+		}
+	}
+
+	fn insert(&mut self, column: usize, key: Vec<u8>, location: Location) {
+		let key_len = key.len() as u64;
+		if let Some(old) = self.columns[column].insert(key, location) {
+			self.live -= INSERT_OVERHEAD + key_len + old.len as u64;
+		}
+		self.live += INSERT_OVERHEAD + key_len + location.len as u64;
+	}
+
+	fn remove(&mut self, column: usize, key: &[u8]) {
+		if let Some(old) = self.columns[column].remove(key) {
+			self.live -= INSERT_OVERHEAD + key.len() as u64 + old.len as u64;
+		}
+	}
+
+	fn needs_compaction(&self) -> bool {
+		let garbage = self.len.saturating_sub(self.live);
+		garbage > MIN_COMPACTION_GARBAGE && garbage > self.live
+	}
+}
+
+/// Operations of a record being built, along with the index updates they imply.
+#[derive(Default)]
+struct Record {
+	body: Vec<u8>,
+	/// Column, key and location of the value in the body for inserts.
+	updates: Vec<(usize, Vec<u8>, Option<(usize, u32)>)>,
+}
+
+impl Record {
+	fn insert(&mut self, column: usize, key: Vec<u8>, value: &[u8]) -> io::Result<()> {
+		self.push_operation(INSERT, column, &key)?;
+		let len = encoded_len(value.len())?;
+		self.body.extend_from_slice(&len.to_le_bytes());
+		let value_offset = self.body.len();
+		self.body.extend_from_slice(value);
+		self.updates.push((column, key, Some((value_offset, len))));
+		Ok(())
+	}
+
+	fn delete(&mut self, column: usize, key: Vec<u8>) -> io::Result<()> {
+		self.push_operation(DELETE, column, &key)?;
+		self.updates.push((column, key, None));
+		Ok(())
+	}
+
+	fn push_operation(&mut self, tag: u8, column: usize, key: &[u8]) -> io::Result<()> {
+		let column = encoded_len(column)?;
+		let key_len = encoded_len(key.len())?;
+		self.body.push(tag);
+		self.body.extend_from_slice(&column.to_le_bytes());
+		self.body.extend_from_slice(&key_len.to_le_bytes());
+		self.body.extend_from_slice(key);
+		Ok(())
+	}
+
+	/// Write the record to the log at the given offset.
+	///
+	/// The caller is responsible for syncing the file.
+	fn write_at(&self, file: &File, offset: u64) -> io::Result<()> {
+		let len = encoded_len(self.body.len())?;
+		let mut writer = Positioned { file, offset };
+		writer.write_all(&len.to_le_bytes())?;
+		writer.write_all(&self.body)?;
+		writer.write_all(&twox_64(&self.body))
+	}
+
+	/// Update the index with the operations of the record, written at the end of the valid part
+	/// of the log.
+	fn commit(self, index: &mut Index) {
+		let offset = index.len;
+		index.len += RECORD_OVERHEAD + self.body.len() as u64;
+		for (column, key, value) in self.updates {
+			match value {
+				Some((value_offset, len)) => index.insert(column, key, Location {
+					offset: offset + 4 + value_offset as u64,
+					len,
+				}),
+				None => index.remove(column, &key),
+			}
+		}
+	}
+
+	/// Append the record at the end of the valid part of the log and update the index.
+	///
+	/// The caller is responsible for syncing the file.
+	fn append(self, file: &File, index: &mut Index) -> io::Result<()> {
+		self.write_at(file, index.len)?;
+		self.commit(index);
+		Ok(())
+	}
+}
+
+/// Log file along with its index.
+struct Log {
+	file: Arc<File>,
+	index: Index,
+}
+
+/// Log holding the live values of a snapshot of the index, meant to replace the current log.
+struct Compaction {
+	/// Log the snapshot has been taken from.
+	source: Arc<File>,
+	/// Length of the valid part of the source log when the snapshot has been taken.
+	source_len: u64,
+	file: File,
+	index: Index,
+}
+
+struct Inner {
+	path: PathBuf,
+	/// Serializes the appends to the log, and the replacement of the log once compacted.
+	writer: Mutex<()>,
+	log: RwLock<Log>,
+	/// Whether a compaction is running in the background.
+	compacting: AtomicBool,
+}
+
+impl Inner {
+	/// Rewrite the log with the live values only.
+	fn compact(&self) -> io::Result<()> {
+		let compaction = self.copy_live_values()?;
+		self.replace_log(compaction)
+	}
+
+	/// Copy the live values of a snapshot of the index to a new log, without blocking reads or
+	/// writes.
+	fn copy_live_values(&self) -> io::Result<Compaction> {
+		let (source, source_len, columns) = {
+			let log = self.log.read();
+			(log.file.clone(), log.index.len, log.index.columns.clone())
+		};
+		debug!(target: "db", "Compacting database log of {} bytes", source_len);
+
+		let file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(true)
+			.open(self.path.join(COMPACTION_FILE))?;
+		let mut index = Index::new(columns.len());
+		let mut record = Record::default();
+		for (column, values) in columns.into_iter().enumerate() {
+			for (key, location) in values {
+				let value = read_value(&source, &location)?;
+				record.insert(column, key, &value)?;
+				if record.body.len() >= COMPACTION_RECORD_SIZE {
+					std::mem::replace(&mut record, Record::default()).append(&file, &mut index)?;
+				}
+			}
+		}
+		if !record.body.is_empty() {
+			record.append(&file, &mut index)?;
+		}
+
+		Ok(Compaction { source, source_len, file, index })
+	}
+
+	/// Copy the records appended since the snapshot of the compaction was taken, and replace the
+	/// current log with the compacted one.
+	fn replace_log(&self, compaction: Compaction) -> io::Result<()> {
+		let Compaction { source, source_len, file, mut index } = compaction;
+		let _writer = self.writer.lock();
+		let len = self.log.read().index.len;
+		let appended = len - source_len;
+		copy(&source, source_len, &file, index.len, appended)?;
+		let compacted_len = index.len + appended;
+		replay(&file, compacted_len, &mut index)?;
+		if index.len != compacted_len {
+			return Err(invalid_data("Records appended during compaction could not be replayed"));
+		}
+		file.sync_all()?;
+		fs::rename(self.path.join(COMPACTION_FILE), self.path.join(LOG_FILE))?;
+		sync_dir(&self.path)?;
+
+		debug!(target: "db", "Compacted database log from {} to {} bytes", len, index.len);
+		*self.log.write() = Log { file: Arc::new(file), index };
+		Ok(())
+	}
+}
+
+/// Log-structured key-value database.
+pub struct LogDb {
+	inner: Arc<Inner>,
+	/// Thread of the last compaction started in the background.
+	compaction: Mutex<Option<thread::JoinHandle<()>>>,
+	/// Whether the database has been opened read-only.
+	read_only: bool,
+	/// Error of a buffered write, returned by the next write or flush.
+	buffered_error: Mutex<Option<io::Error>>,
+}
+
+impl LogDb {
+	/// Open the database in the given directory, creating it if needed.
+	pub fn open(path: &Path, num_columns: u32) -> io::Result<Self> {
+		fs::create_dir_all(path)?;
+		let file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.create(true)
+			.open(path.join(LOG_FILE))?;
+		sync_dir(path)?;
+		Self::open_log(path, file, num_columns, false)
+	}
+
+	/// Open the existing database in the given directory without ever writing to it.
+	///
+	/// Incomplete records at the end of the log are ignored rather than discarded, the log is
+	/// never compacted and writes fail.
+	pub fn open_read_only(path: &Path, num_columns: u32) -> io::Result<Self> {
+		let file = File::open(path.join(LOG_FILE))?;
+		Self::open_log(path, file, num_columns, true)
+	}
+
+	fn open_log(path: &Path, file: File, num_columns: u32, read_only: bool) -> io::Result<Self> {
+		let mut index = Index::new(num_columns as usize + 1);
+		let file_len = file.metadata()?.len();
+		replay(&file, file_len, &mut index)?;
+		if index.len < file_len && read_only {
+			warn!(target: "db", "Ignoring {} bytes of incomplete records at the end of the database log",
+				file_len - index.len);
+		} else if index.len < file_len {
+			warn!(target: "db", "Discarding {} bytes of incomplete records at the end of the database log",
+				file_len - index.len);
+			file.set_len(index.len)?;
+			file.sync_all()?;
+		}
+
+		let needs_compaction = !read_only && index.needs_compaction();
+		let db = LogDb {
+			inner: Arc::new(Inner {
+				path: path.to_owned(),
+				writer: Mutex::new(()),
+				log: RwLock::new(Log { file: Arc::new(file), index }),
+				compacting: AtomicBool::new(false),
+			}),
+			compaction: Mutex::new(None),
+			read_only,
+			buffered_error: Mutex::new(None),
+		};
+		if needs_compaction {
+			db.start_compaction();
+		}
+		Ok(db)
+	}
+
+	/// Compact the log in the background, unless a compaction is already running.
+	fn start_compaction(&self) {
+		if self.inner.compacting.swap(true, Ordering::SeqCst) {
+			return;
+		}
+
+		let inner = self.inner.clone();
+		let spawned = thread::Builder::new()
+			.name("logdb-compaction".into())
+			.spawn(move || {
+				if let Err(e) = inner.compact() {
+					warn!(target: "db", "Error compacting the database log: {:?}", e);
+				}
+				inner.compacting.store(false, Ordering::SeqCst);
+			});
+		match spawned {
+			Ok(handle) => if let Some(previous) = self.compaction.lock().replace(handle) {
+				let _ = previous.join();
+			},
+			Err(e) => {
+				warn!(target: "db", "Error starting the compaction of the database log: {:?}", e);
+				self.inner.compacting.store(false, Ordering::SeqCst);
+			},
+		}
+	}
+
+	/// Append the transaction to the log as a single record.
+	fn write_transaction(&self, transaction: DBTransaction) -> io::Result<()> {
+		if transaction.ops.is_empty() {
+			return Ok(());
+		}
+		if self.read_only {
+			return Err(io::Error::new(io::ErrorKind::PermissionDenied, "The database log is opened read-only"));
+		}
+
+		// the log only changes under the writer lock, so that the record can be written without
+		// blocking the readers.
+		let _writer = self.inner.writer.lock();
+		let (file, offset, record) = {
+			let log = self.inner.log.read();
+			let mut record = Record::default();
+			for op in transaction.ops {
+				match op {
+					DBOp::Insert { col, key, value } =>
+						record.insert(log.index.column(col)?, key.into_vec(), &value)?,
+					DBOp::Delete { col, key } => record.delete(log.index.column(col)?, key.into_vec())?,
+				}
+			}
+			(log.file.clone(), log.index.len, record)
+		};
+		if let Err(e) = record.write_at(&file, offset).and_then(|_| file.sync_data()) {
+			// a partial record followed by other records would be taken for a corruption.
+			let _ = file.set_len(offset);
+			return Err(e);
+		}
+
+		let needs_compaction = {
+			let mut log = self.inner.log.write();
+			record.commit(&mut log.index);
+			log.index.needs_compaction()
+		};
+		if needs_compaction {
+			self.start_compaction();
+		}
+		Ok(())
+	}
+
+	fn read(&self, col: Option<u32>, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+		let log = self.inner.log.read();
+		match log.index.columns[log.index.column(col)?].get(key) {
+			Some(location) => read_value(&log.file, location).map(Some),
+			None => Ok(None),
+		}
+	}
+
+	/// Iterate over the entries of a column starting with the given prefix.
+	fn iter_prefix<'a>(&'a self, col: Option<u32>, prefix: &[u8]) -> Iter<'a> {
+		let column = self.inner.log.read().index.column(col).ok();
+		Iter { inner: &self.inner, column, prefix: prefix.to_vec(), last: None }
+	}
+}
+
+/// Iterator over the entries of a column, in key order.
+///
+/// Each entry is looked up in the index when yielded, after the key of the previous one, so that
+/// the keys are not copied upfront. Entries written during the iteration are therefore yielded
+/// if their key comes after the last yielded one.
+struct Iter<'a> {
+	inner: &'a Inner,
+	/// Column being iterated over, `None` once the iteration is over.
+	column: Option<usize>,
+	prefix: Vec<u8>,
+	/// Key of the last yielded entry.
+	last: Option<Vec<u8>>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+	type Item = (Box<[u8]>, Box<[u8]>);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let column = self.column?;
+		let log = self.inner.log.read();
+		let lower = match &self.last {
+			Some(last) => Bound::Excluded(&last[..]),
+			None => Bound::Included(&self.prefix[..]),
+		};
+		let entry = log.index.columns[column]
+			.range::<[u8], _>((lower, Bound::Unbounded))
+			.next()
+			.filter(|(key, _)| key.starts_with(&self.prefix));
+		let (key, location) = match entry {
+			Some(entry) => entry,
+			None => {
+				self.column = None;
+				return None;
+			}
+		};
+		match read_value(&log.file, location) {
+			Ok(value) => {
+				self.last = Some(key.clone());
+				Some((key.clone().into_boxed_slice(), value.into_boxed_slice()))
+			},
+			Err(e) => {
+				warn!(target: "db", "Error reading database log: {:?}", e);
+				self.column = None;
+				None
+			}
+		}
+	}
+}
+
+impl Drop for LogDb {
+	fn drop(&mut self) {
+		if let Some(compaction) = self.compaction.get_mut().take() {
+			let _ = compaction.join();
+		}
+	}
+}
+
+impl KeyValueDB for LogDb {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> io::Result<Option<DBValue>> {
+		self.read(col, key).map(|value| value.map(|value| DBValue::from_slice(&value)))
+	}
+
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>> {
+		let log = self.inner.log.read();
+		let column = log.index.column(col).ok()?;
+		let (key, location) = log.index.columns[column].range(prefix.to_vec()..).next()?;
+		if !key.starts_with(prefix) {
+			return None;
+		}
+		read_value(&log.file, location).ok().map(|value| value.into_boxed_slice())
+	}
+
+	/// Writes the transaction right away. An error is returned by the next `write` or `flush`,
+	/// and the transactions buffered until then are dropped, so that none is applied over a
+	/// missing one.
+	fn write_buffered(&self, transaction: DBTransaction) {
+		let mut error = self.buffered_error.lock();
+		if error.is_none() {
+			*error = self.write_transaction(transaction).err();
+		}
+	}
+
+	fn write(&self, transaction: DBTransaction) -> io::Result<()> {
+		if let Some(e) = self.buffered_error.lock().take() {
+			return Err(e);
+		}
+		self.write_transaction(transaction)
+	}
+
+	fn flush(&self) -> io::Result<()> {
+		if let Some(e) = self.buffered_error.lock().take() {
+			return Err(e);
+		}
+		self.inner.log.read().file.sync_data()
+	}
+
+	fn iter<'a>(&'a self, col: Option<u32>) -> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a> {
+		Box::new(self.iter_prefix(col, &[]))
+	}
+
+	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
+		-> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+	{
+		Box::new(self.iter_prefix(col, prefix))
+	}
+
+	fn restore(&self, _new_db: &str) -> io::Result<()> {
+		Err(io::Error::new(io::ErrorKind::Other, "Restoring is not supported by the log database"))
+	}
+}
+
+/// Sequential access to a file from the given offset, through positional reads and writes
+/// which leave the cursor of the file alone, so that the file can be shared between threads.
+struct Positioned<'a> {
+	file: &'a File,
+	offset: u64,
+}
+
+impl<'a> Read for Positioned<'a> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let read = read_at(self.file, buf, self.offset)?;
+		self.offset += read as u64;
+		Ok(read)
+	}
+}
+
+impl<'a> Write for Positioned<'a> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let written = write_at(self.file, buf, self.offset)?;
+		self.offset += written as u64;
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+	std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+	std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+	std::os::unix::fs::FileExt::write_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+fn write_at(file: &File, buf: &[u8], offset: u64) -> io::Result<usize> {
+	std::os::windows::fs::FileExt::seek_write(file, buf, offset)
+}
+
+/// Make the creation or replacement of a file of the given directory durable.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+	File::open(path)?.sync_all()
+}
+
+/// Directories can't be opened as files on Windows, where renames are journaled by the file
+/// system.
+#[cfg(windows)]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+	Ok(())
+}
+
+fn read_value(file: &File, location: &Location) -> io::Result<Vec<u8>> {
+	let mut value = vec![0; location.len as usize];
+	Positioned { file, offset: location.offset }.read_exact(&mut value)?;
+	Ok(value)
+}
+
+/// Copy `len` bytes from one file to another.
+fn copy(from: &File, from_offset: u64, to: &File, to_offset: u64, len: u64) -> io::Result<()> {
+	let mut reader = Positioned { file: from, offset: from_offset };
+	let mut writer = Positioned { file: to, offset: to_offset };
+	let mut chunk = vec![0; COPY_CHUNK_SIZE];
+	let mut remaining = len;
+	while remaining > 0 {
+		let chunk_len = std::cmp::min(remaining, COPY_CHUNK_SIZE as u64) as usize;
+		reader.read_exact(&mut chunk[..chunk_len])?;
+		writer.write_all(&chunk[..chunk_len])?;
+		remaining -= chunk_len as u64;
+	}
+	Ok(())
+}
+
+/// Length of a body, column index, key or value as stored in the log.
+fn encoded_len(len: usize) -> io::Result<u32> {
+	u32::try_from(len).map_err(|_| io::Error::new(
+		io::ErrorKind::InvalidInput,
+		format!("Length of {} bytes is too large for the database log", len),
+	))
+}
+
+/// Extend the index with the records of the log past its valid part, stopping at an incomplete
+/// or corrupted record at the end of the log.
+///
+/// Fails on a corrupted record followed by other records.
+fn replay(file: &File, file_len: u64, index: &mut Index) -> io::Result<()> {
+	let mut reader = BufReader::new(Positioned { file, offset: index.len });
+	while index.len + RECORD_OVERHEAD <= file_len {
+		let mut len = [0u8; 4];
+		reader.read_exact(&mut len)?;
+		let len = u32::from_le_bytes(len) as u64;
+		let end = index.len + RECORD_OVERHEAD + len;
+		if end > file_len {
+			break;
+		}
+
+		let mut body = vec![0; len as usize];
+		let mut checksum = [0u8; 8];
+		reader.read_exact(&mut body)?;
+		reader.read_exact(&mut checksum)?;
+		if twox_64(&body) != checksum {
+			if end == file_len {
+				break;
+			}
+			return Err(invalid_data(&format!(
+				"Corrupted record at offset {} of the database log, followed by {} bytes",
+				index.len,
+				file_len - end,
+			)));
+		}
+
+		apply(&body, index.len, index)?;
+		index.len = end;
+	}
+	Ok(())
+}
+
+/// Apply the operations of the record at the given offset to the index.
+fn apply(body: &[u8], offset: u64, index: &mut Index) -> io::Result<()> {
+	let mut pos = 0;
+	while pos < body.len() {
+		let tag = take(body, &mut pos, 1)?[0];
+		let column = take_u32(body, &mut pos)? as usize;
+		let key_len = take_u32(body, &mut pos)? as usize;
+		let key = take(body, &mut pos, key_len)?.to_vec();
+		if column >= index.columns.len() {
+			return Err(invalid_data("Unknown column in the database log"));
+		}
+
+		match tag {
+			INSERT => {
+				let len = take_u32(body, &mut pos)?;
+				let value_offset = pos;
+				take(body, &mut pos, len as usize)?;
+				index.insert(column, key, Location { offset: offset + 4 + value_offset as u64, len });
+			},
+			DELETE => index.remove(column, &key),
+			_ => return Err(invalid_data("Unknown operation in the database log")),
+		}
+	}
+	Ok(())
+}
+
+fn take<'a>(body: &'a [u8], pos: &mut usize, len: usize) -> io::Result<&'a [u8]> {
+	let end = pos.checked_add(len)
+		.filter(|end| *end <= body.len())
+		.ok_or_else(|| invalid_data("Truncated operation in the database log"))?;
+	let data = &body[*pos..end];
+	*pos = end;
+	Ok(data)
+}
+
+fn take_u32(body: &[u8], pos: &mut usize) -> io::Result<u32> {
+	let mut bytes = [0u8; 4];
+	bytes.copy_from_slice(take(body, pos, 4)?);
+	Ok(u32::from_le_bytes(bytes))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tempdir::TempDir;
+
+	fn insert(db: &LogDb, col: Option<u32>, key: &[u8], value: &[u8]) {
+		let mut transaction = DBTransaction::new();
+		transaction.put(col, key, value);
+		db.write(transaction).unwrap();
+	}
+
+	fn get(db: &LogDb, col: Option<u32>, key: &[u8]) -> Option<Vec<u8>> {
+		db.get(col, key).unwrap().map(|value| value.into_vec())
+	}
+
+	#[test]
+	fn reads_written_values() {
+		let dir = TempDir::new("logdb").unwrap();
+		let db = LogDb::open(dir.path(), 2).unwrap();
+
+		insert(&db, None, b"key", b"none");
+		insert(&db, Some(1), b"key", b"one");
+		insert(&db, Some(1), b"prefix1", b"1");
+		insert(&db, Some(1), b"prefix2", b"2");
+		assert_eq!(get(&db, None, b"key"), Some(b"none".to_vec()));
+		assert_eq!(get(&db, Some(0), b"key"), None);
+		assert_eq!(get(&db, Some(1), b"key"), Some(b"one".to_vec()));
+		assert!(db.get(Some(2), b"key").is_err());
+
+		assert_eq!(db.get_by_prefix(Some(1), b"pre").map(|v| v.to_vec()), Some(b"1".to_vec()));
+		assert_eq!(
+			db.iter_from_prefix(Some(1), b"prefix").map(|(k, _)| k.to_vec()).collect::<Vec<_>>(),
+			vec![b"prefix1".to_vec(), b"prefix2".to_vec()],
+		);
+
+		let mut transaction = DBTransaction::new();
+		transaction.delete(Some(1), b"key");
+		transaction.put(None, b"key", b"overwritten");
+		db.write(transaction).unwrap();
+		assert_eq!(get(&db, Some(1), b"key"), None);
+		assert_eq!(get(&db, None, b"key"), Some(b"overwritten".to_vec()));
+	}
+
+	#[test]
+	fn iteration_reads_entries_when_yielded() {
+		let dir = TempDir::new("logdb").unwrap();
+		let db = LogDb::open(dir.path(), 1).unwrap();
+
+		insert(&db, Some(0), b"a", b"1");
+		insert(&db, Some(0), b"b", b"2");
+		insert(&db, Some(0), b"d", b"4");
+		let mut iter = db.iter(Some(0));
+		assert_eq!(iter.next().map(|(k, v)| (k.to_vec(), v.to_vec())), Some((b"a".to_vec(), b"1".to_vec())));
+
+		insert(&db, Some(0), b"b", b"overwritten");
+		insert(&db, Some(0), b"c", b"3");
+		let mut transaction = DBTransaction::new();
+		transaction.delete(Some(0), b"d");
+		db.write(transaction).unwrap();
+		db.inner.compact().unwrap();
+
+		assert_eq!(iter.next().map(|(k, v)| (k.to_vec(), v.to_vec())), Some((b"b".to_vec(), b"overwritten".to_vec())));
+		assert_eq!(iter.next().map(|(k, v)| (k.to_vec(), v.to_vec())), Some((b"c".to_vec(), b"3".to_vec())));
+		assert!(iter.next().is_none());
+	}
+
+	#[test]
+	fn values_persist_after_reopen() {
+		let dir = TempDir::new("logdb").unwrap();
+		{
+			let db = LogDb::open(dir.path(), 1).unwrap();
+			insert(&db, Some(0), b"key1", b"value1");
+			insert(&db, Some(0), b"key2", b"value2");
+			let mut transaction = DBTransaction::new();
+			transaction.delete(Some(0), b"key1");
+			db.write(transaction).unwrap();
+		}
+
+		let db = LogDb::open(dir.path(), 1).unwrap();
+		assert_eq!(get(&db, Some(0), b"key1"), None);
+		assert_eq!(get(&db, Some(0), b"key2"), Some(b"value2".to_vec()));
+	}
+
+	#[test]
+	fn incomplete_record_is_discarded() {
+		let dir = TempDir::new("logdb").unwrap();
+		{
+			let db = LogDb::open(dir.path(), 1).unwrap();
+			insert(&db, Some(0), b"key1", b"value1");
+			insert(&db, Some(0), b"key2", b"value2");
+		}
+		let file = OpenOptions::new().write(true).open(dir.path().join(LOG_FILE)).unwrap();
+		let len = file.metadata().unwrap().len();
+		file.set_len(len - 3).unwrap();
+
+		let db = LogDb::open(dir.path(), 1).unwrap();
+		assert_eq!(get(&db, Some(0), b"key1"), Some(b"value1".to_vec()));
+		assert_eq!(get(&db, Some(0), b"key2"), None);
+
+		insert(&db, Some(0), b"key3", b"value3");
+		drop(db);
+		let db = LogDb::open(dir.path(), 1).unwrap();
+		assert_eq!(get(&db, Some(0), b"key3"), Some(b"value3".to_vec()));
+	}
+
+	#[test]
+	fn corrupted_record_before_the_end_is_refused() {
+		let dir = TempDir::new("logdb").unwrap();
+		{
+			let db = LogDb::open(dir.path(), 1).unwrap();
+			insert(&db, Some(0), b"key1", b"value1");
+			insert(&db, Some(0), b"key2", b"value2");
+		}
+		let path = dir.path().join(LOG_FILE);
+		let mut log = fs::read(&path).unwrap();
+		let len = log.len();
+		// the value of the first record
+		log[4 + INSERT_OVERHEAD as usize + 4] ^= 0xff;
+		fs::write(&path, &log).unwrap();
+
+		assert!(LogDb::open(dir.path(), 1).is_err());
+		assert!(LogDb::open_read_only(dir.path(), 1).is_err());
+		assert_eq!(fs::metadata(&path).unwrap().len(), len as u64);
+	}
+
+	#[test]
+	fn buffered_write_errors_are_returned() {
+		let dir = TempDir::new("logdb").unwrap();
+		LogDb::open(dir.path(), 1).unwrap();
+		let db = LogDb::open_read_only(dir.path(), 1).unwrap();
+
+		let mut transaction = DBTransaction::new();
+		transaction.put(Some(0), b"key", b"value");
+		db.write_buffered(transaction);
+		assert!(db.flush().is_err());
+		db.flush().unwrap();
+
+		let mut transaction = DBTransaction::new();
+		transaction.put(Some(1), b"key", b"value");
+		db.write_buffered(transaction);
+		assert!(db.write(DBTransaction::new()).is_err());
+		db.write(DBTransaction::new()).unwrap();
+	}
+
+	#[test]
+	fn read_only_database_is_left_untouched() {
+		let dir = TempDir::new("logdb").unwrap();
+		assert!(LogDb::open_read_only(dir.path(), 1).is_err());
+		{
+			let db = LogDb::open(dir.path(), 1).unwrap();
+			insert(&db, Some(0), b"key1", b"value1");
+			insert(&db, Some(0), b"key2", b"value2");
+		}
+		let file = OpenOptions::new().write(true).open(dir.path().join(LOG_FILE)).unwrap();
+		let len = file.metadata().unwrap().len() - 3;
+		file.set_len(len).unwrap();
+
+		let db = LogDb::open_read_only(dir.path(), 1).unwrap();
+		assert_eq!(get(&db, Some(0), b"key1"), Some(b"value1".to_vec()));
+		assert_eq!(get(&db, Some(0), b"key2"), None);
+		let mut transaction = DBTransaction::new();
+		transaction.put(Some(0), b"key3", b"value3");
+		assert!(db.write(transaction).is_err());
+		assert_eq!(file.metadata().unwrap().len(), len);
+	}
+
+	#[test]
+	fn compaction_keeps_live_values() {
+		let dir = TempDir::new("logdb").unwrap();
+		let db = LogDb::open(dir.path(), 1).unwrap();
+		for i in 0..16u8 {
+			insert(&db, Some(0), &[i % 4], &[i; 64]);
+		}
+		insert(&db, None, b"meta", b"data");
+
+		let len_before = db.inner.log.read().index.len;
+		db.inner.compact().unwrap();
+		let len_after = db.inner.log.read().index.len;
+		assert!(len_after < len_before);
+		assert_eq!(len_after, RECORD_OVERHEAD + db.inner.log.read().index.live);
+
+		for i in 12..16u8 {
+			assert_eq!(get(&db, Some(0), &[i % 4]), Some(vec![i; 64]));
+		}
+		drop(db);
+
+		let db = LogDb::open(dir.path(), 1).unwrap();
+		assert_eq!(db.inner.log.read().index.len, len_after);
+		assert_eq!(get(&db, None, b"meta"), Some(b"data".to_vec()));
+		assert_eq!(get(&db, Some(0), &[3]), Some(vec![15; 64]));
+	}
+
+	#[test]
+	fn compaction_keeps_values_written_meanwhile() {
+		let dir = TempDir::new("logdb").unwrap();
+		let db = LogDb::open(dir.path(), 1).unwrap();
+		for i in 0..8u8 {
+			insert(&db, Some(0), &[i % 2], &[i; 64]);
+		}
+
+		let compaction = db.inner.copy_live_values().unwrap();
+		insert(&db, Some(0), &[1], b"overwritten");
+		insert(&db, Some(0), &[2], b"inserted");
+		let mut transaction = DBTransaction::new();
+		transaction.delete(Some(0), &[0]);
+		db.write(transaction).unwrap();
+		db.inner.replace_log(compaction).unwrap();
+
+		assert_eq!(get(&db, Some(0), &[0]), None);
+		assert_eq!(get(&db, Some(0), &[1]), Some(b"overwritten".to_vec()));
+		assert_eq!(get(&db, Some(0), &[2]), Some(b"inserted".to_vec()));
+		insert(&db, Some(0), &[3], b"after");
+		drop(db);
+
+		let db = LogDb::open(dir.path(), 1).unwrap();
+		assert_eq!(get(&db, Some(0), &[0]), None);
+		assert_eq!(get(&db, Some(0), &[1]), Some(b"overwritten".to_vec()));
+		assert_eq!(get(&db, Some(0), &[2]), Some(b"inserted".to_vec()));
+		assert_eq!(get(&db, Some(0), &[3]), Some(b"after".to_vec()));
+	}
+
+	#[test]
+	#[cfg(target_pointer_width = "64")]
+	fn lengths_are_checked() {
+		assert_eq!(encoded_len(42).unwrap(), 42);
+		assert!(encoded_len(u32::max_value() as usize + 1).is_err());
+	}
+}
@@ -22,8 +22,22 @@ use std::{
 };
 
 use crate::columns;
-use kvdb::KeyValueDB;
+use crate::utils::db_err;
+use client::backend::StorageCollection;
+use codec::{Decode, Encode};
+use kvdb::{DBTransaction, KeyValueDB};
 use parking_lot::Mutex;
+use primitives::offchain::STORAGE_PREFIX;
+
+/// Keys reserved for the offchain storage changes indexed by non-finalized blocks.
+///
+/// They share the column with the offchain workers storage, so keys starting with
+/// this prefix are rejected by the `OffchainStorage` implementation.
+const INDEXED_PREFIX: &[u8] = b":indexed:";
+/// Offchain storage changes indexed by non-finalized blocks, keyed by block hash.
+const INDEXED_CHANGES_PREFIX: &[u8] = b":indexed:changes:";
+/// Hashes of the non-finalized blocks with indexed changes, keyed by block number.
+const INDEXED_JOURNAL_PREFIX: &[u8] = b":indexed:journal:";
 
 /// Offchain local storage
 #[derive(Clone)]
@@ -56,9 +70,22 @@ impl LocalStorage {
 	}
 }
 
+/// Returns the database key of given item, or `None` if it falls into the reserved keys.
+fn storage_key(prefix: &[u8], key: &[u8]) -> Option<Vec<u8>> {
+	let key: Vec<u8> = prefix.iter().chain(key).cloned().collect();
+	if key.starts_with(INDEXED_PREFIX) {
+		log::warn!("Refusing to access reserved offchain DB key: {:?}", key);
+		return None;
+	}
+	Some(key)
+}
+
 impl client::backend::OffchainStorage for LocalStorage {
 	fn set(&mut self, prefix: &[u8], key: &[u8], value: &[u8]) {
-		let key: Vec<u8> = prefix.iter().chain(key).cloned().collect();
+		let key = match storage_key(prefix, key) {
+			Some(key) => key,
+			None => return,
+		};
 		let mut tx = self.db.transaction();
 		tx.put(columns::OFFCHAIN, &key, value);
 
@@ -68,13 +95,26 @@ impl client::backend::OffchainStorage for LocalStorage {
 	}
 
 	fn get(&self, prefix: &[u8], key: &[u8]) -> Option<Vec<u8>> {
-		let key: Vec<u8> = prefix.iter().chain(key).cloned().collect();
+		let key = storage_key(prefix, key)?;
 		self.db.get(columns::OFFCHAIN, &key)
 			.ok()
 			.and_then(|x| x)
 			.map(|v| v.to_vec())
 	}
 
+	fn remove(&mut self, prefix: &[u8], key: &[u8]) {
+		let key = match storage_key(prefix, key) {
+			Some(key) => key,
+			None => return,
+		};
+		let mut tx = self.db.transaction();
+		tx.delete(columns::OFFCHAIN, &key);
+
+		if let Err(e) = self.db.write(tx) {
+			log::warn!("Error removing from the offchain DB: {:?}", e);
+		}
+	}
+
 	fn compare_and_set(
 		&mut self,
 		prefix: &[u8],
@@ -82,7 +122,10 @@ impl client::backend::OffchainStorage for LocalStorage {
 		old_value: Option<&[u8]>,
 		new_value: &[u8],
 	) -> bool {
-		let key: Vec<u8> = prefix.iter().chain(item_key).cloned().collect();
+		let key = match storage_key(prefix, item_key) {
+			Some(key) => key,
+			None => return false,
+		};
 		let key_lock = {
 			let mut locks = self.locks.lock();
 			locks.entry(key.clone()).or_default().clone()
@@ -114,6 +157,72 @@ impl client::backend::OffchainStorage for LocalStorage {
 	}
 }
 
+/// Write offchain storage changes indexed by a finalized block to the `PERSISTENT` storage.
+pub(crate) fn apply_indexed_changes(transaction: &mut DBTransaction, changes: StorageCollection) {
+	for (key, value) in changes {
+		let key: Vec<u8> = STORAGE_PREFIX.iter().chain(&key).cloned().collect();
+		match value {
+			Some(value) => transaction.put_vec(columns::OFFCHAIN, &key, value),
+			None => transaction.delete(columns::OFFCHAIN, &key),
+		}
+	}
+}
+
+/// Keep offchain storage changes indexed by a non-finalized block until it gets finalized.
+pub(crate) fn note_indexed_changes(
+	db: &dyn KeyValueDB,
+	transaction: &mut DBTransaction,
+	number: u64,
+	hash: &[u8],
+	changes: StorageCollection,
+) -> Result<(), client::error::Error> {
+	if changes.is_empty() {
+		return Ok(());
+	}
+
+	let journal_key = indexed_key(INDEXED_JOURNAL_PREFIX, &number.to_be_bytes());
+	let mut journal = read_journal(db, &journal_key)?;
+	if !journal.iter().any(|h| &h[..] == hash) {
+		journal.push(hash.to_vec());
+	}
+	transaction.put_vec(columns::OFFCHAIN, &journal_key, journal.encode());
+	transaction.put_vec(columns::OFFCHAIN, &indexed_key(INDEXED_CHANGES_PREFIX, hash), changes.encode());
+	Ok(())
+}
+
+/// Promote the offchain storage changes indexed by a newly finalized block to the
+/// `PERSISTENT` storage and discard the ones of the other blocks at the same height.
+pub(crate) fn finalize_indexed_changes(
+	db: &dyn KeyValueDB,
+	transaction: &mut DBTransaction,
+	number: u64,
+	hash: &[u8],
+) -> Result<(), client::error::Error> {
+	let journal_key = indexed_key(INDEXED_JOURNAL_PREFIX, &number.to_be_bytes());
+	for block in read_journal(db, &journal_key)? {
+		let changes_key = indexed_key(INDEXED_CHANGES_PREFIX, &block);
+		if block == hash {
+			let changes = db.get(columns::OFFCHAIN, &changes_key).map_err(db_err)?
+				.and_then(|v| StorageCollection::decode(&mut &v[..]).ok())
+				.unwrap_or_default();
+			apply_indexed_changes(transaction, changes);
+		}
+		transaction.delete(columns::OFFCHAIN, &changes_key);
+	}
+	transaction.delete(columns::OFFCHAIN, &journal_key);
+	Ok(())
+}
+
+fn read_journal(db: &dyn KeyValueDB, key: &[u8]) -> Result<Vec<Vec<u8>>, client::error::Error> {
+	Ok(db.get(columns::OFFCHAIN, key).map_err(db_err)?
+		.and_then(|v| Decode::decode(&mut &v[..]).ok())
+		.unwrap_or_default())
+}
+
+fn indexed_key(prefix: &[u8], key: &[u8]) -> Vec<u8> {
+	prefix.iter().chain(key).cloned().collect()
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -145,4 +254,38 @@ mod tests {
 		assert!(storage.locks.lock().is_empty(), "Locks map should be empty!");
 	}
 
+	#[test]
+	fn should_remove_value() {
+		let mut storage = LocalStorage::new_test();
+		let prefix = b"prefix";
+		let key = b"key";
+
+		storage.set(prefix, key, b"value");
+		storage.remove(prefix, key);
+		assert_eq!(storage.get(prefix, key), None);
+	}
+
+	#[test]
+	fn should_reject_reserved_keys() {
+		let mut storage = LocalStorage::new_test();
+		let db = storage.db.clone();
+		let mut transaction = db.transaction();
+		note_indexed_changes(&*db, &mut transaction, 1, &[1; 32], vec![
+			(b"key".to_vec(), Some(b"value".to_vec())),
+		]).unwrap();
+		db.write(transaction).unwrap();
+
+		// the same database key split differently between prefix and item key
+		assert_eq!(storage.get(b":indexed:", b"changes:"), None);
+		assert_eq!(storage.get(b":indexed:changes:", &[1; 32]), None);
+		storage.set(b":index", b"ed:journal:\0\0\0\0\0\0\0\x01", b"value");
+		assert!(!storage.compare_and_set(b":indexed:journal:", &1u64.to_be_bytes(), None, b"value"));
+		storage.remove(b":indexed:changes:", &[1; 32]);
+
+		let mut transaction = db.transaction();
+		finalize_indexed_changes(&*db, &mut transaction, 1, &[1; 32]).unwrap();
+		db.write(transaction).unwrap();
+		// the indexed changes were left untouched
+		assert_eq!(storage.get(STORAGE_PREFIX, b"key"), Some(b"value".to_vec()));
+	}
 }
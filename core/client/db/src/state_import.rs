@@ -0,0 +1,419 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Import of a full state provided in key order.
+//!
+//! The state is never held in memory as a whole: trie nodes are written to the database as
+//! soon as they can no longer change. Since entries are inserted in key order, inserting an
+//! entry only ever modifies the nodes on the path to the previously inserted key, so only those
+//! are kept in memory.
+//!
+//! The nodes are written to the state column directly rather than through the state database,
+//! like the nodes of the genesis state. The trie of a block is only ever pruned through the
+//! changes of its descendants, which the state database tracks as usual.
+//!
+//! Along with every batch of nodes, the keys of the nodes that were not in the database yet are
+//! written to a journal. Until the import is committed, `revert` removes exactly those nodes,
+//! even if the node was interrupted in the middle of the import.
+
+use std::collections::{HashMap, HashSet};
+
+use codec::{Decode, Encode};
+use client::backend::StateEntry;
+use client::error::{Error, Result};
+use hash_db::{Hasher, Prefix};
+use kvdb::{KeyValueDB, DBTransaction};
+use log::{info, warn};
+use primitives::{H256, Blake2Hasher};
+use primitives::storage::well_known_keys;
+use state_machine::{DBValue, TrieBackend, TrieBackendStorage, prove_read_on_trie_backend};
+use state_machine::backend::Backend as StateBackend;
+use trie::{PrefixedMemoryDB, TrieConfiguration, prefixed_key, trie_types::Layout};
+
+use crate::columns;
+use crate::utils::{db_err, meta_keys};
+
+/// Number of entries inserted into a trie at once.
+const BATCH_SIZE: usize = 10_000;
+
+/// Trie nodes which are not written to the database yet, by prefixed key.
+type PendingNodes = HashMap<Vec<u8>, DBValue>;
+
+/// Trie node storage reading the pending nodes, then the database.
+struct ImportStorage<'a> {
+	db: &'a dyn KeyValueDB,
+	pending: &'a PendingNodes,
+}
+
+impl<'a> TrieBackendStorage<Blake2Hasher> for ImportStorage<'a> {
+	type Overlay = PrefixedMemoryDB<Blake2Hasher>;
+
+	fn get(&self, key: &H256, prefix: Prefix) -> std::result::Result<Option<DBValue>, String> {
+		let key = prefixed_key::<Blake2Hasher>(key, prefix);
+		if let Some(node) = self.pending.get(&key) {
+			return Ok(Some(node.clone()));
+		}
+		self.db.get(columns::STATE, &key)
+			.map(|node| node.map(|node| DBValue::from_slice(&node)))
+			.map_err(|e| format!("Database backend error: {:?}", e))
+	}
+}
+
+/// Writes the nodes of an import, recording the new ones in the journal.
+struct Journal<'a> {
+	db: &'a dyn KeyValueDB,
+	/// Number of batches recorded so far.
+	batches: u64,
+}
+
+impl<'a> Journal<'a> {
+	fn write(&mut self, nodes: PendingNodes) -> Result<()> {
+		let mut transaction = DBTransaction::new();
+		let mut written = Vec::new();
+		for (key, node) in nodes {
+			// nodes already in the database may be part of another state, so they are neither
+			// written again nor removed on revert.
+			if self.db.get(columns::STATE, &key).map_err(db_err)?.is_none() {
+				transaction.put(columns::STATE, &key, &node);
+				written.push(key);
+			}
+		}
+		if !written.is_empty() {
+			transaction.put(columns::META, &journal_key(self.batches), &written.encode());
+			self.batches += 1;
+		}
+		self.db.write(transaction).map_err(db_err)
+	}
+}
+
+fn journal_key(batch: u64) -> Vec<u8> {
+	let mut key = meta_keys::STATE_IMPORT_JOURNAL_PREFIX.to_vec();
+	key.extend_from_slice(&batch.to_be_bytes());
+	key
+}
+
+/// Keys of the journal entries in the database.
+fn journal_keys(db: &dyn KeyValueDB) -> Vec<Vec<u8>> {
+	db.iter_from_prefix(columns::META, meta_keys::STATE_IMPORT_JOURNAL_PREFIX)
+		.take_while(|(key, _)| key.starts_with(meta_keys::STATE_IMPORT_JOURNAL_PREFIX))
+		.map(|(key, _)| key.into_vec())
+		.collect()
+}
+
+/// Commit the imported state in given transaction, so that its nodes are kept for good.
+pub(crate) fn commit(db: &dyn KeyValueDB, transaction: &mut DBTransaction) {
+	for key in journal_keys(db) {
+		transaction.delete(columns::META, &key);
+	}
+}
+
+/// Remove the nodes written by an import that was not committed.
+pub(crate) fn revert(db: &dyn KeyValueDB) -> Result<()> {
+	for key in journal_keys(db) {
+		let mut transaction = DBTransaction::new();
+		if let Some(written) = db.get(columns::META, &key).map_err(db_err)? {
+			let written: Vec<Vec<u8>> = Decode::decode(&mut &written[..])
+				.map_err(|e| Error::Backend(format!("Invalid state import journal: {}", e.what())))?;
+			for node_key in written {
+				transaction.delete(columns::STATE, &node_key);
+			}
+		}
+		transaction.delete(columns::META, &key);
+		db.write(transaction).map_err(db_err)?;
+	}
+	Ok(())
+}
+
+/// A single trie being imported.
+struct TrieImport {
+	root: H256,
+	pending: PendingNodes,
+	batch: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+	last_key: Option<Vec<u8>>,
+}
+
+impl TrieImport {
+	fn new() -> Self {
+		TrieImport {
+			root: Layout::<Blake2Hasher>::trie_root::<_, Vec<u8>, Vec<u8>>(std::iter::empty()),
+			pending: HashMap::new(),
+			batch: Vec::new(),
+			last_key: None,
+		}
+	}
+
+	fn insert(&mut self, journal: &mut Journal, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+		if self.last_key.as_ref().map_or(false, |last_key| key <= *last_key) {
+			return Err(Error::Backend(format!(
+				"State entries are not in key order at {:?}", key,
+			)));
+		}
+		self.last_key = Some(key.clone());
+		self.batch.push((key, Some(value)));
+		if self.batch.len() == BATCH_SIZE {
+			self.write_batch(journal, false)?;
+		}
+		Ok(())
+	}
+
+	/// Write all the nodes of the trie, returning its root.
+	fn finish(mut self, journal: &mut Journal) -> Result<H256> {
+		self.write_batch(journal, true)?;
+		Ok(self.root)
+	}
+
+	/// Insert the batched entries into the trie, then write the nodes which can no longer
+	/// change, or all of them if no more entries are inserted.
+	fn write_batch(&mut self, journal: &mut Journal, is_last: bool) -> Result<()> {
+		let db = journal.db;
+		let batch = std::mem::replace(&mut self.batch, Vec::new());
+		let (root, mut changes) = TrieBackend::new(
+			ImportStorage { db, pending: &self.pending },
+			self.root,
+		).storage_root(batch);
+		for (key, (node, rc)) in changes.drain() {
+			if rc > 0 {
+				self.pending.insert(key, node);
+			} else if rc < 0 {
+				self.pending.remove(&key);
+			}
+		}
+		self.root = root;
+
+		let keep: HashSet<H256> = match self.last_key {
+			Some(ref last_key) if !is_last => {
+				let backend = TrieBackend::new(ImportStorage { db, pending: &self.pending }, self.root);
+				let (_, path) = prove_read_on_trie_backend(&backend, last_key)
+					.map_err(|e| Error::Backend(format!("Error reading imported state: {}", e)))?;
+				path.iter().map(|node| Blake2Hasher::hash(node)).collect()
+			},
+			_ => HashSet::new(),
+		};
+
+		let mut nodes = PendingNodes::new();
+		for (key, node) in std::mem::replace(&mut self.pending, HashMap::new()) {
+			// prefixed keys end with the hash of the node.
+			let hash = H256::from_slice(&key[key.len() - H256::len_bytes()..]);
+			if keep.contains(&hash) {
+				self.pending.insert(key, node);
+			} else {
+				nodes.insert(key, node);
+			}
+		}
+		journal.write(nodes)
+	}
+}
+
+/// Import of a full state, see the module documentation.
+pub(crate) struct StateImport<'a> {
+	journal: Journal<'a>,
+	top: TrieImport,
+	/// Child trie being imported, along with its storage key.
+	child: Option<(Vec<u8>, TrieImport)>,
+	entries: u64,
+}
+
+impl<'a> StateImport<'a> {
+	pub(crate) fn new(db: &'a dyn KeyValueDB) -> Self {
+		StateImport {
+			journal: Journal { db, batches: 0 },
+			top: TrieImport::new(),
+			child: None,
+			entries: 0,
+		}
+	}
+
+	/// Import the given state entries, returning the root of the state.
+	///
+	/// The nodes are kept in the database once the import is `commit`ted in the transaction
+	/// adding the block. They are removed if the import fails, or have to be `revert`ed if the
+	/// block is not added.
+	pub(crate) fn import<I>(self, state: I) -> Result<H256> where
+		I: IntoIterator<Item=Result<StateEntry>>,
+	{
+		let db = self.journal.db;
+		self.import_entries(state).map_err(|e| match revert(db) {
+			Ok(()) => e,
+			Err(revert_error) => {
+				warn!("Failed to remove the nodes of a failed state import: {:?}", revert_error);
+				e
+			},
+		})
+	}
+
+	fn import_entries<I>(mut self, state: I) -> Result<H256> where
+		I: IntoIterator<Item=Result<StateEntry>>,
+	{
+		for entry in state {
+			match entry? {
+				StateEntry::Top(key, value) => {
+					if well_known_keys::is_child_storage_key(&key) {
+						return Err(Error::Backend(format!("Unexpected child trie root at {:?}", key)));
+					}
+					self.finish_child()?;
+					self.top.insert(&mut self.journal, key, value)?;
+				},
+				StateEntry::Child(storage_key, key, value) => {
+					if !well_known_keys::is_child_storage_key(&storage_key) {
+						return Err(Error::Backend(format!("Invalid child storage key {:?}", storage_key)));
+					}
+					if self.child.as_ref().map_or(true, |(current, _)| *current != storage_key) {
+						self.finish_child()?;
+						self.child = Some((storage_key, TrieImport::new()));
+					}
+					let (_, child) = self.child.as_mut().expect("child trie is set above; qed");
+					child.insert(&mut self.journal, key, value)?;
+				},
+			}
+
+			self.entries += 1;
+			if self.entries % 100_000 == 0 {
+				info!("#{} state entries imported", self.entries);
+			}
+		}
+
+		self.finish_child()?;
+		self.top.finish(&mut self.journal)
+	}
+
+	/// Write the child trie being imported and insert its root into the top trie.
+	fn finish_child(&mut self) -> Result<()> {
+		if let Some((storage_key, child)) = self.child.take() {
+			let root = child.finish(&mut self.journal)?;
+			self.top.insert(&mut self.journal, storage_key, root.as_ref().to_vec())?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sr_primitives::traits::{BlakeTwo256, Hash};
+
+	fn new_db() -> kvdb_memorydb::InMemory {
+		kvdb_memorydb::create(crate::utils::NUM_COLUMNS)
+	}
+
+	fn import(db: &dyn KeyValueDB, state: Vec<StateEntry>) -> Result<H256> {
+		StateImport::new(db).import(state.into_iter().map(Ok))
+	}
+
+	fn read(db: &dyn KeyValueDB, root: H256, child_storage_key: Option<&[u8]>, key: &[u8]) -> Option<Vec<u8>> {
+		let pending = PendingNodes::new();
+		let backend = TrieBackend::new(ImportStorage { db, pending: &pending }, root);
+		match child_storage_key {
+			Some(storage_key) => backend.child_storage(storage_key, key).unwrap(),
+			None => backend.storage(key).unwrap(),
+		}
+	}
+
+	fn numbered_entries(count: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+		(0..count as u32).map(|i| (i.to_be_bytes().to_vec(), vec![i as u8; 33])).collect()
+	}
+
+	#[test]
+	fn imports_state_in_batches() {
+		let db = new_db();
+		let top = numbered_entries(BATCH_SIZE * 3 + 7);
+		let root = import(&db, top.iter().cloned().map(|(k, v)| StateEntry::Top(k, v)).collect()).unwrap();
+
+		assert_eq!(root, BlakeTwo256::trie_root(top.clone()));
+		for (key, value) in top {
+			assert_eq!(read(&db, root, None, &key), Some(value));
+		}
+	}
+
+	#[test]
+	fn writes_no_stale_nodes() {
+		let db = new_db();
+		let top = numbered_entries(BATCH_SIZE * 2 + 7);
+		import(&db, top.iter().cloned().map(|(k, v)| StateEntry::Top(k, v)).collect()).unwrap();
+
+		// exactly the nodes of the trie are written, none which were replaced later on.
+		let (_, mut nodes) = TrieBackend::new(PrefixedMemoryDB::<Blake2Hasher>::default(), TrieImport::new().root)
+			.storage_root(top.into_iter().map(|(k, v)| (k, Some(v))));
+		let mut expected: Vec<_> = nodes.drain().into_iter()
+			.map(|(key, (node, _))| (key, node.to_vec()))
+			.collect();
+		expected.sort();
+		let written: Vec<_> = db.iter(columns::STATE)
+			.map(|(key, node)| (key.to_vec(), node.to_vec()))
+			.collect();
+		assert_eq!(written, expected);
+	}
+
+	#[test]
+	fn imports_child_tries() {
+		let db = new_db();
+		let child_key = b":child_storage:default:child".to_vec();
+		let root = import(&db, vec![
+			StateEntry::Top(b":a".to_vec(), vec![1]),
+			StateEntry::Child(child_key.clone(), b"a".to_vec(), vec![2]),
+			StateEntry::Child(child_key.clone(), b"b".to_vec(), vec![3]),
+			StateEntry::Top(b":d".to_vec(), vec![4]),
+		]).unwrap();
+
+		let child_root = BlakeTwo256::trie_root(vec![(b"a".to_vec(), vec![2]), (b"b".to_vec(), vec![3])]);
+		assert_eq!(root, BlakeTwo256::trie_root(vec![
+			(b":a".to_vec(), vec![1]),
+			(child_key.clone(), child_root.as_ref().to_vec()),
+			(b":d".to_vec(), vec![4]),
+		]));
+		assert_eq!(read(&db, root, Some(&child_key), b"b"), Some(vec![3]));
+	}
+
+	#[test]
+	fn reverts_only_the_nodes_it_wrote() {
+		let db = new_db();
+		let top = numbered_entries(100);
+		let root = import(&db, top.iter().cloned().map(|(k, v)| StateEntry::Top(k, v)).collect()).unwrap();
+		let mut transaction = DBTransaction::new();
+		commit(&db, &mut transaction);
+		db.write(transaction).unwrap();
+		let nodes = db.iter(columns::STATE).count();
+
+		// most nodes of the second state are nodes of the first one.
+		let mut changed = top.clone();
+		changed[50].1 = vec![0; 33];
+		import(&db, changed.into_iter().map(|(k, v)| StateEntry::Top(k, v)).collect()).unwrap();
+		assert!(db.iter(columns::STATE).count() > nodes);
+		revert(&db).unwrap();
+
+		assert_eq!(db.iter(columns::STATE).count(), nodes);
+		assert!(journal_keys(&db).is_empty());
+		for (key, value) in top {
+			assert_eq!(read(&db, root, None, &key), Some(value));
+		}
+	}
+
+	#[test]
+	fn rejects_unordered_entries() {
+		let db = new_db();
+		assert!(import(&db, vec![
+			StateEntry::Top(b"b".to_vec(), vec![1]),
+			StateEntry::Top(b"a".to_vec(), vec![2]),
+		]).is_err());
+		assert!(import(&db, vec![
+			StateEntry::Child(b":child_storage:default:b".to_vec(), b"a".to_vec(), vec![1]),
+			StateEntry::Child(b":child_storage:default:a".to_vec(), b"a".to_vec(), vec![2]),
+		]).is_err());
+		assert!(import(&db, vec![
+			StateEntry::Top(b"a".to_vec(), vec![1]),
+			StateEntry::Top(b":child_storage:default:child".to_vec(), vec![2]),
+		]).is_err());
+	}
+}
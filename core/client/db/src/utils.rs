@@ -17,9 +17,8 @@
 //! Db-based backend utility structures and functions, used by both
 //! full and light storages.
 
-#[cfg(feature = "kvdb-rocksdb")]
 use std::sync::Arc;
-use std::{io, convert::TryInto};
+use std::{fs, io, convert::TryInto};
 
 use kvdb::{KeyValueDB, DBTransaction};
 #[cfg(feature = "kvdb-rocksdb")]
@@ -34,8 +33,8 @@ use sr_primitives::traits::{
 	Block as BlockT, Header as HeaderT, Zero,
 	UniqueSaturatedFrom, UniqueSaturatedInto,
 };
-#[cfg(feature = "kvdb-rocksdb")]
-use crate::DatabaseSettings;
+use crate::{DatabaseEngine, DatabaseSettings};
+use crate::logdb::{self, LogDb};
 
 /// Number of columns in the db. Must be the same for both full && light dbs.
 /// Otherwise RocksDb will fail to open database && check its type.
@@ -59,6 +58,8 @@ pub mod meta_keys {
 	pub const LEAF_PREFIX: &[u8; 4] = b"leaf";
 	/// Children prefix list key.
 	pub const CHILDREN_PREFIX: &[u8; 8] = b"children";
+	/// Prefix of the keys of the trie nodes written by an unfinished state import.
+	pub const STATE_IMPORT_JOURNAL_PREFIX: &[u8; 12] = b"state_import";
 }
 
 /// Database metadata.
@@ -205,17 +206,44 @@ pub fn db_err(err: io::Error) -> client::error::Error {
 	client::error::Error::Backend(format!("{}", err))
 }
 
-/// Open RocksDB database.
-#[cfg(feature = "kvdb-rocksdb")]
+/// Opens the database with the engine selected in the settings.
 pub fn open_database(
 	config: &DatabaseSettings,
 	col_meta: Option<u32>,
 	db_type: &str
 ) -> client::error::Result<Arc<dyn KeyValueDB>> {
-	let mut db_config = DatabaseConfig::with_columns(Some(NUM_COLUMNS));
-	db_config.memory_budget = config.cache_size;
-	let path = config.path.to_str().ok_or_else(|| client::error::Error::Backend("Invalid database path".into()))?;
-	let db = Database::open(&db_config, &path).map_err(db_err)?;
+	open(config, col_meta, db_type, false)
+}
+
+/// Opens an existing database with the engine selected in the settings, without writing to it.
+///
+/// The engine and the type of the database are checked but not recorded, and the log database is
+/// opened read-only. RocksDB can't be opened read-only, and still updates its own files.
+pub fn open_database_read_only(
+	config: &DatabaseSettings,
+	col_meta: Option<u32>,
+	db_type: &str
+) -> client::error::Result<Arc<dyn KeyValueDB>> {
+	open(config, col_meta, db_type, true)
+}
+
+fn open(
+	config: &DatabaseSettings,
+	col_meta: Option<u32>,
+	db_type: &str,
+	read_only: bool,
+) -> client::error::Result<Arc<dyn KeyValueDB>> {
+	let recorded = check_engine(config)?;
+	let db: Arc<dyn KeyValueDB> = match config.engine {
+		DatabaseEngine::RocksDb => open_rocksdb(config)?,
+		DatabaseEngine::LogDb if read_only =>
+			Arc::new(LogDb::open_read_only(&config.path, NUM_COLUMNS).map_err(db_err)?),
+		DatabaseEngine::LogDb => Arc::new(LogDb::open(&config.path, NUM_COLUMNS).map_err(db_err)?),
+	};
+	// the engine is only recorded once the database is opened on disk.
+	if !recorded && !read_only {
+		record_engine(config)?;
+	}
 
 	// check database type
 	match db.get(col_meta, meta_keys::TYPE).map_err(db_err)? {
@@ -225,6 +253,7 @@ pub fn open_database(
 					format!("Unexpected database type. Expected: {}", db_type)).into());
 			}
 		},
+		None if read_only => {},
 		None => {
 			let mut transaction = DBTransaction::new();
 			transaction.put(col_meta, meta_keys::TYPE, db_type.as_bytes());
@@ -232,9 +261,64 @@ pub fn open_database(
 		},
 	}
 
+	Ok(db)
+}
+
+/// Name of the file recording the engine the database has been created with.
+const ENGINE_FILE: &str = "engine";
+
+/// Fails if the database has been created with another engine than the selected one. Returns
+/// whether the engine is recorded.
+///
+/// Databases created before the engine was recorded are recognized by their content.
+fn check_engine(config: &DatabaseSettings) -> client::error::Result<bool> {
+	let engine_path = config.path.join(ENGINE_FILE);
+	let (recorded, is_file) = match fs::read_to_string(&engine_path) {
+		Ok(recorded) => (Some(recorded.trim().to_owned()), true),
+		Err(ref e) if e.kind() == io::ErrorKind::NotFound => {
+			let recorded = if config.path.join(logdb::LOG_FILE).exists() {
+				Some(DatabaseEngine::LogDb.name().to_owned())
+			} else if fs::read_dir(&config.path).map_or(false, |mut entries| entries.next().is_some()) {
+				Some(DatabaseEngine::RocksDb.name().to_owned())
+			} else {
+				None
+			};
+			(recorded, false)
+		},
+		Err(e) => return Err(db_err(e)),
+	};
+
+	match recorded {
+		Some(ref recorded) if recorded != config.engine.name() => Err(client::error::Error::Backend(format!(
+			"Database at {} has been created with the {} engine, but the {} engine is selected",
+			config.path.display(),
+			recorded,
+			config.engine.name(),
+		))),
+		_ => Ok(is_file),
+	}
+}
+
+/// Record the selected engine in the database directory.
+fn record_engine(config: &DatabaseSettings) -> client::error::Result<()> {
+	fs::create_dir_all(&config.path).map_err(db_err)?;
+	fs::write(config.path.join(ENGINE_FILE), config.engine.name()).map_err(db_err)
+}
+
+#[cfg(feature = "kvdb-rocksdb")]
+fn open_rocksdb(config: &DatabaseSettings) -> client::error::Result<Arc<dyn KeyValueDB>> {
+	let mut db_config = DatabaseConfig::with_columns(Some(NUM_COLUMNS));
+	db_config.memory_budget = config.cache_size;
+	let path = config.path.to_str().ok_or_else(|| client::error::Error::Backend("Invalid database path".into()))?;
+	let db = Database::open(&db_config, &path).map_err(db_err)?;
 	Ok(Arc::new(db))
 }
 
+#[cfg(not(feature = "kvdb-rocksdb"))]
+fn open_rocksdb(_config: &DatabaseSettings) -> client::error::Result<Arc<dyn KeyValueDB>> {
+	Err(client::error::Error::Backend("Built without support for the rocksdb engine".into()))
+}
+
 /// Read database column entry for the given block.
 pub fn read_db<Block>(
 	db: &dyn KeyValueDB,
@@ -345,4 +429,48 @@ mod tests {
 			_ => unreachable!(),
 		};
 	}
+
+	#[test]
+	fn database_engine_is_checked() {
+		let dir = tempdir::TempDir::new("engine").unwrap();
+		let settings = |engine| DatabaseSettings {
+			cache_size: None,
+			state_cache_size: 0,
+			state_cache_child_ratio: None,
+			path: dir.path().join("db"),
+			pruning: crate::PruningMode::ArchiveAll,
+			engine,
+		};
+
+		assert!(!check_engine(&settings(DatabaseEngine::LogDb)).unwrap());
+		open_database(&settings(DatabaseEngine::LogDb), COLUMN_META, "full").unwrap();
+		assert!(check_engine(&settings(DatabaseEngine::LogDb)).unwrap());
+		assert!(check_engine(&settings(DatabaseEngine::RocksDb)).is_err());
+
+		// a database created before the engine was recorded
+		fs::remove_dir_all(dir.path().join("db")).unwrap();
+		fs::create_dir_all(dir.path().join("db")).unwrap();
+		fs::write(dir.path().join("db").join("CURRENT"), b"").unwrap();
+		assert!(check_engine(&settings(DatabaseEngine::LogDb)).is_err());
+		assert!(!check_engine(&settings(DatabaseEngine::RocksDb)).unwrap());
+		record_engine(&settings(DatabaseEngine::RocksDb)).unwrap();
+		assert_eq!(fs::read_to_string(dir.path().join("db").join(ENGINE_FILE)).unwrap(), "rocksdb");
+	}
+
+	#[test]
+	#[cfg(not(feature = "kvdb-rocksdb"))]
+	fn rocksdb_engine_is_unavailable_without_the_feature() {
+		let dir = tempdir::TempDir::new("engine").unwrap();
+		let settings = DatabaseSettings {
+			cache_size: None,
+			state_cache_size: 0,
+			state_cache_child_ratio: None,
+			path: dir.path().join("db"),
+			pruning: crate::PruningMode::ArchiveAll,
+			engine: DatabaseEngine::RocksDb,
+		};
+
+		assert!(open_database(&settings, COLUMN_META, "full").is_err());
+		assert!(!dir.path().join("db").join(ENGINE_FILE).exists());
+	}
 }
@@ -28,6 +28,8 @@ use hash_db::Hasher;
 use trie::MemoryDB;
 use parking_lot::Mutex;
 
+pub use primitives::offchain::OffchainStorage;
+
 /// In memory array of storage values.
 pub type StorageCollection = Vec<(Vec<u8>, Option<Vec<u8>>)>;
 
@@ -63,6 +65,18 @@ impl NewBlockState {
 	}
 }
 
+/// Entry of a full state, as imported by `Backend::import_state`.
+///
+/// The entries of a state are provided in key order. The entries of a child trie are provided
+/// together, in place of the top-level entry holding its root, which is computed on import.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateEntry {
+	/// Top-level key and value.
+	Top(Vec<u8>, Vec<u8>),
+	/// Child storage key, key and value.
+	Child(Vec<u8>, Vec<u8>, Vec<u8>),
+}
+
 /// Block insertion operation. Keeps hold if the inserted block state and data.
 pub trait BlockImportOperation<Block, H> where
 	Block: BlockT,
@@ -94,6 +108,10 @@ pub trait BlockImportOperation<Block, H> where
 		update: StorageCollection,
 		child_update: ChildStorageCollection,
 	) -> error::Result<()>;
+	/// Set offchain storage changes indexed by the runtime during block import.
+	/// Values are `None` if should be deleted. The changes are kept aside until the
+	/// block is finalized and discarded if it never is.
+	fn update_offchain_storage(&mut self, update: StorageCollection) -> error::Result<()>;
 	/// Inject changes trie data into the database.
 	fn update_changes_trie(&mut self, update: MemoryDB<H>) -> error::Result<()>;
 	/// Insert auxiliary keys. Values are `None` if should be deleted.
@@ -174,6 +192,23 @@ pub trait Backend<Block, H>: AuxStore + Send + Sync where
 	/// successfully reverted.
 	fn revert(&self, n: NumberFor<Block>) -> error::Result<NumberFor<Block>>;
 
+	/// Import a block along with its full state, without having the state of its parent.
+	///
+	/// The state is read from `state` as it is imported, see `StateEntry` for the expected
+	/// order. The block becomes the best and last finalized block. Only possible when no blocks
+	/// other than the genesis block are known to the backend.
+	fn import_state<I>(
+		&self,
+		_header: Block::Header,
+		_body: Option<Vec<Block::Extrinsic>>,
+		_justification: Option<Justification>,
+		_state: I,
+	) -> error::Result<()> where
+		I: IntoIterator<Item=error::Result<StateEntry>>,
+	{
+		Err(error::Error::Backend("Importing state is not supported by the backend".into()))
+	}
+
 	/// Insert auxiliary data into key-value store.
 	fn insert_aux<
 		'a,
@@ -198,26 +233,6 @@ pub trait Backend<Block, H>: AuxStore + Send + Sync where
 	fn get_import_lock(&self) -> &Mutex<()>;
 }
 
-/// Offchain workers local storage.
-pub trait OffchainStorage: Clone + Send + Sync {
-	/// Persist a value in storage under given key and prefix.
-	fn set(&mut self, prefix: &[u8], key: &[u8], value: &[u8]);
-
-	/// Retrieve a value from storage under given key and prefix.
-	fn get(&self, prefix: &[u8], key: &[u8]) -> Option<Vec<u8>>;
-
-	/// Replace the value in storage if given old_value matches the current one.
-	///
-	/// Returns `true` if the value has been set and false otherwise.
-	fn compare_and_set(
-		&mut self,
-		prefix: &[u8],
-		key: &[u8],
-		old_value: Option<&[u8]>,
-		new_value: &[u8],
-	) -> bool;
-}
-
 /// Changes trie storage that supports pruning.
 pub trait PrunableStateChangesTrieStorage<Block: BlockT, H: Hasher>:
 	StateChangesTrieStorage<H, NumberFor<Block>>
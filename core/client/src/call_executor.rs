@@ -14,19 +14,28 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{sync::Arc, cmp::Ord, panic::UnwindSafe, result, cell::RefCell, rc::Rc};
+use std::{
+	sync::{Arc, atomic::{AtomicUsize, Ordering}}, cmp::Ord, panic::UnwindSafe, result,
+	cell::RefCell, rc::Rc, fs, io::{self, Write}, path::{Path, PathBuf},
+	time::{SystemTime, UNIX_EPOCH},
+};
 use codec::{Encode, Decode};
+use log::warn;
 use sr_primitives::{
 	generic::BlockId, traits::Block as BlockT,
 };
 use state_machine::{
 	self, OverlayedChanges, Ext, CodeExecutor, ExecutionManager,
-	ExecutionStrategy, NeverOffchainExt, backend::Backend as _,
+	ExecutionStrategy, NeverOffchainExt, ConsensusFailure, StorageDiff, backend::Backend as _,
 };
 use executor::{RuntimeVersion, RuntimeInfo, NativeVersion};
 use hash_db::Hasher;
 use trie::MemoryDB;
-use primitives::{offchain, H256, Blake2Hasher, NativeOrEncoded, NeverNativeValue};
+use primitives::{
+	offchain, H256, Blake2Hasher, NativeOrEncoded, NeverNativeValue, hexdisplay::HexDisplay,
+};
+use serde_json::json;
+use substrate_telemetry::{telemetry, SUBSTRATE_INFO};
 
 use crate::runtime_api::{ProofRecorder, InitializeBlock};
 use crate::backend;
@@ -93,6 +102,8 @@ where
 
 	/// Execute a call to a contract on top of given state.
 	///
+	/// `at` identifies the block the call is made for, if known. It is only used for diagnostics.
+	///
 	/// No changes are made.
 	fn call_at_state<
 		O: offchain::Externalities,
@@ -104,6 +115,7 @@ where
 		R: Encode + Decode + PartialEq,
 		NC: FnOnce() -> result::Result<R, &'static str> + UnwindSafe,
 	>(&self,
+		at: Option<&BlockId<B>>,
 		state: &S,
 		overlay: &mut OverlayedChanges,
 		method: &str,
@@ -152,6 +164,7 @@ pub struct LocalCallExecutor<B, E> {
 	backend: Arc<B>,
 	executor: E,
 	keystore: Option<primitives::traits::BareCryptoStorePtr>,
+	consensus_failure_dir: Option<PathBuf>,
 }
 
 impl<B, E> LocalCallExecutor<B, E> {
@@ -165,8 +178,117 @@ impl<B, E> LocalCallExecutor<B, E> {
 			backend,
 			executor,
 			keystore,
+			consensus_failure_dir: None,
 		}
 	}
+
+	/// Write a report to the given directory whenever `ExecutionStrategy::Both` detects
+	/// that native and wasm execution diverged.
+	pub fn with_consensus_failure_dir(mut self, dir: Option<PathBuf>) -> Self {
+		self.consensus_failure_dir = dir;
+		self
+	}
+
+	/// Log, report to telemetry and dump to disk a native/wasm divergence.
+	fn report_consensus_failure<Block: BlockT>(
+		&self,
+		at: Option<&BlockId<Block>>,
+		failure: ConsensusFailure,
+	) {
+		let at = at.map(|at| at.to_string());
+		let path = match self.consensus_failure_dir {
+			Some(ref dir) => match write_consensus_failure(dir, at.as_ref().map(|s| s.as_str()), &failure) {
+				Ok(path) => Some(path),
+				Err(e) => {
+					warn!(
+						target: "executor",
+						"Failed to write consensus failure report to {}: {}",
+						dir.display(),
+						e,
+					);
+					None
+				}
+			},
+			None => None,
+		};
+
+		warn!(
+			target: "executor",
+			"Consensus error between wasm and native calling {} at {}. Report: {}",
+			failure.method,
+			at.as_ref().map(|s| s.as_str()).unwrap_or("unknown block"),
+			path.as_ref().map(|p| p.display().to_string()).unwrap_or_else(|| "not written".into()),
+		);
+		telemetry!(SUBSTRATE_INFO; "executor.consensus_failure";
+			"method" => ?failure.method,
+			"at" => ?at,
+			"native_ok" => failure.native_result.is_ok(),
+			"wasm_ok" => failure.wasm_result.is_ok(),
+			"native_changes" => failure.native_changes.top.len(),
+			"wasm_changes" => failure.wasm_changes.top.len(),
+			"report" => ?path
+		);
+	}
+}
+
+fn hex(data: &[u8]) -> String {
+	format!("0x{}", HexDisplay::from(&data))
+}
+
+fn result_to_json(result: &result::Result<Vec<u8>, String>) -> serde_json::Value {
+	match result {
+		Ok(data) => json!({ "ok": hex(data) }),
+		Err(e) => json!({ "err": e }),
+	}
+}
+
+fn changes_to_json(changes: &[(Vec<u8>, Option<Vec<u8>>)]) -> serde_json::Value {
+	changes.iter()
+		.map(|(k, v)| json!([hex(k), v.as_ref().map(|v| hex(v))]))
+		.collect()
+}
+
+fn diff_to_json(diff: &StorageDiff) -> serde_json::Value {
+	json!({
+		"top": changes_to_json(&diff.top),
+		"children": diff.children.iter()
+			.map(|(storage_key, changes)| json!([hex(storage_key), changes_to_json(changes)]))
+			.collect::<Vec<_>>(),
+	})
+}
+
+/// Number of consensus failure reports written by this process, used to keep file names unique.
+static REPORTS_WRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+/// Write the given consensus failure as a JSON document into `dir`, returning the file path.
+///
+/// Reports are never overwritten: the file name carries a timestamp and a per-process sequence
+/// number, and the file is created exclusively.
+fn write_consensus_failure(
+	dir: &Path,
+	at: Option<&str>,
+	failure: &ConsensusFailure,
+) -> io::Result<PathBuf> {
+	let timestamp = SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|d| d.as_millis())
+		.unwrap_or_default();
+	let report = json!({
+		"at": at,
+		"method": failure.method,
+		"call_data": hex(&failure.call_data),
+		"native_result": result_to_json(&failure.native_result),
+		"wasm_result": result_to_json(&failure.wasm_result),
+		"native_changes": diff_to_json(&failure.native_changes),
+		"wasm_changes": diff_to_json(&failure.wasm_changes),
+	});
+
+	fs::create_dir_all(dir)?;
+	let seq = REPORTS_WRITTEN.fetch_add(1, Ordering::Relaxed);
+	let path = dir.join(format!("{}-{}-{}.json", timestamp, seq, failure.method));
+	let mut file = fs::OpenOptions::new().write(true).create_new(true).open(&path)?;
+	file.write_all(&serde_json::to_vec_pretty(&report)?)?;
+	Ok(path)
 }
 
 impl<B, E> Clone for LocalCallExecutor<B, E> where E: Clone {
@@ -175,6 +297,7 @@ impl<B, E> Clone for LocalCallExecutor<B, E> where E: Clone {
 			backend: self.backend.clone(),
 			executor: self.executor.clone(),
 			keystore: self.keystore.clone(),
+			consensus_failure_dir: self.consensus_failure_dir.clone(),
 		}
 	}
 }
@@ -197,7 +320,7 @@ where
 	) -> error::Result<Vec<u8>> {
 		let mut changes = OverlayedChanges::default();
 		let state = self.backend.state_at(*id)?;
-		let return_data = state_machine::new(
+		let mut state_machine = state_machine::new(
 			&state,
 			self.backend.changes_trie_storage(),
 			side_effects_handler,
@@ -206,12 +329,17 @@ where
 			method,
 			call_data,
 			self.keystore.clone(),
-		).execute_using_consensus_failure_handler::<_, NeverNativeValue, fn() -> _>(
+		);
+		let return_data = state_machine.execute_using_consensus_failure_handler::<_, NeverNativeValue, fn() -> _>(
 			strategy.get_manager(),
 			false,
 			None,
 		)
-		.map(|(result, _, _)| result)?;
+		.map(|(result, _, _)| result);
+		if let Some(failure) = state_machine.take_consensus_failure() {
+			self.report_consensus_failure(Some(id), failure);
+		}
+		let return_data = return_data?;
 		self.backend.destroy_state(state)?;
 		Ok(return_data.into_encoded())
 	}
@@ -256,8 +384,9 @@ where
 		};
 
 		let mut state = self.backend.state_at(*at)?;
+		let mut changes = changes.borrow_mut();
 
-		let result = match recorder {
+		let (result, consensus_failure) = match recorder {
 			Some(recorder) => {
 				let trie_state = state.as_trie_backend()
 					.ok_or_else(||
@@ -270,41 +399,49 @@ where
 					recorder.clone()
 				);
 
-				state_machine::new(
+				let mut state_machine = state_machine::new(
 					&backend,
 					self.backend.changes_trie_storage(),
 					side_effects_handler,
-					&mut *changes.borrow_mut(),
+					&mut *changes,
 					&self.executor,
 					method,
 					call_data,
 					keystore,
-				)
-				.execute_using_consensus_failure_handler(
+				);
+				let result = state_machine.execute_using_consensus_failure_handler(
 					execution_manager,
 					false,
 					native_call,
 				)
 				.map(|(result, _, _)| result)
-				.map_err(Into::into)
+				.map_err(Into::into);
+				(result, state_machine.take_consensus_failure())
+			}
+			None => {
+				let mut state_machine = state_machine::new(
+					&state,
+					self.backend.changes_trie_storage(),
+					side_effects_handler,
+					&mut *changes,
+					&self.executor,
+					method,
+					call_data,
+					keystore,
+				);
+				let result = state_machine.execute_using_consensus_failure_handler(
+					execution_manager,
+					false,
+					native_call,
+				)
+				.map(|(result, _, _)| result);
+				(result, state_machine.take_consensus_failure())
 			}
-			None => state_machine::new(
-				&state,
-				self.backend.changes_trie_storage(),
-				side_effects_handler,
-				&mut *changes.borrow_mut(),
-				&self.executor,
-				method,
-				call_data,
-				keystore,
-			)
-			.execute_using_consensus_failure_handler(
-				execution_manager,
-				false,
-				native_call,
-			)
-			.map(|(result, _, _)| result)
-		}?;
+		};
+		if let Some(failure) = consensus_failure {
+			self.report_consensus_failure(Some(at), failure);
+		}
+		let result = result?;
 		self.backend.destroy_state(state)?;
 		Ok(result)
 	}
@@ -335,6 +472,7 @@ where
 		R: Encode + Decode + PartialEq,
 		NC: FnOnce() -> result::Result<R, &'static str> + UnwindSafe,
 	>(&self,
+		at: Option<&BlockId<Block>>,
 		state: &S,
 		changes: &mut OverlayedChanges,
 		method: &str,
@@ -347,7 +485,7 @@ where
 		(S::Transaction, <Blake2Hasher as Hasher>::Out),
 		Option<MemoryDB<Blake2Hasher>>,
 	)> {
-		state_machine::new(
+		let mut state_machine = state_machine::new(
 			state,
 			self.backend.changes_trie_storage(),
 			side_effects_handler,
@@ -356,11 +494,16 @@ where
 			method,
 			call_data,
 			self.keystore.clone(),
-		).execute_using_consensus_failure_handler(
+		);
+		let result = state_machine.execute_using_consensus_failure_handler(
 			manager,
 			true,
 			native_call,
-		)
+		);
+		if let Some(failure) = state_machine.take_consensus_failure() {
+			self.report_consensus_failure(at, failure);
+		}
+		result
 		.map(|(result, storage_tx, changes_tx)| (
 			result,
 			storage_tx.expect("storage_tx is always computed when compute_tx is true; qed"),
@@ -391,3 +534,55 @@ where
 		Some(self.executor.native_version())
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn failure() -> ConsensusFailure {
+		ConsensusFailure {
+			method: "Core_execute_block".into(),
+			call_data: vec![1, 2, 3],
+			native_result: Ok(vec![0]),
+			wasm_result: Err("Trap".into()),
+			native_changes: StorageDiff {
+				top: vec![(b"a".to_vec(), Some(b"1".to_vec())), (b"b".to_vec(), None)],
+				children: vec![(b":child_storage:default:x".to_vec(), vec![(b"c".to_vec(), Some(vec![2]))])],
+			},
+			wasm_changes: StorageDiff::default(),
+		}
+	}
+
+	#[test]
+	fn write_consensus_failure_writes_json_report() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = write_consensus_failure(dir.path(), Some("Number(5)"), &failure()).unwrap();
+
+		assert!(path.starts_with(dir.path()));
+		let report: serde_json::Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+		assert_eq!(report, json!({
+			"at": "Number(5)",
+			"method": "Core_execute_block",
+			"call_data": "0x010203",
+			"native_result": { "ok": "0x00" },
+			"wasm_result": { "err": "Trap" },
+			"native_changes": {
+				"top": [["0x61", "0x31"], ["0x62", null]],
+				"children": [[hex(b":child_storage:default:x"), [["0x63", "0x02"]]]],
+			},
+			"wasm_changes": { "top": [], "children": [] },
+		}));
+	}
+
+	#[test]
+	fn write_consensus_failure_never_overwrites_reports() {
+		let dir = tempfile::tempdir().unwrap();
+		let failure = failure();
+
+		let first = write_consensus_failure(dir.path(), None, &failure).unwrap();
+		let second = write_consensus_failure(dir.path(), None, &failure).unwrap();
+
+		assert_ne!(first, second);
+		assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+	}
+}
@@ -33,7 +33,7 @@ use primitives::{
 };
 use substrate_telemetry::{telemetry, SUBSTRATE_INFO};
 use sr_primitives::{
-	Justification, BuildStorage,
+	Justification, BuildStorage, ApplyResult,
 	generic::{BlockId, SignedBlock, DigestItem},
 	traits::{
 		Block as BlockT, Header as HeaderT, Zero, NumberFor,
@@ -43,6 +43,7 @@ use sr_primitives::{
 use state_machine::{
 	DBValue, Backend as StateBackend, CodeExecutor, ChangesTrieAnchorBlockId,
 	ExecutionStrategy, ExecutionManager, prove_read, prove_child_read,
+	prove_read_keys, prove_child_read_keys, prove_range_read, read_range_proof_check,
 	ChangesTrieRootsStorage, ChangesTrieStorage,
 	key_changes, key_changes_proof, OverlayedChanges, NeverOffchainExt,
 };
@@ -56,12 +57,12 @@ use consensus::{
 
 use crate::{
 	runtime_api::{
-		CallRuntimeAt, ConstructRuntimeApi, Core as CoreApi, ProofRecorder,
+		CallRuntimeAt, ConstructRuntimeApi, Core as CoreApi, ProofRecorder, ApiExt,
 		InitializeBlock,
 	},
 	backend::{
 		self, BlockImportOperation, PrunableStateChangesTrieStorage,
-		StorageCollection, ChildStorageCollection
+		StorageCollection, ChildStorageCollection, StateEntry,
 	},
 	blockchain::{
 		self, Info as ChainInfo, Backend as ChainBackend,
@@ -136,6 +137,7 @@ pub struct ClientImportOperation<Block: BlockT, H: Hasher<Out=Block::Hash>, B: b
 		BlockOrigin,
 		Block::Header,
 		bool,
+		Vec<Block::Hash>,
 		Option<(
 			StorageCollection,
 			ChildStorageCollection,
@@ -211,6 +213,11 @@ pub struct BlockImportNotification<Block: BlockT> {
 	pub header: Block::Header,
 	/// Is this the new best block.
 	pub is_new_best: bool,
+	/// Hashes of the non-finalized ancestors of the block at the time of import,
+	/// starting with its parent.
+	///
+	/// Empty if the parent was already finalized or got finalized with the block.
+	pub non_finalized_ancestors: Vec<Block::Hash>,
 }
 
 /// Summary of a finalized block.
@@ -293,6 +300,45 @@ pub fn new_with_backend<B, E, Block, S, RA>(
 	Client::new(backend, call_executor, build_genesis_storage, Default::default())
 }
 
+/// Walks over at most `count` keys matching `key_prefix` that are strictly greater than
+/// `start_key`, using `next_key` to seek from one key to the next.
+fn keys_paged<E: state_machine::Error>(
+	key_prefix: &StorageKey,
+	start_key: Option<&StorageKey>,
+	count: usize,
+	exists: impl Fn(&[u8]) -> result::Result<bool, E>,
+	next_key: impl Fn(&[u8]) -> result::Result<Option<Vec<u8>>, E>,
+) -> error::Result<Vec<StorageKey>> {
+	let map_e = |e: E| error::Error::from_state(Box::new(e));
+	let mut keys = Vec::new();
+	if count == 0 {
+		return Ok(keys);
+	}
+
+	let mut current_key = match start_key {
+		Some(start_key) if start_key.0 >= key_prefix.0 => start_key.0.clone(),
+		_ => {
+			// the prefix itself is the first candidate, but `next_key` skips it
+			if exists(&key_prefix.0).map_err(map_e)? {
+				keys.push(key_prefix.clone());
+			}
+			key_prefix.0.clone()
+		},
+	};
+
+	while keys.len() < count {
+		match next_key(&current_key).map_err(map_e)? {
+			Some(key) if key.starts_with(&key_prefix.0) => {
+				current_key = key.clone();
+				keys.push(StorageKey(key));
+			},
+			_ => break,
+		}
+	}
+
+	Ok(keys)
+}
+
 /// Figure out the block type for a given type (for now, just a `Client`).
 pub trait BlockOf {
 	/// The type of the block.
@@ -375,6 +421,48 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Ok(keys)
 	}
 
+	/// Given a `BlockId` and a key prefix, return at most `count` matching storage keys that
+	/// are strictly greater than `start_key`, in lexicographic order.
+	///
+	/// The keys are found by seeking from one key to the next, so the work done is bounded by
+	/// `count` rather than by the number of keys matching the prefix.
+	pub fn storage_keys_paged(
+		&self,
+		id: &BlockId<Block>,
+		key_prefix: &StorageKey,
+		start_key: Option<&StorageKey>,
+		count: usize,
+	) -> error::Result<Vec<StorageKey>> {
+		let state = self.state_at(id)?;
+		keys_paged(
+			key_prefix,
+			start_key,
+			count,
+			|key| state.exists_storage(key),
+			|key| state.next_storage_key(key),
+		)
+	}
+
+	/// Given a `BlockId` and a key prefix, return at most `count` matching child storage keys
+	/// that are strictly greater than `start_key`, in lexicographic order.
+	pub fn child_storage_keys_paged(
+		&self,
+		id: &BlockId<Block>,
+		child_storage_key: &StorageKey,
+		key_prefix: &StorageKey,
+		start_key: Option<&StorageKey>,
+		count: usize,
+	) -> error::Result<Vec<StorageKey>> {
+		let state = self.state_at(id)?;
+		keys_paged(
+			key_prefix,
+			start_key,
+			count,
+			|key| state.exists_child_storage(&child_storage_key.0, key),
+			|key| state.next_child_storage_key(&child_storage_key.0, key),
+		)
+	}
+
 	/// Given a `BlockId` and a key, return the value under the key in that block.
 	pub fn storage(&self, id: &BlockId<Block>, key: &StorageKey) -> error::Result<Option<StorageData>> {
 		Ok(self.state_at(id)?
@@ -469,6 +557,74 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				.map_err(Into::into))
 	}
 
+	/// Reads storage values at a given block for a set of keys, returning
+	/// a single read proof covering all of them.
+	pub fn read_proof_keys(&self, id: &BlockId<Block>, keys: &[Vec<u8>]) -> error::Result<Vec<Vec<u8>>> {
+		self.state_at(id)
+			.and_then(|state| prove_read_keys(state, keys)
+				.map_err(Into::into))
+	}
+
+	/// Reads child storage values at a given block + storage_key for a set of
+	/// keys, returning a single read proof covering all of them.
+	pub fn read_child_proof_keys(
+		&self,
+		id: &BlockId<Block>,
+		storage_key: &[u8],
+		keys: &[Vec<u8>],
+	) -> error::Result<Vec<Vec<u8>>> {
+		self.state_at(id)
+			.and_then(|state| prove_child_read_keys(state, storage_key, keys)
+				.map_err(Into::into))
+	}
+
+	/// Reads the storage entries at a given block from `start` (inclusive) on, in the top
+	/// trie or in the given child trie, returning a proof covering about `max_bytes` of them.
+	pub fn state_range_proof(
+		&self,
+		id: &BlockId<Block>,
+		child_storage_key: Option<&[u8]>,
+		start: &[u8],
+		max_bytes: usize,
+	) -> error::Result<Vec<Vec<u8>>> {
+		self.state_at(id)
+			.and_then(|state| prove_range_read(state, child_storage_key, start, max_bytes)
+				.map_err(Into::into))
+	}
+
+	/// Checks a proof generated by `state_range_proof` against the root of the trie it was
+	/// generated for, returning the proven entries and whether they reach the end of the trie.
+	pub fn check_state_range_proof(
+		&self,
+		root: Block::Hash,
+		proof: Vec<Vec<u8>>,
+		start: &[u8],
+	) -> error::Result<(Vec<(Vec<u8>, Vec<u8>)>, bool)> {
+		read_range_proof_check::<Blake2Hasher>(convert_hash(&root), proof, start)
+			.map_err(Into::into)
+	}
+
+	/// Imports a block along with its full state, making it the best and last finalized
+	/// block. Blocks between genesis and the imported one are never downloaded.
+	///
+	/// See `backend::Backend::import_state`.
+	pub fn import_state<I>(
+		&self,
+		header: Block::Header,
+		body: Option<Vec<Block::Extrinsic>>,
+		justification: Option<Justification>,
+		state: I,
+	) -> error::Result<()> where
+		I: IntoIterator<Item=error::Result<StateEntry>>,
+	{
+		let _import_lock = self.backend.get_import_lock().lock();
+		let hash = header.hash();
+		let number = *header.number();
+		self.backend.import_state(header, body, justification, state)?;
+		info!("Imported state of block #{} ({})", number, hash);
+		Ok(())
+	}
+
 	/// Execute a call to a contract on top of state in a block of given hash
 	/// AND returning execution proof.
 	///
@@ -483,6 +639,28 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		prove_execution(state, header, &self.executor, method, call_data)
 	}
 
+	/// Apply the given extrinsic on top of the state of block `at` as if it was
+	/// included in a child of that block, without importing anything.
+	///
+	/// Returns the `ApplyResult` together with the overlay holding all changes
+	/// made while initializing the block and applying the extrinsic.
+	pub fn dry_run_extrinsic(
+		&self,
+		at: &BlockId<Block>,
+		extrinsic: Block::Extrinsic,
+	) -> error::Result<(ApplyResult, OverlayedChanges)> where
+		Self: ProvideRuntimeApi,
+		<Self as ProvideRuntimeApi>::Api: BlockBuilderAPI<Block>,
+	{
+		let header = self.prepare_environment_block(at)?;
+		let mut api = self.runtime_api();
+
+		api.initialize_block(at, &header)?;
+		let result = api.apply_extrinsic(at, extrinsic)?;
+
+		Ok((result, api.extract_changes()))
+	}
+
 	/// Reads given header and generates CHT-based header proof.
 	pub fn header_proof(&self, id: &BlockId<Block>) -> error::Result<(Block::Header, Vec<Vec<u8>>)> {
 		self.header_proof_with_cht_size(id, cht::size())
@@ -955,7 +1133,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		}
 
 		// FIXME #1232: correct path logic for when to execute this function
-		let (storage_update, changes_update, storage_changes) = self.block_execution(
+		let (storage_update, changes_update, storage_changes, offchain_changes) = self.block_execution(
 			&operation.op,
 			&import_headers,
 			origin,
@@ -995,6 +1173,9 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		if let Some(Some(changes_update)) = changes_update {
 			operation.op.update_changes_trie(changes_update)?;
 		}
+		if let Some(offchain_changes) = offchain_changes {
+			operation.op.update_offchain_storage(offchain_changes)?;
+		}
 
 		operation.op.insert_aux(aux)?;
 
@@ -1003,7 +1184,19 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				operation.notify_finalized.push(hash);
 			}
 
-			operation.notify_imported = Some((hash, origin, import_headers.into_post(), is_new_best, storage_changes));
+			let non_finalized_ancestors = if finalized {
+				Vec::new()
+			} else {
+				route_from_finalized.enacted().iter().rev().map(|entry| entry.hash).collect()
+			};
+			operation.notify_imported = Some((
+				hash,
+				origin,
+				import_headers.into_post(),
+				is_new_best,
+				non_finalized_ancestors,
+				storage_changes,
+			));
 		}
 
 		Ok(ImportResult::imported())
@@ -1022,7 +1215,8 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Option<(
 			Vec<(Vec<u8>, Option<Vec<u8>>)>,
 			Vec<(Vec<u8>, Vec<(Vec<u8>, Option<Vec<u8>>)>)>
-		)>
+		)>,
+		Option<Vec<(Vec<u8>, Option<Vec<u8>>)>>,
 	)>
 		where
 			E: CallExecutor<Block, Blake2Hasher> + Send + Sync + Clone,
@@ -1051,6 +1245,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 					}
 				};
 				let (_, storage_update, changes_update) = self.executor.call_at_state::<_, _, _, NeverNativeValue, fn() -> _>(
+					Some(&BlockId::Hash(hash)),
 					transaction_state,
 					&mut overlay,
 					"Core_execute_block",
@@ -1065,15 +1260,21 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 
 				overlay.commit_prospective();
 
+				let offchain_changes = overlay.offchain_storage_changes();
 				let (top, children) = overlay.into_committed();
 				let children = children.map(|(sk, it)| (sk, it.collect())).collect();
 				if import_headers.post().state_root() != &storage_update.1 {
 					return Err(error::Error::InvalidStateRoot);
 				}
 
-				Ok((Some(storage_update.0), Some(changes_update), Some((top.collect(), children))))
+				Ok((
+					Some(storage_update.0),
+					Some(changes_update),
+					Some((top.collect(), children)),
+					Some(offchain_changes),
+				))
 			},
-			None => Ok((None, None, None))
+			None => Ok((None, None, None, None))
 		}
 	}
 
@@ -1179,13 +1380,14 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			Block::Hash, BlockOrigin,
 			Block::Header,
 			bool,
+			Vec<Block::Hash>,
 			Option<(
 				Vec<(Vec<u8>, Option<Vec<u8>>)>,
 				Vec<(Vec<u8>, Vec<(Vec<u8>, Option<Vec<u8>>)>)>,
 				)
 			>),
 	) -> error::Result<()> {
-		let (hash, origin, header, is_new_best, storage_changes) = notify_import;
+		let (hash, origin, header, is_new_best, non_finalized_ancestors, storage_changes) = notify_import;
 
 		if let Some(storage_changes) = storage_changes {
 			// TODO [ToDr] How to handle re-orgs? Should we re-emit all storage changes?
@@ -1202,6 +1404,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			origin,
 			header,
 			is_new_best,
+			non_finalized_ancestors,
 		};
 
 		self.import_notification_sinks.lock()
@@ -1894,7 +2097,7 @@ pub(crate) mod tests {
 	use test_client::{
 		prelude::*,
 		client::backend::Backend as TestBackend,
-		client_db::{Backend, DatabaseSettings, PruningMode},
+		client_db::{Backend, DatabaseEngine, DatabaseSettings, PruningMode},
 		runtime::{self, Block, Transfer, RuntimeApi, TestAPI},
 	};
 
@@ -2829,6 +3032,7 @@ pub(crate) mod tests {
 				state_cache_child_ratio: None,
 				path: tmp.path().into(),
 				pruning: PruningMode::ArchiveAll,
+				engine: DatabaseEngine::RocksDb,
 			},
 			u64::max_value(),
 		).unwrap());
@@ -2905,4 +3109,12 @@ pub(crate) mod tests {
 			expected_err.to_string(),
 		);
 	}
+
+	#[test]
+	fn runtimes_declaring_the_current_core_api_are_not_stack_height_limited() {
+		use crate::runtime_api::{Core, RuntimeApiInfo};
+
+		assert_eq!(<dyn Core<Block>>::ID, executor::core_api_id());
+		assert!(<dyn Core<Block>>::VERSION < executor::LIMITED_CORE_API_VERSION);
+	}
 }
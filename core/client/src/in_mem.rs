@@ -35,6 +35,8 @@ use crate::light;
 use crate::leaves::LeafSet;
 use crate::blockchain::{self, BlockStatus, HeaderBackend};
 
+pub use primitives::offchain::storage::InMemOffchainStorage as OffchainStorage;
+
 struct PendingBlock<B: BlockT> {
 	block: StoredBlock<B>,
 	state: NewBlockState,
@@ -520,6 +522,10 @@ where
 		Ok(())
 	}
 
+	fn update_offchain_storage(&mut self, _update: StorageCollection) -> error::Result<()> {
+		Ok(())
+	}
+
 	fn mark_finalized(&mut self, block: BlockId<Block>, justification: Option<Justification>) -> error::Result<()> {
 		self.finalized_blocks.push((block, justification));
 		Ok(())
@@ -776,47 +782,6 @@ pub fn check_genesis_storage(top: &StorageOverlay, children: &ChildrenStorageOve
 	Ok(())
 }
 
-/// In-memory storage for offchain workers.
-#[derive(Debug, Clone, Default)]
-pub struct OffchainStorage {
-	storage: HashMap<Vec<u8>, Vec<u8>>,
-}
-
-impl backend::OffchainStorage for OffchainStorage {
-	fn set(&mut self, prefix: &[u8], key: &[u8], value: &[u8]) {
-		let key = prefix.iter().chain(key).cloned().collect();
-		self.storage.insert(key, value.to_vec());
-	}
-
-	fn get(&self, prefix: &[u8], key: &[u8]) -> Option<Vec<u8>> {
-		let key: Vec<u8> = prefix.iter().chain(key).cloned().collect();
-		self.storage.get(&key).cloned()
-	}
-
-	fn compare_and_set(
-		&mut self,
-		prefix: &[u8],
-		key: &[u8],
-		old_value: Option<&[u8]>,
-		new_value: &[u8],
-	) -> bool {
-		use std::collections::hash_map::Entry;
-		let key = prefix.iter().chain(key).cloned().collect();
-
-		match self.storage.entry(key) {
-			Entry::Vacant(entry) => if old_value.is_none() {
-				entry.insert(new_value.to_vec());
-				true
-			} else { false },
-			Entry::Occupied(ref mut entry) if Some(entry.get().as_slice()) == old_value => {
-				entry.insert(new_value.to_vec());
-				true
-			},
-			_ => false,
-		}
-	}
-}
-
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -839,26 +804,4 @@ mod tests {
 
 		test_client::trait_tests::test_blockchain_query_by_number_gets_canonical(backend);
 	}
-
-	#[test]
-	fn in_memory_offchain_storage() {
-		use crate::backend::OffchainStorage as _;
-
-		let mut storage = OffchainStorage::default();
-		assert_eq!(storage.get(b"A", b"B"), None);
-		assert_eq!(storage.get(b"B", b"A"), None);
-
-		storage.set(b"A", b"B", b"C");
-		assert_eq!(storage.get(b"A", b"B"), Some(b"C".to_vec()));
-		assert_eq!(storage.get(b"B", b"A"), None);
-
-		storage.compare_and_set(b"A", b"B", Some(b"X"), b"D");
-		assert_eq!(storage.get(b"A", b"B"), Some(b"C".to_vec()));
-		storage.compare_and_set(b"A", b"B", Some(b"C"), b"D");
-		assert_eq!(storage.get(b"A", b"B"), Some(b"D".to_vec()));
-
-		assert!(!storage.compare_and_set(b"B", b"A", Some(b""), b"Y"));
-		assert!(storage.compare_and_set(b"B", b"A", None, b"X"));
-		assert_eq!(storage.get(b"B", b"A"), Some(b"X".to_vec()));
-	}
 }
@@ -326,6 +326,11 @@ where
 		Ok(())
 	}
 
+	fn update_offchain_storage(&mut self, _update: StorageCollection) -> ClientResult<()> {
+		// we're not storing anything locally => ignore changes
+		Ok(())
+	}
+
 	fn mark_finalized(&mut self, block: BlockId<Block>, _justification: Option<Justification>) -> ClientResult<()> {
 		self.finalized_blocks.push(block);
 		Ok(())
@@ -373,6 +378,14 @@ where
 		Err(ClientError::NotAvailableOnLightClient.into())
 	}
 
+	fn next_storage_key(&self, _key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		Err(ClientError::NotAvailableOnLightClient.into())
+	}
+
+	fn next_child_storage_key(&self, _storage_key: &[u8], _key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		Err(ClientError::NotAvailableOnLightClient.into())
+	}
+
 	fn for_keys_with_prefix<A: FnMut(&[u8])>(&self, _prefix: &[u8], _action: A) {
 		// whole state is not available on light node
 	}
@@ -449,6 +462,24 @@ where
 		}
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		match *self {
+			OnDemandOrGenesisState::OnDemand(ref state) =>
+				StateBackend::<H>::next_storage_key(state, key),
+			OnDemandOrGenesisState::Genesis(ref state) =>
+				Ok(state.next_storage_key(key).expect(IN_MEMORY_EXPECT_PROOF)),
+		}
+	}
+
+	fn next_child_storage_key(&self, storage_key: &[u8], key: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		match *self {
+			OnDemandOrGenesisState::OnDemand(ref state) =>
+				StateBackend::<H>::next_child_storage_key(state, storage_key, key),
+			OnDemandOrGenesisState::Genesis(ref state) =>
+				Ok(state.next_child_storage_key(storage_key, key).expect(IN_MEMORY_EXPECT_PROOF)),
+		}
+	}
+
 	fn for_keys_with_prefix<A: FnMut(&[u8])>(&self, prefix: &[u8], action: A) {
 		match *self {
 			OnDemandOrGenesisState::OnDemand(ref state) =>
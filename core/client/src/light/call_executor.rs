@@ -175,6 +175,7 @@ where
 		R: Encode + Decode + PartialEq,
 		NC: FnOnce() -> result::Result<R, &'static str>,
 	>(&self,
+		_at: Option<&BlockId<Block>>,
 		_state: &S,
 		_changes: &mut OverlayedChanges,
 		_method: &str,
@@ -355,6 +356,7 @@ impl<Block, B, Remote, Local> CallExecutor<Block, Blake2Hasher> for
 		R: Encode + Decode + PartialEq,
 		NC: FnOnce() -> result::Result<R, &'static str> + UnwindSafe,
 	>(&self,
+		at: Option<&BlockId<Block>>,
 		state: &S,
 		changes: &mut OverlayedChanges,
 		method: &str,
@@ -381,6 +383,7 @@ impl<Block, B, Remote, Local> CallExecutor<Block, Blake2Hasher> for
 				NC
 			>(
 				&self.remote,
+				at,
 				state,
 				changes,
 				method,
@@ -104,6 +104,10 @@ pub trait ApiExt<Block: BlockT> {
 	/// Extract the recorded proof.
 	/// This stops the proof recording.
 	fn extract_proof(&mut self) -> Option<Vec<Vec<u8>>>;
+
+	/// Extract the changes made to the storage by the calls so far.
+	/// The following calls start from an empty overlay again.
+	fn extract_changes(&mut self) -> OverlayedChanges;
 }
 
 /// Before calling any runtime api function, the runtime need to be initialized
@@ -62,6 +62,16 @@ impl_stubs!(
 	},
 	test_empty_return => |_| Vec::new(),
 	test_exhaust_heap => |_| Vec::with_capacity(16777216),
+	test_allocate_vec => |input: &[u8]| {
+		// Allocate and free a buffer for each size in `input`, so that freed
+		// blocks of the heap are reused.
+		input.iter()
+			.map(|&size| {
+				let buffer = vec![size; size as usize * 1024];
+				buffer[buffer.len() - 1]
+			})
+			.collect::<Vec<_>>()
+	},
 	test_panic => |_| panic!("test panic"),
 	test_conditional_panic => |input: &[u8]| {
 		if input.len() > 0 {
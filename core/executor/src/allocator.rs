@@ -19,8 +19,8 @@
 
 use crate::error::{Error, Result};
 use log::trace;
+use crate::memory::Memory;
 use wasmi::MemoryRef;
-use wasmi::memory_units::Bytes;
 
 // The pointers need to be aligned to 8 bytes.
 const ALIGNMENT: u32 = 8;
@@ -33,10 +33,10 @@ const ALIGNMENT: u32 = 8;
 const N: usize = 22;
 const MAX_POSSIBLE_ALLOCATION: u32 = 16777216; // 2^24 bytes
 
-pub struct FreeingBumpHeapAllocator {
+pub struct FreeingBumpHeapAllocator<M: Memory = MemoryRef> {
 	bumper: u32,
 	heads: [u32; N],
-	heap: MemoryRef,
+	heap: M,
 	max_heap_size: u32,
 	ptr_offset: u32,
 	total_size: u32,
@@ -47,7 +47,7 @@ fn error(msg: &'static str) -> Error {
 	Error::Allocator(msg)
 }
 
-impl FreeingBumpHeapAllocator {
+impl<M: Memory> FreeingBumpHeapAllocator<M> {
 	/// Creates a new allocation heap which follows a freeing-bump strategy.
 	/// The maximum size which can be allocated at once is 16 MiB.
 	///
@@ -55,9 +55,8 @@ impl FreeingBumpHeapAllocator {
 	///
 	/// - `mem` - reference to the linear memory instance on which this allocator operates.
 	/// - `heap_base` - the offset from the beginning of the linear memory where the heap starts.
-	pub fn new(mem: MemoryRef, heap_base: u32) -> Self {
-		let current_size: Bytes = mem.current_size().into();
-		let current_size = current_size.0 as u32;
+	pub fn new(mem: M, heap_base: u32) -> Self {
+		let current_size = mem.byte_size();
 
 		let mut ptr_offset = heap_base;
 		let padding = ptr_offset % ALIGNMENT;
@@ -95,7 +94,7 @@ impl FreeingBumpHeapAllocator {
 			// Something from the free list
 			let item = self.heads[list_index];
 			let four_bytes = self.get_heap_4bytes(item)?;
-			self.heads[list_index] = FreeingBumpHeapAllocator::le_bytes_to_u32(four_bytes);
+			self.heads[list_index] = Self::le_bytes_to_u32(four_bytes);
 			item + 8
 		} else {
 			// Nothing to be freed. Bump.
@@ -125,10 +124,10 @@ impl FreeingBumpHeapAllocator {
 		self.heads[list_index] = ptr - 8;
 
 		let mut slice = self.get_heap_4bytes(ptr - 8)?;
-		FreeingBumpHeapAllocator::write_u32_into_le_bytes(tail, &mut slice);
+		Self::write_u32_into_le_bytes(tail, &mut slice);
 		self.set_heap_4bytes(ptr - 8, slice)?;
 
-		let item_size = FreeingBumpHeapAllocator::get_item_size_from_index(list_index);
+		let item_size = Self::get_item_size_from_index(list_index);
 		self.total_size = self.total_size.checked_sub(item_size as u32 + 8)
 			.ok_or_else(|| error("Unable to subtract from total heap size without overflow"))?;
 		trace!(target: "wasm-heap", "Heap size is {} bytes after deallocation", self.total_size);
@@ -169,11 +168,11 @@ impl FreeingBumpHeapAllocator {
 	}
 
 	fn set_heap(&mut self, ptr: u32, value: u8) -> Result<()> {
-		self.heap.set(self.ptr_offset + ptr, &[value]).map_err(Into::into)
+		self.heap.set(self.ptr_offset + ptr, &[value])
 	}
 
 	fn set_heap_4bytes(&mut self, ptr: u32, value: [u8; 4]) -> Result<()> {
-		self.heap.set(self.ptr_offset + ptr, &value).map_err(Into::into)
+		self.heap.set(self.ptr_offset + ptr, &value)
 	}
 
 }
@@ -438,7 +437,7 @@ mod tests {
 		let mut heap = vec![0; 5];
 
 		// when
-		FreeingBumpHeapAllocator::write_u32_into_le_bytes(1, &mut heap[0..4]);
+		FreeingBumpHeapAllocator::<MemoryRef>::write_u32_into_le_bytes(1, &mut heap[0..4]);
 
 		// then
 		assert_eq!(heap, [1, 0, 0, 0, 0]);
@@ -450,7 +449,7 @@ mod tests {
 		let mut heap = vec![0; 5];
 
 		// when
-		FreeingBumpHeapAllocator::write_u32_into_le_bytes(u32::max_value(), &mut heap[0..4]);
+		FreeingBumpHeapAllocator::<MemoryRef>::write_u32_into_le_bytes(u32::max_value(), &mut heap[0..4]);
 
 		// then
 		assert_eq!(heap, [255, 255, 255, 255, 0]);
@@ -462,7 +461,7 @@ mod tests {
 		let index = 0;
 
 		// when
-		let item_size = FreeingBumpHeapAllocator::get_item_size_from_index(index);
+		let item_size = FreeingBumpHeapAllocator::<MemoryRef>::get_item_size_from_index(index);
 
 		// then
 		assert_eq!(item_size, 8);
@@ -474,7 +473,7 @@ mod tests {
 		let index = 21;
 
 		// when
-		let item_size = FreeingBumpHeapAllocator::get_item_size_from_index(index);
+		let item_size = FreeingBumpHeapAllocator::<MemoryRef>::get_item_size_from_index(index);
 
 		// then
 		assert_eq!(item_size as u32, MAX_POSSIBLE_ALLOCATION);
@@ -76,6 +76,9 @@ pub enum Error {
 	/// Someone tried to allocate more memory than the allowed maximum per allocation.
 	#[display(fmt="Requested allocation size is too large")]
 	RequestedAllocationTooLarge,
+	/// Compiling, instantiating or calling a runtime with `wasmtime` failed.
+	#[display(fmt="Wasmtime error: {}", _0)]
+	Wasmtime(String),
 }
 
 impl std::error::Error for Error {
@@ -0,0 +1,144 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests calling the test runtime with both execution methods and checking they agree.
+
+use primitives::{blake2_256, twox_128, Blake2Hasher};
+use runtime_test::WASM_BINARY;
+use state_machine::TestExternalities as CoreTestExternalities;
+
+use crate::error::Result;
+use crate::wasm_executor::WasmExecutor;
+use crate::wasmtime_executor::CompiledRuntime;
+
+type TestExternalities = CoreTestExternalities<Blake2Hasher, u64>;
+
+fn new_ext() -> TestExternalities {
+	let mut ext = TestExternalities::default();
+	ext.set_storage(b"foo".to_vec(), b"bar".to_vec());
+	ext.set_storage(b"aab".to_vec(), b"2".to_vec());
+	ext.set_storage(b"abb".to_vec(), b"4".to_vec());
+	ext
+}
+
+/// Call `method` with both execution methods and check that they agree on the result
+/// and on the resulting storage.
+fn call_both(method: &str, data: &[u8]) -> Result<Vec<u8>> {
+	let mut interpreted_ext = new_ext();
+	let interpreted = WasmExecutor::new().call(&mut interpreted_ext, 8, WASM_BINARY, method, data);
+
+	let mut compiled_ext = new_ext();
+	let compiled = CompiledRuntime::new::<TestExternalities>(WASM_BINARY, 8)
+		.and_then(|runtime| runtime.call(&mut compiled_ext, method, data));
+
+	assert_eq!(interpreted.as_ref().ok(), compiled.as_ref().ok(), "results of {} differ", method);
+	assert_eq!(interpreted_ext, compiled_ext, "storage after {} differs", method);
+	compiled
+}
+
+#[test]
+fn returning_should_match() {
+	assert_eq!(call_both("test_empty_return", &[]).unwrap(), Vec::<u8>::new());
+}
+
+#[test]
+fn traps_should_match() {
+	assert!(call_both("test_panic", &[]).is_err());
+	assert!(call_both("test_conditional_panic", &[2]).is_err());
+	assert_eq!(call_both("test_conditional_panic", &[]).unwrap(), Vec::<u8>::new());
+}
+
+#[test]
+fn storage_should_match() {
+	assert_eq!(call_both("test_data_in", b"Hello world").unwrap(), b"all ok!".to_vec());
+	assert_eq!(call_both("test_clear_prefix", b"ab").unwrap(), b"all ok!".to_vec());
+}
+
+#[test]
+fn hashing_should_match() {
+	assert_eq!(call_both("test_blake2_256", b"Hello world!").unwrap(), blake2_256(b"Hello world!").to_vec());
+	assert_eq!(call_both("test_twox_128", b"Hello world!").unwrap(), twox_128(b"Hello world!").to_vec());
+	call_both("test_ordered_trie_root", &[]).unwrap();
+}
+
+#[test]
+fn allocator_should_match() {
+	let sizes = [1, 64, 2, 128, 2, 1];
+	assert_eq!(call_both("test_allocate_vec", &sizes).unwrap(), sizes.to_vec());
+	assert!(call_both("test_exhaust_heap", &[]).is_err());
+}
+
+#[test]
+fn sandbox_should_match() {
+	let code = wabt::wat2wasm(r#"
+	(module
+		(import "env" "assert" (func $assert (param i32)))
+		(import "env" "inc_counter" (func $inc_counter (param i32) (result i32)))
+		(func (export "call")
+			(drop
+				(call $inc_counter (i32.const 5))
+			)
+
+			(call $inc_counter (i32.const 3))
+			;; current counter value is on the stack
+
+			;; check whether current == 8
+			i32.const 8
+			i32.eq
+
+			call $assert
+		)
+	)
+	"#).unwrap();
+	assert_eq!(call_both("test_sandbox", &code).unwrap(), vec![1]);
+
+	let code = wabt::wat2wasm(r#"
+	(module
+		(func (export "call") (param $x i32) (result i32)
+			(i32.add
+				(get_local $x)
+				(i32.const 1)
+			)
+		)
+	)
+	"#).unwrap();
+	assert_eq!(call_both("test_sandbox_return_val", &code).unwrap(), vec![1]);
+}
+
+#[test]
+fn sandbox_traps_should_match() {
+	let code = wabt::wat2wasm(r#"
+	(module
+		(import "env" "assert" (func $assert (param i32)))
+		(func (export "call")
+			i32.const 0
+			call $assert
+		)
+	)
+	"#).unwrap();
+	assert_eq!(call_both("test_sandbox", &code).unwrap(), vec![0]);
+
+	let code = wabt::wat2wasm(r#"
+	(module
+		(func (export "call"))
+		(func $start
+			unreachable
+		)
+		(start $start)
+	)
+	"#).unwrap();
+	assert_eq!(call_both("test_sandbox_instantiate", &code).unwrap(), vec![2]);
+}
@@ -35,19 +35,45 @@ mod wasm_executor;
 mod native_executor;
 mod sandbox;
 mod allocator;
+mod memory;
+mod stack_height;
 mod wasm_runtimes_cache;
+mod wasm_disk_cache;
+#[cfg(feature = "wasmtime")]
+mod wasmtime_executor;
+#[cfg(all(test, feature = "wasmtime"))]
+mod integration_tests;
 
 pub mod error;
+pub mod profiling;
 pub use wasmi;
 pub use wasm_executor::WasmExecutor;
 pub use native_executor::{with_native_environment, NativeExecutor, NativeExecutionDispatch};
 pub use wasm_runtimes_cache::RuntimesCache;
+pub use wasm_disk_cache::{WasmDiskCache, CacheKey as WasmCacheKey};
+pub use stack_height::{core_api_id, LIMITED_CORE_API_VERSION};
 pub use state_machine::Externalities;
 pub use runtime_version::{RuntimeVersion, NativeVersion};
 pub use codec::Codec;
 #[doc(hidden)]
 pub use primitives::Blake2Hasher;
 
+/// Specifies how the Wasm runtime is executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WasmExecutionMethod {
+	/// Interpret the runtime with `wasmi`.
+	Interpreted,
+	/// Compile the runtime to native code with `wasmtime`.
+	#[cfg(feature = "wasmtime")]
+	Compiled,
+}
+
+impl Default for WasmExecutionMethod {
+	fn default() -> Self {
+		WasmExecutionMethod::Interpreted
+	}
+}
+
 /// Provides runtime information.
 pub trait RuntimeInfo {
 	/// Native runtime information.
@@ -0,0 +1,82 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Access to the linear memory of a runtime instance.
+//!
+//! Host functions and the heap allocator are written against the [`Memory`] trait,
+//! so that they can be shared between the interpreted and the compiled executors.
+
+use crate::error::{Error, Result};
+use wasmi::MemoryRef;
+use wasmi::memory_units::Bytes;
+
+/// Linear memory of a Wasm instance.
+pub trait Memory {
+	/// Read `size` bytes starting at `offset`.
+	fn get(&self, offset: u32, size: usize) -> Result<Vec<u8>>;
+
+	/// Fill `target` with the bytes starting at `offset`.
+	fn get_into(&self, offset: u32, target: &mut [u8]) -> Result<()>;
+
+	/// Write `data` starting at `offset`.
+	///
+	/// Nothing is written if the range is out of bounds.
+	fn set(&self, offset: u32, data: &[u8]) -> Result<()>;
+
+	/// Current size of the memory in bytes.
+	fn byte_size(&self) -> u32;
+}
+
+impl Memory for MemoryRef {
+	fn get(&self, offset: u32, size: usize) -> Result<Vec<u8>> {
+		wasmi::MemoryInstance::get(self, offset, size).map_err(Into::into)
+	}
+
+	fn get_into(&self, offset: u32, target: &mut [u8]) -> Result<()> {
+		wasmi::MemoryInstance::get_into(self, offset, target).map_err(Into::into)
+	}
+
+	fn set(&self, offset: u32, data: &[u8]) -> Result<()> {
+		wasmi::MemoryInstance::set(self, offset, data).map_err(Into::into)
+	}
+
+	fn byte_size(&self) -> u32 {
+		let size: Bytes = self.current_size().into();
+		size.0 as u32
+	}
+}
+
+/// Copy `len` bytes from `src` at `src_offset` into `dst` at `dst_offset`.
+pub fn transfer<S: Memory, D: Memory>(
+	src: &S,
+	src_offset: u32,
+	dst: &D,
+	dst_offset: u32,
+	len: usize,
+) -> Result<()> {
+	let data = src.get(src_offset, len)?;
+	dst.set(dst_offset, &data)
+}
+
+/// Check that `len` bytes starting at `offset` lie within a memory of `size` bytes.
+pub fn check_bounds(size: u32, offset: u32, len: usize) -> Result<()> {
+	let end = (offset as usize).checked_add(len)
+		.ok_or_else(|| Error::Other("memory access overflows"))?;
+	if end > size as usize {
+		return Err(Error::Other("memory access out of bounds"));
+	}
+	Ok(())
+}
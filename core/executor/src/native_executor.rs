@@ -20,7 +20,7 @@ use state_machine::{CodeExecutor, Externalities};
 use crate::wasm_executor::WasmExecutor;
 use runtime_version::{NativeVersion, RuntimeVersion};
 use codec::{Decode, Encode};
-use crate::RuntimeInfo;
+use crate::{RuntimeInfo, WasmExecutionMethod, WasmDiskCache};
 use primitives::{Blake2Hasher, NativeOrEncoded};
 use log::{trace, warn};
 
@@ -68,18 +68,35 @@ pub struct NativeExecutor<D> {
 	native_version: NativeVersion,
 	/// The number of 64KB pages to allocate for Wasm execution.
 	default_heap_pages: Option<u64>,
+	/// How the Wasm runtime is executed.
+	wasm_method: WasmExecutionMethod,
+	/// Where prepared runtimes are persisted across restarts.
+	disk_cache: Option<WasmDiskCache>,
 }
 
 impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 	/// Create new instance.
 	pub fn new(default_heap_pages: Option<u64>) -> Self {
+		Self::with_wasm_method(WasmExecutionMethod::default(), default_heap_pages)
+	}
+
+	/// Create new instance executing the Wasm runtime with the given method.
+	pub fn with_wasm_method(wasm_method: WasmExecutionMethod, default_heap_pages: Option<u64>) -> Self {
 		NativeExecutor {
 			_dummy: Default::default(),
 			fallback: WasmExecutor::new(),
 			native_version: D::native_version(),
 			default_heap_pages: default_heap_pages,
+			wasm_method,
+			disk_cache: None,
 		}
 	}
+
+	/// Persist prepared runtimes in the given disk cache.
+	pub fn with_disk_cache(mut self, disk_cache: Option<WasmDiskCache>) -> Self {
+		self.disk_cache = disk_cache;
+		self
+	}
 }
 
 impl<D: NativeExecutionDispatch> Clone for NativeExecutor<D> {
@@ -89,6 +106,8 @@ impl<D: NativeExecutionDispatch> Clone for NativeExecutor<D> {
 			fallback: self.fallback.clone(),
 			native_version: D::native_version(),
 			default_heap_pages: self.default_heap_pages,
+			wasm_method: self.wasm_method,
+			disk_cache: self.disk_cache.clone(),
 		}
 	}
 }
@@ -105,7 +124,13 @@ impl<D: NativeExecutionDispatch> RuntimeInfo for NativeExecutor<D> {
 		RUNTIMES_CACHE.with(|cache| {
 			let cache = &mut cache.borrow_mut();
 
-			match cache.fetch_runtime(&self.fallback, ext, self.default_heap_pages) {
+			match cache.fetch_runtime(
+				&self.fallback,
+				ext,
+				self.default_heap_pages,
+				self.wasm_method,
+				self.disk_cache.as_ref(),
+			) {
 				Ok(runtime) => runtime.version(),
 				Err(e) => {
 					warn!(target: "executor", "Failed to fetch runtime: {:?}", e);
@@ -135,7 +160,7 @@ impl<D: NativeExecutionDispatch> CodeExecutor<Blake2Hasher> for NativeExecutor<D
 		RUNTIMES_CACHE.with(|cache| {
 			let cache = &mut cache.borrow_mut();
 			let cached_runtime = match cache.fetch_runtime(
-				&self.fallback, ext, self.default_heap_pages,
+				&self.fallback, ext, self.default_heap_pages, self.wasm_method, self.disk_cache.as_ref(),
 			) {
 				Ok(cached_runtime) => cached_runtime,
 				Err(e) => return (Err(e), false),
@@ -158,21 +183,15 @@ impl<D: NativeExecutionDispatch> CodeExecutor<Blake2Hasher> for NativeExecutor<D
 							.map_or_else(||"<None>".into(), |v| format!("{}", v))
 					);
 					(
-						cached_runtime.with(|module|
-							self.fallback
-								.call_in_wasm_module(ext, module, method, data)
-								.map(NativeOrEncoded::Encoded)
-						),
+						cached_runtime.call(&self.fallback, ext, method, data)
+							.map(NativeOrEncoded::Encoded),
 						false
 					)
 				}
 				(false, _, _) => {
 					(
-						cached_runtime.with(|module|
-							self.fallback
-								.call_in_wasm_module(ext, module, method, data)
-								.map(NativeOrEncoded::Encoded)
-						),
+						cached_runtime.call(&self.fallback, ext, method, data)
+							.map(NativeOrEncoded::Encoded),
 						false
 					)
 				}
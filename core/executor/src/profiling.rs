@@ -0,0 +1,313 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Profiling of host function calls made by the Wasm runtime.
+//!
+//! When enabled, every call into the runtime collects, per host function, the number of
+//! calls, the time spent in them and the number of storage bytes read and written. Once
+//! the call finishes, the resulting [`CallProfile`] is logged as JSON at debug level
+//! under the `wasm-profile` target and appended to the output file, if any. The profile of a
+//! `Core_execute_block` call is the summary of executing one block.
+//!
+//! Native execution doesn't go through host functions and is not profiled.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use parking_lot::Mutex;
+use serde::Serialize;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+	static ref OUTPUT: Mutex<Option<File>> = Mutex::new(None);
+}
+
+thread_local! {
+	static CURRENT: RefCell<Option<Collector>> = RefCell::new(None);
+}
+
+#[cfg(test)]
+thread_local! {
+	/// Profiles reported on this thread, if collected. Lets tests tell their own profiles
+	/// apart from the ones of other tests running concurrently.
+	static REPORTED: RefCell<Option<Vec<CallProfile>>> = RefCell::new(None);
+}
+
+/// Statistics of a single host function.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct HostFunctionStats {
+	/// Number of calls.
+	pub calls: u64,
+	/// Total time spent in the function, in nanoseconds.
+	pub total_ns: u64,
+	/// Longest single call, in nanoseconds.
+	pub max_ns: u64,
+	/// Storage bytes read by the function.
+	pub bytes_read: u64,
+	/// Storage bytes written by the function.
+	pub bytes_written: u64,
+}
+
+/// Profile of a single call into the runtime.
+#[derive(Debug, Clone, Serialize)]
+pub struct CallProfile {
+	/// The called runtime method.
+	pub method: String,
+	/// Length of the input passed to the method.
+	pub input_len: usize,
+	/// Total duration of the call, in nanoseconds.
+	pub total_ns: u64,
+	/// Time spent in host functions, in nanoseconds.
+	pub host_ns: u64,
+	/// Statistics per host function, keyed by name.
+	pub host_functions: BTreeMap<&'static str, HostFunctionStats>,
+}
+
+struct Collector {
+	profile: CallProfile,
+	started: Instant,
+	/// The host function currently running.
+	current: Option<&'static str>,
+}
+
+/// Enable or disable profiling.
+///
+/// If `output` is given, the profile of every call is appended to that file, one JSON object per line.
+pub fn enable(enabled: bool, output: Option<&Path>) -> io::Result<()> {
+	let file = match output {
+		Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+		None => None,
+	};
+	*OUTPUT.lock() = file;
+	ENABLED.store(enabled, Ordering::Relaxed);
+	Ok(())
+}
+
+/// Whether profiling is enabled.
+pub fn is_enabled() -> bool {
+	ENABLED.load(Ordering::Relaxed)
+}
+
+/// Profiles a call into the runtime until dropped.
+pub(crate) struct CallGuard(bool);
+
+impl CallGuard {
+	/// Start profiling a call of `method`, unless profiling is disabled or a call is
+	/// already profiled on this thread.
+	pub(crate) fn start(method: &str, input_len: usize) -> Self {
+		if !is_enabled() {
+			return CallGuard(false);
+		}
+		CallGuard(CURRENT.with(|current| {
+			let mut current = current.borrow_mut();
+			if current.is_some() {
+				return false;
+			}
+			*current = Some(Collector {
+				profile: CallProfile {
+					method: method.into(),
+					input_len,
+					total_ns: 0,
+					host_ns: 0,
+					host_functions: BTreeMap::new(),
+				},
+				started: Instant::now(),
+				current: None,
+			});
+			true
+		}))
+	}
+}
+
+impl Drop for CallGuard {
+	fn drop(&mut self) {
+		if !self.0 {
+			return;
+		}
+		let collector = CURRENT.with(|current| current.borrow_mut().take());
+		if let Some(mut collector) = collector {
+			collector.profile.total_ns = as_nanos(collector.started);
+			report(&collector.profile);
+		}
+	}
+}
+
+/// Profiles a host function call until dropped.
+pub(crate) struct HostCallGuard(Option<(&'static str, Instant)>);
+
+impl HostCallGuard {
+	/// Start profiling a call of host function `name`.
+	pub(crate) fn enter(name: &'static str) -> Self {
+		if !is_enabled() {
+			return HostCallGuard(None);
+		}
+		let active = CURRENT.with(|current| match *current.borrow_mut() {
+			Some(ref mut collector) => {
+				collector.current = Some(name);
+				true
+			},
+			None => false,
+		});
+		HostCallGuard(if active { Some((name, Instant::now())) } else { None })
+	}
+}
+
+impl Drop for HostCallGuard {
+	fn drop(&mut self) {
+		if let Some((name, started)) = self.0.take() {
+			let elapsed = as_nanos(started);
+			with_stats(name, |stats| {
+				stats.calls += 1;
+				stats.total_ns += elapsed;
+				stats.max_ns = stats.max_ns.max(elapsed);
+			});
+			CURRENT.with(|current| if let Some(ref mut collector) = *current.borrow_mut() {
+				collector.profile.host_ns += elapsed;
+				collector.current = None;
+			});
+		}
+	}
+}
+
+/// Account storage bytes read and written by the currently running host function.
+pub(crate) fn record_storage_bytes(read: usize, written: usize) {
+	if !is_enabled() {
+		return;
+	}
+	let name = CURRENT.with(|current| current.borrow().as_ref().and_then(|c| c.current));
+	if let Some(name) = name {
+		with_stats(name, |stats| {
+			stats.bytes_read += read as u64;
+			stats.bytes_written += written as u64;
+		});
+	}
+}
+
+fn with_stats(name: &'static str, f: impl FnOnce(&mut HostFunctionStats)) {
+	CURRENT.with(|current| if let Some(ref mut collector) = *current.borrow_mut() {
+		f(collector.profile.host_functions.entry(name).or_default());
+	});
+}
+
+fn as_nanos(started: Instant) -> u64 {
+	let elapsed = started.elapsed();
+	elapsed.as_secs() * 1_000_000_000 + u64::from(elapsed.subsec_nanos())
+}
+
+fn report(profile: &CallProfile) {
+	#[cfg(test)]
+	REPORTED.with(|reported| if let Some(ref mut reported) = *reported.borrow_mut() {
+		reported.push(profile.clone());
+	});
+
+	let json = match serde_json::to_string(profile) {
+		Ok(json) => json,
+		Err(e) => {
+			warn!(target: "wasm-profile", "Failed to serialize profile: {}", e);
+			return;
+		}
+	};
+	debug!(target: "wasm-profile", "{}", json);
+	if let Some(ref mut file) = *OUTPUT.lock() {
+		if let Err(e) = writeln!(file, "{}", json) {
+			warn!(target: "wasm-profile", "Failed to write profile: {}", e);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use primitives::Blake2Hasher;
+	use runtime_test::WASM_BINARY;
+	use state_machine::TestExternalities;
+	use crate::WasmExecutor;
+
+	lazy_static! {
+		/// Profiling is enabled globally, tests enabling it must not run concurrently.
+		static ref ENABLE_LOCK: Mutex<()> = Mutex::new(());
+	}
+
+	#[test]
+	fn should_collect_host_function_stats() {
+		let _lock = ENABLE_LOCK.lock();
+		enable(true, None).unwrap();
+		let guard = CallGuard::start("Core_execute_block", 3);
+		for _ in 0..2 {
+			let _host = HostCallGuard::enter("ext_get_storage_into");
+			record_storage_bytes(32, 0);
+		}
+		{
+			let _host = HostCallGuard::enter("ext_set_storage");
+			record_storage_bytes(0, 8);
+		}
+		record_storage_bytes(100, 100);
+
+		let profile = CURRENT.with(|current| current.borrow().as_ref().unwrap().profile.clone());
+		drop(guard);
+		enable(false, None).unwrap();
+
+		assert_eq!(profile.method, "Core_execute_block");
+		let get = &profile.host_functions["ext_get_storage_into"];
+		assert_eq!((get.calls, get.bytes_read, get.bytes_written), (2, 64, 0));
+		let set = &profile.host_functions["ext_set_storage"];
+		assert_eq!((set.calls, set.bytes_read, set.bytes_written), (1, 0, 8));
+		assert!(CURRENT.with(|current| current.borrow().is_none()));
+	}
+
+	#[test]
+	fn should_profile_calls_of_the_executor() {
+		let _lock = ENABLE_LOCK.lock();
+		let dir = tempfile::tempdir().unwrap();
+		let output = dir.path().join("profile.json");
+		enable(true, Some(&output)).unwrap();
+		REPORTED.with(|reported| *reported.borrow_mut() = Some(Vec::new()));
+
+		let mut ext = TestExternalities::<Blake2Hasher, u64>::default();
+		WasmExecutor::new().call(&mut ext, 8, WASM_BINARY, "test_data_in", b"Hello world").unwrap();
+		WasmExecutor::new().call_with_custom_signature(
+			&mut ext,
+			8,
+			WASM_BINARY,
+			"test_empty_return",
+			|_| Ok(vec![wasmi::RuntimeValue::I32(0), wasmi::RuntimeValue::I32(0)]),
+			|res, _| Ok(res.map(|_| ())),
+		).unwrap();
+		enable(false, None).unwrap();
+		let profiles = REPORTED.with(|reported| reported.borrow_mut().take().unwrap());
+
+		assert_eq!(profiles.len(), 2);
+		assert_eq!(profiles[0].method, "test_data_in");
+		assert_eq!(profiles[0].input_len, 11);
+		assert_eq!(profiles[0].host_functions["ext_set_storage"].calls, 2);
+		assert_eq!(profiles[1].method, "test_empty_return");
+
+		// executor tests running concurrently on other threads may write to the output as well
+		let written = std::fs::read_to_string(&output).unwrap();
+		for profile in &profiles {
+			let json = serde_json::to_string(profile).unwrap();
+			assert!(written.lines().any(|line| line == json));
+		}
+	}
+}
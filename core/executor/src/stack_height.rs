@@ -0,0 +1,204 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Deterministic stack height limit for runtime code.
+//!
+//! Without a limit, how deep a runtime may recurse depends on the execution engine and on
+//! the native stack of the host, so a runtime recursing deeply enough could crash some
+//! nodes or make them disagree with others. Before the runtime code is instantiated it is
+//! instrumented to keep track of its stack height, counted in values (locals and operands)
+//! rather than bytes. Exceeding `MAX_STACK_HEIGHT` executes `unreachable`, so every node
+//! traps at exactly the same point regardless of the backend.
+//!
+//! Enabling the limit is a consensus change, since calls recursing deeper than it used to
+//! succeed. Runtimes opt into it by declaring version `LIMITED_CORE_API_VERSION` of the
+//! `Core` runtime API, which is newer than the version runtimes currently declare, so the
+//! runtimes existing chains already run are executed as before.
+//!
+//! Whether a runtime is limited is decided once per runtime code, from the version it
+//! reports when called without any state.
+
+use parity_wasm::elements::{self, Module as RawModule};
+use runtime_version::{ApiId, RuntimeVersion};
+
+use crate::error::{Error, Result};
+
+/// The maximal stack height a runtime may reach, in values.
+///
+/// This is a consensus-critical constant: changing it changes which runtime calls succeed.
+/// It is kept below the value stack limit of `wasmi` and low enough for compiled code to
+/// stay within the native stack, so that the instrumented limit is always hit first.
+pub const MAX_STACK_HEIGHT: u32 = 32 * 1024;
+
+/// The first version of the `Core` runtime API whose runtimes are executed with the limit.
+pub const LIMITED_CORE_API_VERSION: u32 = 3;
+
+/// Identifier of the `Core` runtime API.
+///
+/// Derived from the name of the trait the same way `decl_runtime_apis!` does.
+pub fn core_api_id() -> ApiId {
+	let mut id = [0; 8];
+	id.copy_from_slice(blake2_rfc::blake2b::blake2b(8, &[], b"Core").as_bytes());
+	id
+}
+
+/// Whether the runtime with given version is executed with the stack height limit.
+pub(crate) fn is_limited(version: &RuntimeVersion) -> bool {
+	let core_api_id = core_api_id();
+	version.apis.iter().any(|(id, version)| *id == core_api_id && *version >= LIMITED_CORE_API_VERSION)
+}
+
+/// Prepare the given runtime code for execution, given the version the runtime reports.
+///
+/// Runtimes that don't report their version are executed as they are.
+pub(crate) fn prepare(code: &[u8], version: Option<&RuntimeVersion>) -> Result<Vec<u8>> {
+	if version.map_or(false, is_limited) {
+		instrument(code)
+	} else {
+		Ok(code.to_vec())
+	}
+}
+
+/// Instrument the given module with the stack height limiter.
+pub(crate) fn inject_limiter(module: RawModule) -> Result<RawModule> {
+	pwasm_utils::stack_height::inject_limiter(module, MAX_STACK_HEIGHT)
+		.map_err(|_| Error::InvalidCode("stack height instrumentation failed".into()))
+}
+
+/// Instrument the given runtime code with the stack height limiter.
+pub(crate) fn instrument(code: &[u8]) -> Result<Vec<u8>> {
+	let module = elements::deserialize_buffer(code)
+		.map_err(|e| Error::InvalidCode(format!("{}", e)))?;
+	elements::serialize(inject_limiter(module)?)
+		.map_err(|e| Error::InvalidCode(format!("{}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use state_machine::TestExternalities;
+	use primitives::Blake2Hasher;
+	use wabt;
+
+	use crate::WasmExecutor;
+
+	/// A module exporting `test(_, depth)` which recurses `depth` times.
+	///
+	/// The depth is passed as the length of the input, so the same method can be called
+	/// with the signature every runtime method has.
+	fn recursive_module() -> Vec<u8> {
+		wabt::wat2wasm(r#"
+			(module
+				(memory (export "memory") 1)
+				(global (export "__heap_base") i32 (i32.const 1024))
+				(func $recurse (param $depth i32) (result i32)
+					(local $a i64) (local $b i64) (local $c i64) (local $d i64)
+					(if (result i32) (i32.eqz (get_local $depth))
+						(then (i32.const 0))
+						(else (call $recurse (i32.sub (get_local $depth) (i32.const 1))))
+					)
+				)
+				(func (export "test") (param i32 i32) (result i64)
+					(drop (call $recurse (get_local 1)))
+					(i64.const 0)
+				)
+			)
+		"#).unwrap()
+	}
+
+	fn call_with_depth(depth: u32) -> Result<Vec<u8>> {
+		let mut ext = TestExternalities::<Blake2Hasher, u64>::default();
+		WasmExecutor::new().call_with_custom_signature(
+			&mut ext,
+			1,
+			&instrument(&recursive_module()).unwrap(),
+			"test",
+			|_| Ok(vec![wasmi::RuntimeValue::I32(0), wasmi::RuntimeValue::I32(depth as i32)]),
+			|_, _| Ok(Some(Vec::<u8>::new())),
+		)
+	}
+
+	#[test]
+	fn shallow_recursion_succeeds() {
+		assert!(call_with_depth(100).is_ok());
+	}
+
+	#[test]
+	fn deep_recursion_traps_deterministically() {
+		let depth = MAX_STACK_HEIGHT;
+		match call_with_depth(depth) {
+			Err(Error::Trap(trap)) => match trap.kind() {
+				wasmi::TrapKind::Unreachable => {},
+				kind => panic!("expected the injected limiter to trap, got {:?}", kind),
+			},
+			other => panic!("expected a trap, got {:?}", other.map(|_| ())),
+		}
+		// A trapped call leaves no trace in the next one.
+		assert!(call_with_depth(100).is_ok());
+	}
+
+	#[cfg(feature = "wasmtime")]
+	#[test]
+	fn compiled_code_traps_at_the_same_depth() {
+		use crate::wasmtime_executor::CompiledRuntime;
+
+		// Find the deepest recursion the interpreter allows.
+		let (mut ok, mut failed) = (0, MAX_STACK_HEIGHT);
+		while failed - ok > 1 {
+			let depth = ok + (failed - ok) / 2;
+			match call_with_depth(depth) {
+				Ok(_) => ok = depth,
+				Err(_) => failed = depth,
+			}
+		}
+
+		let runtime = CompiledRuntime::new::<TestExternalities<Blake2Hasher, u64>>(
+			&instrument(&recursive_module()).unwrap(),
+			1,
+		).unwrap();
+		let call_compiled = |depth: u32| runtime.call(
+			&mut TestExternalities::<Blake2Hasher, u64>::default(),
+			"test",
+			&vec![0; depth as usize],
+		);
+
+		assert!(call_compiled(ok).is_ok());
+		match call_compiled(ok + 1) {
+			Err(Error::Wasmtime(trap)) => assert!(
+				trap.contains("unreachable"),
+				"expected the injected limiter to trap, got {}",
+				trap,
+			),
+			other => panic!("expected a trap, got {:?}", other.map(|_| ())),
+		}
+		assert!(call_compiled(100).is_ok());
+	}
+
+	#[test]
+	fn only_runtimes_declaring_the_limited_core_api_are_limited() {
+		let version = |apis: Vec<(ApiId, u32)>| RuntimeVersion { apis: apis.into(), ..Default::default() };
+
+		assert!(!is_limited(&version(vec![])));
+		assert!(!is_limited(&version(vec![(core_api_id(), LIMITED_CORE_API_VERSION - 1)])));
+		assert!(!is_limited(&version(vec![([0; 8], LIMITED_CORE_API_VERSION)])));
+		assert!(is_limited(&version(vec![(core_api_id(), LIMITED_CORE_API_VERSION)])));
+	}
+
+	#[test]
+	fn instrumentation_rejects_invalid_code() {
+		assert!(instrument(&[0, 1, 2, 3]).is_err());
+	}
+}
@@ -0,0 +1,319 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Persistent cache of prepared runtime modules.
+//!
+//! Entries are keyed by the hash of the runtime code, the number of heap pages and the
+//! execution method. Every entry starts with a header holding the format version, the
+//! build of the executor that produced it and a checksum of the payload. Entries failing
+//! any of these checks are removed and treated as missing. Once the number of entries
+//! exceeds the limit, the least recently used ones are evicted.
+//!
+//! Runtimes compiled with `wasmtime` additionally have their native code persisted by the
+//! compilation cache of `wasmtime`, in the `compiled` subdirectory.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use filetime::FileTime;
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use primitives::{blake2_256, hexdisplay::HexDisplay};
+
+use crate::WasmExecutionMethod;
+use crate::stack_height;
+
+/// Magic bytes every entry starts with.
+const MAGIC: &[u8; 4] = b"swcm";
+/// Version of the entry format. Bump when the layout or the preparation of modules changes.
+///
+/// Version 2: modules are instrumented with the stack height limiter.
+/// Version 3: compiled runtimes are stored as instrumented code instead of native code.
+/// Version 4: only runtimes opting into the stack height limit are instrumented.
+const FORMAT_VERSION: u32 = 4;
+/// Extension of the entry files.
+const EXTENSION: &str = "wasmcache";
+/// Length of the header: magic, format version, build tag and payload checksum.
+const HEADER_LEN: usize = 4 + 4 + 32 + 32;
+
+/// Default maximal number of entries kept in the cache.
+pub const DEFAULT_MAX_ENTRIES: usize = 16;
+
+lazy_static! {
+	static ref BUILD_TAG: [u8; 32] = blake2_256(build_description().as_bytes());
+}
+
+/// Describe everything a prepared module depends on besides the runtime code: the version
+/// of the executor and the instrumentation.
+///
+/// Prepared modules are Wasm code, so they don't depend on the compiler. The native code of
+/// compiled runtimes is tagged by `wasmtime` itself.
+fn build_description() -> String {
+	format!(
+		"{}-{} stack-height-{}",
+		env!("CARGO_PKG_NAME"),
+		env!("CARGO_PKG_VERSION"),
+		stack_height::MAX_STACK_HEIGHT,
+	)
+}
+
+/// Identity of the executor build. Entries produced by another build are rejected.
+fn build_tag() -> [u8; 32] {
+	*BUILD_TAG
+}
+
+/// Key of a cache entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheKey {
+	/// Hash of the runtime code.
+	pub code_hash: [u8; 32],
+	/// Number of heap pages the runtime was prepared with.
+	pub heap_pages: u64,
+	/// Execution method the runtime was prepared for.
+	pub wasm_method: WasmExecutionMethod,
+}
+
+impl CacheKey {
+	fn file_name(&self) -> String {
+		format!(
+			"{}-{}-{:?}.{}",
+			HexDisplay::from(&self.code_hash),
+			self.heap_pages,
+			self.wasm_method,
+			EXTENSION,
+		).to_lowercase()
+	}
+}
+
+/// A cache of prepared runtime modules in a directory on disk.
+///
+/// The cache is best effort: I/O errors are logged and reported as cache misses.
+///
+/// Entries are trusted: runtimes restored from the cache are not instrumented again, so
+/// a tampered entry could change the outcome of runtime calls. The directory must not be
+/// writable by anyone but the node.
+#[derive(Debug, Clone)]
+pub struct WasmDiskCache {
+	dir: PathBuf,
+	max_entries: usize,
+}
+
+impl WasmDiskCache {
+	/// Create a cache in given directory, keeping at most `DEFAULT_MAX_ENTRIES` entries.
+	///
+	/// The directory is created when the first entry is stored.
+	pub fn new(dir: PathBuf) -> Self {
+		Self::with_max_entries(dir, DEFAULT_MAX_ENTRIES)
+	}
+
+	/// Create a cache in given directory, keeping at most `max_entries` entries.
+	pub fn with_max_entries(dir: PathBuf, max_entries: usize) -> Self {
+		WasmDiskCache { dir, max_entries }
+	}
+
+	/// Directory of the cache.
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// Directory of the compilation cache of `wasmtime`.
+	#[cfg(feature = "wasmtime")]
+	pub fn compiled_dir(&self) -> PathBuf {
+		self.dir.join("compiled")
+	}
+
+	/// Load the payload stored under given key.
+	///
+	/// Returns `None` if there is no such entry or if it fails the integrity checks,
+	/// in which case the entry is removed.
+	pub fn load(&self, key: &CacheKey) -> Option<Vec<u8>> {
+		let path = self.dir.join(key.file_name());
+		let data = match fs::read(&path) {
+			Ok(data) => data,
+			Err(ref e) if e.kind() == io::ErrorKind::NotFound => return None,
+			Err(e) => {
+				warn!(target: "wasm-cache", "Failed to read {}: {}", path.display(), e);
+				return None;
+			}
+		};
+
+		match decode_entry(&data) {
+			Ok(payload) => {
+				debug!(target: "wasm-cache", "Loaded {} from the cache", path.display());
+				// Eviction removes the entries that were used the longest time ago.
+				if let Err(e) = filetime::set_file_mtime(&path, FileTime::now()) {
+					warn!(target: "wasm-cache", "Failed to touch {}: {}", path.display(), e);
+				}
+				Some(payload.to_vec())
+			},
+			Err(reason) => {
+				warn!(target: "wasm-cache", "Removing invalid entry {}: {}", path.display(), reason);
+				if let Err(e) = fs::remove_file(&path) {
+					warn!(target: "wasm-cache", "Failed to remove {}: {}", path.display(), e);
+				}
+				None
+			}
+		}
+	}
+
+	/// Store the payload under given key, evicting the least recently used entries if the
+	/// cache is full.
+	pub fn store(&self, key: &CacheKey, payload: &[u8]) {
+		if let Err(e) = self.try_store(key, payload) {
+			warn!(target: "wasm-cache", "Failed to store {} in the cache: {}", key.file_name(), e);
+		}
+	}
+
+	fn try_store(&self, key: &CacheKey, payload: &[u8]) -> io::Result<()> {
+		fs::create_dir_all(&self.dir)?;
+
+		let mut data = Vec::with_capacity(HEADER_LEN + payload.len());
+		data.extend_from_slice(MAGIC);
+		data.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+		data.extend_from_slice(&build_tag());
+		data.extend_from_slice(&blake2_256(payload));
+		data.extend_from_slice(payload);
+
+		// Write to a temporary file first, so that a crash never leaves a truncated entry behind.
+		let path = self.dir.join(key.file_name());
+		let tmp_path = path.with_extension("tmp");
+		fs::write(&tmp_path, &data)?;
+		fs::rename(&tmp_path, &path)?;
+
+		self.evict()
+	}
+
+	/// Remove the least recently used entries until at most `max_entries` are left.
+	///
+	/// Entries are touched whenever they are loaded, so their modification time is
+	/// the time they were last used.
+	fn evict(&self) -> io::Result<()> {
+		let mut entries = Vec::new();
+		for entry in fs::read_dir(&self.dir)? {
+			let entry = entry?;
+			let path = entry.path();
+			if path.extension().map_or(true, |ext| ext != EXTENSION) {
+				continue;
+			}
+			let modified = entry.metadata()?.modified()?;
+			entries.push((modified, path));
+		}
+
+		if entries.len() <= self.max_entries {
+			return Ok(());
+		}
+		entries.sort();
+		let to_remove = entries.len() - self.max_entries;
+		for (_, path) in entries.into_iter().take(to_remove) {
+			debug!(target: "wasm-cache", "Evicting {}", path.display());
+			fs::remove_file(&path)?;
+		}
+		Ok(())
+	}
+}
+
+/// Check the header of an entry and return its payload.
+fn decode_entry(data: &[u8]) -> Result<&[u8], &'static str> {
+	if data.len() < HEADER_LEN {
+		return Err("entry is truncated");
+	}
+	let (header, payload) = data.split_at(HEADER_LEN);
+	if &header[0..4] != MAGIC {
+		return Err("bad magic");
+	}
+	let mut version = [0u8; 4];
+	version.copy_from_slice(&header[4..8]);
+	if u32::from_le_bytes(version) != FORMAT_VERSION {
+		return Err("unsupported format version");
+	}
+	if header[8..40] != build_tag() {
+		return Err("entry was produced by another build");
+	}
+	if header[40..72] != blake2_256(payload) {
+		return Err("checksum mismatch");
+	}
+	Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn key(n: u8) -> CacheKey {
+		CacheKey {
+			code_hash: [n; 32],
+			heap_pages: 1024,
+			wasm_method: WasmExecutionMethod::Interpreted,
+		}
+	}
+
+	#[test]
+	fn should_store_and_load_entries() {
+		let dir = tempfile::tempdir().unwrap();
+		let cache = WasmDiskCache::new(dir.path().join("wasm-cache"));
+
+		assert_eq!(cache.load(&key(1)), None);
+		cache.store(&key(1), b"prepared");
+		assert_eq!(cache.load(&key(1)), Some(b"prepared".to_vec()));
+		assert_eq!(cache.load(&CacheKey { heap_pages: 8, ..key(1) }), None);
+	}
+
+	#[test]
+	fn should_remove_corrupted_entries() {
+		let dir = tempfile::tempdir().unwrap();
+		let cache = WasmDiskCache::new(dir.path().to_path_buf());
+		cache.store(&key(1), b"prepared");
+
+		let path = dir.path().join(key(1).file_name());
+		let mut data = fs::read(&path).unwrap();
+		*data.last_mut().unwrap() ^= 1;
+		fs::write(&path, &data).unwrap();
+
+		assert_eq!(cache.load(&key(1)), None);
+		assert!(!path.exists());
+	}
+
+	fn set_last_used(dir: &Path, key: &CacheKey, seconds: i64) {
+		let path = dir.join(key.file_name());
+		filetime::set_file_mtime(&path, FileTime::from_unix_time(seconds, 0)).unwrap();
+	}
+
+	#[test]
+	fn should_evict_least_recently_used_entries() {
+		let dir = tempfile::tempdir().unwrap();
+		let cache = WasmDiskCache::with_max_entries(dir.path().to_path_buf(), 2);
+
+		cache.store(&key(1), &[1]);
+		cache.store(&key(2), &[2]);
+		set_last_used(dir.path(), &key(1), 1);
+		set_last_used(dir.path(), &key(2), 2);
+		// loading an entry marks it as used now
+		assert_eq!(cache.load(&key(1)), Some(vec![1]));
+		cache.store(&key(3), &[3]);
+
+		assert_eq!(cache.load(&key(1)), Some(vec![1]));
+		assert_eq!(cache.load(&key(2)), None);
+		assert_eq!(cache.load(&key(3)), Some(vec![3]));
+	}
+
+	#[test]
+	fn build_tag_should_describe_the_build() {
+		let description = build_description();
+		assert!(description.contains(env!("CARGO_PKG_VERSION")));
+		assert!(description.contains(&format!("stack-height-{}", stack_height::MAX_STACK_HEIGHT)));
+	}
+}
@@ -21,12 +21,12 @@ use tiny_keccak;
 use secp256k1;
 
 use wasmi::{
-	Module, ModuleInstance, MemoryInstance, MemoryRef, TableRef, ImportsBuilder, ModuleRef,
+	Module, ModuleInstance, MemoryRef, TableRef, ImportsBuilder, ModuleRef, FuncInstance, FuncRef,
 	memory_units::Pages, RuntimeValue::{I32, I64, self},
 };
-use state_machine::{Externalities, ChildStorageKey};
+use state_machine::{BasicExternalities, Externalities, ChildStorageKey};
 use crate::error::{Error, Result};
-use codec::Encode;
+use codec::{Decode, Encode};
 use primitives::{
 	blake2_128, blake2_256, twox_64, twox_128, twox_256, ed25519, sr25519, Pair, crypto::KeyTypeId,
 	offchain, hexdisplay::HexDisplay, sandbox as sandbox_primitives, H256, Blake2Hasher,
@@ -34,6 +34,10 @@ use primitives::{
 use trie::{TrieConfiguration, trie_types::Layout};
 use crate::sandbox;
 use crate::allocator;
+use crate::memory::{self, Memory};
+use crate::profiling;
+use crate::stack_height;
+use runtime_version::RuntimeVersion;
 use log::trace;
 
 #[cfg(feature="wasm-extern-trace")]
@@ -45,17 +49,55 @@ macro_rules! debug_trace {
 	( $( $x:tt )* ) => ()
 }
 
-struct FunctionExecutor<'e, E: Externalities<Blake2Hasher> + 'e> {
-	sandbox_store: sandbox::Store,
-	heap: allocator::FreeingBumpHeapAllocator,
-	memory: MemoryRef,
-	table: Option<TableRef>,
+/// The function table of a runtime, which the sandbox dispatches calls into the runtime through.
+pub(crate) trait Table: Sized {
+	/// Reference to a function in the table.
+	type FuncRef;
+
+	/// Returns the function at `index`, `None` if the entry is empty.
+	///
+	/// Returns `Err` if `index` is out of the table bounds.
+	fn func(&self, index: u32) -> std::result::Result<Option<Self::FuncRef>, ()>;
+
+	/// Call `func` with given arguments, dispatching the host calls it makes to `executor`.
+	fn invoke<'e, E: Externalities<Blake2Hasher> + 'e, M: Memory + 'e>(
+		executor: &mut FunctionExecutor<'e, E, M, Self>,
+		func: &Self::FuncRef,
+		args: &[RuntimeValue],
+	) -> Result<Option<RuntimeValue>>;
+}
+
+impl Table for TableRef {
+	type FuncRef = FuncRef;
+
+	fn func(&self, index: u32) -> std::result::Result<Option<FuncRef>, ()> {
+		self.get(index).map_err(|_| ())
+	}
+
+	fn invoke<'e, E: Externalities<Blake2Hasher> + 'e, M: Memory + 'e>(
+		executor: &mut FunctionExecutor<'e, E, M, Self>,
+		func: &FuncRef,
+		args: &[RuntimeValue],
+	) -> Result<Option<RuntimeValue>> {
+		FuncInstance::invoke(func, args, executor).map_err(Into::into)
+	}
+}
+
+pub(crate) struct FunctionExecutor<'e, E, M = MemoryRef, T = TableRef> where
+	E: Externalities<Blake2Hasher> + 'e,
+	M: Memory,
+	T: Table,
+{
+	sandbox_store: sandbox::Store<T::FuncRef>,
+	heap: allocator::FreeingBumpHeapAllocator<M>,
+	memory: M,
+	table: Option<T>,
 	ext: &'e mut E,
 	hash_lookup: HashMap<Vec<u8>, Vec<u8>>,
 }
 
-impl<'e, E: Externalities<Blake2Hasher>> FunctionExecutor<'e, E> {
-	fn new(m: MemoryRef, heap_base: u32, t: Option<TableRef>, e: &'e mut E) -> Result<Self> {
+impl<'e, E: Externalities<Blake2Hasher>, M: Memory + Clone, T: Table> FunctionExecutor<'e, E, M, T> {
+	pub(crate) fn new(m: M, heap_base: u32, t: Option<T>, e: &'e mut E) -> Result<Self> {
 		Ok(FunctionExecutor {
 			sandbox_store: sandbox::Store::new(),
 			heap: allocator::FreeingBumpHeapAllocator::new(m.clone(), heap_base),
@@ -67,11 +109,15 @@ impl<'e, E: Externalities<Blake2Hasher>> FunctionExecutor<'e, E> {
 	}
 }
 
-impl<'e, E: Externalities<Blake2Hasher>> sandbox::SandboxCapabilities for FunctionExecutor<'e, E> {
-	fn store(&self) -> &sandbox::Store {
+impl<'e, E: Externalities<Blake2Hasher> + 'e, M: Memory + 'e, T: Table> sandbox::SandboxCapabilities
+	for FunctionExecutor<'e, E, M, T>
+{
+	type SupervisorFuncRef = T::FuncRef;
+
+	fn store(&self) -> &sandbox::Store<T::FuncRef> {
 		&self.sandbox_store
 	}
-	fn store_mut(&mut self) -> &mut sandbox::Store {
+	fn store_mut(&mut self) -> &mut sandbox::Store<T::FuncRef> {
 		&mut self.sandbox_store
 	}
 	fn allocate(&mut self, len: u32) -> Result<u32> {
@@ -81,10 +127,29 @@ impl<'e, E: Externalities<Blake2Hasher>> sandbox::SandboxCapabilities for Functi
 		self.heap.deallocate(ptr)
 	}
 	fn write_memory(&mut self, ptr: u32, data: &[u8]) -> Result<()> {
-		self.memory.set(ptr, data).map_err(Into::into)
+		self.memory.set(ptr, data)
 	}
 	fn read_memory(&self, ptr: u32, len: u32) -> Result<Vec<u8>> {
-		self.memory.get(ptr, len as usize).map_err(Into::into)
+		self.memory.get(ptr, len as usize)
+	}
+	fn invoke(
+		&mut self,
+		dispatch_thunk: &T::FuncRef,
+		invoke_args_ptr: u32,
+		invoke_args_len: u32,
+		state: u32,
+		func_idx: sandbox::SupervisorFuncIndex,
+	) -> Result<i64> {
+		let args = [
+			I32(invoke_args_ptr as i32),
+			I32(invoke_args_len as i32),
+			I32(state as i32),
+			I32(usize::from(func_idx) as i32),
+		];
+		match T::invoke(self, dispatch_thunk, &args)? {
+			Some(I64(result)) => Ok(result),
+			_ => Err(Error::InvalidReturn),
+		}
 	}
 }
 
@@ -92,12 +157,12 @@ trait WritePrimitive<T: Sized> {
 	fn write_primitive(&self, offset: u32, t: T) -> Result<()>;
 }
 
-impl WritePrimitive<u32> for MemoryInstance {
+impl<M: Memory> WritePrimitive<u32> for M {
 	fn write_primitive(&self, offset: u32, t: u32) -> Result<()> {
 		use byteorder::{LittleEndian, ByteOrder};
 		let mut r = [0u8; 4];
 		LittleEndian::write_u32(&mut r, t);
-		self.set(offset, &r).map_err(Into::into)
+		self.set(offset, &r)
 	}
 }
 
@@ -105,7 +170,7 @@ trait ReadPrimitive<T: Sized> {
 	fn read_primitive(&self, offset: u32) -> Result<T>;
 }
 
-impl ReadPrimitive<u32> for MemoryInstance {
+impl<M: Memory> ReadPrimitive<u32> for M {
 	fn read_primitive(&self, offset: u32) -> Result<u32> {
 		use byteorder::{LittleEndian, ByteOrder};
 		let result = self.get(offset, 4)?;
@@ -121,7 +186,7 @@ fn deadline_to_timestamp(deadline: u64) -> Option<offchain::Timestamp> {
 	}
 }
 
-impl_function_executor!(this: FunctionExecutor<'e, E>,
+impl_function_executor!(this: FunctionExecutor<'e, E, M, T>,
 	ext_print_utf8(utf8_data: *const u8, utf8_len: u32) => {
 		if let Ok(utf8) = this.memory.get(utf8_data, utf8_len as usize) {
 			if let Ok(message) = String::from_utf8(utf8) {
@@ -172,6 +237,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 				HexDisplay::from(&key),
 			);
 		}
+		profiling::record_storage_bytes(0, value.len());
 		this.ext.set_storage(key, value);
 		Ok(())
 	},
@@ -208,6 +274,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		}
 		let storage_key = ChildStorageKey::from_vec(storage_key)
 			.ok_or_else(|| "ext_set_child_storage: child storage key is invalid")?;
+		profiling::record_storage_bytes(0, value.len());
 		this.ext.set_child_storage(storage_key, key, value);
 		Ok(())
 	},
@@ -298,6 +365,20 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		this.ext.clear_child_prefix(storage_key, &prefix);
 		Ok(())
 	},
+	ext_offchain_index_set(key_data: *const u8, key_len: u32, value_data: *const u8, value_len: u32) => {
+		let key = this.memory.get(key_data, key_len as usize)
+			.map_err(|_| "Invalid attempt to determine key in ext_offchain_index_set")?;
+		let value = this.memory.get(value_data, value_len as usize)
+			.map_err(|_| "Invalid attempt to determine value in ext_offchain_index_set")?;
+		this.ext.set_offchain_storage(&key, Some(&value));
+		Ok(())
+	},
+	ext_offchain_index_clear(key_data: *const u8, key_len: u32) => {
+		let key = this.memory.get(key_data, key_len as usize)
+			.map_err(|_| "Invalid attempt to determine key in ext_offchain_index_clear")?;
+		this.ext.set_offchain_storage(&key, None);
+		Ok(())
+	},
 	ext_kill_child_storage(storage_key_data: *const u8, storage_key_len: u32) => {
 		let storage_key = this.memory.get(
 			storage_key_data,
@@ -331,6 +412,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 			HexDisplay::from(&key),
 		);
 
+		profiling::record_storage_bytes(maybe_value.as_ref().map_or(0, |v| v.len()), 0);
 		if let Some(value) = maybe_value {
 			let offset = this.heap.allocate(value.len() as u32)? as u32;
 			this.memory.set(offset, &value)
@@ -383,6 +465,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 			HexDisplay::from(&key),
 		);
 
+		profiling::record_storage_bytes(maybe_value.as_ref().map_or(0, |v| v.len()), 0);
 		if let Some(value) = maybe_value {
 			let offset = this.heap.allocate(value.len() as u32)? as u32;
 			this.memory.set(offset, &value)
@@ -425,6 +508,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		if let Some(value) = maybe_value {
 			let value = &value[value_offset as usize..];
 			let written = std::cmp::min(value_len as usize, value.len());
+			profiling::record_storage_bytes(written, 0);
 			this.memory.set(value_data, &value[..written])
 				.map_err(|_| "Invalid attempt to set value in ext_get_storage_into")?;
 			Ok(value.len() as u32)
@@ -475,6 +559,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		if let Some(value) = maybe_value {
 			let value = &value[value_offset as usize..];
 			let written = std::cmp::min(value_len as usize, value.len());
+			profiling::record_storage_bytes(written, 0);
 			this.memory.set(value_data, &value[..written])
 				.map_err(|_| "Invalid attempt to set value in ext_get_child_storage_into")?;
 			Ok(value.len() as u32)
@@ -1210,10 +1295,9 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		let dispatch_thunk = {
 			let table = this.table.as_ref()
 				.ok_or_else(|| "Runtime doesn't have a table; sandbox is unavailable")?;
-			table.get(dispatch_thunk_idx)
+			table.func(dispatch_thunk_idx)
 				.map_err(|_| "dispatch_thunk_idx is out of the table bounds")?
 				.ok_or_else(|| "dispatch_thunk_idx points on an empty table entry")?
-				.clone()
 		};
 
 		let instance_idx_or_err_code =
@@ -1285,11 +1369,11 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 	ext_sandbox_memory_get(memory_idx: u32, offset: u32, buf_ptr: *mut u8, buf_len: u32) -> u32 => {
 		let sandboxed_memory = this.sandbox_store.memory(memory_idx)?;
 
-		match MemoryInstance::transfer(
+		match memory::transfer(
 			&sandboxed_memory,
-			offset as usize,
+			offset,
 			&this.memory,
-			buf_ptr as usize,
+			buf_ptr,
 			buf_len as usize,
 		) {
 			Ok(()) => Ok(sandbox_primitives::ERR_OK),
@@ -1299,11 +1383,11 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 	ext_sandbox_memory_set(memory_idx: u32, offset: u32, val_ptr: *const u8, val_len: u32) -> u32 => {
 		let sandboxed_memory = this.sandbox_store.memory(memory_idx)?;
 
-		match MemoryInstance::transfer(
+		match memory::transfer(
 			&this.memory,
-			val_ptr as usize,
+			val_ptr,
 			&sandboxed_memory,
-			offset as usize,
+			offset,
 			val_len as usize,
 		) {
 			Ok(()) => Ok(sandbox_primitives::ERR_OK),
@@ -1314,7 +1398,7 @@ impl_function_executor!(this: FunctionExecutor<'e, E>,
 		this.sandbox_store.memory_teardown(memory_idx)?;
 		Ok(())
 	},
-	=> <'e, E: Externalities<Blake2Hasher> + 'e>
+	=> <'e, E: Externalities<Blake2Hasher> + 'e, M: Memory + 'e, T: Table + 'e>
 );
 
 /// Wasm rust executor for contracts.
@@ -1343,7 +1427,8 @@ impl WasmExecutor {
 		method: &str,
 		data: &[u8],
 	) -> Result<Vec<u8>> {
-		let module = ::wasmi::Module::from_buffer(code)?;
+		let code = self.prepare_code(heap_pages, code)?;
+		let module = ::wasmi::Module::from_buffer(&code)?;
 		let module = Self::instantiate_module::<E>(heap_pages, &module)?;
 		self.call_in_wasm_module(ext, &module, method, data)
 	}
@@ -1365,7 +1450,8 @@ impl WasmExecutor {
 		create_parameters: F,
 		filter_result: FR,
 	) -> Result<R> {
-		let module = wasmi::Module::from_buffer(code)?;
+		let code = self.prepare_code(heap_pages, code)?;
+		let module = wasmi::Module::from_buffer(&code)?;
 		let module = Self::instantiate_module::<E>(heap_pages, &module)?;
 		self.call_in_wasm_module_with_custom_signature(
 			ext,
@@ -1376,6 +1462,30 @@ impl WasmExecutor {
 		)
 	}
 
+	/// Prepare the given runtime code for execution.
+	///
+	/// The code is instrumented with the stack height limiter if the runtime opts into it
+	/// through its version, see the `stack_height` module.
+	pub(crate) fn prepare_code(&self, heap_pages: usize, code: &[u8]) -> Result<Vec<u8>> {
+		let version = self.runtime_version(heap_pages, code)?;
+		stack_height::prepare(code, version.as_ref())
+	}
+
+	/// Get the version the given runtime code reports.
+	///
+	/// `Core_version` is called without any state, so the result only depends on the code.
+	/// Returns `None` if the runtime doesn't provide its version.
+	pub(crate) fn runtime_version(&self, heap_pages: usize, code: &[u8]) -> Result<Option<RuntimeVersion>> {
+		let module = wasmi::Module::from_buffer(code)?;
+		let instance = Self::instantiate_module::<BasicExternalities>(heap_pages, &module)?;
+		let mut ext = BasicExternalities::default();
+		Ok(
+			self.call_in_wasm_module(&mut ext, &instance, "Core_version", &[])
+				.ok()
+				.and_then(|v| RuntimeVersion::decode(&mut &v[..]).ok())
+		)
+	}
+
 	fn get_mem_instance(module: &ModuleRef) -> Result<MemoryRef> {
 		Ok(module
 			.export_by_name("memory")
@@ -1409,6 +1519,7 @@ impl WasmExecutor {
 		method: &str,
 		data: &[u8],
 	) -> Result<Vec<u8>> {
+		let _profile = profiling::CallGuard::start(method, data.len());
 		self.call_in_wasm_module_with_custom_signature(
 			ext,
 			module_instance,
@@ -1443,6 +1554,10 @@ impl WasmExecutor {
 		create_parameters: F,
 		filter_result: FR,
 	) -> Result<R> {
+		// The input is unknown with a custom signature, profiles of calls going through
+		// `call_in_wasm_module` are started there with the input length.
+		let _profile = profiling::CallGuard::start(method, 0);
+
 		// extract a reference to a linear memory, optional reference to a table
 		// and then initialize FunctionExecutor.
 		let memory = Self::get_mem_instance(module_instance)?;
@@ -1486,7 +1601,7 @@ impl WasmExecutor {
 		let intermediate_instance = ModuleInstance::new(
 			module,
 			&ImportsBuilder::new()
-			.with_resolver("env", FunctionExecutor::<E>::resolver())
+			.with_resolver("env", FunctionExecutor::<E, MemoryRef, TableRef>::resolver())
 		)?;
 
 		// Verify that the module has the heap base global variable.
@@ -1516,7 +1631,7 @@ mod tests {
 	use hex_literal::hex;
 	use primitives::map;
 	use runtime_test::WASM_BINARY;
-	use substrate_offchain::testing;
+	use primitives::offchain::testing;
 
 	type TestExternalities<H> = CoreTestExternalities<H, u64>;
 
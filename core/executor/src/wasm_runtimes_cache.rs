@@ -18,6 +18,11 @@
 
 use crate::error::Error;
 use crate::wasm_executor::WasmExecutor;
+use crate::WasmExecutionMethod;
+use crate::stack_height;
+use crate::wasm_disk_cache::{CacheKey, WasmDiskCache};
+#[cfg(feature = "wasmtime")]
+use crate::wasmtime_executor::{self, CompiledRuntime};
 use log::{trace, warn};
 use codec::Decode;
 use parity_wasm::elements::{deserialize_buffer, DataSegment, Instruction, Module as RawModule};
@@ -39,33 +44,59 @@ enum CacheError {
 	Instantiation(Error),
 }
 
-/// A runtime along with its version and initial state snapshot.
+/// A runtime prepared for execution with one of the execution methods.
+#[derive(Clone)]
+enum WasmRuntime {
+	/// A `wasmi` module instance along with its initial state snapshot.
+	Interpreted {
+		/// A wasm module instance.
+		instance: WasmModuleInstanceRef,
+		/// The snapshot of the instance's state taken just after the instantiation.
+		state_snapshot: StateSnapshot,
+	},
+	/// A runtime compiled with `wasmtime`.
+	#[cfg(feature = "wasmtime")]
+	Compiled(CompiledRuntime),
+}
+
+/// A runtime along with its version and the way it is executed.
 #[derive(Clone)]
 pub struct CachedRuntime {
-	/// A wasm module instance.
-	instance: WasmModuleInstanceRef,
+	/// The runtime ready to be called.
+	runtime: WasmRuntime,
 	/// Runtime version according to `Core_version`.
 	///
 	/// Can be `None` if the runtime doesn't expose this function.
 	version: Option<RuntimeVersion>,
-	/// The snapshot of the instance's state taken just after the instantiation.
-	state_snapshot: StateSnapshot,
+	/// The number of heap pages the runtime was prepared with.
+	heap_pages: u64,
+	/// The execution method the runtime was prepared for.
+	wasm_method: WasmExecutionMethod,
 }
 
 impl CachedRuntime {
-	/// Perform an operation with the clean version of the runtime wasm instance.
-	pub fn with<R, F>(&self, f: F) -> R
-	where
-		F: FnOnce(&WasmModuleInstanceRef) -> R,
-	{
-		self.state_snapshot.apply(&self.instance).expect(
-			"applying the snapshot can only fail if the passed instance is different
-			from the one that was used for creation of the snapshot;
-			we use the snapshot that is directly associated with the instance;
-			thus the snapshot was created using the instance;
-			qed",
-		);
-		f(&self.instance)
+	/// Call a given method in the clean version of the runtime.
+	pub fn call<E: Externalities<Blake2Hasher>>(
+		&self,
+		wasm_executor: &WasmExecutor,
+		ext: &mut E,
+		method: &str,
+		data: &[u8],
+	) -> Result<Vec<u8>, Error> {
+		match self.runtime {
+			WasmRuntime::Interpreted { ref instance, ref state_snapshot } => {
+				state_snapshot.apply(instance).expect(
+					"applying the snapshot can only fail if the passed instance is different
+					from the one that was used for creation of the snapshot;
+					we use the snapshot that is directly associated with the instance;
+					thus the snapshot was created using the instance;
+					qed",
+				);
+				wasm_executor.call_in_wasm_module(ext, instance, method, data)
+			},
+			#[cfg(feature = "wasmtime")]
+			WasmRuntime::Compiled(ref runtime) => runtime.call(ext, method, data),
+		}
 	}
 
 	/// Returns the version of this cached runtime.
@@ -86,7 +117,6 @@ struct StateSnapshot {
 	data_segments: Vec<(u32, Vec<u8>)>,
 	/// The list of all global mutable variables of the module in their sequential order.
 	global_mut_values: Vec<RuntimeValue>,
-	heap_pages: u64,
 }
 
 impl StateSnapshot {
@@ -94,7 +124,6 @@ impl StateSnapshot {
 	fn take(
 		module_instance: &WasmModuleInstanceRef,
 		data_segments: Vec<DataSegment>,
-		heap_pages: u64,
 	) -> Option<Self> {
 		let prepared_segments = data_segments
 			.into_iter()
@@ -135,7 +164,6 @@ impl StateSnapshot {
 		Some(Self {
 			data_segments: prepared_segments,
 			global_mut_values,
-			heap_pages,
 		})
 	}
 
@@ -221,18 +249,23 @@ impl RuntimesCache {
 	/// `wasm_executor`- Rust wasm executor. Executes the provided code in a
 	/// sandboxed Wasm runtime.
 	///
-	/// `ext` - Externalities to use for the runtime. This is used for setting
-	/// up an initial runtime instance. The parameter is only needed for calling
-	/// into the Wasm module to find out the `Core_version`.
+	/// `ext` - Externalities holding the runtime code and the number of heap pages.
+	/// `Core_version` is called without any state, so it doesn't affect `ext`.
 	///
 	/// `default_heap_pages` - Number of 64KB pages to allocate for Wasm execution.
 	/// Defaults to `DEFAULT_HEAP_PAGES` if `None` is provided.
 	///
+	/// `wasm_method` - How the runtime should be executed. A cached runtime prepared
+	/// for another execution method is replaced.
+	///
+	/// `disk_cache` - Where to persist prepared runtimes across restarts. Runtimes are
+	/// persisted as prepared for their version by the `stack_height` module, the native code of compiled
+	/// ones is persisted by the compilation cache of `wasmtime` in the same directory.
+	///
 	/// # Return value
 	///
-	/// If no error occurred a tuple `(wasmi::ModuleRef, Option<RuntimeVersion>)` is
-	/// returned. `RuntimeVersion` is contained if the call to `Core_version` returned
-	/// a version.
+	/// If no error occurred the `CachedRuntime` is returned. Its `RuntimeVersion` is
+	/// contained if the call to `Core_version` returned a version.
 	///
 	/// In case of failure one of two errors can be returned:
 	///
@@ -245,6 +278,8 @@ impl RuntimesCache {
 		wasm_executor: &WasmExecutor,
 		ext: &mut E,
 		default_heap_pages: Option<u64>,
+		wasm_method: WasmExecutionMethod,
+		disk_cache: Option<&WasmDiskCache>,
 	) -> Result<Rc<CachedRuntime>, Error> {
 		let code_hash = ext
 			.original_storage_hash(well_known_keys::CODE)
@@ -267,12 +302,21 @@ impl RuntimesCache {
 			Entry::Occupied(mut o) => {
 				let result = o.get_mut();
 				if let Ok(ref cached_runtime) = result {
-					if cached_runtime.state_snapshot.heap_pages != heap_pages {
+					if cached_runtime.heap_pages != heap_pages
+						|| cached_runtime.wasm_method != wasm_method
+					{
 						trace!(
 							target: "runtimes_cache",
-							"heap_pages were changed. Reinstantiating the instance"
+							"heap_pages or execution method were changed. Reinstantiating the instance"
+						);
+						*result = Self::create_wasm_instance(
+							wasm_executor,
+							ext,
+							code_hash.into(),
+							heap_pages,
+							wasm_method,
+							disk_cache,
 						);
-						*result = Self::create_wasm_instance(wasm_executor, ext, heap_pages);
 						if let Err(ref err) = result {
 							warn!(target: "runtimes_cache", "cannot create a runtime: {:?}", err);
 						}
@@ -282,7 +326,14 @@ impl RuntimesCache {
 			},
 			Entry::Vacant(v) => {
 				trace!(target: "runtimes_cache", "no instance found in cache, creating now.");
-				let result = Self::create_wasm_instance(wasm_executor, ext, heap_pages);
+				let result = Self::create_wasm_instance(
+					wasm_executor,
+					ext,
+					code_hash.into(),
+					heap_pages,
+					wasm_method,
+					disk_cache,
+				);
 				if let Err(ref err) = result {
 					warn!(target: "runtimes_cache", "cannot create a runtime: {:?}", err);
 				}
@@ -294,25 +345,76 @@ impl RuntimesCache {
 	fn create_wasm_instance<E: Externalities<Blake2Hasher>>(
 		wasm_executor: &WasmExecutor,
 		ext: &mut E,
+		code_hash: [u8; 32],
 		heap_pages: u64,
+		wasm_method: WasmExecutionMethod,
+		disk_cache: Option<&WasmDiskCache>,
 	) -> Result<Rc<CachedRuntime>, CacheError> {
 		let code = ext
 			.original_storage(well_known_keys::CODE)
 			.ok_or(CacheError::CodeNotFound)?;
-		let module = WasmModule::from_buffer(&code).map_err(|_| CacheError::InvalidModule)?;
+
+		let key = CacheKey { code_hash, heap_pages, wasm_method };
+		let cached_code = disk_cache.and_then(|disk_cache| disk_cache.load(&key));
+		let is_cached = cached_code.is_some();
+
+		// The version decides how the code is prepared, so it is determined once here
+		// rather than on every call. Preparing the code doesn't change the version.
+		let version = wasm_executor
+			.runtime_version(heap_pages as usize, cached_code.as_ref().unwrap_or(&code))
+			.map_err(CacheError::Instantiation)?;
+		let code = match cached_code {
+			Some(code) => {
+				trace!(target: "runtimes_cache", "restored prepared runtime from the disk cache");
+				code
+			},
+			None => stack_height::prepare(&code, version.as_ref()).map_err(CacheError::Instantiation)?,
+		};
+
+		let runtime = match wasm_method {
+			WasmExecutionMethod::Interpreted => Self::create_interpreted_runtime::<E>(&code, heap_pages)?,
+			#[cfg(feature = "wasmtime")]
+			WasmExecutionMethod::Compiled => {
+				if let Some(disk_cache) = disk_cache {
+					wasmtime_executor::enable_compilation_cache(&disk_cache.compiled_dir());
+				}
+				WasmRuntime::Compiled(
+					CompiledRuntime::new::<E>(&code, heap_pages).map_err(CacheError::Instantiation)?
+				)
+			},
+		};
+		if !is_cached {
+			if let Some(disk_cache) = disk_cache {
+				disk_cache.store(&key, &code);
+			}
+		}
+
+		Ok(Rc::new(CachedRuntime {
+			runtime,
+			version,
+			heap_pages,
+			wasm_method,
+		}))
+	}
+
+	fn create_interpreted_runtime<E: Externalities<Blake2Hasher>>(
+		code: &[u8],
+		heap_pages: u64,
+	) -> Result<WasmRuntime, CacheError> {
+		let module = WasmModule::from_buffer(code).map_err(|_| CacheError::InvalidModule)?;
 
 		// Extract the data segments from the wasm code.
 		//
 		// A return of this error actually indicates that there is a problem in logic, since
 		// we just loaded and validated the `module` above.
-		let data_segments = extract_data_segments(&code).ok_or(CacheError::CantDeserializeWasm)?;
+		let data_segments = extract_data_segments(code).ok_or(CacheError::CantDeserializeWasm)?;
 
 		// Instantiate this module.
 		let instance = WasmExecutor::instantiate_module::<E>(heap_pages as usize, &module)
 			.map_err(CacheError::Instantiation)?;
 
 		// Take state snapshot before executing anything.
-		let state_snapshot = StateSnapshot::take(&instance, data_segments, heap_pages)
+		let state_snapshot = StateSnapshot::take(&instance, data_segments)
 			.expect(
 				"`take` returns `Err` if the module is not valid;
 				we already loaded module above, thus the `Module` is proven to be valid at this point;
@@ -320,15 +422,7 @@ impl RuntimesCache {
 				",
 			);
 
-		let version = wasm_executor
-			.call_in_wasm_module(ext, &instance, "Core_version", &[])
-			.ok()
-			.and_then(|v| RuntimeVersion::decode(&mut v.as_slice()).ok());
-		Ok(Rc::new(CachedRuntime {
-			instance,
-			version,
-			state_snapshot,
-		}))
+		Ok(WasmRuntime::Interpreted { instance, state_snapshot })
 	}
 }
 
@@ -344,3 +438,37 @@ fn extract_data_segments(wasm_code: &[u8]) -> Option<Vec<DataSegment>> {
 		.to_vec();
 	Some(segments)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use runtime_test::WASM_BINARY;
+	use state_machine::TestExternalities as CoreTestExternalities;
+
+	type TestExternalities = CoreTestExternalities<Blake2Hasher, u64>;
+
+	#[test]
+	fn should_persist_interpreted_runtimes() {
+		let dir = tempfile::tempdir().unwrap();
+		let disk_cache = WasmDiskCache::new(dir.path().to_path_buf());
+		let mut ext = TestExternalities::new_with_code(WASM_BINARY, Default::default());
+		let key = CacheKey {
+			code_hash: ext.original_storage_hash(well_known_keys::CODE).unwrap().into(),
+			// `new_with_code` sets the heap pages.
+			heap_pages: 8,
+			wasm_method: WasmExecutionMethod::Interpreted,
+		};
+		let executor = WasmExecutor::new();
+
+		RuntimesCache::new()
+			.fetch_runtime(&executor, &mut ext, None, WasmExecutionMethod::Interpreted, Some(&disk_cache))
+			.unwrap();
+		// the test runtime doesn't provide its version, so it isn't instrumented
+		assert_eq!(disk_cache.load(&key), Some(WASM_BINARY.to_vec()));
+
+		let runtime = RuntimesCache::new()
+			.fetch_runtime(&executor, &mut ext, None, WasmExecutionMethod::Interpreted, Some(&disk_cache))
+			.unwrap();
+		assert_eq!(runtime.call(&executor, &mut ext, "test_empty_return", &[]).unwrap(), Vec::<u8>::new());
+	}
+}
@@ -249,6 +249,14 @@ macro_rules! impl_function_executor {
 				}
 				&Resolver
 			}
+
+			/// Names and signatures of all host functions, ordered by their dispatch index.
+			#[allow(unused)]
+			pub(crate) fn host_functions() -> Vec<(&'static str, $crate::wasmi::Signature)> {
+				vec![
+					$( (stringify!($name), gen_signature!( ( $( $params ),* ) $( -> $returns )? )), )*
+				]
+			}
 		}
 
 		impl $( $pre ) + $crate::wasmi::Externals for $structname {
@@ -257,6 +265,8 @@ macro_rules! impl_function_executor {
 				index: usize,
 				args: $crate::wasmi::RuntimeArgs,
 			) -> std::result::Result<Option<$crate::wasmi::RuntimeValue>, $crate::wasmi::Trap> {
+				const NAMES: &[&str] = &[ $( stringify!($name) ),* ];
+				let _profile = $crate::profiling::HostCallGuard::enter(NAMES.get(index).cloned().unwrap_or("unknown"));
 				let $objectname = self;
 				let mut args = args.as_ref().iter();
 				dispatch_fn! {
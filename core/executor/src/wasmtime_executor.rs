@@ -0,0 +1,409 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Compiled execution of runtimes with `wasmtime`.
+//!
+//! The runtime is compiled to native code once and instantiated afresh for every call,
+//! so no state leaks between calls. Host functions are the ones of the interpreted
+//! executor: every import is dispatched through the same `FunctionExecutor`, operating
+//! on the `wasmtime` linear memory and using the same heap allocator. The sandbox
+//! dispatches calls back into the runtime through the `wasmtime` function table.
+//!
+//! Compiled code can be persisted across restarts by the compilation cache of `wasmtime`,
+//! see `enable_compilation_cache`.
+
+use std::{cell::Cell, fs, io, path::Path, rc::Rc, sync::Once};
+
+use log::{trace, warn};
+use primitives::Blake2Hasher;
+use state_machine::Externalities;
+use wasmi::{RuntimeArgs, RuntimeValue, Signature, ValueType};
+use wasmi::nan_preserving_float::{F32, F64};
+use wasmtime_api::{
+	Callable, Engine, Extern, ExternType, Func, FuncType, HostRef, Instance, Module, Store, Trap,
+	Val, ValType,
+};
+
+use crate::error::{Error, Result};
+use crate::memory::{self, Memory};
+use crate::profiling;
+use crate::sandbox::SandboxCapabilities;
+use crate::wasm_executor::{FunctionExecutor, Table};
+
+impl Memory for HostRef<wasmtime_api::Memory> {
+	fn get(&self, offset: u32, size: usize) -> Result<Vec<u8>> {
+		let memory = self.borrow();
+		memory::check_bounds(memory.data_size() as u32, offset, size)?;
+		// SAFETY: the slice is dropped before returning, so it can't be invalidated by
+		// the memory growing, and a store is only ever used by the thread that created it.
+		let data = unsafe { std::slice::from_raw_parts(memory.data_ptr(), memory.data_size()) };
+		Ok(data[offset as usize..offset as usize + size].to_vec())
+	}
+
+	fn get_into(&self, offset: u32, target: &mut [u8]) -> Result<()> {
+		let memory = self.borrow();
+		memory::check_bounds(memory.data_size() as u32, offset, target.len())?;
+		// SAFETY: as in `get`, the slice doesn't outlive this call.
+		let data = unsafe { std::slice::from_raw_parts(memory.data_ptr(), memory.data_size()) };
+		target.copy_from_slice(&data[offset as usize..offset as usize + target.len()]);
+		Ok(())
+	}
+
+	fn set(&self, offset: u32, data: &[u8]) -> Result<()> {
+		let memory = self.borrow();
+		memory::check_bounds(memory.data_size() as u32, offset, data.len())?;
+		// SAFETY: as in `get`, the slice doesn't outlive this call, and no other slice
+		// of the memory is alive while it is written.
+		let target = unsafe { std::slice::from_raw_parts_mut(memory.data_ptr(), memory.data_size()) };
+		target[offset as usize..offset as usize + data.len()].copy_from_slice(data);
+		Ok(())
+	}
+
+	fn byte_size(&self) -> u32 {
+		self.borrow().data_size() as u32
+	}
+}
+
+impl Table for HostRef<wasmtime_api::Table> {
+	type FuncRef = HostRef<Func>;
+
+	fn func(&self, index: u32) -> std::result::Result<Option<HostRef<Func>>, ()> {
+		let table = self.borrow();
+		if index >= table.size() {
+			return Err(());
+		}
+		match table.get(index) {
+			Val::FuncRef(func) => Ok(Some(func)),
+			_ => Ok(None),
+		}
+	}
+
+	fn invoke<'e, E: Externalities<Blake2Hasher> + 'e, M: Memory + 'e>(
+		executor: &mut FunctionExecutor<'e, E, M, Self>,
+		func: &HostRef<Func>,
+		args: &[RuntimeValue],
+	) -> Result<Option<RuntimeValue>> {
+		let args = args.iter().cloned().map(from_runtime_value).collect::<Vec<_>>();
+		let results = with_host_state(executor, || func.borrow().call(&args))
+			.map_err(|trap| wasmtime_error(trap.borrow().message()))?;
+		match results.get(0) {
+			Some(value) => into_runtime_value(value).map(Some).ok_or(Error::InvalidReturn),
+			None => Ok(None),
+		}
+	}
+}
+
+/// Host functions of the call running on the current thread.
+#[derive(Clone, Copy)]
+struct HostState {
+	/// The `wasmi::Externals` implementing the host functions, with its type erased.
+	externals: *mut (),
+	/// `invoke_index` instantiated for the type of `externals`.
+	invoke_index: unsafe fn(*mut (), usize, RuntimeArgs) -> std::result::Result<Option<RuntimeValue>, wasmi::Trap>,
+}
+
+thread_local! {
+	static HOST_STATE: Cell<Option<HostState>> = Cell::new(None);
+}
+
+/// Invoke the host function `index` of the `X` that `externals` points to.
+///
+/// # Safety
+///
+/// `externals` must point to a live `X` that nothing else accesses during the call.
+unsafe fn invoke_index<X: wasmi::Externals>(
+	externals: *mut (),
+	index: usize,
+	args: RuntimeArgs,
+) -> std::result::Result<Option<RuntimeValue>, wasmi::Trap> {
+	// SAFETY: upheld by the caller.
+	(*(externals as *mut X)).invoke_index(index, args)
+}
+
+/// Make `externals` available to the host functions the runtime calls while `f` runs.
+///
+/// Calls nest: the host functions of the outer call are restored once `f` returns or panics,
+/// so the sandbox can call back into the runtime from a host function.
+fn with_host_state<X: wasmi::Externals, R>(externals: &mut X, f: impl FnOnce() -> R) -> R {
+	struct Restore(Option<HostState>);
+
+	impl Drop for Restore {
+		fn drop(&mut self) {
+			HOST_STATE.with(|s| s.set(self.0.take()));
+		}
+	}
+
+	let state = HostState {
+		externals: externals as *mut X as *mut (),
+		invoke_index: invoke_index::<X>,
+	};
+	let _restore = Restore(HOST_STATE.with(|s| s.replace(Some(state))));
+	f()
+}
+
+/// A host function imported by the runtime, dispatched to the host functions of the
+/// call running on the current thread.
+struct HostFunction {
+	/// Index of the function in `FunctionExecutor::host_functions`.
+	index: usize,
+}
+
+impl Callable for HostFunction {
+	fn call(&self, params: &[Val], results: &mut [Val]) -> std::result::Result<(), HostRef<Trap>> {
+		dispatch(self.index, params, results).map_err(|message| HostRef::new(Trap::new(message)))
+	}
+}
+
+/// Invoke the host function with given index on behalf of the runtime.
+fn dispatch(index: usize, params: &[Val], results: &mut [Val]) -> std::result::Result<(), String> {
+	let state = HOST_STATE.with(|s| s.get())
+		.ok_or_else(|| "host function called outside of a runtime call".to_string())?;
+
+	let args = params.iter()
+		.map(into_runtime_value)
+		.collect::<Option<Vec<_>>>()
+		.ok_or_else(|| "unsupported argument type".to_string())?;
+	// SAFETY: `with_host_state` only publishes a state while the `externals` it borrows
+	// mutably is alive, and takes it back before the borrow ends. The runtime runs on
+	// this thread, and `externals` isn't used by its owner until the runtime returns.
+	// A nested call publishes a state derived from the same borrow and restores this
+	// one before returning.
+	match unsafe { (state.invoke_index)(state.externals, index, RuntimeArgs::from(&args[..])) } {
+		Ok(Some(value)) => {
+			let result = results.get_mut(0)
+				.ok_or_else(|| "host function returned an unexpected value".to_string())?;
+			*result = from_runtime_value(value);
+			Ok(())
+		},
+		Ok(None) => Ok(()),
+		Err(trap) => Err(format!("{}", trap)),
+	}
+}
+
+fn into_runtime_value(value: &Val) -> Option<RuntimeValue> {
+	match *value {
+		Val::I32(v) => Some(RuntimeValue::I32(v)),
+		Val::I64(v) => Some(RuntimeValue::I64(v)),
+		Val::F32(v) => Some(RuntimeValue::F32(F32::from_bits(v))),
+		Val::F64(v) => Some(RuntimeValue::F64(F64::from_bits(v))),
+		_ => None,
+	}
+}
+
+fn from_runtime_value(value: RuntimeValue) -> Val {
+	match value {
+		RuntimeValue::I32(v) => Val::I32(v),
+		RuntimeValue::I64(v) => Val::I64(v),
+		RuntimeValue::F32(v) => Val::F32(v.to_bits()),
+		RuntimeValue::F64(v) => Val::F64(v.to_bits()),
+	}
+}
+
+fn into_val_type(value_type: ValueType) -> ValType {
+	match value_type {
+		ValueType::I32 => ValType::I32,
+		ValueType::I64 => ValType::I64,
+		ValueType::F32 => ValType::F32,
+		ValueType::F64 => ValType::F64,
+	}
+}
+
+fn into_func_type(signature: &Signature) -> FuncType {
+	FuncType::new(
+		signature.params().iter().cloned().map(into_val_type).collect::<Vec<_>>().into_boxed_slice(),
+		signature.return_type().into_iter().map(into_val_type).collect::<Vec<_>>().into_boxed_slice(),
+	)
+}
+
+fn wasmtime_error(err: impl std::fmt::Display) -> Error {
+	Error::Wasmtime(err.to_string())
+}
+
+/// A runtime compiled to native code.
+///
+/// The compiled code lives in a `wasmtime` store, which can only be used by the thread
+/// that created it, just like the `wasmi` instances kept by the runtimes cache.
+#[derive(Clone)]
+pub struct CompiledRuntime {
+	store: HostRef<Store>,
+	module: HostRef<Module>,
+	/// The host functions, resolved against the imports of `module`.
+	imports: Vec<Extern>,
+	heap_pages: u32,
+}
+
+impl CompiledRuntime {
+	/// Compile the given runtime code, prepared by the `stack_height` module.
+	///
+	/// Fails if the code is invalid or imports anything the host doesn't provide.
+	pub fn new<E: Externalities<Blake2Hasher>>(code: &[u8], heap_pages: u64) -> Result<Self> {
+		let raw_module: parity_wasm::elements::Module = parity_wasm::elements::deserialize_buffer(code)
+			.map_err(|e| Error::InvalidCode(format!("{}", e)))?;
+		if raw_module.start_section().is_some() {
+			// Runtime is not allowed to have the `start` function.
+			return Err(Error::RuntimeHasStartFn);
+		}
+
+		// The compilation cache can't be enabled after anything was compiled.
+		COMPILATION_CACHE.call_once(|| trace!(target: "wasm-cache", "Not persisting compiled runtimes"));
+
+		let engine = HostRef::new(Engine::default());
+		let store = HostRef::new(Store::new(&engine));
+		let module = HostRef::new(Module::new(&store, code).map_err(wasmtime_error)?);
+		// Resolve the imports once up front, so that invalid runtimes are rejected
+		// when they are compiled rather than when they are called.
+		let imports = imports::<E>(&store, &module.borrow())?;
+		Ok(CompiledRuntime { store, module, imports, heap_pages: heap_pages as u32 })
+	}
+
+	/// Call a given method of the runtime.
+	///
+	/// Signature of the method needs to be `(I32, I32) -> I64`.
+	pub fn call<E: Externalities<Blake2Hasher>>(
+		&self,
+		ext: &mut E,
+		method: &str,
+		data: &[u8],
+	) -> Result<Vec<u8>> {
+		let _profile = profiling::CallGuard::start(method, data.len());
+		let instance = Instance::new(&self.store, &self.module, &self.imports)
+			.map_err(wasmtime_error)?;
+
+		let memory = instance.find_export_by_name("memory")
+			.and_then(Extern::memory)
+			.cloned()
+			.ok_or(Error::InvalidMemoryReference)?;
+		let heap_base = match instance.find_export_by_name("__heap_base")
+			.and_then(Extern::global)
+			.map(|global| global.borrow().get())
+		{
+			Some(Val::I32(v)) => v as u32,
+			_ => return Err(Error::HeapBaseNotFoundOrInvalid),
+		};
+		if !memory.borrow_mut().grow(self.heap_pages) {
+			return Err(Error::Runtime);
+		}
+
+		let table = instance.find_export_by_name("__indirect_function_table")
+			.and_then(Extern::table)
+			.cloned();
+
+		let func = instance.find_export_by_name(method)
+			.and_then(Extern::func)
+			.cloned()
+			.ok_or_else(|| Error::MethodNotFound(method.to_owned()))?;
+
+		let mut fec = FunctionExecutor::new(memory.clone(), heap_base, table, ext)?;
+		let offset = fec.allocate(data.len() as u32)?;
+		memory.set(offset, data)?;
+
+		let result = with_host_state(&mut fec, || {
+			func.borrow().call(&[Val::I32(offset as i32), Val::I32(data.len() as i32)])
+		});
+		match result {
+			Ok(results) => match results.get(0) {
+				Some(Val::I64(r)) => {
+					let offset = *r as u32;
+					let length = (*r as u64 >> 32) as usize;
+					memory.get(offset, length).map_err(|_| Error::Runtime)
+				},
+				_ => Err(Error::InvalidReturn),
+			},
+			Err(trap) => {
+				trace!(target: "wasm-executor", "Failed to execute code with {} bytes of memory", memory.byte_size());
+				Err(wasmtime_error(trap.borrow().message()))
+			},
+		}
+	}
+}
+
+/// Guards the one-time initialization of the `wasmtime` compilation cache.
+static COMPILATION_CACHE: Once = Once::new();
+
+/// Persist the native code of runtimes compiled from now on in given directory.
+///
+/// `wasmtime` keys the compiled code by the hash of the module and the compiler settings
+/// and evicts old entries on its own. Its cache is global, so only the first call before
+/// anything is compiled has an effect, later calls are ignored.
+pub fn enable_compilation_cache(dir: &Path) {
+	COMPILATION_CACHE.call_once(|| {
+		if let Err(e) = init_compilation_cache(dir) {
+			warn!(target: "wasm-cache", "Failed to enable the compilation cache in {}: {}", dir.display(), e);
+		}
+	});
+}
+
+fn init_compilation_cache(dir: &Path) -> io::Result<()> {
+	fs::create_dir_all(dir)?;
+	// `wasmtime` requires an absolute path.
+	let dir = fs::canonicalize(dir)?;
+	let config_path = dir.join("config.toml");
+	fs::write(
+		&config_path,
+		format!("[cache]\nenabled = true\ndirectory = {:?}\n", dir.join("modules")),
+	)?;
+
+	let errors = wasmtime_environ::cache_init(true, Some(&config_path), None);
+	if errors.is_empty() {
+		Ok(())
+	} else {
+		Err(io::Error::new(io::ErrorKind::Other, errors.join(", ")))
+	}
+}
+
+/// Resolve the imports of the runtime against the host functions.
+fn imports<E: Externalities<Blake2Hasher>>(store: &HostRef<Store>, module: &Module) -> Result<Vec<Extern>> {
+	let host_functions = FunctionExecutor::<E, HostRef<wasmtime_api::Memory>, HostRef<wasmtime_api::Table>>::host_functions();
+	module.imports()
+		.iter()
+		.map(|import| {
+			if import.module().as_str() != "env" {
+				return Err(Error::InvalidCode(format!("Unknown import module {}", import.module().as_str())));
+			}
+			let name = import.name().as_str();
+			let (index, signature) = host_functions.iter()
+				.position(|(host_name, _)| *host_name == name)
+				.map(|index| (index, &host_functions[index].1))
+				.ok_or_else(|| Error::InvalidCode(format!("Export {} not found", name)))?;
+			let func_type = into_func_type(signature);
+			match import.r#type() {
+				ExternType::ExternFunc(ref ty)
+					if ty.params() == func_type.params() && ty.results() == func_type.results() => {},
+				_ => return Err(Error::InvalidCode(format!("Export {} has different signature", name))),
+			}
+			let func = Func::new(store, func_type, Rc::new(HostFunction { index }));
+			Ok(Extern::Func(HostRef::new(func)))
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use runtime_test::WASM_BINARY;
+	use state_machine::TestExternalities as CoreTestExternalities;
+
+	type TestExternalities = CoreTestExternalities<Blake2Hasher, u64>;
+
+	#[test]
+	fn missing_method_should_fail() {
+		let runtime = CompiledRuntime::new::<TestExternalities>(WASM_BINARY, 8).unwrap();
+		match runtime.call(&mut TestExternalities::default(), "test_missing", &[]) {
+			Err(Error::MethodNotFound(method)) => assert_eq!(method, "test_missing"),
+			_ => panic!("calling a missing method should fail"),
+		}
+	}
+}
@@ -39,6 +39,8 @@ const VERSION_KEY: &[u8] = b"grandpa_schema_version";
 const SET_STATE_KEY: &[u8] = b"grandpa_completed_round";
 const AUTHORITY_SET_KEY: &[u8] = b"grandpa_voters";
 const CONSENSUS_CHANGES_KEY: &[u8] = b"grandpa_consensus_changes";
+const WARP_SYNC_SIGNALS_KEY: &[u8] = b"grandpa_warp_sync_signals";
+const WARP_SYNC_INDEX_PROGRESS_KEY: &[u8] = b"grandpa_warp_sync_index_progress";
 
 const CURRENT_VERSION: u32 = 2;
 
@@ -429,6 +431,59 @@ pub(crate) fn update_consensus_changes<H, N, F, R>(
 	write_aux(&[(CONSENSUS_CHANGES_KEY, set.encode().as_slice())])
 }
 
+/// Load the blocks signalling authority set changes, ordered by number. `None` if they have never
+/// been indexed.
+pub(crate) fn load_warp_sync_signals<B: AuxStore, H: Decode, N: Decode>(backend: &B)
+	-> ClientResult<Option<Vec<(N, H)>>>
+{
+	load_decode(backend, WARP_SYNC_SIGNALS_KEY)
+}
+
+/// Update the blocks signalling authority set changes.
+pub(crate) fn update_warp_sync_signals<H, N, F, R>(
+	signals: &[(N, H)],
+	write_aux: F
+) -> R where
+	H: Encode,
+	N: Encode,
+	F: FnOnce(&[(&'static [u8], &[u8])]) -> R,
+{
+	write_aux(&[(WARP_SYNC_SIGNALS_KEY, signals.encode().as_slice())])
+}
+
+/// Load the number of the last finalized block scanned by an unfinished indexing of the blocks
+/// signalling authority set changes. `None` if no indexing is in progress.
+pub(crate) fn load_warp_sync_index_progress<B: AuxStore, N: Decode>(backend: &B) -> ClientResult<Option<N>> {
+	load_decode(backend, WARP_SYNC_INDEX_PROGRESS_KEY)
+}
+
+/// Update the blocks signalling authority set changes along with the number of the last finalized
+/// block scanned by their indexing, which is finished if `progress` is `None`.
+pub(crate) fn update_warp_sync_index<H, N, F, R>(
+	signals: &[(N, H)],
+	progress: Option<N>,
+	write_aux: F
+) -> R where
+	H: Encode,
+	N: Encode,
+	F: FnOnce(&[(&'static [u8], &[u8])], &[&'static [u8]]) -> R,
+{
+	let encoded_signals = signals.encode();
+	match progress {
+		Some(progress) => write_aux(
+			&[
+				(WARP_SYNC_SIGNALS_KEY, encoded_signals.as_slice()),
+				(WARP_SYNC_INDEX_PROGRESS_KEY, progress.encode().as_slice()),
+			],
+			&[],
+		),
+		None => write_aux(
+			&[(WARP_SYNC_SIGNALS_KEY, encoded_signals.as_slice())],
+			&[WARP_SYNC_INDEX_PROGRESS_KEY],
+		),
+	}
+}
+
 #[cfg(test)]
 pub(crate) fn load_authorities<B: AuxStore, H: Decode, N: Decode>(backend: &B)
 	-> Option<AuthoritySet<H, N>> {
@@ -180,8 +180,23 @@ impl<B, E, Block, RA> network::FinalityProofProvider<Block> for FinalityProofPro
 				request.last_finalized,
 				for_block,
 			),
+			FinalityProofRequest::Warp => crate::warp_sync::prove_warp(
+				#[allow(deprecated)]
+				&*self.client.backend().blockchain(),
+				&*self.client,
+				for_block,
+			),
 		}
 	}
+
+	fn prove_warp_to(&self, block: Block::Hash) -> Result<Option<Vec<u8>>, ClientError> {
+		crate::warp_sync::prove_warp_to(
+			#[allow(deprecated)]
+			&*self.client.backend().blockchain(),
+			&*self.client,
+			block,
+		).map(Some)
+	}
 }
 
 /// The effects of block finality.
@@ -227,6 +242,8 @@ type FinalityProof<Header> = Vec<FinalityProofFragment<Header>>;
 enum FinalityProofRequest<H: Encode + Decode> {
 	/// Original version of the request.
 	Original(OriginalFinalityProofRequest<H>),
+	/// Request for the authority set changes since the requested block, used by warp sync.
+	Warp,
 }
 
 /// Original version of finality proof request.
@@ -248,6 +265,11 @@ pub(crate) fn make_finality_proof_request<H: Encode + Decode>(last_finalized: H,
 	}).encode()
 }
 
+/// Prepare data blob associated with warp sync proof request.
+pub(crate) fn make_warp_sync_request<H: Encode + Decode>() -> Vec<u8> {
+	FinalityProofRequest::<H>::Warp.encode()
+}
+
 /// Prepare proof-of-finality for the best possible block in the range: (begin; end].
 ///
 /// It is assumed that the caller already have a proof-of-finality for the block 'begin'.
@@ -32,6 +32,7 @@ use consensus_common::{
 	SelectChain,
 };
 use fg_primitives::GrandpaApi;
+use network::config::BoxWarpSyncVerifier;
 use sr_primitives::Justification;
 use sr_primitives::generic::BlockId;
 use sr_primitives::traits::{
@@ -45,6 +46,7 @@ use crate::authorities::{AuthoritySet, SharedAuthoritySet, DelayKind, PendingCha
 use crate::consensus_changes::SharedConsensusChanges;
 use crate::environment::finalize_block;
 use crate::justification::GrandpaJustification;
+use crate::warp_sync::GrandpaWarpSyncVerifier;
 
 /// A block-import handler for GRANDPA.
 ///
@@ -305,6 +307,21 @@ where
 		// of a forced change block.
 		let mut do_pause = false;
 
+		// index the block for the warp sync proofs, which are only built from the canonical ones.
+		if maybe_change.is_some() {
+			let mut signals = crate::aux_schema::load_warp_sync_signals(&*self.inner)
+				.map_err(|e| ConsensusError::ClientImport(e.to_string()))?
+				.unwrap_or_else(Vec::new);
+			if crate::warp_sync::note_signal(&mut signals, number, hash) {
+				crate::aux_schema::update_warp_sync_signals(
+					&signals,
+					|insert| block.auxiliary.extend(
+						insert.iter().map(|(k, v)| (k.to_vec(), Some(v.to_vec())))
+					)
+				);
+			}
+		}
+
 		// add any pending changes.
 		if let Some(change) = maybe_change {
 			let old = guard.as_mut().clone();
@@ -550,6 +567,27 @@ impl<B, E, Block: BlockT<Hash=H256>, RA, PRA, SC>
 	}
 }
 
+impl<B, E, Block: BlockT<Hash=H256>, RA, PRA, SC>
+	GrandpaBlockImport<B, E, Block, RA, PRA, SC>
+where
+	NumberFor<Block>: grandpa::BlockNumberOps,
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + 'static + Clone + Send + Sync,
+	RA: Send + Sync + 'static,
+{
+	/// Create a verifier of the proofs used to warp sync to the most recent finalized block.
+	///
+	/// Once the target block is imported, the voter is restarted with the authority set
+	/// proven by the verifier.
+	pub fn warp_sync_verifier(&self) -> BoxWarpSyncVerifier<Block> {
+		Box::new(GrandpaWarpSyncVerifier::new(
+			self.inner.clone(),
+			self.authority_set.clone(),
+			self.send_voter_commands.clone(),
+		)) as _
+	}
+}
+
 impl<B, E, Block: BlockT<Hash=H256>, RA, PRA, SC>
 	GrandpaBlockImport<B, E, Block, RA, PRA, SC>
 where
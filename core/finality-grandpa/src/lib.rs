@@ -92,6 +92,7 @@ mod justification;
 mod light_import;
 mod observer;
 mod until_imported;
+mod warp_sync;
 
 pub use communication::Network;
 pub use finality_proof::FinalityProofProvider;
@@ -373,6 +374,12 @@ where
 		}
 	)?;
 
+	warp_sync::index_signals(
+		#[allow(deprecated)]
+		client.backend().blockchain(),
+		&*client,
+	)?;
+
 	let (voter_commands_tx, voter_commands_rx) = mpsc::unbounded();
 
 	Ok((
@@ -27,7 +27,7 @@ use tokio::runtime::current_thread;
 use keyring::Ed25519Keyring;
 use client::{
 	error::Result,
-	runtime_api::{Core, RuntimeVersion, ApiExt},
+	runtime_api::{Core, RuntimeVersion, ApiExt, OverlayedChanges},
 	LongestChain,
 };
 use test_client::{self, runtime::BlockNumber};
@@ -263,6 +263,10 @@ impl ApiExt<Block> for RuntimeApi {
 	fn extract_proof(&mut self) -> Option<Vec<Vec<u8>>> {
 		unimplemented!("Not required for testing!")
 	}
+
+	fn extract_changes(&mut self) -> OverlayedChanges {
+		unimplemented!("Not required for testing!")
+	}
 }
 
 impl GrandpaApi<Block> for RuntimeApi {
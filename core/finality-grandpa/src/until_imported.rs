@@ -472,6 +472,7 @@ mod tests {
 				origin: BlockOrigin::File,
 				header,
 				is_new_best: false,
+				non_finalized_ancestors: Vec::new(),
 			}).unwrap();
 		}
 	}
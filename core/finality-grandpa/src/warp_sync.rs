@@ -0,0 +1,796 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! GRANDPA proofs used by the warp sync.
+//!
+//! A node which warp syncs starts with the genesis authority set and follows the authority set
+//! changes up to the most recent finalized block known to a peer. Each change is proven by:
+//! 1) the header S which schedules the change;
+//! 2) the headers (S; E] up to the block E which enacts it, if the change is delayed;
+//! 3) the justification of E, signed by the authority set before the change.
+//!
+//! Once all the changes are proven, the justification of the most recent finalized block is
+//! checked against the latest authority set. The headers since the oldest change which is not
+//! enacted at that block are provided along with it, and the changes they signal are pending in
+//! the authority set of the synced node. Forced changes can not be proven this way and are only
+//! supported when they are enacted after that block.
+//!
+//! The blocks signalling changes are indexed in the aux storage as they are imported, so that the
+//! proofs are built without going through every header of the chain.
+
+use std::sync::Arc;
+use futures::sync::mpsc;
+use log::{info, trace};
+
+use client::{
+	backend::{AuxStore, Backend}, blockchain::Backend as BlockchainBackend, CallExecutor, Client,
+	error::{Error as ClientError, Result as ClientResult},
+};
+use codec::{Encode, Decode};
+use fork_tree::ForkTree;
+use grandpa::BlockNumberOps;
+use network::config::{WarpSyncProgress, WarpSyncVerifier};
+use sr_primitives::{Justification, generic::{BlockId, OpaqueDigestItemId}};
+use sr_primitives::traits::{NumberFor, Block as BlockT, Header as HeaderT, One, Zero};
+use primitives::{H256, Blake2Hasher};
+use fg_primitives::{AuthorityId, ConsensusLog, ScheduledChange, GRANDPA_ENGINE_ID};
+
+use crate::{NewAuthoritySet, VoterCommand};
+use crate::authorities::{AuthoritySet, DelayKind, PendingChange, SharedAuthoritySet};
+use crate::aux_schema;
+use crate::finality_proof::make_warp_sync_request;
+use crate::justification::GrandpaJustification;
+
+/// Maximum number of authority set changes that we want to return in a single proof.
+const MAX_CHANGES_IN_PROOF: usize = 64;
+
+/// Number of finalized blocks scanned between two saves of the progress of the indexing of the
+/// blocks signalling authority set changes.
+const INDEX_BATCH_SIZE: usize = 4096;
+
+/// Proof of a single authority set change, or of the finality of the target block.
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct WarpSyncFragment<Header: HeaderT> {
+	/// Headers from the block which schedules the change up to the block which enacts it. Only
+	/// the target block for the fragment proving its finality.
+	pub headers: Vec<Header>,
+	/// Justification of the last block of `headers`.
+	pub justification: Justification,
+}
+
+/// Proof of the authority set changes since a given block.
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct WarpSyncProof<Header: HeaderT> {
+	/// Authority set changes, in order.
+	pub changes: Vec<WarpSyncFragment<Header>>,
+	/// Finality of the most recent finalized block. Only provided along with the last changes.
+	pub target: Option<WarpSyncFragment<Header>>,
+}
+
+/// Extract the scheduled authority set change signalled by a header, if any.
+fn find_scheduled_change<Header: HeaderT>(header: &Header) -> Option<ScheduledChange<Header::Number>> {
+	grandpa_log(header).and_then(|log| log.try_into_change())
+}
+
+/// Extract the forced authority set change signalled by a header, if any.
+fn find_forced_change<Header: HeaderT>(header: &Header) -> Option<(Header::Number, ScheduledChange<Header::Number>)> {
+	grandpa_log(header).and_then(|log| log.try_into_forced_change())
+}
+
+fn grandpa_log<Header: HeaderT>(header: &Header) -> Option<ConsensusLog<Header::Number>> {
+	let id = OpaqueDigestItemId::Consensus(&GRANDPA_ENGINE_ID);
+	header.digest().convert_first(|log| log.try_to::<ConsensusLog<Header::Number>>(id))
+}
+
+/// Whether the header signals an authority set change.
+fn signals_change<Header: HeaderT>(header: &Header) -> bool {
+	find_scheduled_change(header).is_some() || find_forced_change(header).is_some()
+}
+
+/// Note a block signalling an authority set change in the index of such blocks, which is ordered
+/// by number. Returns `false` if the block is already indexed.
+pub(crate) fn note_signal<H: PartialEq, N: Copy + Ord>(signals: &mut Vec<(N, H)>, number: N, hash: H) -> bool {
+	if signals.iter().any(|signal| signal.0 == number && signal.1 == hash) {
+		return false;
+	}
+	let idx = signals
+		.binary_search_by_key(&number, |signal| signal.0)
+		.unwrap_or_else(|i| i);
+	signals.insert(idx, (number, hash));
+	true
+}
+
+/// Index the blocks signalling authority set changes, unless they already are.
+///
+/// The index is kept up to date by the block import, so the chain is only scanned when GRANDPA
+/// is started on a database which predates it. The progress of the scan is stored along with the
+/// blocks indexed so far every `INDEX_BATCH_SIZE` finalized blocks, so that an interrupted scan
+/// is resumed on the next start instead of starting over.
+pub(crate) fn index_signals<Block: BlockT<Hash=H256>, B: BlockchainBackend<Block>, A: AuxStore>(
+	blockchain: &B,
+	aux: &A,
+) -> ClientResult<()> {
+	let signals = aux_schema::load_warp_sync_signals::<_, Block::Hash, NumberFor<Block>>(aux)?;
+	let progress = aux_schema::load_warp_sync_index_progress::<_, NumberFor<Block>>(aux)?;
+	let (mut signals, mut number) = match (signals, progress) {
+		(Some(_), None) => return Ok(()),
+		(Some(signals), Some(progress)) => (signals, progress + One::one()),
+		(None, _) => (Vec::new(), One::one()),
+	};
+
+	let info = blockchain.info();
+	if number <= info.best_number {
+		info!(
+			target: "afg",
+			"Indexing the authority set changes of the chain from #{} up to #{}",
+			number,
+			info.best_number,
+		);
+	}
+
+	let mut batch_size = 0;
+	while number <= info.finalized_number {
+		// the blocks preceding the target of a warp sync are missing.
+		if let Some(header) = blockchain.header(BlockId::Number(number))? {
+			if signals_change(&header) {
+				note_signal(&mut signals, number, header.hash());
+			}
+		}
+		batch_size += 1;
+		if batch_size == INDEX_BATCH_SIZE {
+			aux_schema::update_warp_sync_index(
+				&signals,
+				Some(number),
+				|insert, delete| aux.insert_aux(insert, delete),
+			)?;
+			batch_size = 0;
+		}
+		number = number + One::one();
+	}
+	for leaf in blockchain.leaves()? {
+		let mut header = blockchain.header(BlockId::Hash(leaf))?;
+		while let Some(current) = header.take().filter(|header| *header.number() > info.finalized_number) {
+			if signals_change(&current) {
+				note_signal(&mut signals, *current.number(), current.hash());
+			}
+			header = blockchain.header(BlockId::Hash(*current.parent_hash()))?;
+		}
+	}
+
+	aux_schema::update_warp_sync_index(&signals, None, |insert, delete| aux.insert_aux(insert, delete))
+}
+
+/// Load the index of the blocks signalling authority set changes.
+fn load_signals<Block: BlockT, A: AuxStore>(aux: &A) -> ClientResult<Vec<(NumberFor<Block>, Block::Hash)>> {
+	if aux_schema::load_warp_sync_index_progress::<_, NumberFor<Block>>(aux)?.is_some() {
+		return Err(ClientError::Backend(
+			"Cannot generate warp sync proof: authority set changes are still being indexed".into(),
+		));
+	}
+	aux_schema::load_warp_sync_signals(aux)?.ok_or_else(|| ClientError::Backend(
+		"Cannot generate warp sync proof: authority set changes are not indexed".into(),
+	))
+}
+
+/// Collect the authority set changes enacted in the blocks (begin; end], stopping early once
+/// `max_changes` are collected.
+///
+/// Only the canonical blocks of `signals`, the blocks enacting their changes and the blocks
+/// preceding `end` down to the most recent justified one are visited.
+///
+/// Returns the changes, the fragment proving the finality of the last justified block and the
+/// number of the last block visited. The fragment is only provided once `end` is reached. It
+/// starts at the oldest block signalling a change which is not enacted yet, so that the change
+/// can be carried over by the verifier.
+fn collect_changes<Block: BlockT<Hash=H256>, B: BlockchainBackend<Block>>(
+	blockchain: &B,
+	signals: &[(NumberFor<Block>, Block::Hash)],
+	begin_number: NumberFor<Block>,
+	end_number: NumberFor<Block>,
+	max_changes: Option<usize>,
+) -> ClientResult<(
+	Vec<WarpSyncFragment<Block::Header>>,
+	Option<WarpSyncFragment<Block::Header>>,
+	NumberFor<Block>,
+)> {
+	let headers = |from: NumberFor<Block>, to: NumberFor<Block>| -> ClientResult<Vec<Block::Header>> {
+		let mut headers = Vec::new();
+		let mut number = from;
+		while number <= to {
+			headers.push(blockchain.expect_header(BlockId::Number(number))?);
+			number = number + One::one();
+		}
+		Ok(headers)
+	};
+
+	let mut changes = Vec::new();
+	let mut signals = signals.iter()
+		.filter(|(number, _)| *number > begin_number && *number <= end_number)
+		.peekable();
+	// oldest block signalling a change which is not enacted yet
+	let mut unenacted_from: Option<NumberFor<Block>> = None;
+	// number of the block signalling the pending standard change, along with the number of the
+	// block enacting it
+	let mut pending_change: Option<(NumberFor<Block>, NumberFor<Block>)> = None;
+	// block enacting the last change, along with its justification
+	let mut last_enacted: Option<(Block::Header, Justification)> = None;
+	let mut has_forced_change = false;
+	loop {
+		let next_signal = signals.peek().map(|(number, _)| *number);
+		let next_enactment = pending_change
+			.map(|(_, enacted_at)| enacted_at)
+			.filter(|enacted_at| *enacted_at <= end_number);
+		let current_number = match (next_signal, next_enactment) {
+			(Some(signal), Some(enactment)) => signal.min(enactment),
+			(Some(number), None) | (None, Some(number)) => number,
+			(None, None) => break,
+		};
+
+		let current_id = BlockId::Number(current_number);
+		let header = blockchain.expect_header(current_id)?;
+		if next_signal == Some(current_number) {
+			let (_, hash) = signals.next().expect("next_signal is the number of the next signal; qed");
+			// blocks of abandoned forks are indexed as well.
+			if *hash == header.hash() {
+				let forced_change = find_forced_change(&header);
+				let scheduled_change = find_scheduled_change(&header)
+					.filter(|_| pending_change.is_none());
+				if let Some((_, ref change)) = forced_change {
+					// a forced change is enacted without a justification, so it is only carried over
+					// to a target preceding its enactment.
+					if current_number + change.delay <= end_number {
+						return Err(ClientError::Backend(
+							format!("Cannot generate warp sync proof over forced change at #{}", current_number),
+						));
+					}
+					has_forced_change = true;
+				}
+				if let Some(ref change) = scheduled_change {
+					pending_change = Some((current_number, current_number + change.delay));
+				}
+				if forced_change.is_some() || scheduled_change.is_some() {
+					unenacted_from.get_or_insert(current_number);
+				}
+			}
+		}
+
+		let enacts_change = pending_change.map_or(false, |(_, enacted_at)| enacted_at == current_number);
+		if enacts_change {
+			let justification = blockchain.justification(current_id)?.ok_or_else(|| ClientError::Backend(
+				format!("Missing justification of the authority set change at #{}", current_number),
+			))?;
+			let (scheduled_at, _) = pending_change.take().expect("enacts_change is only true for pending changes; qed");
+			changes.push(WarpSyncFragment {
+				headers: headers(scheduled_at, current_number)?,
+				justification: justification.clone(),
+			});
+			if !has_forced_change {
+				unenacted_from = None;
+			}
+			last_enacted = Some((header, justification));
+
+			if max_changes.map_or(false, |max_changes| changes.len() == max_changes) && current_number < end_number {
+				return Ok((changes, None, current_number));
+			}
+		}
+	}
+
+	// the block enacting the last change is justified, so there is no need to look further.
+	let lowest_number = last_enacted.as_ref().map_or(begin_number, |(header, _)| *header.number());
+	let mut number = end_number;
+	while number > lowest_number {
+		let id = BlockId::Number(number);
+		if let Some(justification) = blockchain.justification(id)? {
+			let headers = match unenacted_from {
+				Some(from) if from <= number => headers(from, number)?,
+				_ => vec![blockchain.expect_header(id)?],
+			};
+			return Ok((changes, Some(WarpSyncFragment { headers, justification }), end_number));
+		}
+		number = number - One::one();
+	}
+
+	let target = last_enacted.map(|(header, justification)| WarpSyncFragment { headers: vec![header], justification });
+	Ok((changes, target, end_number))
+}
+
+/// Prepare the proof of the authority set changes since the block `begin`, up to the most
+/// recent finalized block.
+///
+/// Returns None if there are no finalized blocks unknown to the caller.
+pub(crate) fn prove_warp<Block: BlockT<Hash=H256>, B: BlockchainBackend<Block>, A: AuxStore>(
+	blockchain: &B,
+	aux: &A,
+	begin: Block::Hash,
+) -> ClientResult<Option<Vec<u8>>> {
+	let begin_number = blockchain.expect_block_number_from_id(&BlockId::Hash(begin))?;
+	let info = blockchain.info();
+	if info.finalized_number <= begin_number {
+		trace!(
+			target: "finality",
+			"Requested warp sync proof from #{} while we only have finalized #{}. Returning empty proof.",
+			begin_number,
+			info.finalized_number,
+		);
+
+		return Ok(None);
+	}
+
+	let canonical_begin = blockchain.expect_block_hash_from_id(&BlockId::Number(begin_number))?;
+	if begin != canonical_begin {
+		return Err(ClientError::Backend(
+			format!("Cannot generate warp sync proof from non-canonical block: {}", begin),
+		));
+	}
+
+	let (changes, target, current_number) = collect_changes(
+		blockchain,
+		&load_signals::<Block, _>(aux)?,
+		begin_number,
+		info.finalized_number,
+		Some(MAX_CHANGES_IN_PROOF),
+	)?;
+
+	// the target is only provided once all the changes up to it are known. A change which is
+	// not enacted yet is carried over with it.
+	let is_finished = current_number == info.finalized_number;
+	let proof = WarpSyncProof {
+		target: if is_finished { target } else { None },
+		changes,
+	};
+	if proof.changes.is_empty() && proof.target.is_none() {
+		trace!(
+			target: "finality",
+			"No justifications found when making warp sync proof from {}. Returning empty proof.",
+			begin,
+		);
+
+		return Ok(None);
+	}
+
+	trace!(
+		target: "finality",
+		"Built warp sync proof from {} of {} authority set changes.",
+		begin,
+		proof.changes.len(),
+	);
+
+	Ok(Some(proof.encode()))
+}
+
+/// Prepare the proof of all the authority set changes since genesis, followed by the finality
+/// of the given block, which must be finalized and have a justification.
+///
+/// Unlike `prove_warp`, the number of changes is not limited: the proof is used to check a state
+/// snapshot taken at the block rather than sent over the network.
+pub(crate) fn prove_warp_to<Block: BlockT<Hash=H256>, B: BlockchainBackend<Block>, A: AuxStore>(
+	blockchain: &B,
+	aux: &A,
+	block: Block::Hash,
+) -> ClientResult<Vec<u8>> {
+	let number = blockchain.expect_block_number_from_id(&BlockId::Hash(block))?;
+	let info = blockchain.info();
+	if number > info.finalized_number
+		|| blockchain.expect_block_hash_from_id(&BlockId::Number(number))? != block
+	{
+		return Err(ClientError::Backend(
+			format!("Cannot generate warp sync proof to non-finalized block: {}", block),
+		));
+	}
+
+	let signals = load_signals::<Block, _>(aux)?;
+	let (changes, target, _) = collect_changes(blockchain, &signals, Zero::zero(), number, None)?;
+	let target = match target {
+		Some(target) if target.headers.last().map(|header| header.hash()) == Some(block) => target,
+		_ => return Err(ClientError::Backend(
+			format!("Cannot generate warp sync proof to #{}: the block has no justification", number),
+		)),
+	};
+
+	Ok(WarpSyncProof { target: Some(target), changes }.encode())
+}
+
+/// Find the changes signalled by the given headers which are not enacted at the last finalized
+/// block, which must all be enacted after the target block. They are anchored at the target
+/// block, as the headers before it are never imported.
+fn find_pending_changes<Block: BlockT>(
+	headers: &[Block::Header],
+	target: &Block::Header,
+	last_finalized: NumberFor<Block>,
+) -> ClientResult<Vec<PendingChange<Block::Hash, NumberFor<Block>>>> {
+	let anchor = |signal_number: NumberFor<Block>, change: ScheduledChange<NumberFor<Block>>, delay_kind| {
+		let enacted_at = signal_number + change.delay;
+		if enacted_at <= *target.number() {
+			return Err(ClientError::BadJustification(
+				format!("unproven authority set change enacted at #{}", enacted_at),
+			));
+		}
+		Ok(PendingChange {
+			next_authorities: change.next_authorities,
+			delay: enacted_at - *target.number(),
+			canon_height: *target.number(),
+			canon_hash: target.hash(),
+			delay_kind,
+		})
+	};
+
+	let mut pending_changes = Vec::new();
+	for header in headers {
+		// standard changes up to the last finalized block are enacted already.
+		let scheduled_change = find_scheduled_change(header)
+			.filter(|_| *header.number() > last_finalized);
+		if let Some(change) = scheduled_change {
+			pending_changes.push(anchor(*header.number(), change, DelayKind::Finalized)?);
+		}
+		if let Some((median_last_finalized, change)) = find_forced_change(header) {
+			pending_changes.push(anchor(*header.number(), change, DelayKind::Best { median_last_finalized })?);
+		}
+	}
+	Ok(pending_changes)
+}
+
+/// Verifier of the warp sync proofs, tracking the authority set from genesis.
+pub(crate) struct GrandpaWarpSyncVerifier<B, E, Block: BlockT<Hash=H256>, RA> {
+	client: Arc<Client<B, E, Block, RA>>,
+	authority_set: SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
+	send_voter_commands: mpsc::UnboundedSender<VoterCommand<Block::Hash, NumberFor<Block>>>,
+	/// Latest block proven to be finalized.
+	last_finalized: (Block::Hash, NumberFor<Block>),
+	set_id: u64,
+	authorities: Vec<(AuthorityId, u64)>,
+	/// Changes signalled before the target block and enacted after it.
+	pending_changes: Vec<PendingChange<Block::Hash, NumberFor<Block>>>,
+}
+
+impl<B, E, Block: BlockT<Hash=H256>, RA> GrandpaWarpSyncVerifier<B, E, Block, RA>
+	where
+		B: Backend<Block, Blake2Hasher> + 'static,
+		E: CallExecutor<Block, Blake2Hasher> + 'static + Clone + Send + Sync,
+		RA: Send + Sync,
+{
+	/// Create a new verifier starting from the current authority set, which is expected to be
+	/// the genesis one.
+	pub(crate) fn new(
+		client: Arc<Client<B, E, Block, RA>>,
+		authority_set: SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
+		send_voter_commands: mpsc::UnboundedSender<VoterCommand<Block::Hash, NumberFor<Block>>>,
+	) -> Self {
+		let info = client.info().chain;
+		let (set_id, authorities) = {
+			let set = authority_set.inner().read();
+			(set.set_id, set.current_authorities.clone())
+		};
+		GrandpaWarpSyncVerifier {
+			client,
+			authority_set,
+			send_voter_commands,
+			last_finalized: (info.finalized_hash, info.finalized_number),
+			set_id,
+			authorities,
+			pending_changes: Vec::new(),
+		}
+	}
+
+	/// Check the justification of the last header of the fragment, returning that header.
+	fn check_fragment(&self, fragment: &WarpSyncFragment<Block::Header>) -> ClientResult<Block::Header>
+		where NumberFor<Block>: BlockNumberOps,
+	{
+		let last = fragment.headers.last()
+			.ok_or_else(|| ClientError::BadJustification("empty warp sync proof fragment".into()))?;
+		for (parent, child) in fragment.headers.iter().zip(fragment.headers.iter().skip(1)) {
+			if *child.parent_hash() != parent.hash() {
+				return Err(ClientError::BadJustification("unlinked headers in warp sync proof".into()));
+			}
+		}
+		if *last.number() <= self.last_finalized.1 {
+			return Err(ClientError::BadJustification("outdated warp sync proof".into()));
+		}
+
+		GrandpaJustification::<Block>::decode_and_verify_finalizes(
+			&fragment.justification,
+			(last.hash(), *last.number()),
+			self.set_id,
+			&self.authorities.iter().cloned().collect(),
+		)?;
+
+		Ok(last.clone())
+	}
+
+	fn do_verify(&mut self, proof: &[u8]) -> ClientResult<WarpSyncProgress<Block>>
+		where NumberFor<Block>: BlockNumberOps,
+	{
+		let proof = WarpSyncProof::<Block::Header>::decode(&mut &proof[..])
+			.map_err(|_| ClientError::BadJustification("failed to decode warp sync proof".into()))?;
+		if proof.changes.is_empty() && proof.target.is_none() {
+			return Err(ClientError::BadJustification("empty warp sync proof".into()));
+		}
+
+		for fragment in &proof.changes {
+			let enacted = self.check_fragment(fragment)?;
+			let scheduled = &fragment.headers[0];
+			let change = find_scheduled_change(scheduled)
+				.ok_or_else(|| ClientError::BadJustification("missing authority set change in warp sync proof".into()))?;
+			if *scheduled.number() + change.delay != *enacted.number() {
+				return Err(ClientError::BadJustification("unexpected authority set change delay".into()));
+			}
+
+			self.last_finalized = (enacted.hash(), *enacted.number());
+			self.set_id += 1;
+			self.authorities = change.next_authorities;
+		}
+
+		match proof.target {
+			Some(target) => {
+				// the block enacting the last change is finalized by the set before the change,
+				// which is checked above.
+				let enacts_last_change = proof.changes.last().map_or(false, |change|
+					change.headers.last() == target.headers.last() && change.justification == target.justification
+				);
+				let header = if enacts_last_change {
+					target.headers.last().cloned().expect("equal to the last header of a change; qed")
+				} else {
+					self.check_fragment(&target)?
+				};
+				self.pending_changes = find_pending_changes::<Block>(&target.headers, &header, self.last_finalized.1)?;
+				self.last_finalized = (header.hash(), *header.number());
+				Ok(WarpSyncProgress::Complete(header, target.justification))
+			},
+			None => {
+				let header = proof.changes.into_iter().last()
+					.and_then(|fragment| fragment.headers.into_iter().last())
+					.expect("the proof has at least one change when there is no target; \
+						every change is checked to have headers; qed");
+				Ok(WarpSyncProgress::Partial(header))
+			},
+		}
+	}
+}
+
+impl<B, E, Block: BlockT<Hash=H256>, RA> WarpSyncVerifier<Block> for GrandpaWarpSyncVerifier<B, E, Block, RA>
+	where
+		NumberFor<Block>: BlockNumberOps,
+		B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+		E: CallExecutor<Block, Blake2Hasher> + 'static + Clone + Send + Sync,
+		RA: Send + Sync,
+{
+	fn next_request(&mut self) -> (Block::Hash, Vec<u8>) {
+		(self.last_finalized.0, make_warp_sync_request::<Block::Hash>())
+	}
+
+	fn verify(&mut self, proof: &[u8]) -> Result<WarpSyncProgress<Block>, String> {
+		self.do_verify(proof).map_err(|e| e.to_string())
+	}
+
+	fn on_target_imported(&mut self, header: &Block::Header) -> Result<(), String> {
+		let new_set = NewAuthoritySet {
+			canon_hash: header.hash(),
+			canon_number: *header.number(),
+			set_id: self.set_id,
+			authorities: self.authorities.clone(),
+		};
+
+		{
+			let mut authority_set = self.authority_set.inner().write();
+			*authority_set = AuthoritySet {
+				current_authorities: self.authorities.clone(),
+				set_id: self.set_id,
+				pending_standard_changes: ForkTree::new(),
+				pending_forced_changes: Vec::new(),
+			};
+			// every pending change is anchored at the target block, which has no known ancestors.
+			for change in self.pending_changes.drain(..) {
+				authority_set.add_pending_change(change, &|_: &Block::Hash, _: &Block::Hash| Ok::<_, ClientError>(false))
+					.map_err(|e| e.to_string())?;
+			}
+			aux_schema::update_authority_set::<Block, _, _>(
+				&authority_set,
+				Some(&new_set),
+				|insert| self.client.insert_aux(insert, &[]),
+			).map_err(|e| e.to_string())?;
+		}
+
+		info!(
+			target: "afg",
+			"Warp synced to #{} ({}) with authority set {}",
+			new_set.canon_number,
+			new_set.canon_hash,
+			new_set.set_id,
+		);
+
+		// the voter restarts from the target block with the new set
+		let _ = self.send_voter_commands.unbounded_send(VoterCommand::ChangeAuthorities(new_set));
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use test_client::runtime::{Block, Header, H256};
+	use test_client::client::{backend::NewBlockState};
+	use test_client::client::in_mem::Blockchain as InMemoryBlockchain;
+	use test_client::client::blockchain::HeaderBackend;
+	use sr_primitives::generic::DigestItem;
+	use super::*;
+
+	fn header(number: u64, parent_hash: H256, log: Option<ConsensusLog<u64>>) -> Header {
+		let mut header = Header::new(number, H256::default(), H256::default(), parent_hash, Default::default());
+		if let Some(log) = log {
+			header.digest_mut().push(DigestItem::Consensus(GRANDPA_ENGINE_ID, log.encode()));
+		}
+		header
+	}
+
+	fn change(delay: u64) -> ScheduledChange<u64> {
+		ScheduledChange { next_authorities: Vec::new(), delay }
+	}
+
+	fn insert_blocks_with_logs(
+		blockchain: &InMemoryBlockchain<Block>,
+		logs: Vec<(u64, ConsensusLog<u64>)>,
+		justified: &[u64],
+		finalized: u64,
+	) {
+		let mut logs: std::collections::HashMap<_, _> = logs.into_iter().collect();
+		let mut parent_hash = blockchain.info().genesis_hash;
+		for number in 1..=finalized {
+			let header = header(number, parent_hash, logs.remove(&number));
+			parent_hash = header.hash();
+			let justification = if justified.contains(&number) { Some(vec![number as u8]) } else { None };
+			blockchain.insert(header.hash(), header, justification, None, NewBlockState::Final).unwrap();
+		}
+		index_signals::<Block, _, _>(blockchain, blockchain).unwrap();
+	}
+
+	fn insert_blocks(blockchain: &InMemoryBlockchain<Block>, changes: &[u64], justified: &[u64], finalized: u64) {
+		let logs = changes.iter().map(|number| (*number, ConsensusLog::ScheduledChange(change(0)))).collect();
+		insert_blocks_with_logs(blockchain, logs, justified, finalized);
+	}
+
+	fn genesis_blockchain() -> InMemoryBlockchain<Block> {
+		let blockchain = InMemoryBlockchain::<Block>::new();
+		let genesis = header(0, Default::default(), None);
+		blockchain.insert(genesis.hash(), genesis, None, None, NewBlockState::Final).unwrap();
+		blockchain
+	}
+
+	#[test]
+	fn warp_proof_contains_changes_and_target() {
+		let blockchain = genesis_blockchain();
+		let genesis_hash = blockchain.info().genesis_hash;
+		insert_blocks(&blockchain, &[2, 4], &[2, 4, 6], 7);
+
+		let proof = prove_warp::<Block, _, _>(&blockchain, &blockchain, genesis_hash).unwrap().unwrap();
+		let proof = WarpSyncProof::<Header>::decode(&mut &proof[..]).unwrap();
+		let numbers = |fragment: &WarpSyncFragment<Header>|
+			fragment.headers.iter().map(|header| header.number).collect::<Vec<_>>();
+		assert_eq!(proof.changes.iter().map(numbers).collect::<Vec<_>>(), vec![vec![2], vec![4]]);
+		assert_eq!(proof.target.as_ref().map(numbers), Some(vec![6]));
+		assert_eq!(proof.target.unwrap().justification, vec![6]);
+	}
+
+	#[test]
+	fn index_signals_resumes_from_progress() {
+		let blockchain = genesis_blockchain();
+		let genesis_hash = blockchain.info().genesis_hash;
+		insert_blocks(&blockchain, &[2, 4, 6], &[2, 4, 6], 7);
+
+		// an interrupted scan which indexed the blocks up to #3 and missed the change at #2.
+		aux_schema::update_warp_sync_index(
+			&Vec::<(u64, H256)>::new(),
+			Some(3u64),
+			|insert, delete| blockchain.insert_aux(insert, delete),
+		).unwrap();
+		assert!(prove_warp::<Block, _, _>(&blockchain, &blockchain, genesis_hash).is_err());
+
+		index_signals::<Block, _, _>(&blockchain, &blockchain).unwrap();
+		let signals = aux_schema::load_warp_sync_signals::<_, H256, u64>(&blockchain).unwrap().unwrap();
+		assert_eq!(signals.iter().map(|signal| signal.0).collect::<Vec<_>>(), vec![4, 6]);
+		assert_eq!(aux_schema::load_warp_sync_index_progress::<_, u64>(&blockchain).unwrap(), None);
+	}
+
+	#[test]
+	fn warp_proof_skips_signals_of_abandoned_forks() {
+		let blockchain = genesis_blockchain();
+		let genesis_hash = blockchain.info().genesis_hash;
+		insert_blocks(&blockchain, &[2, 4], &[2, 4, 6], 7);
+
+		let mut signals = aux_schema::load_warp_sync_signals(&blockchain).unwrap().unwrap();
+		assert_eq!(signals.iter().map(|signal| signal.0).collect::<Vec<u64>>(), vec![2, 4]);
+		assert!(note_signal(&mut signals, 3, H256::repeat_byte(3)));
+		assert!(!note_signal(&mut signals, 3, H256::repeat_byte(3)));
+		aux_schema::update_warp_sync_signals(&signals, |insert| blockchain.insert_aux(insert, &[])).unwrap();
+
+		let proof = prove_warp::<Block, _, _>(&blockchain, &blockchain, genesis_hash).unwrap().unwrap();
+		let proof = WarpSyncProof::<Header>::decode(&mut &proof[..]).unwrap();
+		assert_eq!(proof.changes.len(), 2);
+		assert_eq!(proof.target.unwrap().justification, vec![6]);
+	}
+
+	#[test]
+	fn warp_proof_fails_without_change_justification() {
+		let blockchain = genesis_blockchain();
+		let genesis_hash = blockchain.info().genesis_hash;
+		insert_blocks(&blockchain, &[2], &[4], 4);
+
+		assert!(prove_warp::<Block, _, _>(&blockchain, &blockchain, genesis_hash).is_err());
+	}
+
+	#[test]
+	fn warp_proof_is_empty_when_nothing_is_finalized() {
+		let blockchain = genesis_blockchain();
+		let genesis_hash = blockchain.info().genesis_hash;
+
+		assert_eq!(prove_warp::<Block, _, _>(&blockchain, &blockchain, genesis_hash).unwrap(), None);
+	}
+
+	#[test]
+	fn warp_proof_to_block_contains_all_changes() {
+		let blockchain = genesis_blockchain();
+		let genesis_hash = blockchain.info().genesis_hash;
+		insert_blocks(&blockchain, &[2, 4], &[2, 4, 6], 7);
+		let hash = |number| blockchain.hash(number).unwrap().unwrap();
+
+		let proof = prove_warp_to::<Block, _, _>(&blockchain, &blockchain, hash(4)).unwrap();
+		let proof = WarpSyncProof::<Header>::decode(&mut &proof[..]).unwrap();
+		assert_eq!(proof.changes.len(), 2);
+		assert_eq!(proof.target.unwrap().headers[0].hash(), hash(4));
+
+		let proof = prove_warp_to::<Block, _, _>(&blockchain, &blockchain, hash(6)).unwrap();
+		let proof = WarpSyncProof::<Header>::decode(&mut &proof[..]).unwrap();
+		assert_eq!(proof.target.unwrap().justification, vec![6]);
+
+		// blocks without a justification can not be proven.
+		assert!(prove_warp_to::<Block, _, _>(&blockchain, &blockchain, hash(5)).is_err());
+		assert!(prove_warp_to::<Block, _, _>(&blockchain, &blockchain, genesis_hash).is_err());
+	}
+
+	#[test]
+	fn warp_proof_target_carries_pending_changes() {
+		let blockchain = genesis_blockchain();
+		let genesis_hash = blockchain.info().genesis_hash;
+		insert_blocks_with_logs(&blockchain, vec![
+			(2, ConsensusLog::ForcedChange(1, change(10))),
+			(3, ConsensusLog::ScheduledChange(change(3))),
+		], &[4], 5);
+
+		let proof = prove_warp::<Block, _, _>(&blockchain, &blockchain, genesis_hash).unwrap().unwrap();
+		let proof = WarpSyncProof::<Header>::decode(&mut &proof[..]).unwrap();
+		assert!(proof.changes.is_empty());
+		let target = proof.target.unwrap();
+		assert_eq!(target.headers.iter().map(|header| header.number).collect::<Vec<_>>(), vec![2, 3, 4]);
+
+		// both changes are anchored at the target block.
+		let target_header = target.headers.last().unwrap();
+		let pending = find_pending_changes::<Block>(&target.headers, target_header, 0).unwrap();
+		assert_eq!(pending.iter().map(|change| (change.canon_hash, change.canon_height, change.delay))
+			.collect::<Vec<_>>(), vec![(target_header.hash(), 4, 2), (target_header.hash(), 4, 8)]);
+		assert_eq!(pending[0].delay_kind, DelayKind::Finalized);
+		assert_eq!(pending[1].delay_kind, DelayKind::Best { median_last_finalized: 1 });
+
+		// changes enacted up to the target must be proven.
+		assert!(find_pending_changes::<Block>(&target.headers[..2], &target.headers[1], 0).is_ok());
+		assert!(find_pending_changes::<Block>(&target.headers, &header(6, target_header.hash(), None), 0).is_err());
+	}
+
+	#[test]
+	fn warp_proof_fails_over_enacted_forced_change() {
+		let blockchain = genesis_blockchain();
+		let genesis_hash = blockchain.info().genesis_hash;
+		insert_blocks_with_logs(&blockchain, vec![(2, ConsensusLog::ForcedChange(1, change(1)))], &[4], 5);
+
+		assert!(prove_warp::<Block, _, _>(&blockchain, &blockchain, genesis_hash).is_err());
+	}
+}
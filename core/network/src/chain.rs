@@ -17,6 +17,7 @@
 //! Blockchain access trait
 
 use client::{self, Client as SubstrateClient, ClientInfo, BlockStatus, CallExecutor};
+use client::backend::StateEntry;
 use client::error::Error;
 use client::light::fetcher::ChangesProof;
 use consensus::{BlockImport, Error as ConsensusError};
@@ -64,6 +65,36 @@ pub trait Client<Block: BlockT>: Send + Sync {
 		key: &StorageKey
 	) -> Result<ChangesProof<Block::Header>, Error>;
 
+	/// Get a proof of the storage entries from `start` on, in the top trie or in the given child
+	/// trie, covering about `max_bytes` of them.
+	fn state_range_proof(
+		&self,
+		block: &Block::Hash,
+		child_storage_key: Option<&[u8]>,
+		start: &[u8],
+		max_bytes: usize,
+	) -> Result<Vec<Vec<u8>>, Error>;
+
+	/// Check a proof generated by `state_range_proof` against the given trie root. Returns the
+	/// proven entries and whether they reach the end of the trie.
+	fn check_state_range_proof(
+		&self,
+		root: &Block::Hash,
+		proof: Vec<Vec<u8>>,
+		start: &[u8],
+	) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Error>;
+
+	/// Import a block along with its full state, making it the best and last finalized block.
+	///
+	/// The state is imported as it is read from `state`, see `StateEntry` for the expected order.
+	fn import_state(
+		&self,
+		header: Block::Header,
+		body: Option<Vec<Block::Extrinsic>>,
+		justification: Option<Justification>,
+		state: Box<dyn Iterator<Item=Result<StateEntry, Error>> + Send>,
+	) -> Result<(), Error>;
+
 	/// Returns `true` if the given `block` is a descendent of `base`.
 	fn is_descendent_of(&self, base: &Block::Hash, block: &Block::Hash) -> Result<bool, Error>;
 }
@@ -72,6 +103,14 @@ pub trait Client<Block: BlockT>: Send + Sync {
 pub trait FinalityProofProvider<Block: BlockT>: Send + Sync {
 	/// Prove finality of the block.
 	fn prove_finality(&self, for_block: Block::Hash, request: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+
+	/// Prove finality of the given finalized block to a node which only knows the genesis block.
+	///
+	/// The proof is checked by a `WarpSyncVerifier`. Returns `None` if warp sync is not
+	/// supported.
+	fn prove_warp_to(&self, _block: Block::Hash) -> Result<Option<Vec<u8>>, Error> {
+		Ok(None)
+	}
 }
 
 impl<B, E, Block, RA> Client<Block> for SubstrateClient<B, E, Block, RA> where
@@ -128,6 +167,36 @@ impl<B, E, Block, RA> Client<Block> for SubstrateClient<B, E, Block, RA> where
 		(self as &SubstrateClient<B, E, Block, RA>).key_changes_proof(first, last, min, max, key)
 	}
 
+	fn state_range_proof(
+		&self,
+		block: &Block::Hash,
+		child_storage_key: Option<&[u8]>,
+		start: &[u8],
+		max_bytes: usize,
+	) -> Result<Vec<Vec<u8>>, Error> {
+		(self as &SubstrateClient<B, E, Block, RA>)
+			.state_range_proof(&BlockId::Hash(block.clone()), child_storage_key, start, max_bytes)
+	}
+
+	fn check_state_range_proof(
+		&self,
+		root: &Block::Hash,
+		proof: Vec<Vec<u8>>,
+		start: &[u8],
+	) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Error> {
+		(self as &SubstrateClient<B, E, Block, RA>).check_state_range_proof(*root, proof, start)
+	}
+
+	fn import_state(
+		&self,
+		header: Block::Header,
+		body: Option<Vec<Block::Extrinsic>>,
+		justification: Option<Justification>,
+		state: Box<dyn Iterator<Item=Result<StateEntry, Error>> + Send>,
+	) -> Result<(), Error> {
+		(self as &SubstrateClient<B, E, Block, RA>).import_state(header, body, justification, state)
+	}
+
 	fn is_descendent_of(&self, base: &Block::Hash, block: &Block::Hash) -> Result<bool, Error> {
 		if base == block {
 			return Ok(false);
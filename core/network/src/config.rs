@@ -27,7 +27,7 @@ use crate::on_demand_layer::OnDemand;
 use crate::service::{ExHashT, TransactionPool};
 use bitflags::bitflags;
 use consensus::import_queue::ImportQueue;
-use sr_primitives::traits::{Block as BlockT};
+use sr_primitives::{Justification, traits::{Block as BlockT}};
 use std::sync::Arc;
 use libp2p::identity::{Keypair, secp256k1, ed25519};
 use libp2p::wasm_ext;
@@ -58,6 +58,12 @@ pub struct Params<B: BlockT, S, H: ExHashT> {
 	/// This object, if `Some`, is used when we need a proof of finality from another node.
 	pub finality_proof_request_builder: Option<BoxFinalityProofRequestBuilder<B>>,
 
+	/// How to verify the proofs of finality used to skip to a recent finalized block.
+	///
+	/// This object, if `Some`, is used when the node is configured with `SyncMode::Fast`.
+	/// Otherwise the node always falls back to a full sync.
+	pub warp_sync_verifier: Option<BoxWarpSyncVerifier<B>>,
+
 	/// The `OnDemand` object acts as a "receiver" for block data requests from the client.
 	/// If `Some`, the network worker will process these requests and answer them.
 	/// Normally used only for light clients.
@@ -146,6 +152,34 @@ impl<B: BlockT> FinalityProofRequestBuilder<B> for DummyFinalityProofRequestBuil
 /// Shared finality proof request builder struct used by the queue.
 pub type BoxFinalityProofRequestBuilder<B> = Box<dyn FinalityProofRequestBuilder<B> + Send + Sync>;
 
+/// Progress made by a verified warp sync proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarpSyncProgress<B: BlockT> {
+	/// The proof reaches the given finalized header. More proofs are needed to reach the most
+	/// recent finalized block known to the remote.
+	Partial(B::Header),
+	/// The proof reaches the most recent finalized block known to the remote, which is the
+	/// target of the sync.
+	Complete(B::Header, Justification),
+}
+
+/// Verifier of the proofs of finality which let a node skip to a recent finalized block
+/// without importing the blocks before it.
+pub trait WarpSyncVerifier<B: BlockT>: Send {
+	/// Build the next proof request. Returns the hash of the block the request is made for and
+	/// the data blob associated with the request.
+	fn next_request(&mut self) -> (B::Hash, Vec<u8>);
+
+	/// Verify a proof received in response to the last request.
+	fn verify(&mut self, proof: &[u8]) -> Result<WarpSyncProgress<B>, String>;
+
+	/// Called once the target block has been imported along with its state.
+	fn on_target_imported(&mut self, header: &B::Header) -> Result<(), String>;
+}
+
+/// Shared warp sync verifier used by the sync.
+pub type BoxWarpSyncVerifier<B> = Box<dyn WarpSyncVerifier<B> + Send + Sync>;
+
 /// Name of a protocol, transmitted on the wire. Should be unique for each chain.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ProtocolId(smallvec::SmallVec<[u8; 6]>);
@@ -259,6 +293,8 @@ pub struct NetworkConfiguration {
 	pub node_name: String,
 	/// Configuration for the transport layer.
 	pub transport: TransportConfig,
+	/// How to bring the node up to date with the chain.
+	pub sync_mode: SyncMode,
 }
 
 impl Default for NetworkConfiguration {
@@ -280,6 +316,7 @@ impl Default for NetworkConfiguration {
 				enable_mdns: false,
 				wasm_external_transport: None,
 			},
+			sync_mode: SyncMode::Full,
 		}
 	}
 }
@@ -313,6 +350,16 @@ impl NetworkConfiguration {
 	}
 }
 
+/// How the node is brought up to date with the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+	/// Download and import all the blocks.
+	Full,
+	/// Download proofs of finality up to a recent finalized block, download the state at that
+	/// block and only import the blocks after it.
+	Fast,
+}
+
 /// Configuration for the transport layer.
 #[derive(Clone)]
 pub enum TransportConfig {
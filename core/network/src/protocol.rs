@@ -16,7 +16,7 @@
 
 use crate::{DiscoveryNetBehaviour, config::ProtocolId};
 use crate::legacy_proto::{LegacyProto, LegacyProtoOut};
-use futures::prelude::*;
+use futures::{prelude::*, sync::mpsc};
 use futures03::{StreamExt as _, TryStreamExt as _};
 use libp2p::{Multiaddr, PeerId};
 use libp2p::core::{ConnectedPoint, nodes::Substream, muxing::StreamMuxerBox};
@@ -36,9 +36,9 @@ use event::Event;
 use consensus_gossip::{ConsensusGossip, MessageRecipient as GossipMessageRecipient};
 use light_dispatch::{LightDispatch, LightDispatchNetwork, RequestData};
 use specialization::NetworkSpecialization;
-use sync::{ChainSync, SyncState};
+use sync::{ChainSync, SyncState, WarpImport, WarpRequest};
 use crate::service::{TransactionPool, ExHashT};
-use crate::config::{BoxFinalityProofRequestBuilder, Roles};
+use crate::config::{BoxFinalityProofRequestBuilder, BoxWarpSyncVerifier, Roles};
 use rustc_hex::ToHex;
 use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
@@ -64,12 +64,16 @@ const TICK_TIMEOUT: time::Duration = time::Duration::from_millis(1100);
 const PROPAGATE_TIMEOUT: time::Duration = time::Duration::from_millis(2900);
 
 /// Current protocol version.
-pub(crate) const CURRENT_VERSION: u32 = 3;
+pub(crate) const CURRENT_VERSION: u32 = 4;
 /// Lowest version we support
 pub(crate) const MIN_VERSION: u32 = 2;
+/// Lowest version supporting state requests.
+pub(crate) const MIN_WARP_SYNC_VERSION: u32 = 4;
 
 // Maximum allowed entries in `BlockResponse`
 const MAX_BLOCK_DATA_RESPONSE: u32 = 128;
+/// Maximum size of the storage entries proven in a `StateResponse`.
+const MAX_STATE_RESPONSE_BYTES: usize = 2 * 1024 * 1024;
 /// When light node connects to the full node and the full node is behind light node
 /// for at least `LIGHT_MAXIMAL_BLOCKS_DIFFERENCE` blocks, we consider it unuseful
 /// and disconnect to free connection slot.
@@ -112,6 +116,10 @@ pub struct Protocol<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
 	finality_proof_provider: Option<Arc<dyn FinalityProofProvider<B>>>,
 	/// Handles opening the unique substream and sending and receiving raw messages.
 	behaviour: LegacyProto<B, Substream<StreamMuxerBox>>,
+	/// Sends the outcome of the import of the warp sync target from the thread running it.
+	warp_import_tx: mpsc::UnboundedSender<(B::Header, bool)>,
+	/// Receives the outcome of the import of the warp sync target.
+	warp_import_rx: mpsc::UnboundedReceiver<(B::Header, bool)>,
 }
 
 /// A peer that we are connected to
@@ -356,14 +364,22 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		transaction_pool: Arc<dyn TransactionPool<H, B>>,
 		finality_proof_provider: Option<Arc<dyn FinalityProofProvider<B>>>,
 		finality_proof_request_builder: Option<BoxFinalityProofRequestBuilder<B>>,
+		warp_sync_verifier: Option<BoxWarpSyncVerifier<B>>,
 		protocol_id: ProtocolId,
 		peerset_config: peerset::PeersetConfig,
 	) -> error::Result<(Protocol<B, S, H>, peerset::PeersetHandle)> {
 		let info = chain.info();
-		let sync = ChainSync::new(config.roles, chain.clone(), &info, finality_proof_request_builder);
+		let sync = ChainSync::new(
+			config.roles,
+			chain.clone(),
+			&info,
+			finality_proof_request_builder,
+			warp_sync_verifier,
+		);
 		let (peerset, peerset_handle) = peerset::Peerset::from_config(peerset_config);
 		let versions = &((MIN_VERSION as u8)..=(CURRENT_VERSION as u8)).collect::<Vec<u8>>();
 		let behaviour = LegacyProto::new(protocol_id, versions, peerset);
+		let (warp_import_tx, warp_import_rx) = mpsc::unbounded();
 
 		let protocol = Protocol {
 			tick_timeout: Box::new(futures_timer::Interval::new(TICK_TIMEOUT).map(|v| Ok::<_, ()>(v)).compat()),
@@ -383,6 +399,8 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			finality_proof_provider,
 			peerset_handle: peerset_handle.clone(),
 			behaviour,
+			warp_import_tx,
+			warp_import_rx,
 		};
 
 		Ok((protocol, peerset_handle))
@@ -547,6 +565,10 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				self.on_finality_proof_request(who, request),
 			GenericMessage::FinalityProofResponse(response) =>
 				return self.on_finality_proof_response(who, response),
+			GenericMessage::StateRequest(request) =>
+				self.on_state_request(who, request),
+			GenericMessage::StateResponse(response) =>
+				self.on_state_response(who, response),
 			GenericMessage::RemoteReadChildRequest(_) => {}
 			GenericMessage::Consensus(msg) => {
 				if self.context_data.peers.get(&who).map_or(false, |peer| peer.info.protocol_version > 2) {
@@ -1462,6 +1484,80 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		}
 	}
 
+	fn on_state_request(
+		&mut self,
+		who: PeerId,
+		request: message::StateRequest<B::Hash>,
+	) {
+		trace!(target: "sync", "State request {} from {} for {} starting at {}",
+			request.id,
+			who,
+			request.block,
+			request.start.to_hex::<String>(),
+		);
+		let proof = match self.context_data.chain.state_range_proof(
+			&request.block,
+			request.child_storage_key.as_ref().map(|key| &key[..]),
+			&request.start,
+			MAX_STATE_RESPONSE_BYTES,
+		) {
+			Ok(proof) => proof,
+			Err(error) => {
+				trace!(target: "sync", "State request {} from {} for {} failed with: {}",
+					request.id,
+					who,
+					request.block,
+					error
+				);
+				Default::default()
+			}
+		};
+		self.send_message(
+			who,
+			GenericMessage::StateResponse(message::StateResponse {
+				id: request.id,
+				proof,
+			}),
+		);
+	}
+
+	fn on_state_response(
+		&mut self,
+		who: PeerId,
+		response: message::StateResponse,
+	) {
+		trace!(target: "sync", "State response {} from {}", response.id, who);
+		if let Err(sync::BadPeer(id, repu)) = self.sync.on_state_data(who, response) {
+			self.behaviour.disconnect_peer(&id);
+			self.peerset_handle.report_peer(id, repu);
+		}
+	}
+
+	/// Start the import of the target block of the warp sync, reading its state as it is
+	/// downloaded.
+	///
+	/// The import runs on its own thread so that the network keeps being polled, which is what
+	/// feeds it the state. Its outcome is reported back through `warp_import_rx`.
+	fn import_warp_target(&mut self, import: WarpImport<B>) {
+		let WarpImport { header, body, justification, state } = import;
+		let chain = self.context_data.chain.clone();
+		let done = self.warp_import_tx.clone();
+		let target = header.clone();
+		let spawned = std::thread::Builder::new()
+			.name("warp-sync-import".into())
+			.spawn(move || {
+				let result = chain.import_state(header.clone(), body, Some(justification), state);
+				if let Err(e) = &result {
+					warn!(target: "sync", "Error importing state of #{} ({}): {:?}", header.number(), header.hash(), e);
+				}
+				let _ = done.unbounded_send((header, result.is_ok()));
+			});
+		if let Err(e) = spawned {
+			warn!(target: "sync", "Failed to spawn the warp sync import: {:?}", e);
+			self.sync.on_state_imported(&target, false);
+		}
+	}
+
 	fn on_remote_body_response(
 		&mut self,
 		peer: PeerId,
@@ -1558,6 +1654,20 @@ Protocol<B, S, H> {
 		for (id, r) in self.sync.finality_proof_requests() {
 			send_message(&mut self.behaviour, &mut self.context_data.peers, id, GenericMessage::FinalityProofRequest(r))
 		}
+		if let Some(import) = self.sync.take_warp_import() {
+			self.import_warp_target(import);
+		}
+		while let Ok(Async::Ready(Some((header, success)))) = self.warp_import_rx.poll() {
+			self.sync.on_state_imported(&header, success);
+		}
+		if let Some((id, r)) = self.sync.warp_request() {
+			let message = match r {
+				WarpRequest::FinalityProof(r) => GenericMessage::FinalityProofRequest(r),
+				WarpRequest::Block(r) => GenericMessage::BlockRequest(r),
+				WarpRequest::State(r) => GenericMessage::StateRequest(r),
+			};
+			send_message(&mut self.behaviour, &mut self.context_data.peers, id, message)
+		}
 
 		let event = match self.behaviour.poll(params) {
 			Async::NotReady => return Async::NotReady,
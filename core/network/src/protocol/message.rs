@@ -24,7 +24,7 @@ pub use self::generic::{
 	RemoteHeaderRequest, RemoteHeaderResponse,
 	RemoteChangesRequest, RemoteChangesResponse,
 	FinalityProofRequest, FinalityProofResponse,
-	FromBlock, RemoteReadChildRequest,
+	FromBlock, RemoteReadChildRequest, StateRequest,
 };
 
 /// A unique ID of a request.
@@ -125,6 +125,16 @@ pub struct RemoteReadResponse {
 	pub proof: Vec<Vec<u8>>,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// State response.
+pub struct StateResponse {
+	/// Id of a request this response was made for.
+	pub id: RequestId,
+	/// Proof of the storage entries from the requested start key on. Empty if the state is not
+	/// available.
+	pub proof: Vec<Vec<u8>>,
+}
+
 /// Generic types.
 pub mod generic {
 	use codec::{Encode, Decode};
@@ -133,6 +143,7 @@ pub mod generic {
 	use super::{
 		RemoteReadResponse, Transactions, Direction,
 		RequestId, BlockAttributes, RemoteCallResponse, ConsensusEngineId,
+		StateResponse,
 	};
 	/// Consensus is mostly opaque to us
 	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
@@ -206,6 +217,10 @@ pub mod generic {
 		FinalityProofRequest(FinalityProofRequest<Hash>),
 		/// Finality proof reponse.
 		FinalityProofResponse(FinalityProofResponse<Hash>),
+		/// State request.
+		StateRequest(StateRequest<Hash>),
+		/// State response.
+		StateResponse(StateResponse),
 		/// Chain-specific message.
 		#[codec(index = "255")]
 		ChainSpecific(Vec<u8>),
@@ -375,4 +390,17 @@ pub mod generic {
 		/// Finality proof (if available).
 		pub proof: Option<Vec<u8>>,
 	}
+
+	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+	/// Request for a chunk of the state of a block.
+	pub struct StateRequest<H> {
+		/// Unique request id.
+		pub id: RequestId,
+		/// Hash of the block to request state of.
+		pub block: H,
+		/// Storage key of the child trie to read. The top trie is read when `None`.
+		pub child_storage_key: Option<Vec<u8>>,
+		/// Key of the first storage entry to return.
+		pub start: Vec<u8>,
+	}
 }
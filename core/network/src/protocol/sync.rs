@@ -31,8 +31,11 @@ use blocks::BlockCollection;
 use client::{BlockStatus, ClientInfo, error::Error as ClientError};
 use consensus::{BlockOrigin, import_queue::{IncomingBlock, BlockImportResult, BlockImportError}};
 use crate::{
-	config::{Roles, BoxFinalityProofRequestBuilder},
-	message::{self, generic::FinalityProofRequest, BlockAttributes, BlockRequest, BlockResponse, FinalityProofResponse},
+	config::{Roles, BoxFinalityProofRequestBuilder, BoxWarpSyncVerifier},
+	message::{
+		self, generic::FinalityProofRequest, BlockAttributes, BlockRequest, BlockResponse,
+		FinalityProofResponse, StateResponse,
+	},
 	protocol
 };
 use either::Either;
@@ -44,10 +47,14 @@ use sr_primitives::{
 	generic::BlockId,
 	traits::{Block as BlockT, Header, NumberFor, Zero, One, CheckedSub, SaturatedConversion}
 };
-use std::{fmt, ops::Range, collections::{HashMap, HashSet, VecDeque}, sync::Arc};
+use std::{fmt, ops::Range, collections::{HashMap, HashSet, VecDeque}, sync::Arc, time::Duration};
+use warp::WarpSync;
+
+pub use warp::{WarpImport, WarpRequest};
 
 mod blocks;
 mod extra_requests;
+mod warp;
 
 /// Maximum blocks to request in a single packet.
 const MAX_BLOCKS_TO_REQUEST: usize = 128;
@@ -68,6 +75,12 @@ const ANNOUNCE_HISTORY_SIZE: usize = 64;
 /// Max number of blocks to download for unknown forks.
 const MAX_UNKNOWN_FORK_DOWNLOAD_LEN: u32 = 32;
 
+/// Time after which a peer which did not answer a warp sync request is no longer asked.
+const WARP_REQUEST_TIMEOUT: Duration = Duration::from_secs(40);
+
+/// Time without any warp sync progress after which the regular sync is used instead.
+const WARP_SYNC_STALL_TIMEOUT: Duration = Duration::from_secs(120);
+
 /// Reputation change when a peer sent us a status message that led to a
 /// database read error.
 const BLOCKCHAIN_STATUS_READ_ERROR_REPUTATION_CHANGE: i32 = -(1 << 16);
@@ -92,6 +105,9 @@ const BAD_BLOCK_REPUTATION_CHANGE: i32 = -(1 << 29);
 /// Reputation change for peers which send us a block with bad justifications.
 const BAD_JUSTIFICATION_REPUTATION_CHANGE: i32 = -(1 << 16);
 
+/// Reputation change for peers which send us invalid warp sync data.
+const BAD_WARP_DATA_REPUTATION_CHANGE: i32 = -(1 << 29);
+
 /// The main data structure which contains all the state for a chains
 /// active syncing strategy.
 pub struct ChainSync<B: BlockT> {
@@ -122,6 +138,8 @@ pub struct ChainSync<B: BlockT> {
 	request_builder: Option<BoxFinalityProofRequestBuilder<B>>,
 	/// A flag that caches idle state with no pending requests.
 	is_idle: bool,
+	/// Warp sync in progress, if any. No blocks are downloaded until it is over.
+	warp: Option<WarpSync<B>>,
 }
 
 /// All the data we have about a Peer that we are trying to sync with
@@ -170,7 +188,9 @@ pub enum PeerSyncState<B: BlockT> {
 	/// Downloading justification for given block hash.
 	DownloadingJustification(B::Hash),
 	/// Downloading finality proof for given block hash.
-	DownloadingFinalityProof(B::Hash)
+	DownloadingFinalityProof(B::Hash),
+	/// Serving a warp sync request.
+	DownloadingWarp,
 }
 
 impl<B: BlockT> PeerSyncState<B> {
@@ -271,7 +291,8 @@ impl<B: BlockT> ChainSync<B> {
 		role: Roles,
 		client: Arc<dyn crate::chain::Client<B>>,
 		info: &ClientInfo<B>,
-		request_builder: Option<BoxFinalityProofRequestBuilder<B>>
+		request_builder: Option<BoxFinalityProofRequestBuilder<B>>,
+		warp_sync_verifier: Option<BoxWarpSyncVerifier<B>>,
 	) -> Self {
 		let mut required_block_attributes = BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION;
 
@@ -279,6 +300,16 @@ impl<B: BlockT> ChainSync<B> {
 			required_block_attributes |= BlockAttributes::BODY
 		}
 
+		// the state is only downloaded into an empty database.
+		let warp = match warp_sync_verifier {
+			Some(verifier) if role.is_full() && info.chain.best_number.is_zero() => Some(WarpSync::new(verifier)),
+			Some(_) => {
+				info!("Blocks have been imported already, using full sync");
+				None
+			}
+			None => None,
+		};
+
 		ChainSync {
 			client,
 			peers: HashMap::new(),
@@ -293,6 +324,7 @@ impl<B: BlockT> ChainSync<B> {
 			best_importing_number: Zero::zero(),
 			request_builder,
 			is_idle: false,
+			warp,
 		}
 	}
 
@@ -335,6 +367,21 @@ impl<B: BlockT> ChainSync<B> {
 		if !info.roles.is_full() {
 			return Ok(None)
 		}
+		// No blocks are downloaded until the warp sync is over.
+		if let Some(warp) = &mut self.warp {
+			debug!(target:"sync", "New peer with best hash {} ({}) during warp sync.", info.best_hash, info.best_number);
+			if info.protocol_version >= protocol::MIN_WARP_SYNC_VERSION {
+				warp.add_peer(who.clone());
+			}
+			self.peers.insert(who, PeerSync {
+				common_number: Zero::zero(),
+				best_hash: info.best_hash,
+				best_number: info.best_number,
+				state: PeerSyncState::Available,
+				recently_announced: Default::default(),
+			});
+			return Ok(None)
+		}
 		match self.block_status(&info.best_hash) {
 			Err(e) => {
 				debug!(target:"sync", "Error reading blockchain: {:?}", e);
@@ -489,9 +536,56 @@ impl<B: BlockT> ChainSync<B> {
 		})
 	}
 
+	/// Get the next warp sync request, if any.
+	///
+	/// The warp sync is abandoned in favour of the regular sync once it is stalled.
+	pub fn warp_request(&mut self) -> Option<(PeerId, WarpRequest<B>)> {
+		if self.warp.as_mut()?.is_stalled(WARP_SYNC_STALL_TIMEOUT) {
+			self.abandon_warp_sync();
+			return None
+		}
+		let warp = self.warp.as_mut()?;
+		if let Some((who, requested_at)) = warp.pending().cloned() {
+			if requested_at.elapsed() < WARP_REQUEST_TIMEOUT {
+				return None
+			}
+			debug!(target: "sync", "Warp sync request to {} timed out", who);
+			warp.remove_peer(&who);
+			if let Some(peer) = self.peers.get_mut(&who) {
+				peer.state = PeerSyncState::Available;
+			}
+		}
+
+		let who = self.peers.iter()
+			.filter(|(id, peer)| peer.state.is_available() && warp.has_peer(id))
+			.max_by_key(|(_, peer)| peer.best_number)
+			.map(|(id, _)| id.clone())?;
+		let request = warp.request(&who)?;
+		self.peers.get_mut(&who)
+			.expect("`who` is taken from the peers above; qed")
+			.state = PeerSyncState::DownloadingWarp;
+		trace!(target: "sync", "New warp sync request for {}", who);
+		Some((who, request))
+	}
+
+	/// Stop the warp sync, the blocks are then downloaded from genesis.
+	///
+	/// Dropping the warp sync ends the state of the import of its target, if it is running, which
+	/// then fails and removes what it imported so far.
+	fn abandon_warp_sync(&mut self) {
+		warn!(target: "sync", "Warp sync is stalled, using full sync");
+		self.warp = None;
+		self.is_idle = false;
+		for peer in self.peers.values_mut() {
+			if let PeerSyncState::DownloadingWarp = peer.state {
+				peer.state = PeerSyncState::Available;
+			}
+		}
+	}
+
 	/// Get an iterator over all block requests of all peers.
 	pub fn block_requests(&mut self) -> impl Iterator<Item = (PeerId, BlockRequest<B>)> + '_ {
-		if self.is_idle {
+		if self.is_idle || self.warp.is_some() {
 			return Either::Left(std::iter::empty())
 		}
 		if self.queue_blocks.len() > MAX_IMPORTING_BLOCKS {
@@ -600,6 +694,17 @@ impl<B: BlockT> ChainSync<B> {
 						}
 					}
 
+					PeerSyncState::DownloadingWarp => {
+						peer.state = PeerSyncState::Available;
+						if let Some(warp) = &mut self.warp {
+							if let Err(e) = warp.on_block_data(&who, blocks) {
+								info!("Invalid warp sync block data provided by {}: {}", who, e);
+								return Err(BadPeer(who, BAD_WARP_DATA_REPUTATION_CHANGE))
+							}
+						}
+						Vec::new()
+					}
+
 					| PeerSyncState::Available
 					| PeerSyncState::DownloadingJustification(..)
 					| PeerSyncState::DownloadingFinalityProof(..) => Vec::new()
@@ -694,6 +799,17 @@ impl<B: BlockT> ChainSync<B> {
 			};
 
 		self.is_idle = false;
+		if let PeerSyncState::DownloadingWarp = peer.state {
+			peer.state = PeerSyncState::Available;
+			if let Some(warp) = &mut self.warp {
+				if let Err(e) = warp.on_finality_proof(&who, resp.proof) {
+					info!("Invalid warp sync proof provided by {}: {}", who, e);
+					return Err(BadPeer(who, BAD_WARP_DATA_REPUTATION_CHANGE))
+				}
+			}
+			return Ok(OnBlockFinalityProof::Nothing)
+		}
+
 		if let PeerSyncState::DownloadingFinalityProof(hash) = peer.state {
 			peer.state = PeerSyncState::Available;
 
@@ -711,6 +827,63 @@ impl<B: BlockT> ChainSync<B> {
 		Ok(OnBlockFinalityProof::Nothing)
 	}
 
+	/// Handle a response from the remote to a state request that we made.
+	pub fn on_state_data(&mut self, who: PeerId, response: StateResponse) -> Result<(), BadPeer> {
+		let peer =
+			if let Some(peer) = self.peers.get_mut(&who) {
+				peer
+			} else {
+				error!(target: "sync", "Called on_state_data with a bad peer ID");
+				return Ok(())
+			};
+
+		match peer.state {
+			PeerSyncState::DownloadingWarp => peer.state = PeerSyncState::Available,
+			_ => {
+				trace!(target: "sync", "Unexpected state response from {}", who);
+				return Ok(())
+			}
+		}
+		self.is_idle = false;
+
+		let warp = match &mut self.warp {
+			Some(warp) => warp,
+			None => return Ok(()),
+		};
+		warp.on_state_data(&who, response, &*self.client).map_err(|e| {
+			info!("Invalid state data provided by {}: {}", who, e);
+			BadPeer(who, BAD_WARP_DATA_REPUTATION_CHANGE)
+		})
+	}
+
+	/// Take the import of the target block of the warp sync, once its body has been downloaded.
+	///
+	/// The import must run while the state is downloaded, and `on_state_imported` be called once
+	/// it finishes.
+	pub fn take_warp_import(&mut self) -> Option<WarpImport<B>> {
+		self.warp.as_mut()?.take_import()
+	}
+
+	/// Call this when the target block of the warp sync has been imported along with its state,
+	/// with or without errors. The regular sync continues from that block.
+	pub fn on_state_imported(&mut self, header: &B::Header, success: bool) {
+		let mut warp = match self.warp.take() {
+			Some(warp) => warp,
+			None => return,
+		};
+		self.is_idle = false;
+		if !success {
+			warn!(target: "sync", "Failed to import the state of the warp sync target, using full sync");
+			return
+		}
+
+		if let Err(e) = warp.on_imported(header) {
+			warn!(target: "sync", "Error finishing warp sync at {}: {}", header.hash(), e);
+		}
+		self.blocks.clear();
+		self.on_block_queued(&header.hash(), *header.number());
+	}
+
 	/// A batch of blocks have been processed, with or without errors.
 	///
 	/// Call this when a batch of blocks have been processed by the import
@@ -920,6 +1093,9 @@ impl<B: BlockT> ChainSync<B> {
 		if let PeerSyncState::AncestorSearch(_, _) = peer.state {
 			return OnBlockAnnounce::Nothing
 		}
+		if self.warp.is_some() {
+			return OnBlockAnnounce::Nothing
+		}
 		// We assume that the announced block is the latest they have seen, and so our common number
 		// is either one further ahead or it's the one they just announced, if we know about it.
 		if known {
@@ -1007,6 +1183,9 @@ impl<B: BlockT> ChainSync<B> {
 		self.peers.remove(&who);
 		self.extra_justifications.peer_disconnected(&who);
 		self.extra_finality_proofs.peer_disconnected(&who);
+		if let Some(warp) = &mut self.warp {
+			warp.remove_peer(&who);
+		}
 		self.is_idle = false;
 	}
 
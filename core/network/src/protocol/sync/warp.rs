@@ -0,0 +1,705 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Warp sync.
+//!
+//! Instead of importing all the blocks from genesis, the node downloads proofs of finality up to
+//! the most recent finalized block known to a peer (the target), then the body of the target and
+//! its state, chunk by chunk. Every chunk is checked against the state root of the target and
+//! handed over to the import of the target, which runs as the state is downloaded. The import
+//! finishes once the whole state is downloaded, and the regular sync continues from there.
+//!
+//! The import expects the entries of the state in key order, with the entries of each child trie
+//! in place of the top-level entry holding its root. The child tries are therefore downloaded as
+//! their roots are found in the top trie, before the download of the top trie resumes.
+//!
+//! Only `MAX_QUEUED_CHUNKS` downloaded chunks may wait for the import, and the next chunk is only
+//! requested once the import has taken all of them, so that a slow import doesn't make the whole
+//! state pile up in memory.
+//!
+//! The warp sync is stalled once all the peers able to serve it have failed or disconnected, or
+//! when nothing has been downloaded for a while, see `is_stalled`. It is then abandoned in favour
+//! of the regular sync.
+
+use std::collections::HashSet;
+use std::sync::{Arc, mpsc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use client::backend::StateEntry;
+use client::error::Error as ClientError;
+use codec::{Decode, Encode};
+use libp2p::PeerId;
+use log::{debug, info, trace};
+use primitives::storage::well_known_keys;
+use sr_primitives::Justification;
+use sr_primitives::traits::{Block as BlockT, Header as HeaderT, Hash as HashT};
+use crate::chain::Client;
+use crate::config::{BoxWarpSyncVerifier, WarpSyncProgress};
+use crate::message::{self, BlockAttributes, BlockData, BlockRequest, StateResponse};
+use crate::message::generic::{FinalityProofRequest, StateRequest};
+
+/// Maximum number of downloaded chunks of state waiting for the import.
+const MAX_QUEUED_CHUNKS: usize = 1;
+
+/// A request made on behalf of the warp sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarpRequest<B: BlockT> {
+	/// Request for a proof of finality.
+	FinalityProof(FinalityProofRequest<B::Hash>),
+	/// Request for the body of the target block.
+	Block(BlockRequest<B>),
+	/// Request for a chunk of the state of the target block.
+	State(StateRequest<B::Hash>),
+}
+
+/// Import of the target block, to be run while its state is downloaded.
+pub struct WarpImport<B: BlockT> {
+	/// Header of the target block.
+	pub header: B::Header,
+	/// Body of the target block.
+	pub body: Option<Vec<B::Extrinsic>>,
+	/// Justification proving the finality of the target block.
+	pub justification: Justification,
+	/// Entries of the state of the target block, in the order expected by the import. Ends once
+	/// the whole state is downloaded, or when the download is abandoned.
+	pub state: Box<dyn Iterator<Item=Result<StateEntry, ClientError>> + Send>,
+}
+
+/// State of a state download.
+struct StateDownload<B: BlockT> {
+	header: B::Header,
+	/// Sends the downloaded chunks to the import.
+	chunks: mpsc::SyncSender<Vec<StateEntry>>,
+	/// Number of chunks sent to the import and not yet taken by it.
+	queued: Arc<AtomicUsize>,
+	/// Chunk which didn't fit in the channel to the import, to be sent once it does.
+	unsent: Option<Vec<StateEntry>>,
+	/// Whether the whole state has been downloaded.
+	complete: bool,
+	/// Number of entries downloaded so far.
+	downloaded: u64,
+	/// Child trie being downloaded, along with its root. The top trie when `None`.
+	current_child: Option<(Vec<u8>, B::Hash)>,
+	/// Key of the first entry of the next chunk.
+	next_key: Vec<u8>,
+	/// Key of the first entry of the top trie to download once the current child trie is.
+	next_top_key: Vec<u8>,
+}
+
+impl<B: BlockT> StateDownload<B> {
+	/// Hand the given chunk over to the import, or keep it until the import has room for it.
+	fn send(&mut self, chunk: Vec<StateEntry>) {
+		// counted before sending, since the import may take the chunk right away.
+		self.queued.fetch_add(1, Ordering::SeqCst);
+		match self.chunks.try_send(chunk) {
+			Ok(()) => {},
+			// the import is only gone if it failed, which is reported separately.
+			Err(mpsc::TrySendError::Disconnected(_)) => {
+				self.queued.fetch_sub(1, Ordering::SeqCst);
+			}
+			Err(mpsc::TrySendError::Full(chunk)) => {
+				self.queued.fetch_sub(1, Ordering::SeqCst);
+				self.unsent = Some(chunk);
+			}
+		}
+	}
+
+	/// Send the chunk kept by `send`, if any. Returns `true` if the import has taken all the
+	/// chunks downloaded so far.
+	fn flush(&mut self) -> bool {
+		if let Some(chunk) = self.unsent.take() {
+			self.send(chunk);
+		}
+		self.unsent.is_none() && self.queued.load(Ordering::SeqCst) == 0
+	}
+}
+
+enum Phase<B: BlockT> {
+	/// Downloading proofs of finality.
+	Proofs,
+	/// Downloading the body of the target block.
+	Body(B::Header, Justification),
+	/// Downloading the state of the target block.
+	State(StateDownload<B>),
+	/// The state has been downloaded and is being imported.
+	Importing,
+}
+
+/// Warp sync state.
+pub struct WarpSync<B: BlockT> {
+	verifier: BoxWarpSyncVerifier<B>,
+	phase: Phase<B>,
+	/// Peers which are able to serve warp sync requests.
+	peers: HashSet<PeerId>,
+	/// Peer serving the current request, and when the request has been made.
+	pending: Option<(PeerId, Instant)>,
+	/// Import of the target block, once its body is downloaded.
+	import: Option<WarpImport<B>>,
+	/// Whether any peer has been able to serve warp sync requests.
+	had_peers: bool,
+	/// When the last valid response was received, or the download waited for the import.
+	last_progress: Instant,
+}
+
+impl<B: BlockT> WarpSync<B> {
+	/// Create a new instance.
+	pub fn new(verifier: BoxWarpSyncVerifier<B>) -> Self {
+		WarpSync {
+			verifier,
+			phase: Phase::Proofs,
+			peers: HashSet::new(),
+			pending: None,
+			import: None,
+			had_peers: false,
+			last_progress: Instant::now(),
+		}
+	}
+
+	/// Note a new peer which is able to serve warp sync requests.
+	pub fn add_peer(&mut self, who: PeerId) {
+		self.peers.insert(who);
+		self.had_peers = true;
+	}
+
+	/// Note that a peer is no longer able to serve warp sync requests.
+	pub fn remove_peer(&mut self, who: &PeerId) {
+		self.peers.remove(who);
+		if self.pending.as_ref().map_or(false, |(peer, _)| peer == who) {
+			self.pending = None;
+		}
+	}
+
+	/// Returns `true` if the given peer is able to serve warp sync requests.
+	pub fn has_peer(&self, who: &PeerId) -> bool {
+		self.peers.contains(who)
+	}
+
+	/// Returns `true` if the warp sync can't make progress: all the peers able to serve it have
+	/// failed or disconnected, or nothing has been downloaded for `timeout`. Waiting for the
+	/// import isn't a lack of progress.
+	pub fn is_stalled(&mut self, timeout: Duration) -> bool {
+		if self.flush_state() {
+			self.last_progress = Instant::now();
+			return false
+		}
+		if let Phase::Importing = self.phase {
+			return false
+		}
+		(self.had_peers && self.peers.is_empty()) || self.last_progress.elapsed() >= timeout
+	}
+
+	/// Returns the peer serving the current request and when the request has been made.
+	pub fn pending(&self) -> Option<&(PeerId, Instant)> {
+		self.pending.as_ref()
+	}
+
+	/// Build the next request and note that it is made to the given peer.
+	///
+	/// Returns `None` if a request is already pending, if the import has not yet taken the chunks
+	/// of state downloaded so far or if there is nothing to request.
+	pub fn request(&mut self, who: &PeerId) -> Option<WarpRequest<B>> {
+		if self.pending.is_some() || self.flush_state() {
+			return None
+		}
+		let request = match &self.phase {
+			Phase::Proofs => {
+				let (block, request) = self.verifier.next_request();
+				WarpRequest::FinalityProof(FinalityProofRequest {
+					id: 0,
+					block,
+					request,
+				})
+			}
+			Phase::Body(header, _) => WarpRequest::Block(BlockRequest {
+				id: 0,
+				fields: BlockAttributes::BODY,
+				from: message::FromBlock::Hash(header.hash()),
+				to: None,
+				direction: message::Direction::Ascending,
+				max: Some(1),
+			}),
+			Phase::State(download) => WarpRequest::State(StateRequest {
+				id: 0,
+				block: download.header.hash(),
+				child_storage_key: download.current_child.as_ref().map(|(key, _)| key.clone()),
+				start: download.next_key.clone(),
+			}),
+			Phase::Importing => return None,
+		};
+		self.pending = Some((who.clone(), Instant::now()));
+		Some(request)
+	}
+
+	/// Handle a response to a proof of finality request.
+	pub fn on_finality_proof(&mut self, who: &PeerId, proof: Option<Vec<u8>>) -> Result<(), String> {
+		if !self.take_pending(who) {
+			return Ok(())
+		}
+		match self.phase {
+			Phase::Proofs => {},
+			_ => return Ok(()),
+		}
+		let proof = match proof {
+			Some(proof) => proof,
+			None => {
+				debug!(target: "sync", "Peer {} is unable to provide warp sync proofs", who);
+				self.peers.remove(who);
+				return Ok(())
+			}
+		};
+
+		let progress = self.verifier.verify(&proof)?;
+		self.last_progress = Instant::now();
+		match progress {
+			WarpSyncProgress::Partial(header) => {
+				trace!(target: "sync", "Warp sync proven finality up to #{} ({})", header.number(), header.hash());
+			}
+			WarpSyncProgress::Complete(header, justification) => {
+				info!("Warp sync target is #{} ({}), downloading its state", header.number(), header.hash());
+				self.phase = Phase::Body(header, justification);
+			}
+		}
+		Ok(())
+	}
+
+	/// Handle a response to a request for the body of the target block.
+	pub fn on_block_data(&mut self, who: &PeerId, blocks: Vec<BlockData<B>>) -> Result<(), String> {
+		if !self.take_pending(who) {
+			return Ok(())
+		}
+		let (header, justification) = match std::mem::replace(&mut self.phase, Phase::Proofs) {
+			Phase::Body(header, justification) => (header, justification),
+			phase => {
+				self.phase = phase;
+				return Ok(())
+			}
+		};
+
+		let hash = header.hash();
+		let body = blocks.into_iter()
+			.find(|block| block.hash == hash)
+			.and_then(|block| block.body);
+		let body = match body {
+			Some(body) => body,
+			None => {
+				debug!(target: "sync", "Peer {} is unable to provide the body of {}", who, hash);
+				self.peers.remove(who);
+				self.phase = Phase::Body(header, justification);
+				return Ok(())
+			}
+		};
+
+		let extrinsics_root = <<B::Header as HeaderT>::Hashing as HashT>::ordered_trie_root(
+			body.iter().map(|extrinsic| extrinsic.encode())
+		);
+		if extrinsics_root != *header.extrinsics_root() {
+			self.phase = Phase::Body(header, justification);
+			return Err(format!("Body of {} does not match its extrinsics root", hash))
+		}
+
+		self.last_progress = Instant::now();
+		let (chunks, received) = mpsc::sync_channel(MAX_QUEUED_CHUNKS);
+		let queued = Arc::new(AtomicUsize::new(0));
+		let taken = queued.clone();
+		self.import = Some(WarpImport {
+			header: header.clone(),
+			body: Some(body),
+			justification,
+			state: Box::new(received.into_iter().flat_map(move |chunk: Vec<StateEntry>| {
+				taken.fetch_sub(1, Ordering::SeqCst);
+				chunk.into_iter().map(Ok)
+			})),
+		});
+		self.phase = Phase::State(StateDownload {
+			header,
+			chunks,
+			queued,
+			unsent: None,
+			complete: false,
+			downloaded: 0,
+			current_child: None,
+			next_key: Vec::new(),
+			next_top_key: Vec::new(),
+		});
+		Ok(())
+	}
+
+	/// Take the import of the target block, once its body is downloaded. It must be run while
+	/// the state is downloaded, and `on_imported` called once it finishes successfully.
+	pub fn take_import(&mut self) -> Option<WarpImport<B>> {
+		self.import.take()
+	}
+
+	/// Handle a response to a state request.
+	///
+	/// The entries are handed over to the import, which is notified of the end of the state once
+	/// all of it has been downloaded.
+	pub fn on_state_data(
+		&mut self,
+		who: &PeerId,
+		response: StateResponse,
+		client: &dyn Client<B>,
+	) -> Result<(), String> {
+		if !self.take_pending(who) {
+			return Ok(())
+		}
+		let download = match &mut self.phase {
+			Phase::State(download) => download,
+			_ => return Ok(()),
+		};
+		if response.proof.is_empty() {
+			debug!(target: "sync", "Peer {} is unable to provide the state of {}", who, download.header.hash());
+			self.peers.remove(who);
+			return Ok(())
+		}
+
+		let root = match &download.current_child {
+			Some((_, root)) => *root,
+			None => *download.header.state_root(),
+		};
+		let (entries, mut complete) = client.check_state_range_proof(&root, response.proof, &download.next_key)
+			.map_err(|e| format!("Invalid state proof: {}", e))?;
+		if entries.is_empty() && !complete {
+			return Err("State proof does not contain any entry".into())
+		}
+		self.last_progress = Instant::now();
+
+		let mut chunk = Vec::with_capacity(entries.len());
+		for (key, value) in entries {
+			download.next_key = key.clone();
+			download.next_key.push(0);
+			download.downloaded += 1;
+			if let Some((storage_key, _)) = &download.current_child {
+				chunk.push(StateEntry::Child(storage_key.clone(), key, value));
+			} else if well_known_keys::is_child_storage_key(&key) {
+				// the rest of the chunk is downloaded again once the child trie is.
+				let root = B::Hash::decode(&mut &value[..])
+					.map_err(|_| format!("Invalid child trie root at {:?}", key))?;
+				download.next_top_key = std::mem::replace(&mut download.next_key, Vec::new());
+				download.current_child = Some((key, root));
+				complete = false;
+				break
+			} else {
+				chunk.push(StateEntry::Top(key, value));
+			}
+		}
+		if !chunk.is_empty() {
+			download.send(chunk);
+		}
+		trace!(
+			target: "sync",
+			"Downloaded {} state entries of {}",
+			download.downloaded,
+			download.header.hash(),
+		);
+
+		if !complete {
+			return Ok(())
+		}
+		if download.current_child.take().is_some() {
+			download.next_key = std::mem::replace(&mut download.next_top_key, Vec::new());
+			return Ok(())
+		}
+
+		// the import is notified of the end of the state once it has taken all of it.
+		download.complete = true;
+		Ok(())
+	}
+
+	/// Notify the verifier that the target block has been imported along with its state.
+	pub fn on_imported(&mut self, header: &B::Header) -> Result<(), String> {
+		self.verifier.on_target_imported(header)
+	}
+
+	/// Hand the downloaded chunks over to the import, ending its state once the whole state has
+	/// been downloaded. Returns `true` if the import has not yet taken all the chunks.
+	fn flush_state(&mut self) -> bool {
+		let download = match &mut self.phase {
+			Phase::State(download) => download,
+			_ => return false,
+		};
+		if !download.flush() {
+			return true
+		}
+		if download.complete {
+			info!("Downloaded {} state entries of {}", download.downloaded, download.header.hash());
+			// dropping the download ends the state of the import.
+			self.phase = Phase::Importing;
+		}
+		false
+	}
+
+	fn take_pending(&mut self, who: &PeerId) -> bool {
+		if self.pending.as_ref().map_or(false, |(peer, _)| peer == who) {
+			self.pending = None;
+			true
+		} else {
+			false
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config::WarpSyncVerifier;
+	use primitives::storage::StorageKey;
+	use sr_primitives::generic::BlockId;
+	use test_client::{DefaultTestClientBuilderExt, TestClientBuilderExt};
+	use test_client::runtime::{Block, Header};
+
+	struct TestVerifier {
+		target: Header,
+	}
+
+	impl WarpSyncVerifier<Block> for TestVerifier {
+		fn next_request(&mut self) -> (<Block as BlockT>::Hash, Vec<u8>) {
+			(self.target.hash(), Vec::new())
+		}
+
+		fn verify(&mut self, proof: &[u8]) -> Result<WarpSyncProgress<Block>, String> {
+			if proof == b"bad" {
+				return Err("bad proof".into())
+			}
+			Ok(WarpSyncProgress::Complete(self.target.clone(), b"justification".to_vec()))
+		}
+
+		fn on_target_imported(&mut self, _: &Header) -> Result<(), String> {
+			Ok(())
+		}
+	}
+
+	fn warp_sync(client: &test_client::TestClient) -> (WarpSync<Block>, Header) {
+		let target = client.header(&BlockId::Number(0)).unwrap().unwrap();
+		(WarpSync::new(Box::new(TestVerifier { target: target.clone() })), target)
+	}
+
+	/// Download the proof and the body of the target from the given peer.
+	fn download_body(warp: &mut WarpSync<Block>, target: &Header, who: &PeerId) {
+		match warp.request(who) {
+			Some(WarpRequest::FinalityProof(_)) => {},
+			request => panic!("Unexpected request: {:?}", request),
+		}
+		warp.on_finality_proof(who, Some(b"good".to_vec())).unwrap();
+		match warp.request(who) {
+			Some(WarpRequest::Block(request)) =>
+				assert_eq!(request.from, message::FromBlock::Hash(target.hash())),
+			request => panic!("Unexpected request: {:?}", request),
+		}
+		warp.on_block_data(who, vec![BlockData::<Block> {
+			hash: target.hash(),
+			header: None,
+			body: Some(Vec::new()),
+			receipt: None,
+			message_queue: None,
+			justification: None,
+		}]).unwrap();
+	}
+
+	/// Answer the next state request made to the given peer. Returns `false` if there is none.
+	fn serve_state(
+		warp: &mut WarpSync<Block>,
+		client: &test_client::TestClient,
+		who: &PeerId,
+	) -> bool {
+		let request = match warp.request(who) {
+			Some(WarpRequest::State(request)) => request,
+			None => return false,
+			request => panic!("Unexpected request: {:?}", request),
+		};
+		let proof = client.state_range_proof(
+			&BlockId::Hash(request.block),
+			request.child_storage_key.as_ref().map(|key| &key[..]),
+			&request.start,
+			64,
+		).unwrap();
+		warp.on_state_data(who, StateResponse { id: 0, proof }, client).unwrap();
+		true
+	}
+
+	/// Download the whole state from the given peer, returning the entries read by the import.
+	fn download_state(
+		warp: &mut WarpSync<Block>,
+		client: &test_client::TestClient,
+		who: &PeerId,
+	) -> Vec<StateEntry> {
+		let state = warp.take_import().unwrap().state;
+		let importer = std::thread::spawn(move || state.collect::<Result<Vec<_>, _>>());
+		let mut chunks = 0;
+		loop {
+			if let Phase::Importing = warp.phase {
+				break
+			}
+			if serve_state(warp, client, who) {
+				chunks += 1;
+			} else {
+				std::thread::sleep(std::time::Duration::from_millis(1));
+			}
+		}
+		assert!(chunks > 1);
+		importer.join().unwrap().unwrap()
+	}
+
+	#[test]
+	fn bad_proof_is_rejected() {
+		let client = test_client::new();
+		let (mut warp, _) = warp_sync(&client);
+		let peer = PeerId::random();
+		warp.add_peer(peer.clone());
+
+		assert!(warp.request(&peer).is_some());
+		assert!(warp.on_finality_proof(&peer, Some(b"bad".to_vec())).is_err());
+		assert!(warp.take_import().is_none());
+	}
+
+	#[test]
+	fn state_download_resumes_with_another_peer() {
+		let client = test_client::new();
+		let (mut warp, target) = warp_sync(&client);
+		let (first, second) = (PeerId::random(), PeerId::random());
+		warp.add_peer(first.clone());
+		warp.add_peer(second.clone());
+		download_body(&mut warp, &target, &first);
+		let mut import = warp.take_import().unwrap();
+		assert!(serve_state(&mut warp, &client, &first));
+		assert!(import.state.next().is_some());
+
+		let start = match warp.request(&first) {
+			Some(WarpRequest::State(request)) => request.start,
+			request => panic!("Unexpected request: {:?}", request),
+		};
+		assert!(!start.is_empty());
+		assert!(warp.request(&second).is_none());
+
+		warp.remove_peer(&first);
+		assert!(warp.pending().is_none());
+		assert!(!warp.has_peer(&first));
+		match warp.request(&second) {
+			Some(WarpRequest::State(request)) => assert_eq!(request.start, start),
+			request => panic!("Unexpected request: {:?}", request),
+		}
+	}
+
+	#[test]
+	fn next_chunk_waits_for_the_import() {
+		let client = test_client::new();
+		let (mut warp, target) = warp_sync(&client);
+		let peer = PeerId::random();
+		warp.add_peer(peer.clone());
+		download_body(&mut warp, &target, &peer);
+		let mut import = warp.take_import().unwrap();
+		assert!(serve_state(&mut warp, &client, &peer));
+
+		assert!(warp.request(&peer).is_none());
+		assert!(warp.pending().is_none());
+
+		assert!(import.state.next().is_some());
+		match warp.request(&peer) {
+			Some(WarpRequest::State(request)) => assert!(!request.start.is_empty()),
+			request => panic!("Unexpected request: {:?}", request),
+		}
+	}
+
+	#[test]
+	fn downloads_whole_state_in_order() {
+		let client = test_client::new();
+		let (mut warp, target) = warp_sync(&client);
+		let peer = PeerId::random();
+		warp.add_peer(peer.clone());
+		download_body(&mut warp, &target, &peer);
+
+		let import = warp.import.as_ref().unwrap();
+		assert_eq!(import.header, target);
+		assert_eq!(import.justification, b"justification".to_vec());
+
+		let entries = download_state(&mut warp, &client, &peer);
+		let keys = client.storage_keys(&BlockId::Number(0), &StorageKey(Vec::new())).unwrap();
+		assert_eq!(entries.len(), keys.len());
+		for (entry, key) in entries.into_iter().zip(keys) {
+			match entry {
+				StateEntry::Top(k, v) => {
+					assert_eq!(k, key.0);
+					assert_eq!(Some(v), client.storage(&BlockId::Number(0), &key).unwrap().map(|v| v.0));
+				}
+				entry => panic!("Unexpected entry: {:?}", entry),
+			}
+		}
+	}
+
+	#[test]
+	fn downloads_child_tries_in_place_of_their_root() {
+		let client = test_client::TestClientBuilder::new()
+			.add_child_storage("child", "a", vec![1; 40])
+			.add_child_storage("child", "b", vec![2; 40])
+			.add_child_storage("child", "c", vec![3; 40])
+			.build();
+		let (mut warp, target) = warp_sync(&client);
+		let peer = PeerId::random();
+		warp.add_peer(peer.clone());
+		download_body(&mut warp, &target, &peer);
+
+		let entries = download_state(&mut warp, &client, &peer);
+		let storage_key: Vec<u8> = well_known_keys::CHILD_STORAGE_KEY_PREFIX.iter()
+			.chain(b"child")
+			.cloned()
+			.collect();
+		let child = |key: &[u8], value| StateEntry::Child(storage_key.clone(), key.to_vec(), value);
+		let mut expected = Vec::new();
+		for key in client.storage_keys(&BlockId::Number(0), &StorageKey(Vec::new())).unwrap() {
+			if key.0 == storage_key {
+				expected.push(child(b"a", vec![1; 40]));
+				expected.push(child(b"b", vec![2; 40]));
+				expected.push(child(b"c", vec![3; 40]));
+			} else {
+				let value = client.storage(&BlockId::Number(0), &key).unwrap().unwrap();
+				expected.push(StateEntry::Top(key.0, value.0));
+			}
+		}
+		assert_eq!(entries, expected);
+	}
+
+	#[test]
+	fn stalls_once_all_peers_failed() {
+		let client = test_client::new();
+		let (mut warp, _) = warp_sync(&client);
+		let timeout = std::time::Duration::from_secs(60);
+		// peers may still connect.
+		assert!(!warp.is_stalled(timeout));
+
+		let peer = PeerId::random();
+		warp.add_peer(peer.clone());
+		assert!(!warp.is_stalled(timeout));
+		assert!(warp.request(&peer).is_some());
+		warp.on_finality_proof(&peer, None).unwrap();
+		assert!(!warp.has_peer(&peer));
+		assert!(warp.is_stalled(timeout));
+	}
+
+	#[test]
+	fn stalls_without_progress() {
+		let client = test_client::new();
+		let (mut warp, target) = warp_sync(&client);
+		let peer = PeerId::random();
+		warp.add_peer(peer.clone());
+		assert!(warp.is_stalled(std::time::Duration::from_secs(0)));
+
+		// waiting for the import is not a lack of progress.
+		download_body(&mut warp, &target, &peer);
+		let _import = warp.take_import().unwrap();
+		assert!(serve_state(&mut warp, &client, &peer));
+		assert!(!warp.is_stalled(std::time::Duration::from_secs(0)));
+	}
+}
@@ -43,7 +43,7 @@ use sr_primitives::{traits::{Block as BlockT, NumberFor}, ConsensusEngineId};
 use crate::{behaviour::{Behaviour, BehaviourOut}, config::{parse_str_addr, parse_addr}};
 use crate::{NetworkState, NetworkStateNotConnectedPeer, NetworkStatePeer};
 use crate::{transport, config::NodeKeyConfig, config::NonReservedPeerMode};
-use crate::config::{Params, TransportConfig};
+use crate::config::{Params, SyncMode, TransportConfig};
 use crate::error::Error;
 use crate::protocol::{self, Protocol, Context, CustomMessageOutcome, PeerInfo};
 use crate::protocol::consensus_gossip::{ConsensusGossip, MessageRecipient as GossipMessageRecipient};
@@ -165,6 +165,16 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 		let local_peer_id = local_public.clone().into_peer_id();
 		info!(target: "sub-libp2p", "Local node identity is: {}", local_peer_id.to_base58());
 
+		let warp_sync_verifier = match params.network_config.sync_mode {
+			SyncMode::Full => None,
+			SyncMode::Fast => {
+				if params.warp_sync_verifier.is_none() {
+					warn!(target: "sync", "Fast sync is not supported by the chain, falling back to full sync");
+				}
+				params.warp_sync_verifier
+			}
+		};
+
 		let num_connected = Arc::new(AtomicUsize::new(0));
 		let is_major_syncing = Arc::new(AtomicBool::new(false));
 		let (protocol, peerset_handle) = Protocol::new(
@@ -176,6 +186,7 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 			params.transaction_pool,
 			params.finality_proof_provider,
 			params.finality_proof_request_builder,
+			warp_sync_verifier,
 			params.protocol_id,
 			peerset_config,
 		)?;
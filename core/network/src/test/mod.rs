@@ -25,6 +25,7 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::config::build_multiaddr;
+use codec::{Decode, Encode};
 use log::trace;
 use crate::chain::FinalityProofProvider;
 use client::{self, ClientInfo, BlockchainEvents, BlockImportNotification, FinalityNotifications, FinalityNotification};
@@ -43,6 +44,7 @@ use futures::prelude::*;
 use futures03::{StreamExt as _, TryStreamExt as _};
 use crate::{NetworkWorker, NetworkService, config::ProtocolId};
 use crate::config::{NetworkConfiguration, TransportConfig, BoxFinalityProofRequestBuilder};
+use crate::config::{BoxWarpSyncVerifier, WarpSyncProgress, WarpSyncVerifier};
 use libp2p::PeerId;
 use parking_lot::Mutex;
 use primitives::{H256, Blake2Hasher};
@@ -448,6 +450,11 @@ pub trait TestNetFactory: Sized {
 		None
 	}
 
+	/// Get warp sync verifier for a fresh full client (if it should warp sync).
+	fn make_warp_sync_verifier(&self, _client: PeersClient) -> Option<BoxWarpSyncVerifier<Block>> {
+		None
+	}
+
 	fn default_config() -> ProtocolConfig {
 		ProtocolConfig::default()
 	}
@@ -493,6 +500,7 @@ pub trait TestNetFactory: Sized {
 			chain: client.clone(),
 			finality_proof_provider: self.make_finality_proof_provider(PeersClient::Full(client.clone())),
 			finality_proof_request_builder,
+			warp_sync_verifier: self.make_warp_sync_verifier(PeersClient::Full(client.clone())),
 			on_demand: None,
 			transaction_pool: Arc::new(EmptyTransactionPool),
 			protocol_id: ProtocolId::from(&b"test-protocol-name"[..]),
@@ -553,6 +561,7 @@ pub trait TestNetFactory: Sized {
 			chain: client.clone(),
 			finality_proof_provider: self.make_finality_proof_provider(PeersClient::Light(client.clone())),
 			finality_proof_request_builder,
+			warp_sync_verifier: None,
 			on_demand: None,
 			transaction_pool: Arc::new(EmptyTransactionPool),
 			protocol_id: ProtocolId::from(&b"test-protocol-name"[..]),
@@ -724,3 +733,74 @@ impl TestNetFactory for JustificationTestNet {
 		(client.as_block_import(), Some(Box::new(ForceFinalized(client))), None, None, Default::default())
 	}
 }
+
+/// Proves the last finalized block along with its justification to warp syncing peers.
+pub struct LastFinalizedProofProvider(PeersClient);
+
+impl FinalityProofProvider<Block> for LastFinalizedProofProvider {
+	fn prove_finality(&self, _for_block: H256, _request: &[u8]) -> ClientResult<Option<Vec<u8>>> {
+		let finalized = BlockId::Hash(self.0.info().chain.finalized_hash);
+		let header = self.0.header(&finalized)?;
+		let justification = self.0.justification(&finalized)?;
+		Ok(header.and_then(|header| justification.map(|justification| (header, justification).encode())))
+	}
+}
+
+/// Trusts the block proven by `LastFinalizedProofProvider` as the warp sync target.
+pub struct LastFinalizedVerifier(H256);
+
+impl WarpSyncVerifier<Block> for LastFinalizedVerifier {
+	fn next_request(&mut self) -> (H256, Vec<u8>) {
+		(self.0, Vec::new())
+	}
+
+	fn verify(&mut self, proof: &[u8]) -> Result<WarpSyncProgress<Block>, String> {
+		let (header, justification) = <(<Block as BlockT>::Header, Justification)>::decode(&mut &proof[..])
+			.map_err(|_| String::from("Invalid proof"))?;
+		Ok(WarpSyncProgress::Complete(header, justification))
+	}
+
+	fn on_target_imported(&mut self, _header: &<Block as BlockT>::Header) -> Result<(), String> {
+		Ok(())
+	}
+}
+
+/// Network where every full peer but the first one warp syncs.
+pub struct WarpSyncTestNet(TestNet);
+
+impl TestNetFactory for WarpSyncTestNet {
+	type Specialization = DummySpecialization;
+	type Verifier = PassThroughVerifier;
+	type PeerData = ();
+
+	fn from_config(config: &ProtocolConfig) -> Self {
+		WarpSyncTestNet(TestNet::from_config(config))
+	}
+
+	fn make_verifier(&self, client: PeersClient, config: &ProtocolConfig) -> Self::Verifier {
+		self.0.make_verifier(client, config)
+	}
+
+	fn peer(&mut self, i: usize) -> &mut Peer<Self::PeerData, Self::Specialization> {
+		self.0.peer(i)
+	}
+
+	fn peers(&self) -> &Vec<Peer<Self::PeerData, Self::Specialization>> {
+		self.0.peers()
+	}
+
+	fn mut_peers<F: FnOnce(&mut Vec<Peer<Self::PeerData, Self::Specialization>>)>(&mut self, closure: F) {
+		self.0.mut_peers(closure)
+	}
+
+	fn make_finality_proof_provider(&self, client: PeersClient) -> Option<Arc<dyn FinalityProofProvider<Block>>> {
+		Some(Arc::new(LastFinalizedProofProvider(client)))
+	}
+
+	fn make_warp_sync_verifier(&self, client: PeersClient) -> Option<BoxWarpSyncVerifier<Block>> {
+		if self.peers().is_empty() {
+			return None
+		}
+		Some(Box::new(LastFinalizedVerifier(client.info().chain.genesis_hash)))
+	}
+}
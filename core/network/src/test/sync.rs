@@ -18,6 +18,7 @@ use client::{backend::Backend, blockchain::HeaderBackend};
 use crate::config::Roles;
 use consensus::BlockOrigin;
 use futures03::TryFutureExt as _;
+use primitives::storage::{StorageKey, well_known_keys};
 use std::time::Duration;
 use tokio::runtime::current_thread;
 use super::*;
@@ -527,3 +528,48 @@ fn light_peer_imports_header_from_announce() {
 	let known_stale_hash = net.peer(0).push_blocks_at(BlockId::Number(0), 1, true);
 	import_with_announce(&mut net, &mut runtime, known_stale_hash);
 }
+
+#[test]
+fn warp_syncs_to_last_finalized_block() {
+	let _ = ::env_logger::try_init();
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let mut net = WarpSyncTestNet::new(1);
+
+	// peer 0 finalizes block #15 with a justification and builds on top of it
+	net.peer(0).push_blocks(20, false);
+	net.peer(0).client().finalize_block(BlockId::Number(15), Some(Vec::new()), true).unwrap();
+	let target = net.peer(0).client().header(&BlockId::Number(15)).unwrap().unwrap();
+
+	// a fresh peer warps to block #15, then imports the blocks after it
+	net.add_full_peer(&Default::default());
+	net.block_until_sync(&mut runtime);
+
+	let client = net.peer(1).client().as_full().unwrap();
+	assert_eq!(client.info().chain.best_hash, net.peer(0).client().info().chain.best_hash);
+	assert_eq!(client.info().chain.finalized_hash, target.hash());
+	assert!(client.header(&BlockId::Number(1)).unwrap().is_none());
+	assert_eq!(
+		client.storage(&BlockId::Hash(target.hash()), &StorageKey(well_known_keys::CODE.to_vec())).unwrap(),
+		net.peer(0).client().as_full().unwrap()
+			.storage(&BlockId::Hash(target.hash()), &StorageKey(well_known_keys::CODE.to_vec())).unwrap(),
+	);
+}
+
+#[test]
+fn falls_back_to_full_sync_without_warp_sync_proofs() {
+	let _ = ::env_logger::try_init();
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let mut net = WarpSyncTestNet::new(1);
+
+	// without a justification, peer 0 is unable to prove the finality of block #15
+	net.peer(0).push_blocks(20, false);
+	net.peer(0).client().finalize_block(BlockId::Number(15), None, true).unwrap();
+
+	// a fresh peer gives up on the warp sync and imports all the blocks
+	net.add_full_peer(&Default::default());
+	net.block_until_sync(&mut runtime);
+
+	let client = net.peer(1).client().as_full().unwrap();
+	assert_eq!(client.info().chain.best_hash, net.peer(0).client().info().chain.best_hash);
+	assert!(client.header(&BlockId::Number(1)).unwrap().is_some());
+}
@@ -23,17 +23,19 @@ use std::{
 
 use client::backend::OffchainStorage;
 use futures::{StreamExt as _, Future, FutureExt as _, future, channel::mpsc};
-use log::{info, debug, warn, error};
+use log::{info, debug, warn};
 use network::{PeerId, Multiaddr, NetworkStateInfo};
 use codec::{Encode, Decode};
+use rand::{RngCore, rngs::OsRng};
 use primitives::offchain::{
 	Externalities as OffchainExt, HttpRequestId, Timestamp, HttpRequestStatus, HttpError,
-	OpaqueNetworkState, OpaquePeerId, OpaqueMultiaddr, StorageKind,
+	OpaqueNetworkState, OpaquePeerId, OpaqueMultiaddr, StorageKind, STORAGE_PREFIX,
 };
 use sr_primitives::{generic::BlockId, traits::{self, Extrinsic}};
 use transaction_pool::txpool::{Pool, ChainApi};
 
 mod http;
+pub(crate) mod local;
 mod timestamp;
 
 /// A message between the offchain extension and the processing thread.
@@ -47,6 +49,8 @@ enum ExtMessage {
 pub(crate) struct Api<Storage, Block: traits::Block> {
 	sender: mpsc::UnboundedSender<ExtMessage>,
 	db: Storage,
+	/// Fork-aware view of the `LOCAL` storage at the block the worker is running at.
+	local: local::ForkAwareStorage<Storage>,
 	network_state: Arc<dyn NetworkStateInfo + Send + Sync>,
 	_at: BlockId<Block>,
 	/// Is this node a potential validator?
@@ -55,17 +59,6 @@ pub(crate) struct Api<Storage, Block: traits::Block> {
 	http: http::HttpApi,
 }
 
-fn unavailable_yet<R: Default>(name: &str) -> R {
-	error!(
-		"The {:?} API is not available for offchain workers yet. Follow \
-		https://github.com/paritytech/substrate/issues/1458 for details", name
-	);
-	Default::default()
-}
-
-const LOCAL_DB: &str = "LOCAL (fork-aware) DB";
-const STORAGE_PREFIX: &[u8] = b"storage";
-
 impl<Storage, Block> OffchainExt for Api<Storage, Block>
 where
 	Storage: OffchainStorage,
@@ -101,13 +94,15 @@ where
 	}
 
 	fn random_seed(&mut self) -> [u8; 32] {
-		unavailable_yet("random_seed")
+		let mut seed = [0u8; 32];
+		OsRng.fill_bytes(&mut seed);
+		seed
 	}
 
 	fn local_storage_set(&mut self, kind: StorageKind, key: &[u8], value: &[u8]) {
 		match kind {
 			StorageKind::PERSISTENT => self.db.set(STORAGE_PREFIX, key, value),
-			StorageKind::LOCAL => unavailable_yet(LOCAL_DB),
+			StorageKind::LOCAL => self.local.set(key, value),
 		}
 	}
 
@@ -122,14 +117,14 @@ where
 			StorageKind::PERSISTENT => {
 				self.db.compare_and_set(STORAGE_PREFIX, key, old_value, new_value)
 			},
-			StorageKind::LOCAL => unavailable_yet(LOCAL_DB),
+			StorageKind::LOCAL => self.local.compare_and_set(key, old_value, new_value),
 		}
 	}
 
 	fn local_storage_get(&mut self, kind: StorageKind, key: &[u8]) -> Option<Vec<u8>> {
 		match kind {
 			StorageKind::PERSISTENT => self.db.get(STORAGE_PREFIX, key),
-			StorageKind::LOCAL => unavailable_yet(LOCAL_DB),
+			StorageKind::LOCAL => self.local.get(key),
 		}
 	}
 
@@ -266,6 +261,7 @@ impl<A: ChainApi> AsyncApi<A> {
 	pub fn new<S: OffchainStorage>(
 		transaction_pool: Arc<Pool<A>>,
 		db: S,
+		local: local::ForkAwareStorage<S>,
 		at: BlockId<A::Block>,
 		network_state: Arc<dyn NetworkStateInfo + Send + Sync>,
 		is_validator: bool,
@@ -277,6 +273,7 @@ impl<A: ChainApi> AsyncApi<A> {
 		let api = Api {
 			sender,
 			db,
+			local,
 			network_state,
 			_at: at,
 			is_validator,
@@ -358,9 +355,11 @@ mod tests {
 		);
 
 		let mock = Arc::new(MockNetworkStateInfo());
+		let local = local::ForkAwareStorage::new(db.clone(), 1, vec![vec![1; 32]]);
 		AsyncApi::new(
 			pool,
 			db,
+			local,
 			BlockId::Number(Zero::zero()),
 			mock,
 			false,
@@ -401,6 +400,19 @@ mod tests {
 		assert!(new_now.unix_millis() - 100 >= now.unix_millis());
 	}
 
+	#[test]
+	fn should_get_random_seed() {
+		// given
+		let mut api = offchain_api().0;
+
+		// when
+		let seed = api.random_seed();
+
+		// then
+		assert_ne!(seed, [0u8; 32]);
+		assert_ne!(seed, api.random_seed());
+	}
+
 	#[test]
 	fn should_set_and_get_local_storage() {
 		// given
@@ -448,6 +460,24 @@ mod tests {
 		assert_eq!(api.local_storage_get(kind, key), Some(b"value".to_vec()));
 	}
 
+	#[test]
+	fn should_set_and_get_fork_aware_local_storage() {
+		// given
+		let kind = StorageKind::LOCAL;
+		let mut api = offchain_api().0;
+		let key = b"test";
+
+		// when
+		assert_eq!(api.local_storage_get(kind, key), None);
+		api.local_storage_set(kind, key, b"value");
+
+		// then
+		assert_eq!(api.local_storage_get(kind, key), Some(b"value".to_vec()));
+		assert_eq!(api.local_storage_get(StorageKind::PERSISTENT, key), None);
+		assert_eq!(api.local_storage_compare_and_set(kind, key, Some(b"value"), b"xxx"), true);
+		assert_eq!(api.local_storage_get(kind, key), Some(b"xxx".to_vec()));
+	}
+
 	#[test]
 	fn should_convert_network_states() {
 		// given
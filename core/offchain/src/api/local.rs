@@ -0,0 +1,298 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Fork-aware (`LOCAL`) offchain storage.
+//!
+//! Values written by an offchain worker running at some block are kept in an overlay
+//! keyed by the hash of that block. A worker sees the values written at its own block
+//! and at any of its non-finalized ancestors, falling back to the values committed
+//! by finalized blocks. When a block gets finalized its overlay is merged into the
+//! finalized layer, while the overlays of blocks on abandoned forks are discarded.
+//! Writes of workers running at blocks at or below the last finalized block, which
+//! has been processed already, are dropped.
+
+use client::backend::OffchainStorage;
+use codec::{Codec, Encode, Decode};
+
+/// Per-block overlays, keyed by block hash followed by the item key.
+const OVERLAY_PREFIX: &[u8] = b"local_overlay";
+/// Values committed by finalized blocks.
+const FINALIZED_PREFIX: &[u8] = b"local_finalized";
+/// Keys written in each block overlay, keyed by block hash.
+const JOURNAL_PREFIX: &[u8] = b"local_journal";
+/// Number of the last finalized block, followed by the blocks that have a non-empty
+/// overlay and are awaiting finalization.
+const PENDING_PREFIX: &[u8] = b"local_pending";
+const PENDING_KEY: &[u8] = b"blocks";
+
+/// A view of the `LOCAL` offchain storage at given block.
+#[derive(Clone)]
+pub(crate) struct ForkAwareStorage<Storage> {
+	db: Storage,
+	/// Number of the block the worker is running at.
+	number: u64,
+	/// Encoded hashes of the block the worker is running at and of its
+	/// non-finalized ancestors, starting with the block itself.
+	///
+	/// Empty if the block is already finalized, in which case writes
+	/// go straight to the finalized layer.
+	branch: Vec<Vec<u8>>,
+}
+
+impl<Storage: OffchainStorage> ForkAwareStorage<Storage> {
+	/// Create a view at block `number` with given branch of non-finalized blocks.
+	pub fn new(db: Storage, number: u64, branch: Vec<Vec<u8>>) -> Self {
+		ForkAwareStorage { db, number, branch }
+	}
+
+	/// Retrieve the value visible at this block.
+	pub fn get(&self, key: &[u8]) -> Option<Vec<u8>> {
+		self.branch.iter()
+			.find_map(|hash| self.db.get(OVERLAY_PREFIX, &overlay_key(hash, key)))
+			.or_else(|| self.db.get(FINALIZED_PREFIX, key))
+	}
+
+	/// Set the value at this block.
+	pub fn set(&mut self, key: &[u8], value: &[u8]) {
+		self.write(key, value);
+	}
+
+	/// Replace the value visible at this block if `old_value` matches the current one.
+	///
+	/// Only one worker runs at given block, so the overlay of that block is never
+	/// written concurrently. Returns `false` if the block has been finalized since
+	/// the worker started, in which case the write is dropped.
+	pub fn compare_and_set(&mut self, key: &[u8], old_value: Option<&[u8]>, new_value: &[u8]) -> bool {
+		if self.branch.is_empty() {
+			return self.is_writable(last_finalized(&self.db))
+				&& self.db.compare_and_set(FINALIZED_PREFIX, key, old_value, new_value);
+		}
+
+		if self.get(key).as_ref().map(|v| &v[..]) != old_value {
+			return false;
+		}
+		self.write(key, new_value)
+	}
+
+	/// Whether writes at this block are kept, given the number of the last finalized block.
+	///
+	/// The overlay of a block is processed when that block gets finalized, so anything
+	/// written there afterwards would be committed at a later finalization, on top of
+	/// newer values. Blocks finalized at import write to the finalized layer as long as
+	/// they are the last finalized block.
+	fn is_writable(&self, finalized: u64) -> bool {
+		self.number > finalized || (self.number == finalized && self.branch.is_empty())
+	}
+
+	/// Write the value at this block, returning `false` if the write was dropped.
+	fn write(&mut self, key: &[u8], value: &[u8]) -> bool {
+		let hash = match self.branch.first() {
+			Some(hash) => hash.clone(),
+			None => {
+				if !self.is_writable(last_finalized(&self.db)) {
+					return false;
+				}
+				self.db.set(FINALIZED_PREFIX, key, value);
+				return true;
+			},
+		};
+
+		// register the overlay atomically with checking that the block is not finalized yet
+		let number = self.number;
+		let mut is_writable = false;
+		update(&mut self.db, PENDING_PREFIX, PENDING_KEY, |(finalized, pending): &mut Pending| {
+			is_writable = number > *finalized;
+			if is_writable && !pending.iter().any(|(_, h)| *h == hash) {
+				pending.push((number, hash.clone()));
+			}
+		});
+		if !is_writable {
+			return false;
+		}
+
+		update(&mut self.db, JOURNAL_PREFIX, &hash, |keys: &mut Vec<Vec<u8>>| {
+			if !keys.iter().any(|k| &k[..] == key) {
+				keys.push(key.to_vec());
+			}
+		});
+		let overlay_key = overlay_key(&hash, key);
+		self.db.set(OVERLAY_PREFIX, &overlay_key, value);
+
+		// the block could have been finalized in the meantime, before the value was in place
+		if last_finalized(&self.db) >= number {
+			self.db.remove(OVERLAY_PREFIX, &overlay_key);
+			self.db.remove(JOURNAL_PREFIX, &hash);
+			return false;
+		}
+		true
+	}
+}
+
+/// Number of the last finalized block and the blocks with pending overlays.
+type Pending = (u64, Vec<(u64, Vec<u8>)>);
+
+/// Returns the number of the last finalized block processed by `on_finalized`.
+fn last_finalized<Storage: OffchainStorage>(db: &Storage) -> u64 {
+	db.get(PENDING_PREFIX, PENDING_KEY)
+		.and_then(|v| Pending::decode(&mut &v[..]).ok())
+		.map_or(0, |(finalized, _)| finalized)
+}
+
+/// Commit overlays of blocks up to `finalized` and discard the abandoned ones.
+///
+/// `canonical` should return the encoded hash of the finalized block at given height.
+/// Overlays are processed in ascending block order, so values written by later
+/// blocks take precedence.
+pub(crate) fn on_finalized<Storage: OffchainStorage>(
+	db: &mut Storage,
+	finalized: u64,
+	canonical: impl Fn(u64) -> Option<Vec<u8>>,
+) {
+	let mut to_process = Vec::new();
+	update(db, PENDING_PREFIX, PENDING_KEY, |(last_finalized, pending): &mut Pending| {
+		*last_finalized = (*last_finalized).max(finalized);
+		let (done, left): (Vec<_>, Vec<_>) = pending.drain(..).partition(|(number, _)| *number <= finalized);
+		to_process = done;
+		*pending = left;
+	});
+	to_process.sort_by_key(|(number, _)| *number);
+
+	for (number, hash) in to_process {
+		let is_canonical = canonical(number).as_ref() == Some(&hash);
+		let keys: Vec<Vec<u8>> = db.get(JOURNAL_PREFIX, &hash)
+			.and_then(|v| Decode::decode(&mut &v[..]).ok())
+			.unwrap_or_default();
+
+		for key in keys {
+			let overlay_key = overlay_key(&hash, &key);
+			if is_canonical {
+				if let Some(value) = db.get(OVERLAY_PREFIX, &overlay_key) {
+					db.set(FINALIZED_PREFIX, &key, &value);
+				}
+			}
+			db.remove(OVERLAY_PREFIX, &overlay_key);
+		}
+		db.remove(JOURNAL_PREFIX, &hash);
+	}
+}
+
+fn overlay_key(hash: &[u8], key: &[u8]) -> Vec<u8> {
+	hash.iter().chain(key).cloned().collect()
+}
+
+/// Atomically update a SCALE-encoded value stored under given key.
+fn update<Storage: OffchainStorage, T: Codec + Default>(
+	db: &mut Storage,
+	prefix: &[u8],
+	key: &[u8],
+	mut f: impl FnMut(&mut T),
+) {
+	loop {
+		let old = db.get(prefix, key);
+		let mut value = old.as_ref()
+			.and_then(|v| T::decode(&mut &v[..]).ok())
+			.unwrap_or_default();
+		f(&mut value);
+		if db.compare_and_set(prefix, key, old.as_ref().map(|v| &v[..]), &value.encode()) {
+			return;
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use client_db::offchain::LocalStorage;
+
+	fn hash(n: u8) -> Vec<u8> {
+		vec![n; 32]
+	}
+
+	#[test]
+	fn should_see_values_of_ancestors_but_not_siblings() {
+		let db = LocalStorage::new_test();
+		let mut a1 = ForkAwareStorage::new(db.clone(), 1, vec![hash(1)]);
+		let a2 = ForkAwareStorage::new(db.clone(), 2, vec![hash(2), hash(1)]);
+		let b1 = ForkAwareStorage::new(db.clone(), 1, vec![hash(11)]);
+
+		a1.set(b"key", b"a1");
+
+		assert_eq!(a1.get(b"key"), Some(b"a1".to_vec()));
+		assert_eq!(a2.get(b"key"), Some(b"a1".to_vec()));
+		assert_eq!(b1.get(b"key"), None);
+	}
+
+	#[test]
+	fn should_compare_and_set_on_top_of_ancestors() {
+		let db = LocalStorage::new_test();
+		let mut a1 = ForkAwareStorage::new(db.clone(), 1, vec![hash(1)]);
+		let mut a2 = ForkAwareStorage::new(db.clone(), 2, vec![hash(2), hash(1)]);
+
+		a1.set(b"key", b"a1");
+
+		assert!(!a2.compare_and_set(b"key", None, b"a2"));
+		assert!(a2.compare_and_set(b"key", Some(b"a1"), b"a2"));
+		assert_eq!(a2.get(b"key"), Some(b"a2".to_vec()));
+		assert_eq!(a1.get(b"key"), Some(b"a1".to_vec()));
+	}
+
+	#[test]
+	fn should_drop_writes_of_blocks_finalized_in_the_meantime() {
+		let mut db = LocalStorage::new_test();
+		let mut a1 = ForkAwareStorage::new(db.clone(), 1, vec![hash(1)]);
+		let mut a2 = ForkAwareStorage::new(db.clone(), 2, vec![hash(2), hash(1)]);
+		a2.set(b"key", b"a2");
+		on_finalized(&mut db, 2, |n| Some(hash(n as u8)));
+
+		// the worker at A1 writes after A2 has been finalized
+		a1.set(b"key", b"a1");
+		assert!(!a1.compare_and_set(b"key", Some(b"a2"), b"a1"));
+		assert!(!a2.compare_and_set(b"key", Some(b"a2"), b"a2 again"));
+		on_finalized(&mut db, 3, |n| Some(hash(n as u8)));
+
+		let finalized = ForkAwareStorage::new(db.clone(), 3, vec![]);
+		assert_eq!(finalized.get(b"key"), Some(b"a2".to_vec()));
+		assert_eq!(db.get(JOURNAL_PREFIX, &hash(1)), None);
+		assert_eq!(db.get(OVERLAY_PREFIX, &overlay_key(&hash(1), b"key")), None);
+		assert_eq!(last_finalized(&db), 3);
+
+		// the last finalized block still writes to the finalized layer, older ones don't
+		assert!(ForkAwareStorage::new(db.clone(), 3, vec![]).compare_and_set(b"key", Some(b"a2"), b"a3"));
+		assert!(!ForkAwareStorage::new(db.clone(), 2, vec![]).compare_and_set(b"key", Some(b"a3"), b"a2"));
+		assert_eq!(finalized.get(b"key"), Some(b"a3".to_vec()));
+	}
+
+	#[test]
+	fn should_commit_canonical_and_discard_abandoned_overlays() {
+		let mut db = LocalStorage::new_test();
+		ForkAwareStorage::new(db.clone(), 1, vec![hash(1)]).set(b"key", b"a1");
+		ForkAwareStorage::new(db.clone(), 2, vec![hash(2), hash(1)]).set(b"key", b"a2");
+		ForkAwareStorage::new(db.clone(), 1, vec![hash(11)]).set(b"other", b"b1");
+		ForkAwareStorage::new(db.clone(), 3, vec![hash(3), hash(2), hash(1)]).set(b"key", b"a3");
+
+		on_finalized(&mut db, 2, |n| Some(hash(n as u8)));
+
+		let finalized = ForkAwareStorage::new(db.clone(), 2, vec![]);
+		assert_eq!(finalized.get(b"key"), Some(b"a2".to_vec()));
+		assert_eq!(finalized.get(b"other"), None);
+		assert_eq!(db.get(OVERLAY_PREFIX, &overlay_key(&hash(11), b"other")), None);
+		assert_eq!(db.get(JOURNAL_PREFIX, &hash(1)), None);
+
+		// the overlay of the non-finalized block is kept on top
+		let a3 = ForkAwareStorage::new(db.clone(), 3, vec![hash(3)]);
+		assert_eq!(a3.get(b"key"), Some(b"a3".to_vec()));
+	}
+}
@@ -37,35 +37,86 @@ use std::{
 	fmt,
 	marker::PhantomData,
 	sync::Arc,
+	time::Instant,
 };
 
-use client::runtime_api::ApiExt;
-use futures::future::Future;
+use client::{blockchain::HeaderBackend, runtime_api::ApiExt};
+use codec::Encode;
+use futures::{executor::ThreadPool, future::{self, Future}, task::SpawnExt as _};
 use log::{debug, warn};
 use network::NetworkStateInfo;
+use parking_lot::Mutex;
 use primitives::{offchain, ExecutionContext};
-use sr_primitives::{generic::BlockId, traits::{self, ProvideRuntimeApi}};
+use sr_primitives::{
+	generic::BlockId,
+	traits::{self, Header as _, ProvideRuntimeApi, SaturatedConversion},
+};
+use substrate_telemetry::{telemetry, SUBSTRATE_INFO};
 use transaction_pool::txpool::{Pool, ChainApi};
 
 mod api;
 
-pub mod testing;
-
 pub use offchain_primitives::OffchainWorkerApi;
 
+/// Offchain workers scheduling configuration.
+#[derive(Debug, Clone)]
+pub struct Config {
+	/// Maximal number of offchain workers running concurrently.
+	///
+	/// Workers for blocks imported while all threads are busy are queued.
+	pub max_concurrent_workers: usize,
+	/// Only run offchain workers for blocks that were the new best block when imported.
+	pub skip_non_best: bool,
+	/// Skip queued offchain workers if a block with higher number has been
+	/// imported before they got a chance to start.
+	pub skip_superseded: bool,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			max_concurrent_workers: 4,
+			skip_non_best: false,
+			skip_superseded: false,
+		}
+	}
+}
+
 /// An offchain workers manager.
 pub struct OffchainWorkers<Client, Storage, Block: traits::Block> {
 	client: Arc<Client>,
 	db: Storage,
+	config: Config,
+	/// A thread pool where the offchain workers are being run.
+	///
+	/// If it isn't possible to create one we fall back to spawning a thread per worker.
+	thread_pool: Option<ThreadPool>,
+	/// Highest block number offchain workers have been scheduled for.
+	latest_scheduled: Arc<Mutex<u64>>,
 	_block: PhantomData<Block>,
 }
 
 impl<Client, Storage, Block: traits::Block> OffchainWorkers<Client, Storage, Block> {
-	/// Creates new `OffchainWorkers`.
+	/// Creates new `OffchainWorkers` with default configuration.
 	pub fn new(client: Arc<Client>, db: Storage) -> Self {
+		Self::with_config(client, db, Config::default())
+	}
+
+	/// Creates new `OffchainWorkers` with given configuration.
+	pub fn with_config(client: Arc<Client>, db: Storage, config: Config) -> Self {
+		let thread_pool = ThreadPool::builder()
+			.name_prefix("offchain-worker-")
+			.pool_size(config.max_concurrent_workers.max(1))
+			.create()
+			.map_err(|e| warn!("Unable to create offchain workers thread pool: {:?}", e))
+			.ok();
+
 		Self {
 			client,
 			db,
+			config,
+			thread_pool,
+			latest_scheduled: Default::default(),
 			_block: PhantomData,
 		}
 	}
@@ -87,66 +138,123 @@ impl<Client, Storage, Block> OffchainWorkers<
 	Block,
 > where
 	Block: traits::Block,
-	Client: ProvideRuntimeApi + Send + Sync + 'static,
+	Client: ProvideRuntimeApi + HeaderBackend<Block> + Send + Sync + 'static,
 	Client::Api: OffchainWorkerApi<Block>,
 	Storage: client::backend::OffchainStorage + 'static,
 {
 	/// Start the offchain workers after given block.
+	///
+	/// `is_new_best` tells whether the block has become the new best block when imported
+	/// and `non_finalized_ancestors` are the hashes of its non-finalized ancestors at that
+	/// time, starting with its parent, as reported in the import notification.
 	#[must_use]
 	pub fn on_block_imported<A>(
 		&self,
-		number: &<Block::Header as traits::Header>::Number,
+		header: &Block::Header,
+		is_new_best: bool,
+		non_finalized_ancestors: &[Block::Hash],
 		pool: &Arc<Pool<A>>,
 		network_state: Arc<dyn NetworkStateInfo + Send + Sync>,
 		is_validator: bool,
 	) -> impl Future<Output = ()> where A: ChainApi<Block=Block> + 'static {
+		let at = BlockId::hash(header.hash());
+		if self.config.skip_non_best && !is_new_best {
+			debug!("Skipping offchain workers at non-best block {:?}", at);
+			return future::Either::Right(future::ready(()));
+		}
+
 		let runtime = self.client.runtime_api();
-		let at = BlockId::number(*number);
 		let has_api = runtime.has_api::<dyn OffchainWorkerApi<Block>>(&at);
 		debug!("Checking offchain workers at {:?}: {:?}", at, has_api);
 
 		if has_api.unwrap_or(false) {
+			let number = *header.number();
+			let branch = if number > self.client.info().finalized_number {
+				std::iter::once(header.hash())
+					.chain(non_finalized_ancestors.iter().cloned())
+					.map(|hash| hash.encode())
+					.collect()
+			} else {
+				Vec::new()
+			};
+			let local = api::local::ForkAwareStorage::new(self.db.clone(), number.saturated_into(), branch);
 			let (api, runner) = api::AsyncApi::new(
 				pool.clone(),
 				self.db.clone(),
+				local,
 				at.clone(),
 				network_state.clone(),
 				is_validator,
 			);
 			debug!("Spawning offchain workers at {:?}", at);
-			let number = *number;
+			let block_number: u64 = number.saturated_into();
+			{
+				let mut latest = self.latest_scheduled.lock();
+				*latest = (*latest).max(block_number);
+			}
+			let latest_scheduled = self.latest_scheduled.clone();
+			let skip_superseded = self.config.skip_superseded;
 			let client = self.client.clone();
-			spawn_worker(move || {
+			self.spawn_worker(move || {
+				if skip_superseded && *latest_scheduled.lock() > block_number {
+					debug!("Skipping offchain workers at superseded block {:?}", at);
+					return;
+				}
+
 				let runtime = client.runtime_api();
 				let api = Box::new(api);
 				debug!("Running offchain workers at {:?}", at);
+				let started = Instant::now();
 				let run = runtime.offchain_worker_with_context(
 					&at,
 					ExecutionContext::OffchainCall(Some((api, offchain::Capabilities::all()))),
 					number,
 				);
+				let duration = started.elapsed();
+				debug!("Offchain workers at {:?} finished in {:?}", at, duration);
+				telemetry!(SUBSTRATE_INFO; "offchain.worker_finished";
+					"number" => block_number,
+					"hash" => ?at,
+					"duration_ms" => duration.as_millis() as u64,
+					"success" => run.is_ok(),
+				);
 				if let Err(e) =	run {
 					log::error!("Error running offchain workers at {:?}: {:?}", at, e);
 				}
 			});
-			futures::future::Either::Left(runner.process())
+			future::Either::Left(runner.process())
 		} else {
-			futures::future::Either::Right(futures::future::ready(()))
+			future::Either::Right(future::ready(()))
 		}
 	}
-}
 
-/// Spawns a new offchain worker.
-///
-/// We spawn offchain workers for each block in a separate thread,
-/// since they can run for a significant amount of time
-/// in a blocking fashion and we don't want to block the runtime.
-///
-/// Note that we should avoid that if we switch to future-based runtime in the future,
-/// alternatively:
-/// TODO [ToDr] (#1458) we can consider using a thread pool instead.
-fn spawn_worker(f: impl FnOnce() -> () + Send + 'static) {
-	std::thread::spawn(f);
+	/// Commit the `LOCAL` storage of the finalized chain up to given block
+	/// and discard the one of abandoned forks.
+	pub fn on_block_finalized(&self, header: &Block::Header) {
+		let client = &self.client;
+		api::local::on_finalized(
+			&mut self.db.clone(),
+			(*header.number()).saturated_into(),
+			|number| client.hash(number.saturated_into()).ok().and_then(|h| h).map(|h| h.encode()),
+		);
+	}
+
+	/// Spawns a new offchain worker.
+	///
+	/// Offchain workers can run for a significant amount of time in a blocking fashion,
+	/// so we run them on a dedicated thread pool to avoid blocking the runtime and to
+	/// bound the number of workers running concurrently.
+	fn spawn_worker(&self, f: impl FnOnce() -> () + Send + 'static) {
+		match self.thread_pool.clone() {
+			Some(mut thread_pool) => {
+				thread_pool.spawn(future::lazy(move |_| f()))
+					.expect("ThreadPool can never fail to spawn tasks; QED");
+			},
+			None => {
+				std::thread::spawn(f);
+			},
+		}
+	}
 }
 
 #[cfg(test)]
@@ -175,12 +283,53 @@ mod tests {
 		let db = client_db::offchain::LocalStorage::new_test();
 		let network_state = Arc::new(MockNetworkStateInfo());
 
+		let header = client.header(&BlockId::number(0)).unwrap().unwrap();
+
 		// when
 		let offchain = OffchainWorkers::new(client, db);
-		futures::executor::block_on(offchain.on_block_imported(&0u64, &pool, network_state, false));
+		futures::executor::block_on(offchain.on_block_imported(&header, true, &[], &pool, network_state, false));
 
 		// then
 		assert_eq!(pool.status().ready, 1);
 		assert_eq!(pool.ready().next().unwrap().is_propagateable(), false);
 	}
+
+	#[test]
+	fn should_skip_workers_for_non_best_blocks() {
+		// given
+		let _ = env_logger::try_init();
+		let client = Arc::new(test_client::new());
+		let pool = Arc::new(Pool::new(Default::default(), transaction_pool::ChainApi::new(client.clone())));
+		let db = client_db::offchain::LocalStorage::new_test();
+		let network_state = Arc::new(MockNetworkStateInfo());
+		let header = client.header(&BlockId::number(0)).unwrap().unwrap();
+		let config = Config { skip_non_best: true, ..Default::default() };
+
+		// when
+		let offchain = OffchainWorkers::with_config(client, db, config);
+		futures::executor::block_on(offchain.on_block_imported(&header, false, &[], &pool, network_state, false));
+
+		// then
+		assert_eq!(pool.status().ready, 0);
+	}
+
+	#[test]
+	fn should_skip_superseded_workers() {
+		// given
+		let _ = env_logger::try_init();
+		let client = Arc::new(test_client::new());
+		let pool = Arc::new(Pool::new(Default::default(), transaction_pool::ChainApi::new(client.clone())));
+		let db = client_db::offchain::LocalStorage::new_test();
+		let network_state = Arc::new(MockNetworkStateInfo());
+		let header = client.header(&BlockId::number(0)).unwrap().unwrap();
+		let config = Config { skip_superseded: true, ..Default::default() };
+		let offchain = OffchainWorkers::with_config(client, db, config);
+
+		// when
+		*offchain.latest_scheduled.lock() = 1;
+		futures::executor::block_on(offchain.on_block_imported(&header, true, &[], &pool, network_state, false));
+
+		// then
+		assert_eq!(pool.status().ready, 0);
+	}
 }
@@ -22,6 +22,40 @@ use rstd::convert::TryFrom;
 
 pub use crate::crypto::KeyTypeId;
 
+#[cfg(feature = "std")]
+pub mod storage;
+#[cfg(feature = "std")]
+pub mod testing;
+
+/// Prefix of the `PERSISTENT` offchain storage entries.
+///
+/// Values indexed by the runtime during block import end up under this prefix,
+/// so they are readable by offchain workers.
+pub const STORAGE_PREFIX: &[u8] = b"storage";
+
+/// Offchain workers local storage.
+pub trait OffchainStorage: Clone + Send + Sync {
+	/// Persist a value in storage under given key and prefix.
+	fn set(&mut self, prefix: &[u8], key: &[u8], value: &[u8]);
+
+	/// Retrieve a value from storage under given key and prefix.
+	fn get(&self, prefix: &[u8], key: &[u8]) -> Option<Vec<u8>>;
+
+	/// Remove a value from storage under given key and prefix.
+	fn remove(&mut self, prefix: &[u8], key: &[u8]);
+
+	/// Replace the value in storage if given old_value matches the current one.
+	///
+	/// Returns `true` if the value has been set and false otherwise.
+	fn compare_and_set(
+		&mut self,
+		prefix: &[u8],
+		key: &[u8],
+		old_value: Option<&[u8]>,
+		new_value: &[u8],
+	) -> bool;
+}
+
 /// A type of supported crypto.
 #[derive(Clone, Copy, PartialEq, Eq, Encode, Decode)]
 #[cfg_attr(feature = "std", derive(Debug))]
@@ -20,17 +20,18 @@ use std::{
 	collections::BTreeMap,
 	sync::Arc,
 };
-use client::backend::OffchainStorage;
-use parking_lot::RwLock;
-use primitives::offchain::{
+use crate::offchain::{
 	self,
+	storage::InMemOffchainStorage,
 	HttpError,
 	HttpRequestId as RequestId,
 	HttpRequestStatus as RequestStatus,
 	Timestamp,
 	StorageKind,
 	OpaqueNetworkState,
+	OffchainStorage,
 };
+use parking_lot::RwLock;
 
 /// Pending request.
 #[derive(Debug, Default, PartialEq, Eq)]
@@ -64,9 +65,15 @@ pub struct State {
 	pub requests: BTreeMap<RequestId, PendingRequest>,
 	expected_requests: BTreeMap<RequestId, PendingRequest>,
 	/// Persistent local storage
-	pub persistent_storage: client::in_mem::OffchainStorage,
+	pub persistent_storage: InMemOffchainStorage,
 	/// Local storage
-	pub local_storage: client::in_mem::OffchainStorage,
+	pub local_storage: InMemOffchainStorage,
+	/// Seed returned by `random_seed`.
+	///
+	/// Tests can set it to get deterministic randomness in the runtime.
+	pub seed: [u8; 32],
+	/// Encoded transactions submitted to the pool.
+	pub transactions: Vec<Vec<u8>>,
 }
 
 impl State {
@@ -138,8 +145,9 @@ impl offchain::Externalities for TestOffchainExt {
 		unimplemented!("not needed in tests so far")
 	}
 
-	fn submit_transaction(&mut self, _ex: Vec<u8>) -> Result<(), ()> {
-		unimplemented!("not needed in tests so far")
+	fn submit_transaction(&mut self, ex: Vec<u8>) -> Result<(), ()> {
+		self.0.write().transactions.push(ex);
+		Ok(())
 	}
 
 	fn network_state(&self) -> Result<OpaqueNetworkState, ()> {
@@ -155,7 +163,7 @@ impl offchain::Externalities for TestOffchainExt {
 	}
 
 	fn random_seed(&mut self) -> [u8; 32] {
-		unimplemented!("not needed in tests so far")
+		self.0.read().seed
 	}
 
 	fn local_storage_set(&mut self, kind: StorageKind, key: &[u8], value: &[u8]) {
@@ -289,3 +297,23 @@ impl offchain::Externalities for TestOffchainExt {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::offchain::Externalities;
+
+	#[test]
+	fn should_return_configured_seed() {
+		// given
+		let (mut ext, state) = TestOffchainExt::new();
+		assert_eq!(ext.random_seed(), [0u8; 32]);
+
+		// when
+		state.write().seed = [7u8; 32];
+
+		// then
+		assert_eq!(ext.random_seed(), [7u8; 32]);
+		assert_eq!(ext.random_seed(), [7u8; 32]);
+	}
+}
@@ -0,0 +1,166 @@
+// Copyright 2017-2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Middleware limiting the rate of calls made over a single connection.
+
+use std::{collections::HashMap, sync::Arc, time::Instant};
+
+use jsonrpc_core::{
+	Call, Error, ErrorCode, Middleware, Output,
+	futures::{future::{self, Either}, Future},
+	middleware::{NoopCallFuture, NoopFuture},
+};
+use log::debug;
+use parking_lot::Mutex;
+use pubsub::PubSubMetadata;
+
+/// Error code returned when a connection exceeds its rate limit.
+const RATE_LIMIT_EXCEEDED: i64 = -32005;
+
+/// Metadata of a call telling apart the connection the call is made over.
+pub trait ConnectionMetadata: PubSubMetadata {
+	/// Returns the id of the connection, unique for the lifetime of the process, or `None`
+	/// if the call isn't made over a persistent connection.
+	fn connection_id(&self) -> Option<u64>;
+}
+
+/// Token bucket of a single connection.
+struct Bucket {
+	/// Number of calls the connection can make right away.
+	allowance: f64,
+	/// Last time the allowance was updated.
+	last_update: Instant,
+}
+
+/// RPC middleware limiting the number of calls per second made over each connection.
+///
+/// Only transports with persistent connections (i.e. WebSockets) are limited, connections
+/// are identified by the id in their metadata. Calls made over HTTP are never limited.
+#[derive(Clone, Default)]
+pub struct RpcMiddleware {
+	/// Maximum number of calls per second, `None` if unlimited.
+	max_calls_per_second: Option<u32>,
+	/// Buckets of the currently open connections, keyed by the connection id.
+	buckets: Arc<Mutex<HashMap<u64, Bucket>>>,
+}
+
+impl RpcMiddleware {
+	/// Create new middleware allowing at most `max_calls_per_second` calls per connection.
+	///
+	/// Short bursts of up to `max_calls_per_second` calls are allowed.
+	pub fn new(max_calls_per_second: Option<u32>) -> Self {
+		RpcMiddleware {
+			max_calls_per_second,
+			buckets: Default::default(),
+		}
+	}
+
+	/// Returns true if a call may be made over the connection with given id.
+	fn try_acquire(&self, connection_id: u64, max_calls_per_second: u32, is_new: &mut bool) -> bool {
+		let rate = f64::from(max_calls_per_second);
+		let now = Instant::now();
+		let mut buckets = self.buckets.lock();
+		let bucket = buckets.entry(connection_id).or_insert_with(|| {
+			*is_new = true;
+			Bucket { allowance: rate, last_update: now }
+		});
+
+		let elapsed = now.duration_since(bucket.last_update);
+		let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1_000_000_000.0;
+		bucket.allowance = (bucket.allowance + elapsed * rate).min(rate);
+		bucket.last_update = now;
+
+		if bucket.allowance < 1.0 {
+			false
+		} else {
+			bucket.allowance -= 1.0;
+			true
+		}
+	}
+}
+
+impl<M: ConnectionMetadata> Middleware<M> for RpcMiddleware {
+	type Future = NoopFuture;
+	type CallFuture = NoopCallFuture;
+
+	fn on_call<F, X>(&self, call: Call, meta: M, next: F) -> Either<Self::CallFuture, X> where
+		F: Fn(Call, M) -> X + Send + Sync,
+		X: Future<Item = Option<Output>, Error = ()> + Send + 'static,
+	{
+		let (max_calls_per_second, connection_id, session) = match (
+			self.max_calls_per_second,
+			meta.connection_id(),
+			meta.session(),
+		) {
+			(Some(max_calls_per_second), Some(connection_id), Some(session)) =>
+				(max_calls_per_second, connection_id, session),
+			_ => return Either::B(next(call, meta)),
+		};
+
+		let mut is_new = false;
+		let allowed = self.try_acquire(connection_id, max_calls_per_second, &mut is_new);
+		if is_new {
+			// forget the bucket once the connection is closed
+			let buckets = self.buckets.clone();
+			session.on_drop(move || {
+				buckets.lock().remove(&connection_id);
+			});
+		}
+
+		if allowed {
+			return Either::B(next(call, meta));
+		}
+
+		debug!(target: "rpc", "Rate limit of {} calls per second exceeded", max_calls_per_second);
+		let output = match call {
+			Call::MethodCall(call) => Some(Output::from(
+				Err(Error {
+					code: ErrorCode::ServerError(RATE_LIMIT_EXCEEDED),
+					message: "Rate limit exceeded".into(),
+					data: None,
+				}),
+				call.id,
+				call.jsonrpc,
+			)),
+			// notifications don't expect any response
+			Call::Notification(_) => None,
+			Call::Invalid { .. } => return Either::B(next(call, meta)),
+		};
+		Either::A(Box::new(future::ok(output)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn should_limit_calls_per_connection() {
+		let middleware = RpcMiddleware::new(Some(2));
+		let mut is_new = false;
+
+		assert!(middleware.try_acquire(1, 2, &mut is_new));
+		assert!(is_new);
+		is_new = false;
+		assert!(middleware.try_acquire(1, 2, &mut is_new));
+		assert!(!is_new);
+		assert!(!middleware.try_acquire(1, 2, &mut is_new));
+
+		// other connections have their own allowance
+		assert!(middleware.try_acquire(2, 2, &mut is_new));
+		assert!(is_new);
+	}
+}
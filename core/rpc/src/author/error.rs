@@ -20,6 +20,7 @@ use client;
 use transaction_pool::txpool;
 use crate::rpc;
 use crate::errors;
+use crate::policy::UnsafeRpcError;
 
 /// Author RPC Result type.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -49,6 +50,8 @@ pub enum Error {
 	/// Some random issue with the key store. Shouldn't happen.
 	#[display(fmt="The key store is unavailable")]
 	KeyStoreUnavailable,
+	/// Call to an unsafe RPC was denied.
+	UnsafeRpcCalled(UnsafeRpcError),
 }
 
 impl std::error::Error for Error {
@@ -57,6 +60,7 @@ impl std::error::Error for Error {
 			Error::Client(ref err) => Some(err),
 			Error::Pool(ref err) => Some(err),
 			Error::Verification(ref err) => Some(&**err),
+			Error::UnsafeRpcCalled(ref err) => Some(err),
 			_ => None,
 		}
 	}
@@ -144,6 +148,7 @@ impl From<Error> for rpc::Error {
 					request to insert the key successfully.".into()
 				),
 			},
+			Error::UnsafeRpcCalled(e) => e.into(),
 			e => errors::internal(e),
 		}
 	}
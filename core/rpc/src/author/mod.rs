@@ -25,6 +25,7 @@ mod tests;
 use std::{sync::Arc, convert::TryInto};
 
 use client::{self, Client};
+use crate::policy::DenyUnsafe;
 use crate::rpc::futures::{Sink, Future};
 use crate::subscriptions::Subscriptions;
 use futures03::{StreamExt as _, compat::Compat};
@@ -63,6 +64,8 @@ pub trait AuthorApi<Hash, BlockHash> {
 	fn submit_extrinsic(&self, extrinsic: Bytes) -> Result<Hash>;
 
 	/// Insert a key into the keystore.
+	///
+	/// This method is unsafe and is denied on RPC servers exposed to untrusted clients.
 	#[rpc(name = "author_insertKey")]
 	fn insert_key(&self,
 		key_type: String,
@@ -71,6 +74,8 @@ pub trait AuthorApi<Hash, BlockHash> {
 	) -> Result<Bytes>;
 
 	/// Generate new session keys and returns the corresponding public keys.
+	///
+	/// This method is unsafe and is denied on RPC servers exposed to untrusted clients.
 	#[rpc(name = "author_rotateKeys")]
 	fn rotate_keys(&self) -> Result<Bytes>;
 
@@ -79,6 +84,8 @@ pub trait AuthorApi<Hash, BlockHash> {
 	fn pending_extrinsics(&self) -> Result<Vec<Bytes>>;
 
 	/// Remove given extrinsic from the pool and temporarily ban it to prevent reimporting.
+	///
+	/// This method is unsafe and is denied on RPC servers exposed to untrusted clients.
 	#[rpc(name = "author_removeExtrinsic")]
 	fn remove_extrinsic(&self,
 		bytes_or_hash: Vec<hash::ExtrinsicOrHash<Hash>>
@@ -118,6 +125,8 @@ pub struct Author<B, E, P, RA> where P: PoolChainApi + Sync + Send + 'static {
 	subscriptions: Subscriptions,
 	/// The key store.
 	keystore: BareCryptoStorePtr,
+	/// Whether to deny unsafe calls
+	deny_unsafe: DenyUnsafe,
 }
 
 impl<B, E, P, RA> Author<B, E, P, RA> where P: PoolChainApi + Sync + Send + 'static {
@@ -127,12 +136,14 @@ impl<B, E, P, RA> Author<B, E, P, RA> where P: PoolChainApi + Sync + Send + 'sta
 		pool: Arc<Pool<P>>,
 		subscriptions: Subscriptions,
 		keystore: BareCryptoStorePtr,
+		deny_unsafe: DenyUnsafe,
 	) -> Self {
 		Author {
 			client,
 			pool,
 			subscriptions,
 			keystore,
+			deny_unsafe,
 		}
 	}
 }
@@ -155,6 +166,8 @@ impl<B, E, P, RA> AuthorApi<ExHash<P>, BlockHash<P>> for Author<B, E, P, RA> whe
 		suri: String,
 		maybe_public: Option<Bytes>,
 	) -> Result<Bytes> {
+		self.deny_unsafe.check_if_safe()?;
+
 		let key_type = key_type.as_str().try_into().map_err(|_| Error::BadKeyType)?;
 		let mut keystore = self.keystore.write();
 		let maybe_password = keystore.password();
@@ -179,6 +192,8 @@ impl<B, E, P, RA> AuthorApi<ExHash<P>, BlockHash<P>> for Author<B, E, P, RA> whe
 	}
 
 	fn rotate_keys(&self) -> Result<Bytes> {
+		self.deny_unsafe.check_if_safe()?;
+
 		let best_block_hash = self.client.info().chain.best_hash;
 		self.client.runtime_api().generate_session_keys(
 			&generic::BlockId::Hash(best_block_hash),
@@ -204,6 +219,8 @@ impl<B, E, P, RA> AuthorApi<ExHash<P>, BlockHash<P>> for Author<B, E, P, RA> whe
 	fn remove_extrinsic(&self,
 		bytes_or_hash: Vec<hash::ExtrinsicOrHash<ExHash<P>>>
 	) -> Result<Vec<ExHash<P>>> {
+		self.deny_unsafe.check_if_safe()?;
+
 		let hashes = bytes_or_hash.into_iter()
 			.map(|x| match x {
 				hash::ExtrinsicOrHash::Hash(h) => Ok(h),
@@ -54,6 +54,7 @@ fn submit_transaction_should_not_cause_error() {
 		pool: Arc::new(Pool::new(Default::default(), ChainApi::new(client))),
 		subscriptions: Subscriptions::new(Arc::new(runtime.executor())),
 		keystore: keystore.clone(),
+		deny_unsafe: DenyUnsafe::No,
 	};
 	let xt = uxt(AccountKeyring::Alice, 1).encode();
 	let h: H256 = blake2_256(&xt).into();
@@ -77,6 +78,7 @@ fn submit_rich_transaction_should_not_cause_error() {
 		pool: Arc::new(Pool::new(Default::default(), ChainApi::new(client.clone()))),
 		subscriptions: Subscriptions::new(Arc::new(runtime.executor())),
 		keystore: keystore.clone(),
+		deny_unsafe: DenyUnsafe::No,
 	};
 	let xt = uxt(AccountKeyring::Alice, 0).encode();
 	let h: H256 = blake2_256(&xt).into();
@@ -102,6 +104,7 @@ fn should_watch_extrinsic() {
 		pool: pool.clone(),
 		subscriptions: Subscriptions::new(Arc::new(runtime.executor())),
 		keystore: keystore.clone(),
+		deny_unsafe: DenyUnsafe::No,
 	};
 	let (subscriber, id_rx, data) = ::jsonrpc_pubsub::typed::Subscriber::new_test("test");
 
@@ -144,6 +147,7 @@ fn should_return_pending_extrinsics() {
 		pool: pool.clone(),
 		subscriptions: Subscriptions::new(Arc::new(runtime.executor())),
 		keystore: keystore.clone(),
+		deny_unsafe: DenyUnsafe::No,
 	};
 	let ex = uxt(AccountKeyring::Alice, 0);
 	AuthorApi::submit_extrinsic(&p, ex.encode().into()).unwrap();
@@ -164,6 +168,7 @@ fn should_remove_extrinsics() {
 		pool: pool.clone(),
 		subscriptions: Subscriptions::new(Arc::new(runtime.executor())),
 		keystore: keystore.clone(),
+		deny_unsafe: DenyUnsafe::No,
 	};
 	let ex1 = uxt(AccountKeyring::Alice, 0);
 	p.submit_extrinsic(ex1.encode().into()).unwrap();
@@ -193,6 +198,7 @@ fn should_insert_key() {
 		pool: Arc::new(Pool::new(Default::default(), ChainApi::new(client))),
 		subscriptions: Subscriptions::new(Arc::new(runtime.executor())),
 		keystore: keystore.clone(),
+		deny_unsafe: DenyUnsafe::No,
 	};
 
 	let suri = "//Alice";
@@ -219,6 +225,7 @@ fn should_rotate_keys() {
 		pool: Arc::new(Pool::new(Default::default(), ChainApi::new(client))),
 		subscriptions: Subscriptions::new(Arc::new(runtime.executor())),
 		keystore: keystore.clone(),
+		deny_unsafe: DenyUnsafe::No,
 	};
 
 	let new_public_keys = p.rotate_keys().expect("Rotates the keys");
@@ -238,4 +245,34 @@ fn should_rotate_keys() {
 
 	assert_eq!(session_keys.ed25519, ed25519_key_pair.public().into());
 	assert_eq!(session_keys.sr25519, sr25519_key_pair.public().into());
-}
\ No newline at end of file
+}
+
+#[test]
+fn should_deny_unsafe_calls() {
+	let runtime = runtime::Runtime::new().unwrap();
+	let client = Arc::new(test_client::new());
+	let keystore = KeyStore::new();
+	let p = Author {
+		client: client.clone(),
+		pool: Arc::new(Pool::new(Default::default(), ChainApi::new(client))),
+		subscriptions: Subscriptions::new(Arc::new(runtime.executor())),
+		keystore: keystore.clone(),
+		deny_unsafe: DenyUnsafe::Yes,
+	};
+
+	assert_matches!(
+		p.insert_key(
+			String::from_utf8(key_types::ED25519.0.to_vec()).expect("Keytype is a valid string"),
+			"//Alice".into(),
+			None,
+		),
+		Err(Error::UnsafeRpcCalled(_))
+	);
+	assert_matches!(p.rotate_keys(), Err(Error::UnsafeRpcCalled(_)));
+	assert_matches!(p.remove_extrinsic(vec![]), Err(Error::UnsafeRpcCalled(_)));
+	assert!(keystore.read().ed25519_public_keys(key_types::ED25519).is_empty());
+
+	// safe calls are still allowed
+	let xt = uxt(AccountKeyring::Alice, 0).encode();
+	assert_matches!(AuthorApi::submit_extrinsic(&p, xt.into()), Ok(_));
+}
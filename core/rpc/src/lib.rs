@@ -23,11 +23,13 @@
 mod errors;
 mod helpers;
 mod metadata;
+mod policy;
 mod subscriptions;
 
 use jsonrpc_core as rpc;
 
 pub use metadata::Metadata;
+pub use policy::{DenyUnsafe, UnsafeRpcError};
 pub use rpc::IoHandlerExtension as RpcExtension;
 pub use subscriptions::Subscriptions;
 
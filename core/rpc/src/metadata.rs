@@ -15,11 +15,15 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 //! RPC Metadata
-use std::sync::Arc;
+use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
 
 use jsonrpc_pubsub::{Session, PubSubMetadata};
+use rpc_servers::ConnectionMetadata;
 use crate::rpc::futures::sync::mpsc;
 
+/// Id of the next connection with a persistent session.
+static NEXT_CONNECTION_ID: AtomicU64 = AtomicU64::new(0);
+
 /// RPC Metadata.
 ///
 /// Manages persistent session for transports that support it
@@ -28,6 +32,7 @@ use crate::rpc::futures::sync::mpsc;
 #[derive(Default, Clone)]
 pub struct Metadata {
 	session: Option<Arc<Session>>,
+	connection_id: Option<u64>,
 }
 
 impl crate::rpc::Metadata for Metadata {}
@@ -37,11 +42,18 @@ impl PubSubMetadata for Metadata {
 	}
 }
 
+impl ConnectionMetadata for Metadata {
+	fn connection_id(&self) -> Option<u64> {
+		self.connection_id
+	}
+}
+
 impl Metadata {
 	/// Create new `Metadata` with session (Pub/Sub) support.
 	pub fn new(transport: mpsc::Sender<String>) -> Self {
 		Metadata {
 			session: Some(Arc::new(Session::new(transport))),
+			connection_id: Some(NEXT_CONNECTION_ID.fetch_add(1, Ordering::Relaxed)),
 		}
 	}
 
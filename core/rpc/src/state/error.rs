@@ -38,6 +38,35 @@ pub enum Error {
 		/// Details of the error message.
 		details: String,
 	},
+	/// Requested number of keys exceeds the maximum.
+	#[display(fmt = "count exceeds maximum value. value: {}, max: {}", value, max)]
+	InvalidCount {
+		/// Provided value
+		value: u32,
+		/// Maximum allowed value
+		max: u32,
+	},
+	/// Prefix matches more keys than can be returned at once.
+	#[display(fmt = "Prefix matches more than {} keys, use `state_getKeysPaged` instead", max)]
+	TooManyKeys {
+		/// Maximum number of keys returned at once.
+		max: usize,
+	},
+	/// Call queries more keys than are allowed at once.
+	#[display(fmt = "Cannot query more than {} keys at once", max)]
+	TooManyQueriedKeys {
+		/// Maximum number of keys queried at once.
+		max: usize,
+	},
+	/// Response would exceed the maximum size.
+	#[display(fmt = "Response exceeds the maximum size of {} bytes", max)]
+	ResponseTooLarge {
+		/// Maximum size of the response in bytes.
+		max: usize,
+	},
+	/// Runtime metadata couldn't be used to decode events.
+	#[display(fmt = "Invalid runtime metadata: {}", _0)]
+	InvalidMetadata(String),
 }
 
 impl std::error::Error for Error {
@@ -60,6 +89,31 @@ impl From<Error> for rpc::Error {
 				message: format!("{}", e),
 				data: None,
 			},
+			Error::InvalidMetadata(_) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 2),
+				message: format!("{}", e),
+				data: None,
+			},
+			Error::InvalidCount { .. } => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 3),
+				message: format!("{}", e),
+				data: None,
+			},
+			Error::TooManyKeys { .. } => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 4),
+				message: format!("{}", e),
+				data: None,
+			},
+			Error::ResponseTooLarge { .. } => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 5),
+				message: format!("{}", e),
+				data: None,
+			},
+			Error::TooManyQueriedKeys { .. } => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 6),
+				message: format!("{}", e),
+				data: None,
+			},
 			e => errors::internal(e),
 		}
 	}
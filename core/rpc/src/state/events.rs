@@ -0,0 +1,406 @@
+// Copyright 2017-2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Decoding and filtering of runtime events based on the runtime metadata.
+//!
+//! Events are stored by `srml-system` as a SCALE-encoded `Vec<EventRecord>`. The
+//! encoding is not self-describing, so in order to split it into separate records
+//! the decoder needs to know the size of every event argument. The runtime metadata
+//! only provides the names of the argument types, which are resolved to their
+//! encoding by a [`TypeRegistry`]. Types that depend on the runtime configuration,
+//! such as `AccountId` or `Balance`, have to be registered by the node.
+
+use std::collections::HashMap;
+
+use codec::{Compact, Decode};
+use primitives::{twox_128, Bytes, H256};
+use serde::{Serialize, Deserialize};
+use srml_metadata::{DecodeDifferent, ModuleMetadata, RuntimeMetadata, RuntimeMetadataPrefixed};
+
+/// Maximal depth of nested types and aliases the decoder resolves, guarding against
+/// self-referential definitions.
+const MAX_TYPE_DEPTH: usize = 64;
+
+/// A phase of a block's execution an event was deposited in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Phase {
+	/// Applying an extrinsic with the given index.
+	ApplyExtrinsic(u32),
+	/// Finalizing the block.
+	Finalization,
+}
+
+/// A kind of phase used to filter events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PhaseFilter {
+	/// Events deposited while applying extrinsics.
+	ApplyExtrinsic,
+	/// Events deposited while finalizing the block.
+	Finalization,
+}
+
+/// Filter applied to the events of a block before they are sent to the subscriber.
+///
+/// A missing field matches any value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct EventFilter {
+	/// Name of the module that deposited the event.
+	#[serde(default)]
+	pub module: Option<String>,
+	/// Name of the event.
+	#[serde(default)]
+	pub event: Option<String>,
+	/// Phase the event was deposited in.
+	#[serde(default)]
+	pub phase: Option<PhaseFilter>,
+}
+
+impl EventFilter {
+	/// Returns true if the given event passes the filter.
+	pub fn matches(&self, event: &RuntimeEvent) -> bool {
+		self.module.as_ref().map_or(true, |module| module == &event.module)
+			&& self.event.as_ref().map_or(true, |name| name == &event.name)
+			&& self.phase.map_or(true, |phase| match (phase, event.phase) {
+				(PhaseFilter::ApplyExtrinsic, Phase::ApplyExtrinsic(_)) => true,
+				(PhaseFilter::Finalization, Phase::Finalization) => true,
+				_ => false,
+			})
+	}
+}
+
+/// A decoded runtime event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeEvent {
+	/// Phase the event was deposited in.
+	pub phase: Phase,
+	/// Name of the module that deposited the event.
+	pub module: String,
+	/// Name of the event.
+	pub name: String,
+	/// SCALE-encoded arguments of the event.
+	pub data: Bytes,
+	/// Topics the event was deposited with.
+	pub topics: Vec<H256>,
+}
+
+/// Events of a block that passed the subscription filter.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventNotification<Hash> {
+	/// Hash of the block the events were deposited in.
+	pub block: Hash,
+	/// Events matching the filter.
+	pub events: Vec<RuntimeEvent>,
+}
+
+/// Maps type names used in the event metadata to their definitions.
+///
+/// Definitions are type names themselves, eventually resolving to primitive
+/// integers, `bool`, fixed size arrays, tuples, `Vec`, `Option` and `Compact`.
+#[derive(Debug, Clone)]
+pub struct TypeRegistry {
+	aliases: HashMap<String, String>,
+}
+
+impl Default for TypeRegistry {
+	/// Creates a registry with the types used by the events of the SRML modules which don't
+	/// depend on the runtime configuration.
+	fn default() -> Self {
+		let mut registry = TypeRegistry { aliases: HashMap::new() };
+		for (name, definition) in &[
+			("SessionIndex", "u32"),
+			("ReferendumIndex", "u32"),
+			("PropIndex", "u32"),
+			("ProposalIndex", "u32"),
+			("MemberCount", "u32"),
+			("AuthorityWeight", "u64"),
+			("VoteThreshold", "u8"),
+			("Kind", "[u8; 16]"),
+			("OpaqueTimeSlot", "Vec<u8>"),
+		] {
+			registry.register(name, definition);
+		}
+		registry
+	}
+}
+
+impl TypeRegistry {
+	/// Registers (or overrides) the definition of the given type name.
+	pub fn register(&mut self, name: &str, definition: &str) {
+		self.aliases.insert(name.into(), definition.into());
+	}
+
+	/// Advances `input` past a value of the given type.
+	fn skip(&self, ty: &str, input: &mut &[u8]) -> Result<(), String> {
+		self.skip_nested(ty, 0, input)
+	}
+
+	/// Advances `input` past a value of the given type, nested `depth` types deep.
+	fn skip_nested(&self, ty: &str, depth: usize, input: &mut &[u8]) -> Result<(), String> {
+		if depth > MAX_TYPE_DEPTH {
+			return Err(format!("Type nested more than {} types deep: {}", MAX_TYPE_DEPTH, ty));
+		}
+		let depth = depth + 1;
+
+		let ty = strip_path(ty.trim());
+		if let Some(definition) = self.aliases.get(ty) {
+			return self.skip_nested(definition, depth, input);
+		}
+
+		match ty {
+			"()" => return Ok(()),
+			"bool" | "u8" | "i8" => return advance(input, 1),
+			"u16" | "i16" => return advance(input, 2),
+			"u32" | "i32" => return advance(input, 4),
+			"u64" | "i64" => return advance(input, 8),
+			"u128" | "i128" => return advance(input, 16),
+			_ => {},
+		}
+
+		if ty.starts_with('(') && ty.ends_with(')') {
+			for item in split_top_level(&ty[1..ty.len() - 1]) {
+				self.skip_nested(item, depth, input)?;
+			}
+			return Ok(());
+		}
+
+		if ty.starts_with('[') && ty.ends_with(']') {
+			let inner = &ty[1..ty.len() - 1];
+			let separator = inner.rfind(';').ok_or_else(|| format!("Invalid array type: {}", ty))?;
+			let len: usize = inner[separator + 1..].trim().parse()
+				.map_err(|_| format!("Invalid array length: {}", ty))?;
+			return self.skip_many(&inner[..separator], len, depth, input);
+		}
+
+		match generic(ty) {
+			Some(("PhantomData", _)) => Ok(()),
+			Some(("Compact", _)) => Compact::<u128>::decode(input)
+				.map(|_| ())
+				.map_err(|e| format!("Invalid compact value: {}", e.what())),
+			Some(("Vec", item)) => {
+				let len = Compact::<u32>::decode(input)
+					.map_err(|e| format!("Invalid vector length: {}", e.what()))?;
+				// every item but a zero-sized one takes at least one byte
+				if len.0 as usize > input.len() {
+					return Err(format!("Vector length {} exceeds the remaining input", len.0));
+				}
+				self.skip_many(item, len.0 as usize, depth, input)
+			},
+			// `Option<bool>` is encoded as a single byte.
+			Some(("Option", "bool")) => advance(input, 1),
+			Some(("Option", item)) => match u8::decode(input) {
+				Ok(0) => Ok(()),
+				Ok(1) => self.skip_nested(item, depth, input),
+				_ => Err(format!("Invalid option of {}", item)),
+			},
+			_ => Err(format!("Unknown type: {}", ty)),
+		}
+	}
+
+	fn skip_many(&self, ty: &str, count: usize, depth: usize, input: &mut &[u8]) -> Result<(), String> {
+		match strip_path(ty.trim()) {
+			"u8" | "i8" => advance(input, count),
+			ty => (0..count).try_for_each(|_| self.skip_nested(ty, depth, input)),
+		}
+	}
+}
+
+/// Returns the modules described by the metadata of any supported version.
+fn modules(metadata: &RuntimeMetadataPrefixed) -> Result<&[ModuleMetadata], String> {
+	match metadata.1 {
+		RuntimeMetadata::V0(_) | RuntimeMetadata::V1(_) | RuntimeMetadata::V2(_) |
+		RuntimeMetadata::V3(_) | RuntimeMetadata::V4(_) | RuntimeMetadata::V5(_) |
+		RuntimeMetadata::V6(_) => Err("Metadata versions prior to V7 are not supported".into()),
+		RuntimeMetadata::V7(ref metadata) => Ok(&decoded(&metadata.modules)?[..]),
+	}
+}
+
+/// Returns the storage key `srml-system` stores the events of a block under, as described
+/// by the runtime metadata.
+pub fn events_key(metadata: &RuntimeMetadataPrefixed) -> Result<Vec<u8>, String> {
+	for module in modules(metadata)? {
+		if !decoded(&module.name)?.eq_ignore_ascii_case("system") {
+			continue;
+		}
+		if let Some(storage) = module.storage.as_ref() {
+			let storage = decoded(storage)?;
+			let prefix = decoded(&storage.prefix)?;
+			for entry in decoded(&storage.entries)? {
+				if *decoded(&entry.name)? == "Events" {
+					return Ok(twox_128(format!("{} Events", prefix).as_bytes()).to_vec());
+				}
+			}
+		}
+	}
+
+	Err("Runtime doesn't store events".into())
+}
+
+/// Decodes the events deposited by `srml-system` using the metadata of a runtime.
+pub struct EventDecoder {
+	events_key: Vec<u8>,
+	/// Modules with events, in the order of the outer event enum variants.
+	modules: Vec<(String, Vec<(String, Vec<String>)>)>,
+	types: TypeRegistry,
+}
+
+impl EventDecoder {
+	/// Creates a decoder from the runtime metadata.
+	pub fn new(metadata: RuntimeMetadataPrefixed, types: TypeRegistry) -> Result<Self, String> {
+		let events_key = events_key(&metadata)?;
+
+		let mut event_modules = Vec::new();
+		for module in modules(&metadata)? {
+			if let Some(events) = module.event.as_ref() {
+				let events = decoded(events)?
+					.iter()
+					.map(|event| Ok((decoded(&event.name)?.clone(), decoded(&event.arguments)?.clone())))
+					.collect::<Result<Vec<_>, String>>()?;
+				event_modules.push((decoded(&module.name)?.clone(), events));
+			}
+		}
+
+		Ok(EventDecoder {
+			events_key,
+			modules: event_modules,
+			types,
+		})
+	}
+
+	/// Returns the storage key events are stored under.
+	pub fn events_key(&self) -> &[u8] {
+		&self.events_key
+	}
+
+	/// Decodes the encoded `Vec<EventRecord>`.
+	///
+	/// The records are not length-prefixed, so the end of a record that fails to decode, e.g.
+	/// because of an argument type missing from the registry, is unknown. Decoding stops at
+	/// such a record; the records decoded before it are returned together with the error.
+	pub fn decode(&self, mut input: &[u8]) -> (Vec<RuntimeEvent>, Option<String>) {
+		let input = &mut input;
+		let count = match Compact::<u32>::decode(input) {
+			Ok(count) => count.0,
+			Err(e) => return (Vec::new(), Some(format!("Invalid number of events: {}", e.what()))),
+		};
+
+		// every record takes at least one byte, which bounds the allocation for a corrupt count
+		let mut events = Vec::with_capacity(std::cmp::min(count as usize, input.len()));
+		for index in 0..count {
+			match self.decode_record(input) {
+				Ok(event) => events.push(event),
+				Err(e) => return (events, Some(format!("Invalid event record {} of {}: {}", index, count, e))),
+			}
+		}
+
+		(events, None)
+	}
+
+	fn decode_record(&self, input: &mut &[u8]) -> Result<RuntimeEvent, String> {
+		let phase = match u8::decode(input) {
+			Ok(0) => Phase::ApplyExtrinsic(u32::decode(input)
+				.map_err(|e| format!("Invalid extrinsic index: {}", e.what()))?),
+			Ok(1) => Phase::Finalization,
+			_ => return Err("Invalid event phase".into()),
+		};
+
+		let module_index = u8::decode(input).map_err(|e| e.what().to_string())?;
+		let (module, module_events) = self.modules.get(module_index as usize)
+			.ok_or_else(|| format!("Unknown module with index {}", module_index))?;
+		let event_index = u8::decode(input).map_err(|e| e.what().to_string())?;
+		let (name, arguments) = module_events.get(event_index as usize)
+			.ok_or_else(|| format!("Unknown event of {} with index {}", module, event_index))?;
+
+		let data_start = *input;
+		for argument in arguments {
+			self.types.skip(argument, input)
+				.map_err(|e| format!("Unable to decode {}::{}: {}", module, name, e))?;
+		}
+		let data = data_start[..data_start.len() - input.len()].to_vec();
+
+		let topics = Vec::<H256>::decode(input)
+			.map_err(|e| format!("Invalid event topics: {}", e.what()))?;
+
+		Ok(RuntimeEvent {
+			phase,
+			module: module.clone(),
+			name: name.clone(),
+			data: data.into(),
+			topics,
+		})
+	}
+}
+
+fn decoded<B, O>(value: &DecodeDifferent<B, O>) -> Result<&O, String> {
+	match value {
+		DecodeDifferent::Decoded(value) => Ok(value),
+		DecodeDifferent::Encode(_) => Err("Metadata is not decoded".into()),
+	}
+}
+
+fn advance(input: &mut &[u8], len: usize) -> Result<(), String> {
+	if input.len() < len {
+		return Err("Unexpected end of input".into());
+	}
+	*input = &input[len..];
+	Ok(())
+}
+
+/// Strips the module path from a type name, e.g. `rstd::marker::PhantomData<T>` becomes
+/// `PhantomData<T>`.
+fn strip_path(ty: &str) -> &str {
+	let end = ty.find('<').unwrap_or_else(|| ty.len());
+	match ty[..end].rfind("::") {
+		Some(separator) => &ty[separator + 2..],
+		None => ty,
+	}
+}
+
+/// Splits `Name<Param>` into `("Name", "Param")`.
+fn generic(ty: &str) -> Option<(&str, &str)> {
+	let start = ty.find('<')?;
+	if !ty.ends_with('>') {
+		return None;
+	}
+	Some((ty[..start].trim(), ty[start + 1..ty.len() - 1].trim()))
+}
+
+/// Splits a comma separated list of types, ignoring commas nested in brackets.
+fn split_top_level(list: &str) -> Vec<&str> {
+	let mut items = Vec::new();
+	let mut depth = 0;
+	let mut start = 0;
+	for (i, c) in list.char_indices() {
+		match c {
+			'<' | '(' | '[' => depth += 1,
+			'>' | ')' | ']' => depth -= 1,
+			',' if depth == 0 => {
+				items.push(&list[start..i]);
+				start = i + 1;
+			},
+			_ => {},
+		}
+	}
+	if !list[start..].trim().is_empty() {
+		items.push(&list[start..]);
+	}
+	items
+}
@@ -17,6 +17,7 @@
 //! Substrate state API.
 
 pub mod error;
+pub mod events;
 
 #[cfg(test)]
 mod tests;
@@ -29,12 +30,14 @@ use std::{
 use futures03::{future, StreamExt as _, TryStreamExt as _};
 
 use client::{self, Client, CallExecutor, BlockchainEvents, runtime_api::Metadata};
+use codec::Decode;
 use crate::rpc::Result as RpcResult;
 use crate::rpc::futures::{stream, Future, Sink, Stream};
 use crate::subscriptions::Subscriptions;
 use jsonrpc_derive::rpc;
 use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
 use log::{warn, trace};
+use parking_lot::Mutex;
 use primitives::hexdisplay::HexDisplay;
 use primitives::storage::{self, StorageKey, StorageData, StorageChangeSet};
 use primitives::{H256, Blake2Hasher, Bytes};
@@ -44,11 +47,32 @@ use sr_primitives::traits::{
 	SaturatedConversion
 };
 use runtime_version::RuntimeVersion;
+use serde::{Serialize, Deserialize};
 use self::error::Result;
+use self::events::{EventDecoder, EventFilter, EventNotification, TypeRegistry};
+use srml_metadata::RuntimeMetadataPrefixed;
 use state_machine::{self, ExecutionStrategy};
 
 pub use self::gen_client::Client as StateClient;
 
+/// Maximal number of keys returned by `state_getKeys` and `state_getChildKeys`.
+const STORAGE_KEYS_MAX_COUNT: usize = 10_000;
+
+/// Maximal number of keys a single `state_getKeysPaged` call may return.
+const STORAGE_KEYS_PAGED_MAX_COUNT: u32 = 1_000;
+
+/// Maximal number of blocks `state_queryStorage` may query at once.
+const QUERY_STORAGE_MAX_BLOCKS: usize = 4_096;
+
+/// Maximal number of keys `state_queryStorage` may query at once.
+const QUERY_STORAGE_MAX_KEYS: usize = 1_000;
+
+/// Maximal number of keys `state_getReadProof` and `state_getChildReadProof` may prove at once.
+const READ_PROOF_MAX_KEYS: usize = 1_000;
+
+/// Maximal total size of the keys, values and proofs returned by a single call.
+const MAX_RESPONSE_SIZE: usize = 10 * 1024 * 1024;
+
 /// Substrate state API
 #[rpc]
 pub trait StateApi<Hash> {
@@ -60,9 +84,24 @@ pub trait StateApi<Hash> {
 	fn call(&self, name: String, bytes: Bytes, hash: Option<Hash>) -> Result<Bytes>;
 
 	/// Returns the keys with prefix, leave empty to get all the keys
+	///
+	/// Fails if the prefix matches too many keys, use `state_getKeysPaged` in that case.
 	#[rpc(name = "state_getKeys")]
 	fn storage_keys(&self, prefix: StorageKey, hash: Option<Hash>) -> Result<Vec<StorageKey>>;
 
+	/// Returns up to `count` keys with prefix, in lexicographic order.
+	///
+	/// If `start_key` is passed, only the keys following it are returned, so the last key
+	/// of a page can be used to request the next one.
+	#[rpc(name = "state_getKeysPaged", alias("state_getKeysPagedAt"))]
+	fn storage_keys_paged(
+		&self,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+		hash: Option<Hash>,
+	) -> Result<Vec<StorageKey>>;
+
 	/// Returns a storage entry at a specific block's state.
 	#[rpc(name = "state_getStorage", alias("state_getStorageAt"))]
 	fn storage(&self, key: StorageKey, hash: Option<Hash>) -> Result<Option<StorageData>>;
@@ -131,6 +170,19 @@ pub trait StateApi<Hash> {
 		hash: Option<Hash>
 	) -> Result<Vec<StorageChangeSet<Hash>>>;
 
+	/// Returns proof of storage entries at a specific block's state.
+	#[rpc(name = "state_getReadProof")]
+	fn read_proof(&self, keys: Vec<StorageKey>, hash: Option<Hash>) -> Result<ReadProof<Hash>>;
+
+	/// Returns proof of child storage entries at a specific block's state.
+	#[rpc(name = "state_getChildReadProof")]
+	fn read_child_proof(
+		&self,
+		child_storage_key: StorageKey,
+		keys: Vec<StorageKey>,
+		hash: Option<Hash>
+	) -> Result<ReadProof<Hash>>;
+
 	/// New runtime version subscription
 	#[pubsub(
 		subscription = "state_runtimeVersion",
@@ -160,6 +212,41 @@ pub trait StateApi<Hash> {
 	fn unsubscribe_storage(
 		&self, metadata: Option<Self::Metadata>, id: SubscriptionId
 	) -> RpcResult<bool>;
+
+	/// New runtime events subscription.
+	///
+	/// Only events matching the filter are sent, blocks without matching events are skipped.
+	#[pubsub(
+		subscription = "state_events",
+		subscribe,
+		name = "state_subscribeEvents",
+		alias("chain_subscribeEvents")
+	)]
+	fn subscribe_events(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<EventNotification<Hash>>,
+		filter: Option<EventFilter>
+	);
+
+	/// Unsubscribe from runtime events subscription.
+	#[pubsub(
+		subscription = "state_events",
+		unsubscribe,
+		name = "state_unsubscribeEvents",
+		alias("chain_unsubscribeEvents")
+	)]
+	fn unsubscribe_events(&self, metadata: Option<Self::Metadata>, id: SubscriptionId) -> RpcResult<bool>;
+}
+
+/// Storage read proof.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadProof<Hash> {
+	/// Block hash used to generate the proof.
+	pub at: Hash,
+	/// Trie nodes proving that the storage entries are included in the state trie at `at`.
+	pub proof: Vec<Bytes>,
 }
 
 /// State API with subscriptions support.
@@ -168,6 +255,10 @@ pub struct State<B, E, Block: BlockT, RA> {
 	client: Arc<Client<B, E, Block, RA>>,
 	/// Current subscriptions.
 	subscriptions: Subscriptions,
+	/// Types used to decode runtime events.
+	event_types: TypeRegistry,
+	/// Events decoders created so far, by the spec version of the runtime they decode events of.
+	event_decoders: Arc<Mutex<HashMap<u32, Arc<EventDecoder>>>>,
 }
 
 /// Ranges to query in state_queryStorage.
@@ -194,9 +285,19 @@ impl<B, E, Block: BlockT, RA> State<B, E, Block, RA> where
 		Self {
 			client,
 			subscriptions,
+			event_types: Default::default(),
+			event_decoders: Default::default(),
 		}
 	}
 
+	/// Use given types to decode runtime events. The default registry only knows the types
+	/// that don't depend on the runtime configuration.
+	pub fn with_event_types(mut self, event_types: TypeRegistry) -> Self {
+		self.event_types = event_types;
+		self.event_decoders = Default::default();
+		self
+	}
+
 	/// Splits the `query_storage` block range into 'filtered' and 'unfiltered' subranges.
 	/// Blocks that contain changes within filtered subrange could be filtered using changes tries.
 	/// Blocks that contain changes within unfiltered subrange must be filtered manually.
@@ -210,6 +311,15 @@ impl<B, E, Block: BlockT, RA> State<B, E, Block, RA> where
 		let to_hdr = self.client.header(&BlockId::hash(to))?;
 		match (from_hdr, to_hdr) {
 			(Some(ref from), Some(ref to)) if from.number() <= to.number() => {
+				let range_len = (*to.number() - *from.number()).saturated_into::<usize>().saturating_add(1);
+				if range_len > QUERY_STORAGE_MAX_BLOCKS {
+					return Err(invalid_block_range(
+						Some(from),
+						Some(to),
+						format!("Range of {} blocks exceeds the maximum of {}", range_len, QUERY_STORAGE_MAX_BLOCKS),
+					))
+				}
+
 				// check if we can get from `to` to `from` by going through parent_hashes.
 				let from_number = *from.number();
 				let blocks = {
@@ -342,6 +452,39 @@ impl<B, E, Block, RA> State<B, E, Block, RA> where
 	}
 }
 
+impl<B, E, Block, RA> State<B, E, Block, RA> where
+	Block: BlockT<Hash=H256>,
+	B: client::backend::Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher> + Clone,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: Metadata<Block>
+{
+	/// Returns the events decoder for the runtime at given block.
+	///
+	/// The decoder is created from the runtime metadata, unless one was already created for
+	/// a runtime with the same spec version.
+	fn event_decoder(
+		client: &Client<B, E, Block, RA>,
+		event_types: &TypeRegistry,
+		event_decoders: &Mutex<HashMap<u32, Arc<EventDecoder>>>,
+		block: Block::Hash,
+	) -> Result<Arc<EventDecoder>> {
+		let id = BlockId::Hash(block);
+		let spec_version = client.runtime_version_at(&id)?.spec_version;
+		if let Some(decoder) = event_decoders.lock().get(&spec_version) {
+			return Ok(decoder.clone());
+		}
+
+		let metadata = client.runtime_api().metadata(&id)?;
+		let metadata = RuntimeMetadataPrefixed::decode(&mut &metadata[..])
+			.map_err(|e| error::Error::InvalidMetadata(e.what().into()))?;
+		let decoder = Arc::new(EventDecoder::new(metadata, event_types.clone())
+			.map_err(error::Error::InvalidMetadata)?);
+		event_decoders.lock().insert(spec_version, decoder.clone());
+		Ok(decoder)
+	}
+}
+
 impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA> where
 	Block: BlockT<Hash=H256> + 'static,
 	B: client::backend::Backend<Block, Blake2Hasher> + Send + Sync + 'static,
@@ -367,7 +510,39 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA> where
 	fn storage_keys(&self, key_prefix: StorageKey, block: Option<Block::Hash>) -> Result<Vec<StorageKey>> {
 		let block = self.unwrap_or_best(block)?;
 		trace!(target: "rpc", "Querying storage keys at {:?}", block);
-		Ok(self.client.storage_keys(&BlockId::Hash(block), &key_prefix)?)
+		let keys = self.client.storage_keys_paged(
+			&BlockId::Hash(block),
+			&key_prefix,
+			None,
+			STORAGE_KEYS_MAX_COUNT + 1,
+		)?;
+		check_keys(&keys)?;
+		Ok(keys)
+	}
+
+	fn storage_keys_paged(
+		&self,
+		key_prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+		block: Option<Block::Hash>,
+	) -> Result<Vec<StorageKey>> {
+		if count > STORAGE_KEYS_PAGED_MAX_COUNT {
+			return Err(error::Error::InvalidCount {
+				value: count,
+				max: STORAGE_KEYS_PAGED_MAX_COUNT,
+			});
+		}
+		let block = self.unwrap_or_best(block)?;
+		trace!(target: "rpc", "Querying {} storage keys at {:?}", count, block);
+		let keys = self.client.storage_keys_paged(
+			&BlockId::Hash(block),
+			&key_prefix.unwrap_or_else(|| StorageKey(Vec::new())),
+			start_key.as_ref(),
+			count as usize,
+		)?;
+		check_response_size(keys.iter().map(|key| key.0.len()))?;
+		Ok(keys)
 	}
 
 	fn storage(&self, key: StorageKey, block: Option<Block::Hash>) -> Result<Option<StorageData>> {
@@ -405,7 +580,15 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA> where
 	) -> Result<Vec<StorageKey>> {
 		let block = self.unwrap_or_best(block)?;
 		trace!(target: "rpc", "Querying child storage keys at {:?}", block);
-		Ok(self.client.child_storage_keys(&BlockId::Hash(block), &child_storage_key, &key_prefix)?)
+		let keys = self.client.child_storage_keys_paged(
+			&BlockId::Hash(block),
+			&child_storage_key,
+			&key_prefix,
+			None,
+			STORAGE_KEYS_MAX_COUNT + 1,
+		)?;
+		check_keys(&keys)?;
+		Ok(keys)
 	}
 
 	fn child_storage_hash(
@@ -443,14 +626,50 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA> where
 		from: Block::Hash,
 		to: Option<Block::Hash>
 	) -> Result<Vec<StorageChangeSet<Block::Hash>>> {
+		check_queried_keys(&keys, QUERY_STORAGE_MAX_KEYS)?;
 		let range = self.split_query_storage_range(from, to)?;
 		let mut changes = Vec::new();
 		let mut last_values = HashMap::new();
 		self.query_storage_unfiltered(&range, &keys, &mut last_values, &mut changes)?;
 		self.query_storage_filtered(&range, &keys, &last_values, &mut changes)?;
+		check_response_size(changes.iter()
+			.flat_map(|change_set| change_set.changes.iter())
+			.map(|(key, value)| key.0.len() + value.as_ref().map_or(0, |value| value.0.len()))
+		)?;
 		Ok(changes)
 	}
 
+	fn read_proof(&self, keys: Vec<StorageKey>, block: Option<Block::Hash>) -> Result<ReadProof<Block::Hash>> {
+		check_queried_keys(&keys, READ_PROOF_MAX_KEYS)?;
+		let block = self.unwrap_or_best(block)?;
+		trace!(target: "rpc", "Generating read proof at {:?} for {} keys", block, keys.len());
+		let keys = keys.into_iter().map(|key| key.0).collect::<Vec<_>>();
+		let proof = self.client.read_proof_keys(&BlockId::Hash(block), &keys)?;
+		check_response_size(proof.iter().map(Vec::len))?;
+		Ok(ReadProof {
+			at: block,
+			proof: proof.into_iter().map(Bytes).collect(),
+		})
+	}
+
+	fn read_child_proof(
+		&self,
+		child_storage_key: StorageKey,
+		keys: Vec<StorageKey>,
+		block: Option<Block::Hash>
+	) -> Result<ReadProof<Block::Hash>> {
+		check_queried_keys(&keys, READ_PROOF_MAX_KEYS)?;
+		let block = self.unwrap_or_best(block)?;
+		trace!(target: "rpc", "Generating child read proof at {:?} for {} keys", block, keys.len());
+		let keys = keys.into_iter().map(|key| key.0).collect::<Vec<_>>();
+		let proof = self.client.read_child_proof_keys(&BlockId::Hash(block), &child_storage_key.0, &keys)?;
+		check_response_size(proof.iter().map(Vec::len))?;
+		Ok(ReadProof {
+			at: block,
+			proof: proof.into_iter().map(Bytes).collect(),
+		})
+	}
+
 	fn subscribe_storage(
 		&self,
 		_meta: Self::Metadata,
@@ -506,6 +725,95 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA> where
 		Ok(self.subscriptions.cancel(id))
 	}
 
+	fn subscribe_events(
+		&self,
+		_meta: Self::Metadata,
+		subscriber: Subscriber<EventNotification<Block::Hash>>,
+		filter: Option<EventFilter>
+	) {
+		let filter = filter.unwrap_or_default();
+		let best_hash = self.client.info().chain.best_hash;
+		let mut decoder = match Self::event_decoder(&self.client, &self.event_types, &self.event_decoders, best_hash) {
+			Ok(decoder) => decoder,
+			Err(err) => {
+				let _ = subscriber.reject(err.into());
+				return;
+			},
+		};
+
+		let key = StorageKey(decoder.events_key().to_vec());
+		let code_key = StorageKey(storage::well_known_keys::CODE.to_vec());
+		let stream = match self.client.storage_changes_notification_stream(
+			Some(&[key.clone(), code_key.clone()]),
+			None,
+		) {
+			Ok(stream) => stream,
+			Err(err) => {
+				let _ = subscriber.reject(error::Error::from(err).into());
+				return;
+			},
+		};
+
+		self.subscriptions.add(subscriber, |sink| {
+			let client = self.client.clone();
+			let event_types = self.event_types.clone();
+			let event_decoders = self.event_decoders.clone();
+
+			let stream = stream
+				.filter_map(move |(block, changes)| {
+					let changed = |changed: &StorageKey| changes.iter()
+						.find(|(child_key, changed_key, _)| child_key.is_none() && *changed_key == changed)
+						.map(|(_, _, data)| data.map(|data| data.0.clone()));
+
+					// the runtime has been upgraded, its events might be different
+					if changed(&code_key).is_some() {
+						match Self::event_decoder(&client, &event_types, &event_decoders, block) {
+							Ok(new_decoder) => decoder = new_decoder,
+							Err(err) => {
+								warn!(target: "rpc", "Failed to create events decoder at {:?}: {}", block, err);
+								return future::ready(None);
+							},
+						}
+					}
+
+					let data = match changed(&key).and_then(|data| data) {
+						Some(data) => data,
+						None => return future::ready(None),
+					};
+
+					let (events, err) = decoder.decode(&data);
+					if let Some(err) = err {
+						warn!(
+							target: "rpc",
+							"Skipping undecodable events at {:?} ({} decoded): {}",
+							block,
+							events.len(),
+							err,
+						);
+					}
+					let events = events.into_iter()
+						.filter(|event| filter.matches(event))
+						.collect::<Vec<_>>();
+					if events.is_empty() {
+						future::ready(None)
+					} else {
+						future::ready(Some(Ok::<_, ()>(Ok(EventNotification { block, events }))))
+					}
+				})
+				.compat();
+
+			sink
+				.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+				.send_all(stream)
+				// we ignore the resulting Stream (if the first stream is over we are unsubscribed)
+				.map(|_| ())
+		})
+	}
+
+	fn unsubscribe_events(&self, _meta: Option<Self::Metadata>, id: SubscriptionId) -> RpcResult<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+
 	fn runtime_version(&self, at: Option<Block::Hash>) -> Result<RuntimeVersion> {
 		let at = self.unwrap_or_best(at)?;
 		Ok(self.client.runtime_version_at(&BlockId::Hash(at))?)
@@ -583,6 +891,34 @@ pub(crate) fn split_range(size: usize, middle: Option<usize>) -> (Range<usize>,
 	(range1, range2)
 }
 
+/// Ensures that the keys fit into a single `state_getKeys` response.
+fn check_keys(keys: &[StorageKey]) -> Result<()> {
+	if keys.len() > STORAGE_KEYS_MAX_COUNT {
+		return Err(error::Error::TooManyKeys { max: STORAGE_KEYS_MAX_COUNT });
+	}
+	check_response_size(keys.iter().map(|key| key.0.len()))
+}
+
+/// Ensures that a call doesn't query more than `max` keys, before any work is done for them.
+fn check_queried_keys(keys: &[StorageKey], max: usize) -> Result<()> {
+	if keys.len() > max {
+		return Err(error::Error::TooManyQueriedKeys { max });
+	}
+	Ok(())
+}
+
+/// Ensures that the total size of the returned items doesn't exceed `MAX_RESPONSE_SIZE`.
+fn check_response_size(sizes: impl Iterator<Item = usize>) -> Result<()> {
+	let mut total = 0usize;
+	for size in sizes {
+		total = total.saturating_add(size);
+		if total > MAX_RESPONSE_SIZE {
+			return Err(error::Error::ResponseTooLarge { max: MAX_RESPONSE_SIZE });
+		}
+	}
+	Ok(())
+}
+
 fn invalid_block_range<H: Header>(from: Option<&H>, to: Option<&H>, reason: String) -> error::Error {
 	let to_string = |x: Option<&H>| match x {
 		None => "unknown hash".into(),
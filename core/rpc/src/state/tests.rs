@@ -18,6 +18,12 @@ use super::*;
 use self::error::Error;
 
 use assert_matches::assert_matches;
+use codec::{Compact, Encode};
+use self::events::{EventFilter, PhaseFilter, Phase};
+use srml_metadata::{
+	DecodeDifferent, EventMetadata, ModuleMetadata, RuntimeMetadata, RuntimeMetadataV7,
+	StorageEntryMetadata, StorageEntryModifier, StorageEntryType, StorageMetadata,
+};
 use primitives::storage::well_known_keys;
 use sr_io::blake2_256;
 use test_client::{
@@ -79,6 +85,62 @@ fn should_return_child_storage() {
 		client.child_storage_size(child_key.clone(), key.clone(), None),
 		Ok(Some(1))
 	);
+	assert_eq!(
+		client.child_storage_keys(child_key.clone(), StorageKey(b"k".to_vec()), None).unwrap(),
+		vec![key.clone()],
+	);
+	assert!(client.child_storage_keys(child_key, StorageKey(b"l".to_vec()), None).unwrap().is_empty());
+}
+
+#[test]
+fn should_return_read_proof() {
+	const KEY: &[u8] = b":mock";
+	const VALUE: &[u8] = b"hello world";
+
+	let core = tokio::runtime::Runtime::new().unwrap();
+	let client = Arc::new(TestClientBuilder::new()
+		.add_extra_storage(KEY.to_vec(), VALUE.to_vec())
+		.add_child_storage("test", "key", vec![42_u8])
+		.build());
+	let genesis_hash = client.genesis_hash();
+	let state_root = *client.header(&BlockId::Hash(genesis_hash)).unwrap().unwrap().state_root();
+	let client = State::new(client, Subscriptions::new(Arc::new(core.executor())));
+	let child_key = StorageKey(well_known_keys::CHILD_STORAGE_KEY_PREFIX.iter().chain(b"test").cloned().collect());
+
+	let read_proof = client.read_proof(vec![StorageKey(KEY.to_vec())], Some(genesis_hash).into()).unwrap();
+	assert_eq!(read_proof.at, genesis_hash);
+	let proof = read_proof.proof.into_iter().map(|node| node.0).collect::<Vec<_>>();
+	assert_eq!(
+		state_machine::read_proof_check::<Blake2Hasher>(state_root, proof, KEY).unwrap(),
+		Some(VALUE.to_vec()),
+	);
+
+	let read_proof = client.read_child_proof(
+		child_key.clone(),
+		vec![StorageKey(b"key".to_vec())],
+		None,
+	).unwrap();
+	let proof = read_proof.proof.into_iter().map(|node| node.0).collect::<Vec<_>>();
+	assert_eq!(
+		state_machine::read_child_proof_check::<Blake2Hasher>(state_root, proof, &child_key.0, b"key").unwrap(),
+		Some(vec![42_u8]),
+	);
+}
+
+#[test]
+fn should_reject_read_proof_of_too_many_keys() {
+	let core = tokio::runtime::Runtime::new().unwrap();
+	let client = State::new(Arc::new(test_client::new()), Subscriptions::new(Arc::new(core.executor())));
+	let keys = (0..READ_PROOF_MAX_KEYS + 1).map(|i| StorageKey((i as u32).encode())).collect::<Vec<_>>();
+
+	assert_matches!(
+		client.read_proof(keys.clone(), None),
+		Err(Error::TooManyQueriedKeys { max: READ_PROOF_MAX_KEYS })
+	);
+	assert_matches!(
+		client.read_child_proof(StorageKey(b":child_storage:default:test".to_vec()), keys, None),
+		Err(Error::TooManyQueriedKeys { max: READ_PROOF_MAX_KEYS })
+	);
 }
 
 #[test]
@@ -163,6 +225,69 @@ fn should_send_initial_storage_changes_and_notifications() {
 	assert_eq!(core.block_on(next.into_future()).unwrap().0, None);
 }
 
+#[test]
+fn should_notify_about_events_until_unsubscribed() {
+	let mut core = tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+	let (subscriber, id, transport) = Subscriber::new_test("test");
+
+	let api = State::new(Arc::new(test_client::new()), Subscriptions::new(Arc::new(remote)));
+
+	// the test runtime has no metadata, decode its events like a runtime with balances
+	let metadata = RuntimeMetadataPrefixed(0, RuntimeMetadata::V7(RuntimeMetadataV7 {
+		modules: DecodeDifferent::Decoded(vec![
+			module_metadata("System", Some(vec![("ExtrinsicSuccess", vec![])])),
+			module_metadata("Balances", Some(vec![
+				("Transfer", vec!["AccountId", "AccountId", "Balance", "Balance"]),
+			])),
+		]),
+	}));
+	let mut types = TypeRegistry::default();
+	types.register("AccountId", "[u8; 32]");
+	types.register("Balance", "u128");
+	let decoder = EventDecoder::new(metadata, types).unwrap();
+	let events_key = decoder.events_key().to_vec();
+	let spec_version = api.client.runtime_version_at(&BlockId::Number(0)).unwrap().spec_version;
+	api.event_decoders.lock().insert(spec_version, Arc::new(decoder));
+
+	api.subscribe_events(Default::default(), subscriber, None);
+
+	// assert id assigned
+	assert_eq!(core.block_on(id), Ok(Ok(SubscriptionId::Number(1))));
+
+	let mut records = Compact(2u32).encode();
+	// phase, module index, event index, arguments, topics
+	records.extend((0u8, 0u32, 1u8, 0u8).encode());
+	records.extend(([1u8; 32], [2u8; 32], 100u128, 1u128).encode());
+	records.extend(Vec::<H256>::new().encode());
+	records.extend((0u8, 0u32, 0u8, 0u8, Vec::<H256>::new()).encode());
+	let import_events = || {
+		let mut builder = api.client.new_block(Default::default()).unwrap();
+		builder.push_storage_change(events_key.clone(), Some(records.clone())).unwrap();
+		api.client.import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
+		api.client.info().chain.best_hash
+	};
+	let block = import_events();
+
+	// assert decoded events sent to transport
+	let (notification, next) = core.block_on(transport.into_future()).unwrap();
+	let notification: serde_json::Value = serde_json::from_str(&notification.unwrap()).unwrap();
+	let result = &notification["params"]["result"];
+	assert_eq!(result["block"], serde_json::to_value(block).unwrap());
+	let events = result["events"].as_array().unwrap();
+	assert_eq!(events.len(), 2);
+	assert_eq!(events[0]["module"], "Balances");
+	assert_eq!(events[0]["name"], "Transfer");
+	assert_eq!(events[1]["module"], "System");
+	assert_eq!(events[1]["name"], "ExtrinsicSuccess");
+
+	// no more notifications once unsubscribed
+	assert_eq!(api.unsubscribe_events(None, SubscriptionId::Number(1)), Ok(true));
+	assert_eq!(api.unsubscribe_events(None, SubscriptionId::Number(1)), Ok(false));
+	import_events();
+	assert_eq!(core.block_on(next.into_future()).unwrap().0, None);
+}
+
 #[test]
 fn should_query_storage() {
 	fn run_tests(client: Arc<TestClient>) {
@@ -237,12 +362,43 @@ fn should_query_storage() {
 			],
 		});
 		assert_eq!(result.unwrap(), expected);
+
+		// Too many keys are rejected before the range is queried
+		let keys = (0..QUERY_STORAGE_MAX_KEYS + 1).map(|k| StorageKey((k as u32).encode())).collect();
+		assert_matches!(
+			api.query_storage(keys, genesis_hash, None.into()),
+			Err(Error::TooManyQueriedKeys { max: QUERY_STORAGE_MAX_KEYS })
+		);
 	}
 
 	run_tests(Arc::new(test_client::new()));
 	run_tests(Arc::new(TestClientBuilder::new().set_support_changes_trie(true).build()));
 }
 
+#[test]
+fn should_return_keys_paged() {
+	let core = tokio::runtime::Runtime::new().unwrap();
+	let mut builder = TestClientBuilder::new();
+	let key = |i: u8| StorageKey(b":paged".iter().cloned().chain(Some(i)).collect());
+	for i in 0..5u8 {
+		builder = builder.add_extra_storage(key(i).0, vec![i]);
+	}
+	let api = State::new(Arc::new(builder.build()), Subscriptions::new(Arc::new(core.executor())));
+	let prefix = Some(StorageKey(b":paged".to_vec()));
+
+	let first_page = api.storage_keys_paged(prefix.clone(), 3, None, None).unwrap();
+	assert_eq!(first_page, (0..3).map(key).collect::<Vec<_>>());
+
+	let second_page = api.storage_keys_paged(prefix.clone(), 3, first_page.last().cloned(), None).unwrap();
+	assert_eq!(second_page, (3..5).map(key).collect::<Vec<_>>());
+
+	assert_matches!(
+		api.storage_keys_paged(prefix, STORAGE_KEYS_PAGED_MAX_COUNT + 1, None, None),
+		Err(Error::InvalidCount { .. })
+	);
+	assert_eq!(api.storage_keys(StorageKey(b":paged".to_vec()), None).unwrap().len(), 5);
+}
+
 #[test]
 fn should_split_ranges() {
 	assert_eq!(split_range(1, None), (0..1, None));
@@ -295,3 +451,132 @@ fn should_notify_on_runtime_version_initially() {
 		// no more notifications on this channel
 	assert_eq!(core.block_on(next.into_future()).unwrap().0, None);
 }
+
+fn module_metadata(name: &str, events: Option<Vec<(&str, Vec<&str>)>>) -> ModuleMetadata {
+	let storage = if name == "System" {
+		Some(DecodeDifferent::Decoded(StorageMetadata {
+			prefix: DecodeDifferent::Decoded("System".into()),
+			entries: DecodeDifferent::Decoded(vec![StorageEntryMetadata {
+				name: DecodeDifferent::Decoded("Events".into()),
+				modifier: StorageEntryModifier::Default,
+				ty: StorageEntryType::Plain(DecodeDifferent::Decoded("Vec<EventRecord<Event>>".into())),
+				default: DecodeDifferent::Decoded(vec![0]),
+				documentation: DecodeDifferent::Decoded(vec![]),
+			}]),
+		}))
+	} else {
+		None
+	};
+
+	ModuleMetadata {
+		name: DecodeDifferent::Decoded(name.into()),
+		storage,
+		calls: None,
+		event: events.map(|events| DecodeDifferent::Decoded(events.into_iter().map(|(name, arguments)| EventMetadata {
+			name: DecodeDifferent::Decoded(name.into()),
+			arguments: DecodeDifferent::Decoded(arguments.into_iter().map(Into::into).collect()),
+			documentation: DecodeDifferent::Decoded(vec![]),
+		}).collect())),
+		constants: DecodeDifferent::Decoded(vec![]),
+	}
+}
+
+#[test]
+fn should_decode_and_filter_events() {
+	let metadata = || RuntimeMetadataPrefixed(0, RuntimeMetadata::V7(RuntimeMetadataV7 {
+		modules: DecodeDifferent::Decoded(vec![
+			module_metadata("System", Some(vec![("ExtrinsicSuccess", vec![])])),
+			module_metadata("Timestamp", None),
+			module_metadata("Balances", Some(vec![
+				("NewAccount", vec!["AccountId", "Balance"]),
+				("Transfer", vec!["AccountId", "AccountId", "Balance", "Balance"]),
+			])),
+			module_metadata("Staking", Some(vec![
+				("OfflineWarning", vec!["AccountId", "u32", "Vec<(u8, Option<Compact<Balance>>)>"]),
+			])),
+		]),
+	}));
+	let mut types = TypeRegistry::default();
+	types.register("AccountId", "[u8; 32]");
+	types.register("Balance", "u128");
+	let decoder = EventDecoder::new(metadata(), types).unwrap();
+	assert_eq!(decoder.events_key(), &primitives::twox_128(b"System Events")[..]);
+
+	let transfer = ([1u8; 32], [2u8; 32], 100u128, 1u128).encode();
+	let warning = ([3u8; 32], 5u32, vec![(1u8, Some(Compact(7u128))), (2u8, None)]).encode();
+	let mut records = Compact(4u32).encode();
+	// phase, module index, event index, arguments, topics
+	records.extend((0u8, 0u32, 1u8, 1u8).encode());
+	records.extend(&transfer);
+	records.extend(vec![H256::repeat_byte(9)].encode());
+	records.extend((0u8, 0u32, 0u8, 0u8, Vec::<H256>::new()).encode());
+	records.extend((1u8, 2u8, 0u8).encode());
+	records.extend(&warning);
+	records.extend(Vec::<H256>::new().encode());
+	records.extend((0u8, 1u32, 0u8, 0u8, Vec::<H256>::new()).encode());
+
+	let (events, err) = decoder.decode(&records);
+	assert_eq!(err, None);
+	assert_eq!(events.len(), 4);
+	assert_eq!(events[0].module, "Balances");
+	assert_eq!(events[0].name, "Transfer");
+	assert_eq!(events[0].phase, Phase::ApplyExtrinsic(0));
+	assert_eq!(events[0].data.0, transfer);
+	assert_eq!(events[0].topics, vec![H256::repeat_byte(9)]);
+	assert_eq!(events[2].name, "OfflineWarning");
+	assert_eq!(events[2].phase, Phase::Finalization);
+	assert_eq!(events[2].data.0, warning);
+
+	let filter = EventFilter { module: Some("System".into()), event: None, phase: None };
+	assert_eq!(events.iter().filter(|e| filter.matches(e)).count(), 2);
+	let filter = EventFilter { module: None, event: None, phase: Some(PhaseFilter::Finalization) };
+	assert_eq!(events.iter().filter(|e| filter.matches(e)).count(), 1);
+	let filter: EventFilter = serde_json::from_str(r#"{"module":"Balances","event":"Transfer"}"#).unwrap();
+	assert_eq!(events.iter().filter(|e| filter.matches(e)).count(), 1);
+	assert!(serde_json::from_str::<EventFilter>(r#"{"module":"Balances","topic":"0x00"}"#).is_err());
+
+	// decoding stops at a truncated record, keeping the records before it
+	let (truncated, err) = decoder.decode(&records[..records.len() - 3]);
+	assert!(err.is_some());
+	assert_eq!(truncated, events[..3].to_vec());
+
+	// a corrupt number of events doesn't preallocate more than the input allows
+	let (events, err) = decoder.decode(&Compact(u32::max_value()).encode());
+	assert!(events.is_empty());
+	assert!(err.is_some());
+
+	// node specific types are not known by default
+	let decoder = EventDecoder::new(metadata(), Default::default()).unwrap();
+	let (events, err) = decoder.decode(&records);
+	assert!(events.is_empty());
+	assert!(err.unwrap().contains("Balances::Transfer"));
+}
+
+#[test]
+fn should_reject_self_referential_types_and_oversized_vectors() {
+	let metadata = RuntimeMetadataPrefixed(0, RuntimeMetadata::V7(RuntimeMetadataV7 {
+		modules: DecodeDifferent::Decoded(vec![
+			module_metadata("System", Some(vec![
+				("Recursive", vec!["Node"]),
+				("Empty", vec!["Vec<()>"]),
+			])),
+		]),
+	}));
+	let mut types = TypeRegistry::default();
+	types.register("Node", "(u8, Node)");
+	let decoder = EventDecoder::new(metadata, types).unwrap();
+
+	let mut records = Compact(1u32).encode();
+	records.extend((0u8, 0u32, 0u8, 0u8).encode());
+	records.extend(vec![0u8; 1024]);
+	let (events, err) = decoder.decode(&records);
+	assert!(events.is_empty());
+	assert!(err.unwrap().contains("nested"));
+
+	let mut records = Compact(1u32).encode();
+	records.extend((0u8, 0u32, 0u8, 1u8).encode());
+	records.extend(Compact(u32::max_value()).encode());
+	let (events, err) = decoder.decode(&records);
+	assert!(events.is_empty());
+	assert!(err.unwrap().contains("exceeds the remaining input"));
+}
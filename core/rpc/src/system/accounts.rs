@@ -0,0 +1,113 @@
+// Copyright 2017-2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Account nonce RPC for runtimes built on `srml-system`.
+//!
+//! `substrate-service` doesn't know the account and index types of the runtime, so it
+//! doesn't register these methods. Nodes register them along with their other RPC
+//! extensions, with `ServiceBuilder::with_rpc_extensions`.
+
+use std::{fmt::Display, sync::Arc};
+
+use client::blockchain::HeaderBackend;
+use codec::{Codec, Encode};
+use jsonrpc_derive::rpc;
+use log::debug;
+use primitives::hexdisplay::HexDisplay;
+use sr_primitives::generic::BlockId;
+use sr_primitives::traits::{ProvideRuntimeApi, SimpleArithmetic, One};
+use srml_system_rpc_runtime_api::AccountNonceApi;
+use transaction_pool::txpool::{self, Pool};
+
+use super::error::{Error, Result};
+
+pub use self::gen_client::Client as AccountsClient;
+
+/// System RPC methods querying account state.
+#[rpc]
+pub trait AccountsApi<AccountId, Index> {
+	/// Returns the next valid index (aka nonce) for given account.
+	///
+	/// This method takes into consideration all pending transactions
+	/// currently in the pool and if no transactions are found in the pool
+	/// it fallbacks to query the index from the runtime (aka. state nonce).
+	#[rpc(name = "system_accountNextIndex", alias("account_nextIndex"))]
+	fn nonce(&self, account: AccountId) -> Result<Index>;
+}
+
+/// An implementation of account RPC methods backed by `AccountNonceApi`.
+pub struct Accounts<P: txpool::ChainApi, C> {
+	client: Arc<C>,
+	pool: Arc<Pool<P>>,
+}
+
+impl<P: txpool::ChainApi, C> Accounts<P, C> {
+	/// Create new `Accounts` given client and transaction pool.
+	pub fn new(client: Arc<C>, pool: Arc<Pool<P>>) -> Self {
+		Accounts {
+			client,
+			pool,
+		}
+	}
+}
+
+impl<P, C, AccountId, Index> AccountsApi<AccountId, Index> for Accounts<P, C>
+where
+	C: ProvideRuntimeApi,
+	C: HeaderBackend<P::Block>,
+	C: Send + Sync + 'static,
+	C::Api: AccountNonceApi<P::Block, AccountId, Index>,
+	P: txpool::ChainApi + Sync + Send + 'static,
+	AccountId: Clone + Display + Codec,
+	Index: Clone + Display + Codec + SimpleArithmetic,
+{
+	fn nonce(&self, account: AccountId) -> Result<Index> {
+		let api = self.client.runtime_api();
+		let best = self.client.info().best_hash;
+		let at = BlockId::hash(best);
+
+		let nonce = api.account_nonce(&at, account.clone())
+			.map_err(|e| Error::RuntimeApi(format!("Unable to query nonce: {:?}", e)))?;
+
+		debug!(target: "rpc", "State nonce for {}: {}", account, nonce);
+		// Now we need to query the transaction pool
+		// and find transactions originating from the same sender.
+		//
+		// Since extrinsics are opaque to us, we look for them using
+		// `provides` tag, which `srml-system` sets to `(sender, nonce)`.
+		// And increment the nonce if we find a transaction that matches
+		// the current one.
+		let mut current_nonce = nonce.clone();
+		let mut current_tag = (account.clone(), nonce).encode();
+		for tx in self.pool.ready() {
+			debug!(
+				target: "rpc",
+				"Current nonce to {}, checking {} vs {:?}",
+				current_nonce,
+				HexDisplay::from(&current_tag),
+				tx.provides.iter().map(|x| format!("{}", HexDisplay::from(x))).collect::<Vec<_>>(),
+			);
+			// since transactions in `ready()` need to be ordered by nonce
+			// it's fine to continue with current iterator.
+			if tx.provides.get(0) == Some(&current_tag) {
+				current_nonce += Index::one();
+				current_tag = (account.clone(), current_nonce.clone()).encode();
+			}
+		}
+
+		Ok(current_nonce)
+	}
+}
@@ -28,6 +28,12 @@ pub enum Error {
 	/// Provided block range couldn't be resolved to a list of blocks.
 	#[display(fmt = "Node is not fully functional: {}", _0)]
 	NotHealthy(Health),
+	/// Runtime API call failed.
+	#[display(fmt = "Runtime API call failed: {}", _0)]
+	RuntimeApi(String),
+	/// Peer argument is malformatted.
+	#[display(fmt = "Peer argument is malformatted: {}", _0)]
+	MalformattedPeerArg(String),
 }
 
 impl std::error::Error for Error {}
@@ -43,6 +49,16 @@ impl From<Error> for rpc::Error {
 				message: format!("{}", e),
 				data: serde_json::to_value(h).ok(),
 			},
+			Error::RuntimeApi(_) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 2),
+				message: format!("{}", e),
+				data: None,
+			},
+			Error::MalformattedPeerArg(_) => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 3),
+				message: format!("{}", e),
+				data: None,
+			},
 		}
 	}
 }
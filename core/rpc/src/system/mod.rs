@@ -16,6 +16,7 @@
 
 //! Substrate system API.
 
+pub mod accounts;
 pub mod error;
 pub mod helpers;
 
@@ -23,12 +24,14 @@ pub mod helpers;
 mod tests;
 
 use crate::helpers::Receiver;
+use crate::policy::DenyUnsafe;
+use crate::rpc::{self, futures::{future, Future}};
 use futures03::{channel::{mpsc, oneshot}, compat::Compat};
 use jsonrpc_derive::rpc;
-use network;
+use network::{self, PeerId, config::NonReservedPeerMode};
 use sr_primitives::traits::{self, Header as HeaderT};
 
-use self::error::Result;
+use self::error::{Error, Result};
 
 pub use self::helpers::{Properties, SystemInfo, Health, PeerInfo};
 pub use self::gen_client::Client as SystemClient;
@@ -70,12 +73,37 @@ pub trait SystemApi<Hash, Number> {
 	// TODO: make this stable and move structs https://github.com/paritytech/substrate/issues/1890
 	#[rpc(name = "system_networkState", returns = "network::NetworkState")]
 	fn system_network_state(&self) -> Receiver<network::NetworkState>;
+
+	/// Adds a reserved peer. Returns the empty string or an error. The string
+	/// parameter should encode a `p2p` multiaddr.
+	///
+	/// `/ip4/198.51.100.19/tcp/30333/p2p/QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV`
+	/// is an example of a valid, passing multiaddr with PeerId attached.
+	///
+	/// This method is unsafe and is denied on RPC servers exposed to untrusted clients.
+	#[rpc(name = "system_addReservedPeer", returns = "()")]
+	fn system_add_reserved_peer(&self, peer: String) -> rpc::BoxFuture<()>;
+
+	/// Remove a reserved peer. Returns the empty string or an error. The string
+	/// should encode only the PeerId e.g. `QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV`.
+	///
+	/// This method is unsafe and is denied on RPC servers exposed to untrusted clients.
+	#[rpc(name = "system_removeReservedPeer", returns = "()")]
+	fn system_remove_reserved_peer(&self, peer_id: String) -> rpc::BoxFuture<()>;
+
+	/// Sets whether connections to non-reserved peers are accepted (`"accept"`) or
+	/// denied (`"deny"`). Switching to `"deny"` disconnects all non-reserved peers.
+	///
+	/// This method is unsafe and is denied on RPC servers exposed to untrusted clients.
+	#[rpc(name = "system_setNonReservedPeerMode", returns = "()")]
+	fn system_set_non_reserved_peer_mode(&self, mode: String) -> rpc::BoxFuture<()>;
 }
 
 /// System API implementation
 pub struct System<B: traits::Block> {
 	info: SystemInfo,
 	send_back: mpsc::UnboundedSender<Request<B>>,
+	deny_unsafe: DenyUnsafe,
 }
 
 /// Request to be processed.
@@ -86,6 +114,12 @@ pub enum Request<B: traits::Block> {
 	Peers(oneshot::Sender<Vec<PeerInfo<B::Hash, <B::Header as HeaderT>::Number>>>),
 	/// Must return the state of the network.
 	NetworkState(oneshot::Sender<network::NetworkState>),
+	/// Must add a reserved peer, given its `p2p` multiaddr, and return the result.
+	NetworkAddReservedPeer(String, oneshot::Sender<std::result::Result<(), String>>),
+	/// Must remove a reserved peer.
+	NetworkRemoveReservedPeer(PeerId, oneshot::Sender<()>),
+	/// Must change the policy for connections to non-reserved peers.
+	NetworkSetNonReservedPeerMode(NonReservedPeerMode, oneshot::Sender<()>),
 }
 
 impl<B: traits::Block> System<B> {
@@ -93,13 +127,17 @@ impl<B: traits::Block> System<B> {
 	///
 	/// The `send_back` will be used to transmit some of the requests. The user is responsible for
 	/// reading from that channel and answering the requests.
+	///
+	/// The `deny_unsafe` decides whether the methods managing the peerset are allowed.
 	pub fn new(
 		info: SystemInfo,
-		send_back: mpsc::UnboundedSender<Request<B>>
+		send_back: mpsc::UnboundedSender<Request<B>>,
+		deny_unsafe: DenyUnsafe,
 	) -> Self {
 		System {
 			info,
 			send_back,
+			deny_unsafe,
 		}
 	}
 }
@@ -138,4 +176,46 @@ impl<B: traits::Block> SystemApi<B::Hash, <B::Header as HeaderT>::Number> for Sy
 		let _ = self.send_back.unbounded_send(Request::NetworkState(tx));
 		Receiver(Compat::new(rx))
 	}
+
+	fn system_add_reserved_peer(&self, peer: String) -> rpc::BoxFuture<()> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return Box::new(future::err(err.into()));
+		}
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkAddReservedPeer(peer, tx));
+		Box::new(Receiver(Compat::new(rx)).and_then(|result| {
+			result.map_err(|e| Error::MalformattedPeerArg(e).into())
+		}))
+	}
+
+	fn system_remove_reserved_peer(&self, peer_id: String) -> rpc::BoxFuture<()> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return Box::new(future::err(err.into()));
+		}
+
+		let peer_id = match peer_id.parse::<PeerId>() {
+			Ok(peer_id) => peer_id,
+			Err(e) => return Box::new(future::err(Error::MalformattedPeerArg(format!("{:?}", e)).into())),
+		};
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkRemoveReservedPeer(peer_id, tx));
+		Box::new(Receiver(Compat::new(rx)))
+	}
+
+	fn system_set_non_reserved_peer_mode(&self, mode: String) -> rpc::BoxFuture<()> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return Box::new(future::err(err.into()));
+		}
+
+		let mode = match NonReservedPeerMode::parse(&mode) {
+			Some(mode) => mode,
+			None => return Box::new(future::err(rpc::Error::invalid_params(
+				format!("Unknown non-reserved peer mode: {}, expected `accept` or `deny`", mode)
+			))),
+		};
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkSetNonReservedPeerMode(mode, tx));
+		Box::new(Receiver(Compat::new(rx)))
+	}
 }
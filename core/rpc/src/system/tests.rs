@@ -21,7 +21,8 @@ use network::config::Roles;
 use test_client::runtime::Block;
 use assert_matches::assert_matches;
 use futures03::{prelude::*, channel::mpsc};
-use std::thread;
+use std::{sync::Arc, thread};
+use crate::policy::UnsafeRpcError;
 
 struct Status {
 	pub peers: usize,
@@ -80,17 +81,33 @@ fn api<T: Into<Option<Status>>>(sync: T) -> System<Block> {
 						peerset: serde_json::Value::Null,
 					});
 				}
+				Request::NetworkAddReservedPeer(peer, sender) => {
+					let _ = match network::config::parse_str_addr(&peer) {
+						Ok(_) => sender.send(Ok(())),
+						Err(s) => sender.send(Err(format!("{:?}", s))),
+					};
+				}
+				Request::NetworkRemoveReservedPeer(_peer_id, sender) => {
+					let _ = sender.send(());
+				}
+				Request::NetworkSetNonReservedPeerMode(_mode, sender) => {
+					let _ = sender.send(());
+				}
 			};
 
 			future::ready(())
 		}))
 	});
-	System::new(SystemInfo {
+	System::new(info(), tx, DenyUnsafe::No)
+}
+
+fn info() -> SystemInfo {
+	SystemInfo {
 		impl_name: "testclient".into(),
 		impl_version: "0.2.0".into(),
 		chain_name: "testchain".into(),
 		properties: Default::default(),
-	}, tx)
+	}
 }
 
 fn wait_receiver<T>(rx: Receiver<T>) -> T {
@@ -220,3 +237,85 @@ fn system_network_state() {
 		}
 	);
 }
+
+#[test]
+fn system_network_add_reserved() {
+	let good_peer_id = "/ip4/198.51.100.19/tcp/30333/p2p/QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV";
+	let bad_peer_id = "/ip4/198.51.100.19/tcp/30333";
+	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+	let good = api(None).system_add_reserved_peer(good_peer_id.into());
+	assert_eq!(runtime.block_on(good), Ok(()));
+	let bad = api(None).system_add_reserved_peer(bad_peer_id.into());
+	assert!(runtime.block_on(bad).is_err());
+}
+
+#[test]
+fn system_network_remove_reserved() {
+	let good_peer_id = "QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV";
+	let bad_peer_id = "/ip4/198.51.100.19/tcp/30333/p2p/QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV";
+	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+	let good = api(None).system_remove_reserved_peer(good_peer_id.into());
+	assert_eq!(runtime.block_on(good), Ok(()));
+	let bad = api(None).system_remove_reserved_peer(bad_peer_id.into());
+	assert!(runtime.block_on(bad).is_err());
+}
+
+#[test]
+fn system_network_set_non_reserved_peer_mode() {
+	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+	let deny = api(None).system_set_non_reserved_peer_mode("deny".into());
+	assert_eq!(runtime.block_on(deny), Ok(()));
+	let unknown = api(None).system_set_non_reserved_peer_mode("reject".into());
+	assert!(runtime.block_on(unknown).is_err());
+}
+
+#[test]
+fn system_reserved_peers_are_unsafe() {
+	let (tx, _rx) = mpsc::unbounded();
+	let api = System::<Block>::new(info(), tx, DenyUnsafe::Yes);
+	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+	assert_eq!(
+		runtime.block_on(api.system_add_reserved_peer("QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV".into())),
+		Err(UnsafeRpcError.into()),
+	);
+	assert_eq!(
+		runtime.block_on(api.system_set_non_reserved_peer_mode("deny".into())),
+		Err(UnsafeRpcError.into()),
+	);
+}
+
+#[test]
+fn system_account_next_index_counts_pending_transactions() {
+	use self::accounts::{Accounts, AccountsApi};
+	use test_client::{prelude::*, consensus::BlockOrigin, runtime::{AccountId, Transfer}};
+	use transaction_pool::{txpool::Pool, ChainApi};
+	use sr_primitives::generic::BlockId;
+
+	let transfer = |nonce| Transfer {
+		from: AccountKeyring::Alice.into(),
+		to: AccountKeyring::Ferdie.into(),
+		amount: 42,
+		nonce,
+	};
+	let client = Arc::new(test_client::new());
+	let mut builder = client.new_block(Default::default()).unwrap();
+	builder.push_transfer(transfer(0)).unwrap();
+	client.import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
+
+	let pool = Arc::new(Pool::new(Default::default(), ChainApi::new(client.clone())));
+	let accounts = Accounts::new(client, pool.clone());
+	let nonce = |account: AccountKeyring| AccountsApi::<AccountId, u64>::nonce(&accounts, account.into());
+
+	// the state nonce, without pending transactions
+	assert_eq!(nonce(AccountKeyring::Alice).unwrap(), 1);
+	assert_eq!(nonce(AccountKeyring::Bob).unwrap(), 0);
+
+	pool.submit_one(&BlockId::number(1), transfer(1).into_signed_tx()).unwrap();
+	pool.submit_one(&BlockId::number(1), transfer(2).into_signed_tx()).unwrap();
+	assert_eq!(nonce(AccountKeyring::Alice).unwrap(), 3);
+	assert_eq!(nonce(AccountKeyring::Bob).unwrap(), 0);
+}
@@ -17,7 +17,7 @@
 use crate::{NewService, NetworkStatus, NetworkState, error::{self, Error}, DEFAULT_PROTOCOL_ID};
 use crate::{SpawnTaskHandle, start_rpc_servers, build_network_future, TransactionPoolAdapter};
 use crate::TaskExecutor;
-use crate::config::Configuration;
+use crate::config::{Configuration, RpcEventTypes};
 use client::{BlockchainEvents, Client, runtime_api};
 use codec::{Decode, Encode, IoReader};
 use consensus_common::import_queue::ImportQueue;
@@ -26,19 +26,57 @@ use futures03::{FutureExt as _, compat::Compat, StreamExt as _, TryStreamExt as
 use keystore::{Store as Keystore, KeyStorePtr};
 use log::{info, warn};
 use network::{FinalityProofProvider, OnDemand, NetworkService, NetworkStateInfo};
-use network::{config::BoxFinalityProofRequestBuilder, specialization::NetworkSpecialization};
+use network::{config::{BoxFinalityProofRequestBuilder, BoxWarpSyncVerifier}, specialization::NetworkSpecialization};
 use parking_lot::{Mutex, RwLock};
 use primitives::{Blake2Hasher, H256, Hasher};
 use rpc::{self, system::SystemInfo};
 use sr_primitives::{BuildStorage, generic::BlockId};
 use sr_primitives::traits::{Block as BlockT, ProvideRuntimeApi, NumberFor, One, Zero, Header, SaturatedConversion};
-use substrate_executor::{NativeExecutor, NativeExecutionDispatch};
+use substrate_executor::{NativeExecutor, NativeExecutionDispatch, WasmDiskCache};
 use serde::{Serialize, de::DeserializeOwned};
 use std::{io::{Read, Write, Seek}, marker::PhantomData, sync::Arc, sync::atomic::AtomicBool};
 use sysinfo::{get_current_pid, ProcessExt, System, SystemExt};
 use tel::{telemetry, SUBSTRATE_INFO};
 use transaction_pool::txpool::{self, ChainApi, Pool as TransactionPool};
 
+/// A utility trait for building an RPC extension given a `DenyUnsafe` instance.
+///
+/// The RPC extensions are built for every RPC server separately, since only the
+/// servers that are not exposed to untrusted clients may serve unsafe methods.
+pub trait RpcExtensionBuilder {
+	/// The type of the RPC extension that will be built.
+	type Output: rpc::RpcExtension<rpc::Metadata>;
+
+	/// Returns an instance of the RPC extension for a particular `DenyUnsafe` value,
+	/// e.g. the RPC extension might deny some of its methods.
+	fn build(&self, deny_unsafe: rpc::DenyUnsafe) -> Self::Output;
+}
+
+impl<F, R> RpcExtensionBuilder for F where
+	F: Fn(rpc::DenyUnsafe) -> R,
+	R: rpc::RpcExtension<rpc::Metadata>,
+{
+	type Output = R;
+
+	fn build(&self, deny_unsafe: rpc::DenyUnsafe) -> R {
+		(*self)(deny_unsafe)
+	}
+}
+
+/// An `RpcExtensionBuilder` returning clones of the same RPC extension, regardless
+/// of the `DenyUnsafe` value. Used for RPC extensions without unsafe methods.
+pub struct NoopRpcExtensionBuilder<R>(pub R);
+
+impl<R> RpcExtensionBuilder for NoopRpcExtensionBuilder<R> where
+	R: Clone + rpc::RpcExtension<rpc::Metadata>,
+{
+	type Output = R;
+
+	fn build(&self, _deny_unsafe: rpc::DenyUnsafe) -> R {
+		self.0.clone()
+	}
+}
+
 /// Aggregator for the components required to build a service.
 ///
 /// # Usage
@@ -66,6 +104,7 @@ pub struct ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFpr
 	import_queue: TImpQu,
 	finality_proof_request_builder: Option<TFprb>,
 	finality_proof_provider: Option<TFpp>,
+	warp_sync_verifier: Option<BoxWarpSyncVerifier<TBl>>,
 	network_protocol: TNetP,
 	transaction_pool: Arc<TExPool>,
 	rpc_extensions: TRpc,
@@ -95,20 +134,16 @@ where TGen: Serialize + DeserializeOwned + BuildStorage {
 		(),
 		(),
 		(),
-		()
+		NoopRpcExtensionBuilder<()>
 	>, Error> {
 		let keystore = Keystore::open(config.keystore_path.clone(), config.keystore_password.clone())?;
 
-		let db_settings = client_db::DatabaseSettings {
-			cache_size: None,
-			state_cache_size: config.state_cache_size,
-			state_cache_child_ratio:
-				config.state_cache_child_ratio.map(|v| (v, 100)),
-			path: config.database_path.clone(),
-			pruning: config.pruning.clone(),
-		};
+		let db_settings = database_settings(&config);
 
-		let executor = NativeExecutor::<TExecDisp>::new(config.default_heap_pages);
+		let executor = NativeExecutor::<TExecDisp>::with_wasm_method(
+			config.wasm_method,
+			config.default_heap_pages,
+		).with_disk_cache(config.wasm_cache_path.clone().map(WasmDiskCache::new));
 
 		let client = Arc::new(client_db::new_client(
 			db_settings,
@@ -116,6 +151,7 @@ where TGen: Serialize + DeserializeOwned + BuildStorage {
 			&config.chain_spec,
 			config.execution_strategies.clone(),
 			Some(keystore.clone()),
+			config.consensus_failure_path.clone(),
 		)?);
 
 		Ok(ServiceBuilder {
@@ -127,9 +163,10 @@ where TGen: Serialize + DeserializeOwned + BuildStorage {
 			import_queue: (),
 			finality_proof_request_builder: None,
 			finality_proof_provider: None,
+			warp_sync_verifier: None,
 			network_protocol: (),
 			transaction_pool: Arc::new(()),
-			rpc_extensions: Default::default(),
+			rpc_extensions: NoopRpcExtensionBuilder(()),
 			marker: PhantomData,
 		})
 	}
@@ -177,7 +214,7 @@ where TGen: Serialize + DeserializeOwned + BuildStorage {
 		(),
 		(),
 		(),
-		()
+		NoopRpcExtensionBuilder<()>
 	>, Error> {
 		let keystore = Keystore::open(config.keystore_path.clone(), config.keystore_password.clone())?;
 
@@ -188,9 +225,13 @@ where TGen: Serialize + DeserializeOwned + BuildStorage {
 				config.state_cache_child_ratio.map(|v| (v, 100)),
 			path: config.database_path.clone(),
 			pruning: config.pruning.clone(),
+			engine: config.database,
 		};
 
-		let executor = NativeExecutor::<TExecDisp>::new(config.default_heap_pages);
+		let executor = NativeExecutor::<TExecDisp>::with_wasm_method(
+			config.wasm_method,
+			config.default_heap_pages,
+		).with_disk_cache(config.wasm_cache_path.clone().map(WasmDiskCache::new));
 
 		let db_storage = client_db::light::LightStorage::new(db_settings)?;
 		let light_blockchain = client::light::new_light_blockchain(db_storage);
@@ -208,9 +249,10 @@ where TGen: Serialize + DeserializeOwned + BuildStorage {
 			import_queue: (),
 			finality_proof_request_builder: None,
 			finality_proof_provider: None,
+			warp_sync_verifier: None,
 			network_protocol: (),
 			transaction_pool: Arc::new(()),
-			rpc_extensions: Default::default(),
+			rpc_extensions: NoopRpcExtensionBuilder(()),
 			marker: PhantomData,
 		})
 	}
@@ -246,6 +288,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPo
 			import_queue: self.import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
@@ -286,6 +329,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPo
 			import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
@@ -310,6 +354,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPo
 			import_queue: self.import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
@@ -347,6 +392,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPo
 			import_queue: self.import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
@@ -376,6 +422,17 @@ impl<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPo
 		self.with_opt_finality_proof_provider(|client| build(client).map(Option::Some))
 	}
 
+	/// Defines which verifier to use for warp syncing to the most recent finalized block.
+	///
+	/// The verifier is only used when the node is configured to use fast sync.
+	pub fn with_warp_sync_verifier(
+		mut self,
+		builder: impl FnOnce(Arc<TCl>) -> Result<Option<BoxWarpSyncVerifier<TBl>>, Error>
+	) -> Result<Self, Error> {
+		self.warp_sync_verifier = builder(self.client.clone())?;
+		Ok(self)
+	}
+
 	/// Defines which import queue to use.
 	pub fn with_import_queue_and_opt_fprb<UImpQu, UFprb>(
 		mut self,
@@ -400,6 +457,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPo
 			import_queue,
 			finality_proof_request_builder: fprb,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions: self.rpc_extensions,
@@ -435,6 +493,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPo
 			import_queue: self.import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: Arc::new(transaction_pool),
 			rpc_extensions: self.rpc_extensions,
@@ -443,11 +502,25 @@ impl<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPo
 	}
 
 	/// Defines the RPC extensions to use.
+	///
+	/// The same extensions are served on every RPC server. Use
+	/// [`with_rpc_extensions_builder`](ServiceBuilder::with_rpc_extensions_builder)
+	/// for extensions with unsafe methods.
 	pub fn with_rpc_extensions<URpc>(
 		self,
 		rpc_ext_builder: impl FnOnce(Arc<TCl>, Arc<TExPool>) -> URpc
 	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
-		TNetP, TExPool, URpc>, Error> {
+		TNetP, TExPool, NoopRpcExtensionBuilder<URpc>>, Error> {
+		self.with_rpc_extensions_builder(|client, pool| NoopRpcExtensionBuilder(rpc_ext_builder(client, pool)))
+	}
+
+	/// Defines a builder of the RPC extensions to use. The extensions are built for every
+	/// RPC server, knowing whether unsafe methods should be denied on it.
+	pub fn with_rpc_extensions_builder<URpcBuilder>(
+		self,
+		rpc_ext_builder: impl FnOnce(Arc<TCl>, Arc<TExPool>) -> URpcBuilder
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
+		TNetP, TExPool, URpcBuilder>, Error> {
 		let rpc_extensions = rpc_ext_builder(self.client.clone(), self.transaction_pool.clone());
 
 		Ok(ServiceBuilder {
@@ -459,6 +532,7 @@ impl<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPo
 			import_queue: self.import_queue,
 			finality_proof_request_builder: self.finality_proof_request_builder,
 			finality_proof_provider: self.finality_proof_provider,
+			warp_sync_verifier: self.warp_sync_verifier,
 			network_protocol: self.network_protocol,
 			transaction_pool: self.transaction_pool,
 			rpc_extensions,
@@ -467,6 +541,19 @@ impl<TBl, TRtApi, TCfg, TGen, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPo
 	}
 }
 
+/// Settings of the full node database described by the given configuration.
+pub(crate) fn database_settings<C, G>(config: &Configuration<C, G>) -> client_db::DatabaseSettings {
+	client_db::DatabaseSettings {
+		cache_size: None,
+		state_cache_size: config.state_cache_size,
+		state_cache_child_ratio:
+			config.state_cache_child_ratio.map(|v| (v, 100)),
+		path: config.database_path.clone(),
+		pruning: config.pruning.clone(),
+		engine: config.database,
+	}
+}
+
 /// Implemented on `ServiceBuilder`. Allows importing blocks once you have given all the required
 /// components to the builder.
 pub trait ServiceBuilderImport {
@@ -495,6 +582,28 @@ pub trait ServiceBuilderExport {
 	) -> Result<(), Error>;
 }
 
+/// Implemented on `ServiceBuilder`. Allows exporting and importing the full state at a finalized
+/// block once you have given all the required components to the builder.
+pub trait ServiceBuilderState {
+	/// Type of block of the builder.
+	type Block: BlockT;
+
+	/// Performs the export of the full state at a finalized block, the last finalized
+	/// one if `block` is `None`, along with the proof of its finality.
+	fn export_state(
+		&self,
+		output: impl Write,
+		block: Option<NumberFor<Self::Block>>,
+	) -> Result<(), Error>;
+
+	/// Imports a state snapshot into a database which only knows the genesis block, once its
+	/// finality is checked by the warp sync verifier.
+	fn import_state(
+		self,
+		input: impl Read,
+	) -> Result<(), Error>;
+}
+
 /// Implemented on `ServiceBuilder`. Allows reverting the chain once you have given all the
 /// required components to the builder.
 pub trait ServiceBuilderRevert {
@@ -553,6 +662,33 @@ where
 	}
 }
 
+impl<TBl, TRtApi, TCfg, TGen, TBackend, TExec, TFchr, TSc, TImpQu, TFprb, TNetP, TExPool, TRpc>
+	ServiceBuilderState for ServiceBuilder<TBl, TRtApi, TCfg, TGen, Client<TBackend, TExec, TBl, TRtApi>,
+		TFchr, TSc, TImpQu, TFprb, Arc<FinalityProofProvider<TBl>>, TNetP, TExPool, TRpc>
+where
+	TBl: BlockT<Hash = <Blake2Hasher as Hasher>::Out>,
+	TBackend: 'static + client::backend::Backend<TBl, Blake2Hasher> + Send,
+	TExec: 'static + client::CallExecutor<TBl, Blake2Hasher> + Send + Sync + Clone
+{
+	type Block = TBl;
+
+	fn export_state(
+		&self,
+		output: impl Write,
+		block: Option<NumberFor<TBl>>,
+	) -> Result<(), Error> {
+		let finality_proof_provider = self.finality_proof_provider.as_ref().map(|provider| &**provider);
+		crate::chain_ops::export_state(&self.client, finality_proof_provider, block, output)
+	}
+
+	fn import_state(
+		self,
+		input: impl Read,
+	) -> Result<(), Error> {
+		crate::chain_ops::import_state(&self.client, self.warp_sync_verifier, input)
+	}
+}
+
 impl<TBl, TRtApi, TCfg, TGen, TBackend, TExec, TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPool, TRpc>
 	ServiceBuilderRevert for ServiceBuilder<TBl, TRtApi, TCfg, TGen, Client<TBackend, TExec, TBl, TRtApi>,
 		TFchr, TSc, TImpQu, TFprb, TFpp, TNetP, TExPool, TRpc>
@@ -604,7 +740,7 @@ ServiceBuilder<
 	TImpQu: 'static + ImportQueue<TBl>,
 	TNetP: NetworkSpecialization<TBl>,
 	TExPoolApi: 'static + ChainApi<Block = TBl, Hash = <TBl as BlockT>::Hash>,
-	TRpc: rpc::RpcExtension<rpc::Metadata> + Clone,
+	TRpc: RpcExtensionBuilder,
 {
 	/// Builds the service.
 	pub fn build(self) -> Result<NewService<
@@ -634,6 +770,7 @@ ServiceBuilder<
 			import_queue,
 			finality_proof_request_builder,
 			finality_proof_provider,
+			warp_sync_verifier,
 			network_protocol,
 			transaction_pool,
 			rpc_extensions
@@ -645,6 +782,7 @@ ServiceBuilder<
 			self.import_queue,
 			self.finality_proof_request_builder,
 			self.finality_proof_provider,
+			self.warp_sync_verifier,
 			self.network_protocol,
 			self.transaction_pool,
 			self.rpc_extensions
@@ -662,14 +800,15 @@ ServiceBuilder<
 					import_queue,
 					finality_proof_request_builder,
 					finality_proof_provider,
+					warp_sync_verifier,
 					network_protocol,
 					transaction_pool,
 					rpc_extensions
 				))
 			},
 			|h, c, tx| maintain_transaction_pool(h, c, tx),
-			|n, o, p, ns, v| offchain_workers(n, o, p, ns, v),
-			|c, ssb, si, te, tp, ext, ks| start_rpc(c, ssb, si, te, tp, ext, ks),
+			|h, b, a, o, p, ns, v| offchain_workers(h, b, a, o, p, ns, v),
+			|c, ssb, si, te, tp, ext, ks, et, du, rm| start_rpc(c, ssb, si, te, tp, ext, ks, et, du, rm),
 		)
 	}
 }
@@ -682,6 +821,9 @@ pub(crate) fn start_rpc<Api, Backend, Block, Executor, PoolApi>(
 	transaction_pool: Arc<TransactionPool<PoolApi>>,
 	rpc_extensions: impl rpc::RpcExtension<rpc::Metadata>,
 	keystore: KeyStorePtr,
+	event_types: RpcEventTypes,
+	deny_unsafe: rpc::DenyUnsafe,
+	rpc_middleware: rpc_servers::RpcMiddleware,
 ) -> rpc_servers::RpcHandler<rpc::Metadata>
 where
 	Block: BlockT<Hash = <Blake2Hasher as primitives::Hasher>::Out>,
@@ -695,14 +837,16 @@ where
 	use rpc::{chain, state, author, system};
 	let subscriptions = rpc::Subscriptions::new(task_executor.clone());
 	let chain = chain::Chain::new(client.clone(), subscriptions.clone());
-	let state = state::State::new(client.clone(), subscriptions.clone());
+	let state = state::State::new(client.clone(), subscriptions.clone())
+		.with_event_types(event_types);
 	let author = rpc::author::Author::new(
 		client,
 		transaction_pool,
 		subscriptions,
 		keystore,
+		deny_unsafe,
 	);
-	let system = system::System::new(rpc_system_info, system_send_back);
+	let system = system::System::new(rpc_system_info, system_send_back, deny_unsafe);
 
 	rpc_servers::rpc_handler((
 		state::StateApi::to_delegate(state),
@@ -710,7 +854,7 @@ where
 		author::AuthorApi::to_delegate(author),
 		system::SystemApi::to_delegate(system),
 		rpc_extensions,
-	))
+	), rpc_middleware)
 }
 
 pub(crate) fn maintain_transaction_pool<Api, Backend, Block, Executor, PoolApi>(
@@ -740,7 +884,9 @@ pub(crate) fn maintain_transaction_pool<Api, Backend, Block, Executor, PoolApi>(
 }
 
 pub(crate) fn offchain_workers<Api, Backend, Block, Executor, PoolApi>(
-	number: &NumberFor<Block>,
+	header: &Block::Header,
+	is_new_best: bool,
+	non_finalized_ancestors: &[Block::Hash],
 	offchain: &offchain::OffchainWorkers<
 		Client<Backend, Executor, Block, Api>,
 		<Backend as client::backend::Backend<Block, Blake2Hasher>>::OffchainStorage,
@@ -760,8 +906,14 @@ where
 	Executor: client::CallExecutor<Block, Blake2Hasher> + 'static,
 	PoolApi: txpool::ChainApi<Hash = Block::Hash, Block = Block> + 'static,
 {
-	let future = offchain.on_block_imported(number, pool, network_state.clone(), is_validator)
-		.map(|()| Ok(()));
+	let future = offchain.on_block_imported(
+		header,
+		is_new_best,
+		non_finalized_ancestors,
+		pool,
+		network_state.clone(),
+		is_validator,
+	).map(|()| Ok(()));
 	Ok(Box::new(Compat::new(future)))
 }
 
@@ -16,9 +16,22 @@
 
 //! Chain utilities.
 
+use std::io::{Read, Write};
+
+use client::{Client, CallExecutor, backend::{Backend, StateEntry}, error::Error as ClientError};
+use codec::{Decode, Encode, IoReader};
+use log::{info, warn, error};
+use network::FinalityProofProvider;
+use network::config::{BoxWarpSyncVerifier, WarpSyncProgress};
+use primitives::{Blake2Hasher, H256, HexDisplay, storage::well_known_keys};
+use sr_primitives::generic::BlockId;
+use sr_primitives::traits::{Block as BlockT, Header as HeaderT, NumberFor, Zero};
+use state_machine::backend::Backend as StateBackend;
+
 use crate::RuntimeGenesis;
-use crate::error;
+use crate::error::{self, Error};
 use crate::chain_spec::ChainSpec;
+use crate::config::Configuration;
 
 #[macro_export]
 macro_rules! export_blocks {
@@ -224,3 +237,223 @@ pub fn build_spec<G>(spec: ChainSpec<G>, raw: bool) -> error::Result<String>
 {
 	Ok(spec.to_json(raw)?)
 }
+
+/// Magic bytes every state snapshot starts with.
+const STATE_SNAPSHOT_MAGIC: [u8; 4] = *b"sbss";
+/// Version of the state snapshot format.
+const STATE_SNAPSHOT_VERSION: u32 = 2;
+
+/// Leading part of a state snapshot, followed by a sequence of `StateSnapshotEntry`.
+#[derive(Encode, Decode)]
+struct StateSnapshotHeader<H> {
+	magic: [u8; 4],
+	version: u32,
+	/// Genesis header of the chain the snapshot belongs to.
+	genesis: H,
+	/// Header of the block the state is taken at.
+	header: H,
+	/// Proof of the finality of that block since genesis, checked by a `WarpSyncVerifier`.
+	finality_proof: Vec<u8>,
+}
+
+/// A single storage entry of a state snapshot.
+#[derive(Encode, Decode)]
+enum StateSnapshotEntry {
+	/// Top-level key and value.
+	Top(Vec<u8>, Vec<u8>),
+	/// Child storage key, key and value.
+	Child(Vec<u8>, Vec<u8>, Vec<u8>),
+	/// End of the snapshot. A snapshot without it is truncated.
+	End,
+}
+
+/// Iterate over the keys of a trie in order, given the function returning the key following
+/// another one and the value of the empty key.
+fn keys_in_order<E>(
+	has_empty_key: bool,
+	mut next_key: impl FnMut(&[u8]) -> Result<Option<Vec<u8>>, E>,
+) -> impl Iterator<Item=Result<Vec<u8>, E>> {
+	let mut next = if has_empty_key { Some(Ok(Vec::new())) } else { next_key(&[]).transpose() };
+	std::iter::from_fn(move || {
+		let key = next.take()?;
+		if let Ok(ref key) = key {
+			next = next_key(key).transpose();
+		}
+		Some(key)
+	})
+}
+
+/// Write the full state at the given finalized block, the last finalized one by default,
+/// to `output`, along with the proof of its finality built by `finality_proof_provider`.
+pub fn export_state<B, E, Block, RA>(
+	client: &Client<B, E, Block, RA>,
+	finality_proof_provider: Option<&dyn FinalityProofProvider<Block>>,
+	block: Option<NumberFor<Block>>,
+	mut output: impl Write,
+) -> error::Result<()> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	let finalized_number = client.info().chain.finalized_number;
+	let number = block.unwrap_or(finalized_number);
+	if number > finalized_number {
+		return Err(Error::Other(format!(
+			"Block #{} is not finalized, the last finalized block is #{}", number, finalized_number,
+		)));
+	}
+
+	let id = BlockId::Number(number);
+	let header = client.header(&id)?
+		.ok_or_else(|| Error::Other(format!("Unknown block #{}", number)))?;
+	let genesis = client.header(&BlockId::Number(Zero::zero()))?
+		.ok_or_else(|| Error::Other("Genesis block is missing".into()))?;
+	let finality_proof = finality_proof_provider
+		.ok_or_else(|| Error::Other("Exporting state requires a finality proof provider".into()))?
+		.prove_warp_to(header.hash())?
+		.ok_or_else(|| Error::Other("The finality proof provider does not support warp sync".into()))?;
+	info!("Exporting state at #{} ({})", number, header.hash());
+
+	output.write_all(&StateSnapshotHeader {
+		magic: STATE_SNAPSHOT_MAGIC,
+		version: STATE_SNAPSHOT_VERSION,
+		genesis,
+		header,
+		finality_proof,
+	}.encode())?;
+
+	// The keys are read one after the other so that the state is never held in memory.
+	let state = client.state_at(&id)?;
+	let state_err = |e| Error::Other(format!("Error reading state: {}", e));
+	let mut count = 0u64;
+	let mut write_entry = |entry: StateSnapshotEntry| -> error::Result<()> {
+		output.write_all(&entry.encode())?;
+		count += 1;
+		if count % 100_000 == 0 {
+			info!("#{} entries exported", count);
+		}
+		Ok(())
+	};
+	let has_empty_key = state.storage(&[]).map_err(state_err)?.is_some();
+	for key in keys_in_order(has_empty_key, |key| state.next_storage_key(key)) {
+		let key = key.map_err(state_err)?;
+		// Child roots are recomputed on import from the child entries.
+		if well_known_keys::is_child_storage_key(&key) {
+			let has_empty_key = state.child_storage(&key, &[]).map_err(state_err)?.is_some();
+			let child_keys = keys_in_order(has_empty_key, |child_key| state.next_child_storage_key(&key, child_key));
+			for child_key in child_keys {
+				let child_key = child_key.map_err(state_err)?;
+				if let Some(value) = state.child_storage(&key, &child_key).map_err(state_err)? {
+					write_entry(StateSnapshotEntry::Child(key.clone(), child_key, value))?;
+				}
+			}
+		} else if let Some(value) = state.storage(&key).map_err(state_err)? {
+			write_entry(StateSnapshotEntry::Top(key, value))?;
+		}
+	}
+	output.write_all(&StateSnapshotEntry::End.encode())?;
+	output.flush()?;
+
+	info!("Exported {} state entries", count);
+	Ok(())
+}
+
+/// Import a state snapshot read from `input` into the client, which must only know the genesis
+/// block of the chain the snapshot belongs to.
+///
+/// The finality of the snapshot block is checked by `warp_sync_verifier`, which is then notified
+/// of the import. The entries are imported as they are read.
+pub fn import_state<B, E, Block, RA>(
+	client: &Client<B, E, Block, RA>,
+	warp_sync_verifier: Option<BoxWarpSyncVerifier<Block>>,
+	input: impl Read,
+) -> error::Result<()> where
+	B: Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	let mut input = IoReader(input);
+	let decode_err = |e: codec::Error| Error::Other(format!("Error reading state snapshot: {}", e));
+
+	let snapshot = StateSnapshotHeader::<Block::Header>::decode(&mut input).map_err(decode_err)?;
+	if snapshot.magic != STATE_SNAPSHOT_MAGIC {
+		return Err("Input is not a state snapshot".into());
+	}
+	if snapshot.version != STATE_SNAPSHOT_VERSION {
+		return Err(Error::Other(format!("Unsupported state snapshot version {}", snapshot.version)));
+	}
+	if snapshot.genesis.hash() != client.info().chain.genesis_hash {
+		return Err("State snapshot belongs to another chain".into());
+	}
+
+	let mut verifier = warp_sync_verifier
+		.ok_or_else(|| Error::Other("Importing state requires a warp sync verifier".into()))?;
+	let justification = match verifier.verify(&snapshot.finality_proof).map_err(Error::Other)? {
+		WarpSyncProgress::Complete(ref header, justification) if *header == snapshot.header => justification,
+		_ => return Err("The finality proof of the state snapshot does not prove its block".into()),
+	};
+
+	let hash = snapshot.header.hash();
+	info!("Importing state at #{} ({})", snapshot.header.number(), hash);
+	let mut count = 0u64;
+	let mut finished = false;
+	let state = std::iter::from_fn(|| {
+		if finished {
+			return None;
+		}
+		let entry = match StateSnapshotEntry::decode(&mut input) {
+			Ok(StateSnapshotEntry::Top(key, value)) => Ok(StateEntry::Top(key, value)),
+			Ok(StateSnapshotEntry::Child(storage_key, key, value)) => Ok(StateEntry::Child(storage_key, key, value)),
+			Ok(StateSnapshotEntry::End) => return None,
+			Err(e) => {
+				finished = true;
+				Err(ClientError::Msg(format!("Error reading state snapshot: {}", e)))
+			},
+		};
+		count += 1;
+		Some(entry)
+	});
+	client.import_state(snapshot.header.clone(), None, Some(justification), state)?;
+	verifier.on_target_imported(&snapshot.header).map_err(Error::Other)?;
+
+	info!("Imported {} state entries", count);
+	Ok(())
+}
+
+/// Check the integrity of the database of the given configuration, removing the entries no
+/// block refers to if `repair` is set.
+pub fn check_db<Block, C, G>(
+	config: &Configuration<C, G>,
+	repair: bool,
+) -> error::Result<()> where
+	Block: BlockT<Hash=H256>,
+{
+	let report = client_db::check_database::<Block>(crate::builder::database_settings(config), repair)?;
+
+	info!(
+		"Checked {} canonical blocks and {} states ({} trie nodes)",
+		report.blocks,
+		report.states,
+		report.nodes,
+	);
+	for (start, end) in &report.gaps {
+		info!("Blocks #{} to #{} are not in the database", start, end);
+	}
+	for (column, key) in &report.dangling {
+		warn!("Dangling {} entry: 0x{}", column, HexDisplay::from(key));
+	}
+	if report.repaired {
+		info!("Removed {} dangling entries", report.dangling.len());
+	} else if !report.dangling.is_empty() {
+		warn!("Found {} dangling entries, left in place", report.dangling.len());
+	}
+	for e in &report.errors {
+		error!("{}", e);
+	}
+
+	if report.errors.is_empty() {
+		Ok(())
+	} else {
+		Err(Error::Other(format!("Found {} inconsistencies in the database", report.errors.len())))
+	}
+}
@@ -17,8 +17,10 @@
 //! Service configuration.
 
 pub use client::ExecutionStrategies;
-pub use client_db::PruningMode;
+pub use client_db::{DatabaseEngine, PruningMode};
 pub use network::config::{ExtTransport, NetworkConfiguration, Roles};
+pub use rpc::state::events::TypeRegistry as RpcEventTypes;
+pub use substrate_executor::WasmExecutionMethod;
 
 use std::{path::PathBuf, net::SocketAddr};
 use transaction_pool;
@@ -48,6 +50,8 @@ pub struct Configuration<C, G> {
 	pub keystore_path: PathBuf,
 	/// Path to the database.
 	pub database_path: PathBuf,
+	/// Key-value store backing the database.
+	pub database: DatabaseEngine,
 	/// Cache Size for internal database in MiB
 	pub database_cache_size: Option<u32>,
 	/// Size of internal state cache in Bytes
@@ -72,6 +76,13 @@ pub struct Configuration<C, G> {
 	pub rpc_ws_max_connections: Option<usize>,
 	/// CORS settings for HTTP & WS servers. `None` if all origins are allowed.
 	pub rpc_cors: Option<Vec<String>>,
+	/// RPC methods to expose on the HTTP & WS servers.
+	pub rpc_methods: RpcMethods,
+	/// Maximum number of RPC calls per second over a single WebSockets connection.
+	/// `None` if unlimited.
+	pub rpc_rate_limit: Option<u32>,
+	/// Types used to decode the runtime events sent to RPC subscribers.
+	pub rpc_event_types: RpcEventTypes,
 	/// Telemetry service URL. `None` if disabled.
 	pub telemetry_endpoints: Option<TelemetryEndpoints>,
 	/// External WASM transport for the telemetry. If `Some`, when connection to a telemetry
@@ -79,8 +90,16 @@ pub struct Configuration<C, G> {
 	pub telemetry_external_transport: Option<ExtTransport>,
 	/// The default number of 64KB pages to allocate for Wasm execution
 	pub default_heap_pages: Option<u64>,
+	/// How the Wasm runtime is executed.
+	pub wasm_method: WasmExecutionMethod,
+	/// Directory where prepared Wasm runtimes are cached across restarts. `None` if disabled.
+	pub wasm_cache_path: Option<PathBuf>,
+	/// Directory where native/wasm divergence reports are written. `None` if disabled.
+	pub consensus_failure_path: Option<PathBuf>,
 	/// Should offchain workers be executed.
 	pub offchain_worker: bool,
+	/// Offchain workers scheduling configuration.
+	pub offchain_worker_config: offchain::Config,
 	/// Enable authoring even when offline.
 	pub force_authoring: bool,
 	/// Disable GRANDPA when running in validator mode
@@ -109,6 +128,7 @@ impl<C: Default, G: Serialize + DeserializeOwned + BuildStorage> Configuration<C
 			network: Default::default(),
 			keystore_path: Default::default(),
 			database_path: Default::default(),
+			database: Default::default(),
 			database_cache_size: Default::default(),
 			state_cache_size: Default::default(),
 			state_cache_child_ratio: Default::default(),
@@ -119,10 +139,17 @@ impl<C: Default, G: Serialize + DeserializeOwned + BuildStorage> Configuration<C
 			rpc_ws: None,
 			rpc_ws_max_connections: None,
 			rpc_cors: Some(vec![]),
+			rpc_methods: Default::default(),
+			rpc_rate_limit: None,
+			rpc_event_types: Default::default(),
 			telemetry_endpoints: None,
 			telemetry_external_transport: None,
 			default_heap_pages: None,
+			wasm_method: Default::default(),
+			wasm_cache_path: None,
+			consensus_failure_path: None,
 			offchain_worker: Default::default(),
+			offchain_worker_config: Default::default(),
 			force_authoring: false,
 			disable_grandpa: false,
 			keystore_password: None,
@@ -146,6 +173,24 @@ impl<C: Default, G: Serialize + DeserializeOwned + BuildStorage> Configuration<C
 	}
 }
 
+/// Available RPC methods.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RpcMethods {
+	/// Expose every RPC method on servers listening on `localhost`,
+	/// otherwise serve only safe RPC methods.
+	Auto,
+	/// Allow only a safe subset of RPC methods.
+	Safe,
+	/// Expose every RPC method (even potentially unsafe ones).
+	Unsafe,
+}
+
+impl Default for RpcMethods {
+	fn default() -> RpcMethods {
+		RpcMethods::Auto
+	}
+}
+
 /// Returns platform info
 pub fn platform() -> String {
 	let env = Target::env();
@@ -48,14 +48,19 @@ use sr_primitives::generic::BlockId;
 use sr_primitives::traits::NumberFor;
 
 pub use self::error::Error;
-pub use self::builder::{ServiceBuilder, ServiceBuilderExport, ServiceBuilderImport, ServiceBuilderRevert};
-pub use config::{Configuration, Roles, PruningMode};
+pub use self::builder::{
+	ServiceBuilder, ServiceBuilderExport, ServiceBuilderImport, ServiceBuilderRevert, ServiceBuilderState,
+	RpcExtensionBuilder, NoopRpcExtensionBuilder,
+};
+pub use config::{Configuration, Roles, PruningMode, DatabaseEngine, RpcEventTypes, RpcMethods, WasmExecutionMethod};
 pub use chain_spec::{ChainSpec, Properties};
 pub use transaction_pool::txpool::{
 	self, Pool as TransactionPool, Options as TransactionPoolOptions, ChainApi, IntoPoolError
 };
 pub use client::FinalityNotifications;
-pub use rpc::Metadata as RpcMetadata;
+pub use rpc::{Metadata as RpcMetadata, DenyUnsafe};
+pub use offchain::Config as OffchainWorkerConfig;
+pub use substrate_executor::profiling as wasm_profiling;
 #[doc(hidden)]
 pub use std::{ops::Deref, result::Result, sync::Arc};
 #[doc(hidden)]
@@ -153,6 +158,7 @@ macro_rules! new_impl {
 			import_queue,
 			finality_proof_request_builder,
 			finality_proof_provider,
+			warp_sync_verifier,
 			network_protocol,
 			transaction_pool,
 			rpc_extensions
@@ -194,6 +200,7 @@ macro_rules! new_impl {
 			chain: client.clone(),
 			finality_proof_provider,
 			finality_proof_request_builder,
+			warp_sync_verifier,
 			on_demand,
 			transaction_pool: transaction_pool_adapter.clone() as _,
 			import_queue,
@@ -210,7 +217,11 @@ macro_rules! new_impl {
 		let offchain_storage = client.backend().offchain_storage();
 		let offchain_workers = match ($config.offchain_worker, offchain_storage) {
 			(true, Some(db)) => {
-				Some(Arc::new(offchain::OffchainWorkers::new(client.clone(), db)))
+				Some(Arc::new(offchain::OffchainWorkers::with_config(
+					client.clone(),
+					db,
+					$config.offchain_worker_config.clone(),
+				)))
 			},
 			(true, None) => {
 				log::warn!("Offchain workers disabled, due to lack of offchain storage support in backend.");
@@ -231,7 +242,6 @@ macro_rules! new_impl {
 			let events = client.import_notification_stream()
 				.map(|v| Ok::<_, ()>(v)).compat()
 				.for_each(move |notification| {
-					let number = *notification.header.number();
 					let txpool = txpool.upgrade();
 
 					if let (Some(txpool), Some(client)) = (txpool.as_ref(), wclient.upgrade()) {
@@ -245,7 +255,9 @@ macro_rules! new_impl {
 					let offchain = offchain.as_ref().and_then(|o| o.upgrade());
 					if let (Some(txpool), Some(offchain)) = (txpool, offchain) {
 						let future = $offchain_workers(
-							&number,
+							&notification.header,
+							notification.is_new_best,
+							&notification.non_finalized_ancestors,
 							&offchain,
 							&txpool,
 							&network_state_info,
@@ -261,6 +273,22 @@ macro_rules! new_impl {
 			let _ = to_spawn_tx.unbounded_send(Box::new(events));
 		}
 
+		if let Some(offchain) = offchain_workers.as_ref().map(Arc::downgrade) {
+			// finality notifications
+			let events = client.finality_notification_stream()
+				.map(|v| Ok::<_, ()>(v)).compat()
+				.for_each(move |notification| {
+					if let Some(offchain) = offchain.upgrade() {
+						offchain.on_block_finalized(&notification.header);
+					}
+					Ok(())
+				})
+				.select(exit.clone())
+				.then(|_| Ok(()));
+
+			let _ = to_spawn_tx.unbounded_send(Box::new(events));
+		}
+
 		{
 			// extrinsic notifications
 			let network = Arc::downgrade(&network);
@@ -340,7 +368,7 @@ macro_rules! new_impl {
 
 		// RPC
 		let (system_rpc_tx, system_rpc_rx) = futures03::channel::mpsc::unbounded();
-		let gen_handler = || {
+		let gen_handler = |deny_unsafe: rpc::DenyUnsafe, rpc_middleware: rpc_servers::RpcMiddleware| {
 			let system_info = rpc::system::SystemInfo {
 				chain_name: $config.chain_spec.name().into(),
 				impl_name: $config.impl_name.into(),
@@ -353,11 +381,14 @@ macro_rules! new_impl {
 				system_info.clone(),
 				Arc::new(SpawnTaskHandle { sender: to_spawn_tx.clone() }),
 				transaction_pool.clone(),
-				rpc_extensions.clone(),
+				rpc_extensions.build(deny_unsafe),
 				keystore.clone(),
+				$config.rpc_event_types.clone(),
+				deny_unsafe,
+				rpc_middleware,
 			)
 		};
-		let rpc_handlers = gen_handler();
+		let rpc_handlers = gen_handler(rpc::DenyUnsafe::No, Default::default());
 		let rpc = start_rpc_servers(&$config, gen_handler)?;
 
 		let _ = to_spawn_tx.unbounded_send(Box::new(build_network_future(
@@ -727,6 +758,20 @@ fn build_network_future<
 				rpc::system::Request::NetworkState(sender) => {
 					let _ = sender.send(network.network_state());
 				}
+				rpc::system::Request::NetworkAddReservedPeer(peer_addr, sender) => {
+					let _ = sender.send(network.service().add_reserved_peer(peer_addr));
+				}
+				rpc::system::Request::NetworkRemoveReservedPeer(peer_id, sender) => {
+					network.service().remove_reserved_peer(peer_id);
+					let _ = sender.send(());
+				}
+				rpc::system::Request::NetworkSetNonReservedPeerMode(mode, sender) => {
+					match mode {
+						network::config::NonReservedPeerMode::Accept => network.service().accept_unreserved_peers(),
+						network::config::NonReservedPeerMode::Deny => network.service().deny_unreserved_peers(),
+					}
+					let _ = sender.send(());
+				}
 			};
 		}
 
@@ -797,10 +842,12 @@ NewService<TCfg, TBl, TCl, TSc, TNetStatus, TNet, TTxPool, TOc> {
 
 /// Starts RPC servers that run in their own thread, and returns an opaque object that keeps them alive.
 #[cfg(not(target_os = "unknown"))]
-fn start_rpc_servers<C, G, H: FnMut() -> rpc_servers::RpcHandler<rpc::Metadata>>(
+fn start_rpc_servers<C, G, H>(
 	config: &Configuration<C, G>,
 	mut gen_handler: H
-) -> Result<Box<dyn std::any::Any + Send + Sync>, error::Error> {
+) -> Result<Box<dyn std::any::Any + Send + Sync>, error::Error> where
+	H: FnMut(rpc::DenyUnsafe, rpc_servers::RpcMiddleware) -> rpc_servers::RpcHandler<rpc::Metadata>,
+{
 	fn maybe_start_server<T, F>(address: Option<SocketAddr>, mut start: F) -> Result<Option<T>, io::Error>
 		where F: FnMut(&SocketAddr) -> Result<T, io::Error>,
 	{
@@ -819,10 +866,25 @@ fn start_rpc_servers<C, G, H: FnMut() -> rpc_servers::RpcHandler<rpc::Metadata>>
 		})
 	}
 
+	fn deny_unsafe(address: &SocketAddr, methods: &RpcMethods) -> rpc::DenyUnsafe {
+		let is_exposed_addr = !address.ip().is_loopback();
+		match (is_exposed_addr, methods) {
+			(_, RpcMethods::Unsafe) | (false, RpcMethods::Auto) => rpc::DenyUnsafe::No,
+			_ => rpc::DenyUnsafe::Yes,
+		}
+	}
+
 	Ok(Box::new((
 		maybe_start_server(
 			config.rpc_http,
-			|address| rpc_servers::start_http(address, config.rpc_cors.as_ref(), gen_handler()),
+			|address| rpc_servers::start_http(
+				address,
+				config.rpc_cors.as_ref(),
+				gen_handler(
+					deny_unsafe(address, &config.rpc_methods),
+					rpc_servers::RpcMiddleware::new(config.rpc_rate_limit),
+				),
+			),
 		)?,
 		maybe_start_server(
 			config.rpc_ws,
@@ -830,7 +892,10 @@ fn start_rpc_servers<C, G, H: FnMut() -> rpc_servers::RpcHandler<rpc::Metadata>>
 				address,
 				config.rpc_ws_max_connections,
 				config.rpc_cors.as_ref(),
-				gen_handler(),
+				gen_handler(
+					deny_unsafe(address, &config.rpc_methods),
+					rpc_servers::RpcMiddleware::new(config.rpc_rate_limit),
+				),
 			),
 		)?.map(Mutex::new),
 	)))
@@ -838,7 +903,7 @@ fn start_rpc_servers<C, G, H: FnMut() -> rpc_servers::RpcHandler<rpc::Metadata>>
 
 /// Starts RPC servers that run in their own thread, and returns an opaque object that keeps them alive.
 #[cfg(target_os = "unknown")]
-fn start_rpc_servers<C, G, H: FnMut() -> components::RpcHandler>(
+fn start_rpc_servers<C, G, H: FnMut(rpc::DenyUnsafe, rpc_servers::RpcMiddleware) -> components::RpcHandler>(
 	_: &Configuration<C, G>,
 	_: H
 ) -> Result<Box<std::any::Any + Send + Sync>, error::Error> {
@@ -34,7 +34,7 @@ use service::{
 	Error,
 };
 use network::{multiaddr, Multiaddr};
-use network::config::{NetworkConfiguration, TransportConfig, NodeKeyConfig, Secret, NonReservedPeerMode};
+use network::config::{NetworkConfiguration, TransportConfig, NodeKeyConfig, Secret, NonReservedPeerMode, SyncMode};
 use sr_primitives::{generic::BlockId, traits::Block as BlockT};
 use consensus::{BlockImportParams, BlockImport};
 
@@ -161,6 +161,7 @@ fn node_config<G> (
 			enable_mdns: false,
 			wasm_external_transport: None,
 		},
+		sync_mode: SyncMode::Full,
 	};
 
 	Configuration {
@@ -173,6 +174,7 @@ fn node_config<G> (
 		keystore_path: root.join("key"),
 		keystore_password: None,
 		database_path: root.join("db"),
+		database: Default::default(),
 		database_cache_size: None,
 		state_cache_size: 16777216,
 		state_cache_child_ratio: None,
@@ -185,10 +187,17 @@ fn node_config<G> (
 		rpc_ws: None,
 		rpc_ws_max_connections: None,
 		rpc_cors: None,
+		rpc_methods: Default::default(),
+		rpc_rate_limit: None,
+		rpc_event_types: Default::default(),
 		telemetry_endpoints: None,
 		telemetry_external_transport: None,
 		default_heap_pages: None,
+		wasm_method: Default::default(),
+		wasm_cache_path: None,
+		consensus_failure_path: None,
 		offchain_worker: false,
+		offchain_worker_config: Default::default(),
 		force_authoring: false,
 		disable_grandpa: false,
 		dev_key_seed: key_seed,
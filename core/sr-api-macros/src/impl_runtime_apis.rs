@@ -317,6 +317,10 @@ fn generate_runtime_api_base_structures(impls: &[ItemImpl]) -> Result<TokenStrea
 							.collect()
 					})
 			}
+
+			fn extract_changes(&mut self) -> #crate_::runtime_api::OverlayedChanges {
+				::std::mem::replace(&mut *self.changes.borrow_mut(), Default::default())
+			}
 		}
 
 		#[cfg(any(feature = "std", test))]
@@ -144,6 +144,18 @@ export_api! {
 		/// Clear the child storage entries with a key that starts with the given prefix.
 		fn clear_child_prefix(storage_key: &[u8], prefix: &[u8]);
 
+		/// Write a key-value pair to the offchain storage during block import.
+		///
+		/// Note this storage is not part of the consensus, it's only accessible by
+		/// offchain workers and RPCs of the importing node.
+		fn offchain_index_set(key: &[u8], value: &[u8]);
+
+		/// Remove a key and its value from the offchain storage during block import.
+		///
+		/// Note this storage is not part of the consensus, it's only accessible by
+		/// offchain workers and RPCs of the importing node.
+		fn offchain_index_clear(key: &[u8]);
+
 		/// "Commit" all existing operations and compute the resultant storage root.
 		fn storage_root() -> [u8; 32];
 
@@ -148,6 +148,18 @@ impl StorageApi for () {
 		});
 	}
 
+	fn offchain_index_set(key: &[u8], value: &[u8]) {
+		ext::with(|ext|
+			ext.set_offchain_storage(key, Some(value))
+		);
+	}
+
+	fn offchain_index_clear(key: &[u8]) {
+		ext::with(|ext|
+			ext.set_offchain_storage(key, None)
+		);
+	}
+
 	fn storage_root() -> [u8; 32] {
 		ext::with(|ext|
 			ext.storage_root()
@@ -230,6 +230,10 @@ pub mod ext {
 			prefix_data: *const u8,
 			prefix_len: u32
 		);
+		/// Set value for key in the offchain storage during block import.
+		fn ext_offchain_index_set(key_data: *const u8, key_len: u32, value_data: *const u8, value_len: u32);
+		/// Remove key and value from the offchain storage during block import.
+		fn ext_offchain_index_clear(key_data: *const u8, key_len: u32);
 		/// Gets the value of the given key from storage.
 		///
 		/// The host allocates the memory for storing the value.
@@ -735,6 +739,23 @@ impl StorageApi for () {
 		}
 	}
 
+	fn offchain_index_set(key: &[u8], value: &[u8]) {
+		unsafe {
+			ext_offchain_index_set.get()(
+				key.as_ptr(), key.len() as u32,
+				value.as_ptr(), value.len() as u32
+			);
+		}
+	}
+
+	fn offchain_index_clear(key: &[u8]) {
+		unsafe {
+			ext_offchain_index_clear.get()(
+				key.as_ptr(), key.len() as u32
+			);
+		}
+	}
+
 	fn kill_child_storage(storage_key: &[u8]) {
 		unsafe {
 			ext_kill_child_storage.get()(
@@ -72,6 +72,8 @@ impl<Address, Call, Signature, Extra: SignedExtension> Extrinsic
 {
 	type Call = Call;
 
+	type SignaturePayload = (Address, Signature, Extra);
+
 	fn is_signed(&self) -> Option<bool> {
 		Some(self.signature.is_some())
 	}
@@ -79,6 +81,10 @@ impl<Address, Call, Signature, Extra: SignedExtension> Extrinsic
 	fn new_unsigned(function: Call) -> Option<Self> {
 		Some(UncheckedExtrinsic::new_unsigned(function))
 	}
+
+	fn new_signed(function: Call, (signed, signature, extra): Self::SignaturePayload) -> Option<Self> {
+		Some(UncheckedExtrinsic::new_signed(function, signed, signature, extra))
+	}
 }
 
 impl<Address, AccountId, Call, Signature, Extra, Lookup>
@@ -847,6 +847,7 @@ impl<'a> ::serde::Deserialize<'a> for OpaqueExtrinsic {
 
 impl traits::Extrinsic for OpaqueExtrinsic {
 	type Call = ();
+	type SignaturePayload = ();
 
 	fn is_signed(&self) -> Option<bool> {
 		None
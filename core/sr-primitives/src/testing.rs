@@ -187,6 +187,7 @@ pub struct ExtrinsicWrapper<Xt>(Xt);
 
 impl<Xt> traits::Extrinsic for ExtrinsicWrapper<Xt> {
 	type Call = ();
+	type SignaturePayload = ();
 
 	fn is_signed(&self) -> Option<bool> {
 		None
@@ -256,6 +257,16 @@ impl<'a, Xt> Deserialize<'a> for Block<Xt> where Block<Xt>: Decode {
 #[derive(PartialEq, Eq, Clone, Encode, Decode)]
 pub struct TestXt<Call, Extra>(pub Option<(u64, Extra)>, pub Call);
 
+impl<Call, Extra> TestXt<Call, Extra> {
+	/// Create a new unsigned test transaction.
+	///
+	/// Unlike `Extrinsic::new_unsigned`, which refuses to create unsigned test transactions,
+	/// this always succeeds.
+	pub fn unsigned(call: Call) -> Self {
+		TestXt(None, call)
+	}
+}
+
 impl<Call, Extra> Serialize for TestXt<Call, Extra> where TestXt<Call, Extra>: Encode {
 	fn serialize<S>(&self, seq: S) -> Result<S::Ok, S::Error> where S: Serializer {
 		self.using_encoded(|bytes| seq.serialize_bytes(bytes))
@@ -274,6 +285,7 @@ impl<Call: Codec + Sync + Send, Context, Extra> Checkable<Context> for TestXt<Ca
 }
 impl<Call: Codec + Sync + Send, Extra> traits::Extrinsic for TestXt<Call, Extra> {
 	type Call = Call;
+	type SignaturePayload = (u64, Extra);
 
 	fn is_signed(&self) -> Option<bool> {
 		Some(self.0.is_some())
@@ -282,6 +294,10 @@ impl<Call: Codec + Sync + Send, Extra> traits::Extrinsic for TestXt<Call, Extra>
 	fn new_unsigned(_c: Call) -> Option<Self> {
 		None
 	}
+
+	fn new_signed(c: Call, signed_data: Self::SignaturePayload) -> Option<Self> {
+		Some(TestXt(Some(signed_data), c))
+	}
 }
 
 impl<Origin, Call, Extra> Applyable for TestXt<Call, Extra> where
@@ -743,6 +743,13 @@ pub trait Extrinsic: Sized {
 	/// The function call.
 	type Call;
 
+	/// The data carried by signed extrinsics, usually the signer, signature and
+	/// signed extensions.
+	///
+	/// Extrinsic types that can't be signed should use `()` and keep the default
+	/// `new_signed`.
+	type SignaturePayload;
+
 	/// Is this `Extrinsic` signed?
 	/// If no information are available about signed/unsigned, `None` should be returned.
 	fn is_signed(&self) -> Option<bool> { None }
@@ -750,6 +757,10 @@ pub trait Extrinsic: Sized {
 	/// New instance of an unsigned extrinsic aka "inherent". `None` if this is an opaque
 	/// extrinsic type.
 	fn new_unsigned(_call: Self::Call) -> Option<Self> { None }
+
+	/// New instance of a signed extrinsic aka "transaction". `None` if this is an opaque
+	/// extrinsic type or it can't be signed.
+	fn new_signed(_call: Self::Call, _signed_data: Self::SignaturePayload) -> Option<Self> { None }
 }
 
 /// Extract the hashing type for a block.
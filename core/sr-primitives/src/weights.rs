@@ -25,6 +25,8 @@
 
 use crate::{Fixed64, traits::Saturating};
 use crate::codec::{Encode, Decode};
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
 
 pub use crate::transaction_validity::TransactionPriority;
 use crate::traits::Bounded;
@@ -34,8 +36,9 @@ pub type Weight = u32;
 
 /// A generalized group of dispatch types. This is only distinguishing normal, user-triggered transactions
 /// (`Normal`) and anything beyond which serves a higher purpose to the system (`Operational`).
-#[cfg_attr(feature = "std", derive(Debug))]
-#[derive(PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+#[derive(PartialEq, Eq, Clone, Copy, Encode, Decode)]
 pub enum DispatchClass {
 	/// A normal dispatch.
 	Normal,
@@ -65,7 +68,7 @@ impl From<SimpleDispatchInfo> for DispatchClass {
 
 /// A bundle of static information collected from the `#[weight = $x]` attributes.
 #[cfg_attr(feature = "std", derive(PartialEq, Eq, Debug))]
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Default, Encode, Decode)]
 pub struct DispatchInfo {
 	/// Weight of this transaction.
 	pub weight: Weight,
@@ -27,7 +27,8 @@
 //!
 //! # Pruning.
 //! See `RefWindow` for pruning algorithm details. `StateDb` prunes on each canonicalization until pruning
-//! constraints are satisfied.
+//! constraints are satisfied. The window may be limited by the number of blocks, by the memory used
+//! for the in-memory structures of both the non-canonical overlay and the pruning window, or both.
 
 mod noncanonical;
 mod pruning;
@@ -122,12 +123,16 @@ pub struct CommitSet<H: Hash> {
 	pub meta: ChangeSet<Vec<u8>>,
 }
 
-/// Pruning constraints. If none are specified pruning is
+/// Pruning constraints. Canonical blocks are pruned while any of the specified constraints is
+/// violated. If none are specified only non-canonical states are kept.
 #[derive(Default, Debug, Clone, Eq, PartialEq)]
 pub struct Constraints {
-	/// Maximum blocks. Defaults to 0 when unspecified, effectively keeping only non-canonical states.
+	/// Maximum blocks. Defaults to 0 when unspecified and `max_mem` is not set either,
+	/// effectively keeping only non-canonical states.
 	pub max_blocks: Option<u32>,
-	/// Maximum memory in the pruning overlay.
+	/// Maximum memory in bytes used by the non-canonical overlay and the pruning window combined.
+	/// The memory usage is an estimate. Blocks are never pruned while they are pinned, nor when
+	/// the non-canonical overlay alone exceeds the limit.
 	pub max_mem: Option<usize>,
 }
 
@@ -151,6 +156,15 @@ impl PruningMode {
 		})
 	}
 
+	/// Create a mode that keeps canonical blocks within the given memory budget in bytes and,
+	/// optionally, at most the given number of blocks.
+	pub fn keep_mem(max_blocks: Option<u32>, max_mem: usize) -> PruningMode {
+		PruningMode::Constrained(Constraints {
+			max_blocks,
+			max_mem: Some(max_mem),
+		})
+	}
+
 	/// Is this an archive (either ArchiveAll or ArchiveCanonical) pruning mode?
 	pub fn is_archive(&self) -> bool {
 		match *self {
@@ -185,10 +199,6 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 		trace!(target: "state-db", "StateDb settings: {:?}", mode);
 		let non_canonical: NonCanonicalOverlay<BlockHash, Key> = NonCanonicalOverlay::new(db)?;
 		let pruning: Option<RefWindow<BlockHash, Key>> = match mode {
-			PruningMode::Constrained(Constraints {
-				max_mem: Some(_),
-				..
-			}) => unimplemented!(),
 			PruningMode::Constrained(_) => Some(RefWindow::new(db)?),
 			PruningMode::ArchiveAll | PruningMode::ArchiveCanonical => None,
 		};
@@ -216,6 +226,21 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 		}
 	}
 
+	pub fn insert_detached_block<E: fmt::Debug>(&mut self, hash: &BlockHash, number: u64, parent_hash: &BlockHash, mut changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		match self.mode {
+			PruningMode::ArchiveAll => {
+				changeset.deleted.clear();
+				Ok(CommitSet {
+					data: changeset,
+					meta: Default::default(),
+				})
+			},
+			PruningMode::Constrained(_) | PruningMode::ArchiveCanonical => {
+				self.non_canonical.insert_detached(hash, number, parent_hash, changeset)
+			}
+		}
+	}
+
 	pub fn canonicalize_block<E: fmt::Debug>(&mut self, hash: &BlockHash) -> Result<CommitSet<Key>, Error<E>> {
 		let mut commit = CommitSet::default();
 		if self.mode == PruningMode::ArchiveAll {
@@ -255,12 +280,22 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 
 	fn prune(&mut self, commit: &mut CommitSet<Key>) {
 		if let (&mut Some(ref mut pruning), &PruningMode::Constrained(ref constraints)) = (&mut self.pruning, &self.mode) {
+			let max_blocks = match constraints.max_mem {
+				Some(_) => constraints.max_blocks,
+				None => Some(constraints.max_blocks.unwrap_or(0)),
+			};
+			// memory left to the window once the non-canonical overlay is accounted for. pruning can't
+			// help if the overlay alone exceeds the limit.
+			let window_max_mem = constraints.max_mem
+				.and_then(|max_mem| max_mem.checked_sub(self.non_canonical.mem_used()));
 			loop {
-				if pruning.window_size() <= constraints.max_blocks.unwrap_or(0) as u64 {
+				if pruning.window_size() == 0 {
 					break;
 				}
 
-				if constraints.max_mem.map_or(false, |m| pruning.mem_used() > m) {
+				let blocks_exceeded = max_blocks.map_or(false, |m| pruning.window_size() > m as u64);
+				let mem_exceeded = window_max_mem.map_or(false, |m| pruning.mem_used() > m);
+				if !blocks_exceeded && !mem_exceeded {
 					break;
 				}
 
@@ -273,6 +308,16 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 		}
 	}
 
+	/// Returns `true` if the non-canonical overlay alone uses more memory than allowed by the
+	/// pruning constraints. Pruning can't help in this case, only finalizing more blocks can.
+	pub fn non_canonical_mem_exceeded(&self) -> bool {
+		match self.mode {
+			PruningMode::Constrained(Constraints { max_mem: Some(max_mem), .. }) =>
+				self.non_canonical.mem_used() > max_mem,
+			_ => false,
+		}
+	}
+
 	/// Revert all non-canonical blocks with the best block number.
 	/// Returns a database commit or `None` if not possible.
 	/// For archive an empty commit set is returned.
@@ -374,6 +419,12 @@ impl<BlockHash: Hash, Key: Hash> StateDb<BlockHash, Key> {
 		self.db.write().insert_block(hash, number, parent_hash, changeset)
 	}
 
+	/// Add a new non-canonical block whose parent is not known to the state database. The parent
+	/// is assumed to be canonicalized. Fails if there are non-canonical blocks.
+	pub fn insert_detached_block<E: fmt::Debug>(&self, hash: &BlockHash, number: u64, parent_hash: &BlockHash, changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		self.db.write().insert_detached_block(hash, number, parent_hash, changeset)
+	}
+
 	/// Finalize a previously inserted block.
 	pub fn canonicalize_block<E: fmt::Debug>(&self, hash: &BlockHash) -> Result<CommitSet<Key>, Error<E>> {
 		self.db.write().canonicalize_block(hash)
@@ -413,6 +464,12 @@ impl<BlockHash: Hash, Key: Hash> StateDb<BlockHash, Key> {
 		return self.db.read().is_pruned(hash, number)
 	}
 
+	/// Check if the non-canonical overlay alone exceeds the memory budget of the pruning
+	/// constraints, which only finalizing more blocks can address.
+	pub fn non_canonical_mem_exceeded(&self) -> bool {
+		self.db.read().non_canonical_mem_exceeded()
+	}
+
 	/// Apply all pending changes
 	pub fn apply_pending(&self) {
 		self.db.write().apply_pending();
@@ -544,4 +601,27 @@ mod tests {
 		assert!(sdb.is_pruned(&H256::from_low_u64_be(22), 2));
 		assert!(db.data_eq(&make_db(&[1, 21, 3, 921, 922, 93, 94])));
 	}
+
+	#[test]
+	fn prune_mem_exceeded_by_non_canonical() {
+		let (db, sdb) = make_test_db(PruningMode::Constrained(Constraints {
+			max_blocks: None,
+			max_mem: Some(0),
+		}));
+		// the window is kept, as pruning it can't bring the memory used under the limit.
+		assert!(!sdb.is_pruned(&H256::from_low_u64_be(1), 1));
+		assert!(sdb.non_canonical_mem_exceeded());
+		assert!(db.data_eq(&make_db(&[1, 21, 3, 91, 921, 922, 93, 94])));
+	}
+
+	#[test]
+	fn prune_mem_within_budget() {
+		let (db, sdb) = make_test_db(PruningMode::Constrained(Constraints {
+			max_blocks: None,
+			max_mem: Some(1024 * 1024),
+		}));
+		assert!(!sdb.is_pruned(&H256::from_low_u64_be(1), 1));
+		assert!(!sdb.non_canonical_mem_exceeded());
+		assert!(db.data_eq(&make_db(&[1, 21, 3, 91, 921, 922, 93, 94])));
+	}
 }
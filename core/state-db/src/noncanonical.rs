@@ -20,7 +20,7 @@
 //! All pending changes are kept in memory until next call to `apply_pending` or
 //! `revert_pending`
 
-use std::fmt;
+use std::{fmt, mem};
 use std::collections::{HashMap, VecDeque, hash_map::Entry};
 use super::{Error, DBValue, ChangeSet, CommitSet, MetaDb, Hash, to_meta_key};
 use codec::{Encode, Decode};
@@ -37,6 +37,7 @@ pub struct NonCanonicalOverlay<BlockHash: Hash, Key: Hash> {
 	pending_canonicalizations: Vec<BlockHash>,
 	pending_insertions: Vec<BlockHash>,
 	values: HashMap<Key, (u32, DBValue)>, //ref counted
+	values_mem: usize, //approximate memory used by `values`
 	pinned: HashMap<BlockHash, HashMap<Key, DBValue>>, //would be deleted but kept around because block is pinned
 }
 
@@ -60,19 +61,31 @@ struct BlockOverlay<BlockHash: Hash, Key: Hash> {
 	deleted: Vec<Key>,
 }
 
-fn insert_values<Key: Hash>(values: &mut HashMap<Key, (u32, DBValue)>, inserted: Vec<(Key, DBValue)>) {
+/// Approximate memory taken by a single entry of the values map.
+fn value_mem<Key: Hash>(value: &DBValue) -> usize {
+	mem::size_of::<Key>() + value.len()
+}
+
+/// Returns the amount of memory added to `values`.
+fn insert_values<Key: Hash>(values: &mut HashMap<Key, (u32, DBValue)>, inserted: Vec<(Key, DBValue)>) -> usize {
+	let mut added = 0;
 	for (k, v) in inserted {
 		debug_assert!(values.get(&k).map_or(true, |(_, value)| *value == v));
-		let (ref mut counter, _) = values.entry(k).or_insert_with(|| (0, v));
+		let (ref mut counter, _) = values.entry(k).or_insert_with(|| {
+			added += value_mem::<Key>(&v);
+			(0, v)
+		});
 		*counter += 1;
 	}
+	added
 }
 
 fn discard_values<Key: Hash>(
 	values: &mut HashMap<Key, (u32, DBValue)>,
 	inserted: Vec<Key>,
 	mut into: Option<&mut HashMap<Key, DBValue>>,
-) {
+) -> usize {
+	let mut released = 0;
 	for k in inserted {
 		match values.entry(k) {
 			Entry::Occupied(mut e) => {
@@ -80,6 +93,7 @@ fn discard_values<Key: Hash>(
 				*counter -= 1;
 				if *counter == 0 {
 					let (key, (_, value)) = e.remove_entry();
+					released += value_mem::<Key>(&value);
 					if let Some(ref mut into) = into {
 						into.insert(key, value);
 					}
@@ -90,6 +104,7 @@ fn discard_values<Key: Hash>(
 			}
 		}
 	}
+	released
 }
 
 fn discard_descendants<BlockHash: Hash, Key: Hash>(
@@ -99,7 +114,8 @@ fn discard_descendants<BlockHash: Hash, Key: Hash>(
 	parents: &mut HashMap<BlockHash, BlockHash>,
 	pinned: &mut HashMap<BlockHash, HashMap<Key, DBValue>>,
 	hash: &BlockHash,
-) {
+) -> usize {
+	let mut released = 0;
 	let mut discarded = Vec::new();
 	if let Some(level) = levels.get_mut(index) {
 		*level = level.drain(..).filter_map(|overlay| {
@@ -107,7 +123,7 @@ fn discard_descendants<BlockHash: Hash, Key: Hash>(
 			if parent == *hash {
 				parents.remove(&overlay.hash);
 				discarded.push(overlay.hash);
-				discard_values(&mut values, overlay.inserted, pinned.get_mut(hash));
+				released += discard_values(&mut values, overlay.inserted, pinned.get_mut(hash));
 				None
 			} else {
 				Some(overlay)
@@ -115,8 +131,9 @@ fn discard_descendants<BlockHash: Hash, Key: Hash>(
 		}).collect();
 	}
 	for hash in discarded {
-		discard_descendants(levels, values, index + 1, parents, pinned, &hash);
+		released += discard_descendants(levels, values, index + 1, parents, pinned, &hash);
 	}
+	released
 }
 
 impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
@@ -131,6 +148,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		let mut levels = VecDeque::new();
 		let mut parents = HashMap::new();
 		let mut values = HashMap::new();
+		let mut values_mem = 0;
 		if let Some((ref hash, mut block)) = last_canonicalized {
 			// read the journal
 			trace!(target: "state-db", "Reading uncanonicalized journal. Last canonicalized #{} ({:?})", block, hash);
@@ -151,7 +169,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 								inserted: inserted,
 								deleted: record.deleted,
 							};
-							insert_values(&mut values, record.inserted);
+							values_mem += insert_values(&mut values, record.inserted);
 							trace!(target: "state-db", "Uncanonicalized journal entry {}.{} ({} inserted, {} deleted)", block, index, overlay.inserted.len(), overlay.deleted.len());
 							level.push(overlay);
 							parents.insert(record.hash, record.parent_hash);
@@ -177,9 +195,24 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 			pending_insertions: Default::default(),
 			pinned: Default::default(),
 			values: values,
+			values_mem,
 		})
 	}
 
+	/// Insert a block which does not extend the canonical chain known to the overlay, such as a
+	/// block downloaded together with its full state. The parent is assumed to be canonicalized.
+	/// Only possible when there are no non-canonical blocks.
+	pub fn insert_detached<E: fmt::Debug>(&mut self, hash: &BlockHash, number: u64, parent_hash: &BlockHash, changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		if number == 0 || !self.levels.is_empty() || !self.pending_canonicalizations.is_empty() {
+			return Err(Error::InvalidBlockNumber);
+		}
+		if self.last_canonicalized.as_ref().map_or(false, |&(_, n)| number <= n) {
+			return Err(Error::InvalidBlockNumber);
+		}
+		self.last_canonicalized = None;
+		self.insert(hash, number, parent_hash, changeset)
+	}
+
 	/// Insert a new block into the overlay. If inserted on the second level or lover expects parent to be present in the window.
 	pub fn insert<E: fmt::Debug>(&mut self, hash: &BlockHash, number: u64, parent_hash: &BlockHash, changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
 		let mut commit = CommitSet::default();
@@ -235,7 +268,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		};
 		commit.meta.inserted.push((journal_key, journal_record.encode()));
 		trace!(target: "state-db", "Inserted uncanonicalized changeset {}.{} ({} inserted, {} deleted)", number, index, journal_record.inserted.len(), journal_record.deleted.len());
-		insert_values(&mut self.values, journal_record.inserted);
+		self.values_mem += insert_values(&mut self.values, journal_record.inserted);
 		self.pending_insertions.push(hash.clone());
 		Ok(commit)
 	}
@@ -341,7 +374,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 			for (i, overlay) in level.into_iter().enumerate() {
 				self.parents.remove(&overlay.hash);
 				if i != index {
-					discard_descendants(
+					self.values_mem -= discard_descendants(
 						&mut self.levels,
 						&mut self.values,
 						0,
@@ -350,7 +383,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 						&overlay.hash,
 					);
 				}
-				discard_values(&mut self.values, overlay.inserted, self.pinned.get_mut(&overlay.hash));
+				self.values_mem -= discard_values(&mut self.values, overlay.inserted, self.pinned.get_mut(&overlay.hash));
 			}
 		}
 		if let Some(hash) = last {
@@ -372,6 +405,12 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		None
 	}
 
+	/// Approximate memory used by the overlay values, in bytes. Values kept around for pinned
+	/// blocks are not included.
+	pub fn mem_used(&self) -> usize {
+		self.values_mem
+	}
+
 	/// Check if the block is in the canonicalization queue. 
 	pub fn have_block(&self, hash: &BlockHash) -> bool {
 		(self.parents.contains_key(hash) || self.pending_insertions.contains(hash))
@@ -385,7 +424,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 			for overlay in level.into_iter() {
 				commit.meta.deleted.push(overlay.journal_key);
 				self.parents.remove(&overlay.hash);
-				discard_values(&mut self.values, overlay.inserted, None);
+				self.values_mem -= discard_values(&mut self.values, overlay.inserted, None);
 			}
 			commit
 		})
@@ -402,7 +441,7 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 				.expect("Hash is added in insert");
 
 			let	overlay = self.levels[level_index].pop().expect("Empty levels are not allowed in self.levels");
-			discard_values(&mut self.values, overlay.inserted, None);
+			self.values_mem -= discard_values(&mut self.values, overlay.inserted, None);
 			if self.levels[level_index].is_empty() {
 				debug_assert_eq!(level_index, self.levels.len() - 1);
 				self.levels.pop_back();
@@ -530,6 +569,33 @@ mod tests {
 		assert!(db.data_eq(&make_db(&[1, 3, 4])));
 	}
 
+	#[test]
+	fn insert_detached_canonicalize_one() {
+		let h1 = H256::random();
+		let h2 = H256::random();
+		let h3 = H256::random();
+		let mut db = make_db(&[1, 2]);
+		let mut overlay = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		db.commit(&overlay.insert::<io::Error>(&h1, 1, &H256::default(), make_changeset(&[3], &[])).unwrap());
+		let mut commit = CommitSet::default();
+		overlay.canonicalize::<io::Error>(&h1, &mut commit).unwrap();
+		db.commit(&commit);
+		overlay.apply_pending();
+
+		assert!(overlay.insert::<io::Error>(&h3, 10, &h2, make_changeset(&[4], &[])).is_err());
+		assert!(overlay.insert_detached::<io::Error>(&h3, 1, &h2, make_changeset(&[4], &[])).is_err());
+		db.commit(&overlay.insert_detached::<io::Error>(&h3, 10, &h2, make_changeset(&[4], &[])).unwrap());
+		assert_eq!(overlay.last_canonicalized, Some((h2, 9)));
+		let mut commit = CommitSet::default();
+		overlay.canonicalize::<io::Error>(&h3, &mut commit).unwrap();
+		db.commit(&commit);
+		overlay.apply_pending();
+		assert!(db.data_eq(&make_db(&[1, 2, 3, 4])));
+
+		let overlay2 = NonCanonicalOverlay::<H256, H256>::new(&db).unwrap();
+		assert_eq!(overlay.last_canonicalized, overlay2.last_canonicalized);
+	}
+
 	#[test]
 	fn restore_from_journal() {
 		let h1 = H256::random();
@@ -611,12 +677,14 @@ mod tests {
 		db.commit(&overlay.insert::<io::Error>(&h_1, 1, &H256::default(), c_1).unwrap());
 		db.commit(&overlay.insert::<io::Error>(&h_2, 1, &H256::default(), c_2).unwrap());
 		assert!(contains(&overlay, 1));
+		assert_eq!(overlay.mem_used(), 64);
 		let mut commit = CommitSet::default();
 		overlay.canonicalize::<io::Error>(&h_1, &mut commit).unwrap();
 		db.commit(&commit);
 		assert!(contains(&overlay, 1));
 		overlay.apply_pending();
 		assert!(!contains(&overlay, 1));
+		assert_eq!(overlay.mem_used(), 0);
 	}
 
 	#[test]
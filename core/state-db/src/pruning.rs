@@ -21,7 +21,9 @@
 //! If a node is re-inserted into the window it gets removed from
 //! the death list.
 //! The changes are journaled in the DB.
+//! Memory used by the window is estimated from the number of keys pending deletion.
 
+use std::mem;
 use std::collections::{HashMap, HashSet, VecDeque};
 use codec::{Encode, Decode};
 use crate::{CommitSet, Error, MetaDb, to_meta_key, Hash};
@@ -44,6 +46,9 @@ pub struct RefWindow<BlockHash: Hash, Key: Hash> {
 	/// Number of calls of `prune_one` after
 	/// last call `apply_pending` or `revert_pending`
 	pending_prunings: usize,
+	/// Number of keys in the blocks pruned with `prune_one` after
+	/// last call `apply_pending` or `revert_pending`
+	pending_pruned_keys: usize,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -64,6 +69,12 @@ fn to_journal_key(block: u64) -> Vec<u8> {
 	to_meta_key(PRUNING_JOURNAL, &block)
 }
 
+/// Approximate memory taken by a single key pending deletion. Each key is stored
+/// both in the death row and in the death index.
+fn key_mem<Key: Hash>() -> usize {
+	2 * mem::size_of::<Key>() + mem::size_of::<u64>()
+}
+
 impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 	pub fn new<D: MetaDb>(db: &D) -> Result<RefWindow<BlockHash, Key>, Error<D::Error>> {
 		let last_pruned = db.get_meta(&to_meta_key(LAST_PRUNED, &()))
@@ -79,6 +90,7 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 			pending_number: pending_number,
 			pending_canonicalizations: 0,
 			pending_prunings: 0,
+			pending_pruned_keys: 0,
 		};
 		// read the journal
 		trace!(target: "state-db", "Reading pruning journal. Pending #{}", pending_number);
@@ -127,8 +139,9 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 		self.death_rows.get(self.pending_prunings).map(|r| r.hash.clone())
 	}
 
+	/// Approximate memory used by the blocks remaining in the window, in bytes.
 	pub fn mem_used(&self) -> usize {
-		0
+		self.death_index.len().saturating_sub(self.pending_pruned_keys) * key_mem::<Key>()
 	}
 
 	pub fn pending(&self) -> u64 {
@@ -148,6 +161,7 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 			commit.meta.inserted.push((to_meta_key(LAST_PRUNED, &()), index.encode()));
 			commit.meta.deleted.push(pruned.journal_key.clone());
 			self.pending_prunings += 1;
+			self.pending_pruned_keys += pruned.deleted.len();
 		} else {
 			warn!(target: "state-db", "Trying to prune when there's nothing to prune");
 		}
@@ -182,6 +196,7 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 			self.pending_number += 1;
 		}
 		self.pending_prunings = 0;
+		self.pending_pruned_keys = 0;
 	}
 
 	/// Revert all pending changes
@@ -195,12 +210,13 @@ impl<BlockHash: Hash, Key: Hash> RefWindow<BlockHash, Key> {
 		self.death_index.retain(|_, block| *block < new_max_block);
 		self.pending_canonicalizations = 0;
 		self.pending_prunings = 0;
+		self.pending_pruned_keys = 0;
 	}
 }
 
 #[cfg(test)]
 mod tests {
-	use super::RefWindow;
+	use super::{RefWindow, key_mem};
 	use primitives::H256;
 	use crate::CommitSet;
 	use crate::test::{make_db, make_commit, TestDb};
@@ -376,4 +392,66 @@ mod tests {
 		pruning.apply_pending();
 		assert_eq!(pruning.pending_number, 3);
 	}
+
+	#[test]
+	fn mem_used_tracks_window() {
+		let mut db = make_db(&[1, 2, 3]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db).unwrap();
+		assert_eq!(pruning.mem_used(), 0);
+		let mut commit = make_commit(&[4], &[1, 2]);
+		pruning.note_canonical(&H256::random(), &mut commit);
+		db.commit(&commit);
+		let mut commit = make_commit(&[5], &[3]);
+		pruning.note_canonical(&H256::random(), &mut commit);
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert_eq!(pruning.mem_used(), 3 * key_mem::<H256>());
+
+		let restored: RefWindow<H256, H256> = RefWindow::new(&db).unwrap();
+		assert_eq!(restored.mem_used(), pruning.mem_used());
+
+		let mut commit = CommitSet::default();
+		pruning.prune_one(&mut commit);
+		assert_eq!(pruning.mem_used(), key_mem::<H256>());
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert_eq!(pruning.mem_used(), key_mem::<H256>());
+
+		let mut commit = CommitSet::default();
+		pruning.prune_one(&mut commit);
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert_eq!(pruning.mem_used(), 0);
+	}
+
+	#[test]
+	fn reinserted_keys_release_mem() {
+		let mut db = make_db(&[1, 2, 3]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db).unwrap();
+		let mut commit = make_commit(&[], &[1, 2]);
+		pruning.note_canonical(&H256::random(), &mut commit);
+		db.commit(&commit);
+		let mut commit = make_commit(&[2], &[]);
+		pruning.note_canonical(&H256::random(), &mut commit);
+		db.commit(&commit);
+		pruning.apply_pending();
+		assert_eq!(pruning.mem_used(), key_mem::<H256>());
+	}
+
+	#[test]
+	fn revert_pending_releases_mem() {
+		let mut db = make_db(&[1, 2, 3]);
+		let mut pruning: RefWindow<H256, H256> = RefWindow::new(&db).unwrap();
+		let mut commit = make_commit(&[4], &[1]);
+		pruning.note_canonical(&H256::random(), &mut commit);
+		db.commit(&commit);
+		pruning.apply_pending();
+
+		let mut commit = make_commit(&[5], &[2, 3]);
+		pruning.note_canonical(&H256::random(), &mut commit);
+		pruning.prune_one(&mut commit);
+		assert_eq!(pruning.mem_used(), 2 * key_mem::<H256>());
+		pruning.revert_pending();
+		assert_eq!(pruning.mem_used(), key_mem::<H256>());
+	}
 }
@@ -67,6 +67,12 @@ pub trait Backend<H: Hasher> {
 		Ok(self.child_storage(storage_key, key)?.is_some())
 	}
 
+	/// Return the smallest key in storage that is strictly greater than `key`, if any.
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error>;
+
+	/// Return the smallest key in child storage that is strictly greater than `key`, if any.
+	fn next_child_storage_key(&self, storage_key: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error>;
+
 	/// Retrieve all entries keys of child storage and call `f` for each of those keys.
 	fn for_keys_in_child_storage<F: FnMut(&[u8])>(&self, storage_key: &[u8], f: F);
 
@@ -317,6 +323,16 @@ impl<H: Hasher> Backend<H> for InMemory<H> {
 		Ok(self.inner.get(&None).map(|map| map.get(key).is_some()).unwrap_or(false))
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		Ok(self.inner.get(&None)
+			.and_then(|map| map.keys().filter(|k| &k[..] > key).min().cloned()))
+	}
+
+	fn next_child_storage_key(&self, storage_key: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		Ok(self.inner.get(&Some(storage_key.to_vec()))
+			.and_then(|map| map.keys().filter(|k| &k[..] > key).min().cloned()))
+	}
+
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
 		self.inner.get(&None).map(|map| map.keys().filter(|key| key.starts_with(prefix)).map(|k| &**k).for_each(f));
 	}
@@ -153,6 +153,11 @@ impl<H: Hasher> Externalities<H> for BasicExternalities where H::Out: Ord {
 		}
 	}
 
+	fn set_offchain_storage(&mut self, _key: &[u8], _value: Option<&[u8]>) {
+		// offchain storage is not part of the state, there is nowhere to index it
+		warn!("Call to set offchain storage on basic externalities, the change is discarded.");
+	}
+
 	fn chain_id(&self) -> u64 { 42 }
 
 	fn storage_root(&mut self) -> H::Out {
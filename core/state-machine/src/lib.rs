@@ -230,6 +230,13 @@ pub trait Externalities<H: Hasher> {
 	/// Set or clear a child storage entry. Return whether the operation succeeds.
 	fn place_child_storage(&mut self, storage_key: ChildStorageKey<H>, key: Vec<u8>, value: Option<Vec<u8>>);
 
+	/// Set or clear an entry of the offchain storage.
+	///
+	/// The change is written to the offchain database once the block is finalized
+	/// and is not part of the consensus state. Externalities that are not backed by
+	/// a database, like `BasicExternalities`, discard it.
+	fn set_offchain_storage(&mut self, key: &[u8], value: Option<&[u8]>);
+
 	/// Get the identity of the chain.
 	fn chain_id(&self) -> u64;
 
@@ -439,6 +446,68 @@ impl ExecutionStrategy {
 	}
 }
 
+/// Storage changes made by a single call, relative to the overlay it started from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageDiff {
+	/// Changed top-level keys with their new values, sorted by key. `None` if deleted.
+	pub top: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+	/// Changed child storage keys, grouped by storage key and sorted.
+	pub children: Vec<(Vec<u8>, Vec<(Vec<u8>, Option<Vec<u8>>)>)>,
+}
+
+impl StorageDiff {
+	fn between(before: &OverlayedChangeSet, after: &OverlayedChangeSet) -> Self {
+		let mut top: Vec<_> = after.top.iter()
+			.filter(|(k, v)| before.top.get(*k).map_or(true, |b| b.value != v.value))
+			.map(|(k, v)| (k.clone(), v.value.clone()))
+			.collect();
+		top.sort();
+
+		let mut children: Vec<_> = after.children.iter()
+			.filter_map(|(storage_key, (_, changes))| {
+				let prior = before.children.get(storage_key).map(|(_, c)| c);
+				let mut changes: Vec<_> = changes.iter()
+					.filter(|(k, v)| prior.map_or(true, |p| p.get(*k) != Some(*v)))
+					.map(|(k, v)| (k.clone(), v.clone()))
+					.collect();
+				if changes.is_empty() {
+					return None;
+				}
+				changes.sort();
+				Some((storage_key.clone(), changes))
+			})
+			.collect();
+		children.sort();
+
+		StorageDiff { top, children }
+	}
+}
+
+/// Details of a call whose native and wasm executions disagreed under `ExecutionManager::Both`.
+#[derive(Debug, Clone)]
+pub struct ConsensusFailure {
+	/// The runtime method that was called.
+	pub method: String,
+	/// The encoded call data.
+	pub call_data: Vec<u8>,
+	/// Encoded native result, or the debug representation of its error.
+	pub native_result: result::Result<Vec<u8>, String>,
+	/// Encoded wasm result, or the debug representation of its error.
+	pub wasm_result: result::Result<Vec<u8>, String>,
+	/// Storage changes made by the native execution.
+	pub native_changes: StorageDiff,
+	/// Storage changes made by the wasm execution.
+	pub wasm_changes: StorageDiff,
+}
+
+fn describe_result<R: Encode, E: fmt::Debug>(
+	result: &CallResult<R, E>,
+) -> result::Result<Vec<u8>, String> {
+	match result {
+		Ok(value) => Ok(value.as_encoded().into_owned()),
+		Err(e) => Err(format!("{:?}", e)),
+	}
+}
 
 /// Evaluate to ExecutionManager::NativeWhenPossible, without having to figure out the type.
 pub fn native_when_possible<E, R: Decode>() -> ExecutionManager<DefaultHandler<R, E>> {
@@ -475,6 +544,7 @@ pub fn new<'a, H, N, B, T, O, Exec>(
 		method,
 		call_data,
 		keystore,
+		consensus_failure: None,
 		_hasher: PhantomData,
 	}
 }
@@ -489,6 +559,7 @@ pub struct StateMachine<'a, H, N, B, T, O, Exec> {
 	method: &'a str,
 	call_data: &'a [u8],
 	keystore: Option<BareCryptoStorePtr>,
+	consensus_failure: Option<ConsensusFailure>,
 	_hasher: PhantomData<(H, N)>,
 }
 
@@ -581,7 +652,7 @@ impl<'a, H, N, B, T, O, Exec> StateMachine<'a, H, N, B, T, O, Exec> where
 		let (result, was_native, storage_delta, changes_delta) = self.execute_aux(compute_tx, true, native_call.take());
 
 		if was_native {
-			self.overlay.prospective = orig_prospective.clone();
+			let native_prospective = std::mem::replace(&mut self.overlay.prospective, orig_prospective.clone());
 			let (wasm_result, _, wasm_storage_delta, wasm_changes_delta) = self.execute_aux(compute_tx, false, native_call);
 
 			if (result.is_ok() && wasm_result.is_ok()
@@ -589,6 +660,14 @@ impl<'a, H, N, B, T, O, Exec> StateMachine<'a, H, N, B, T, O, Exec> where
 				|| result.is_err() && wasm_result.is_err() {
 				(result, storage_delta, changes_delta)
 			} else {
+				self.consensus_failure = Some(ConsensusFailure {
+					method: self.method.to_owned(),
+					call_data: self.call_data.to_vec(),
+					native_result: describe_result(&result),
+					wasm_result: describe_result(&wasm_result),
+					native_changes: StorageDiff::between(&orig_prospective, &native_prospective),
+					wasm_changes: StorageDiff::between(&orig_prospective, &self.overlay.prospective),
+				});
 				(on_consensus_failure(wasm_result, result), wasm_storage_delta, wasm_changes_delta)
 			}
 		} else {
@@ -616,6 +695,12 @@ impl<'a, H, N, B, T, O, Exec> StateMachine<'a, H, N, B, T, O, Exec> where
 		}
 	}
 
+	/// Take the details of the last native/wasm divergence detected by
+	/// `ExecutionManager::Both`, if any.
+	pub fn take_consensus_failure(&mut self) -> Option<ConsensusFailure> {
+		self.consensus_failure.take()
+	}
+
 	/// Execute a call using the given state backend, overlayed changes, and call executor.
 	/// Produces a state-backend-specific "transaction" which can be used to apply the changes
 	/// to the backing store, such as the disk.
@@ -740,6 +825,7 @@ where
 		method,
 		call_data,
 		keystore,
+		consensus_failure: None,
 		_hasher: PhantomData,
 	};
 	let (result, _, _) = sm.execute_using_consensus_failure_handler::<_, NeverNativeValue, fn() -> _>(
@@ -793,6 +879,7 @@ where
 		method,
 		call_data,
 		keystore,
+		consensus_failure: None,
 		_hasher: PhantomData,
 	};
 	sm.execute_using_consensus_failure_handler::<_, NeverNativeValue, fn() -> _>(
@@ -836,6 +923,42 @@ where
 }
 
 
+/// Generate storage read proof for a set of keys.
+pub fn prove_read_keys<B, H, I>(
+	mut backend: B,
+	keys: I,
+) -> Result<Vec<Vec<u8>>, Box<dyn Error>>
+where
+	B: Backend<H>,
+	H: Hasher,
+	H::Out: Ord,
+	I: IntoIterator,
+	I::Item: AsRef<[u8]>,
+{
+	let trie_backend = backend.as_trie_backend()
+		.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<dyn Error>)?;
+	prove_read_keys_on_trie_backend(trie_backend, keys)
+}
+
+/// Generate child storage read proof for a set of keys.
+pub fn prove_child_read_keys<B, H, I>(
+	mut backend: B,
+	storage_key: &[u8],
+	keys: I,
+) -> Result<Vec<Vec<u8>>, Box<dyn Error>>
+where
+	B: Backend<H>,
+	H: Hasher,
+	H::Out: Ord,
+	I: IntoIterator,
+	I::Item: AsRef<[u8]>,
+{
+	let trie_backend = backend.as_trie_backend()
+		.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<dyn Error>)?;
+	prove_child_read_keys_on_trie_backend(trie_backend, storage_key, keys)
+}
+
+
 /// Generate storage read proof on pre-created trie backend.
 pub fn prove_read_on_trie_backend<S, H>(
 	trie_backend: &TrieBackend<S, H>,
@@ -867,6 +990,102 @@ where
 	Ok((result, proving_backend.extract_proof()))
 }
 
+/// Generate storage read proof for a set of keys on pre-created trie backend.
+pub fn prove_read_keys_on_trie_backend<S, H, I>(
+	trie_backend: &TrieBackend<S, H>,
+	keys: I,
+) -> Result<Vec<Vec<u8>>, Box<dyn Error>>
+where
+	S: trie_backend_essence::TrieBackendStorage<H>,
+	H: Hasher,
+	H::Out: Ord,
+	I: IntoIterator,
+	I::Item: AsRef<[u8]>,
+{
+	let proving_backend = proving_backend::ProvingBackend::<_, H>::new(trie_backend);
+	for key in keys.into_iter() {
+		proving_backend.storage(key.as_ref()).map_err(|e| Box::new(e) as Box<dyn Error>)?;
+	}
+	Ok(proving_backend.extract_proof())
+}
+
+/// Generate child storage read proof for a set of keys on pre-created trie backend.
+pub fn prove_child_read_keys_on_trie_backend<S, H, I>(
+	trie_backend: &TrieBackend<S, H>,
+	storage_key: &[u8],
+	keys: I,
+) -> Result<Vec<Vec<u8>>, Box<dyn Error>>
+where
+	S: trie_backend_essence::TrieBackendStorage<H>,
+	H: Hasher,
+	H::Out: Ord,
+	I: IntoIterator,
+	I::Item: AsRef<[u8]>,
+{
+	let proving_backend = proving_backend::ProvingBackend::<_, H>::new(trie_backend);
+	for key in keys.into_iter() {
+		proving_backend.child_storage(storage_key, key.as_ref())
+			.map_err(|e| Box::new(e) as Box<dyn Error>)?;
+	}
+	Ok(proving_backend.extract_proof())
+}
+
+/// Generate a proof of the storage entries from `start` (inclusive) on, in the top trie or
+/// in the given child trie. The proof covers about `max_bytes` of keys and values.
+pub fn prove_range_read<B, H>(
+	mut backend: B,
+	child_storage_key: Option<&[u8]>,
+	start: &[u8],
+	max_bytes: usize,
+) -> Result<Vec<Vec<u8>>, Box<dyn Error>>
+where
+	B: Backend<H>,
+	H: Hasher,
+	H::Out: Ord
+{
+	let trie_backend = backend.as_trie_backend()
+		.ok_or_else(|| Box::new(ExecutionError::UnableToGenerateProof) as Box<dyn Error>)?;
+	prove_range_read_on_trie_backend(trie_backend, child_storage_key, start, max_bytes)
+}
+
+/// Generate a proof of the storage entries from `start` on, on pre-created trie backend.
+pub fn prove_range_read_on_trie_backend<S, H>(
+	trie_backend: &TrieBackend<S, H>,
+	child_storage_key: Option<&[u8]>,
+	start: &[u8],
+	max_bytes: usize,
+) -> Result<Vec<Vec<u8>>, Box<dyn Error>>
+where
+	S: trie_backend_essence::TrieBackendStorage<H>,
+	H: Hasher,
+	H::Out: Ord
+{
+	let mut proof_recorder = ProofRecorder::new();
+	proving_backend::ProvingBackendEssence {
+		backend: trie_backend.essence(),
+		proof_recorder: &mut proof_recorder,
+	}.record_range(child_storage_key, start, max_bytes).map_err(|e| Box::new(e) as Box<dyn Error>)?;
+	Ok(proof_recorder.drain().into_iter().map(|n| n.data.to_vec()).collect())
+}
+
+/// Check storage range proof, generated by `prove_range_read` call, against the root of
+/// the trie it was generated for.
+///
+/// Returns the proven entries from `start` on, and whether they reach the end of the trie.
+pub fn read_range_proof_check<H>(
+	root: H::Out,
+	proof: Vec<Vec<u8>>,
+	start: &[u8],
+) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Box<dyn Error>>
+where
+	H: Hasher,
+	H::Out: Ord
+{
+	let db = create_proof_check_backend_storage::<H>(proof);
+	trie::read_range::<Layout<H>, _>(&db, &root, start)
+		.map_err(|e| Box::new(format!("Trie lookup error: {}", e)) as Box<dyn Error>)
+}
+
 /// Check storage read proof, generated by `prove_read` call.
 pub fn read_proof_check<H>(
 	root: H::Out,
@@ -1099,6 +1318,41 @@ mod tests {
 		assert!(consensus_failed);
 	}
 
+	#[test]
+	fn dual_execution_strategy_records_consensus_failure() {
+		let backend = trie_backend::tests::test_trie();
+		let changes_trie_storage = InMemoryChangesTrieStorage::<Blake2Hasher, u64>::new();
+		let mut overlay = OverlayedChanges::default();
+		let executor = DummyCodeExecutor {
+			change_changes_trie_config: false,
+			native_available: true,
+			native_succeeds: true,
+			fallback_succeeds: false,
+		};
+		let mut sm = new(
+			&backend,
+			Some(&changes_trie_storage),
+			NeverOffchainExt::new(),
+			&mut overlay,
+			&executor,
+			"test",
+			&[1, 2, 3],
+			None,
+		);
+		assert!(sm.execute_using_consensus_failure_handler::<_, NeverNativeValue, fn() -> _>(
+			ExecutionManager::Both(|we, _ne| we),
+			true,
+			None,
+		).is_err());
+
+		let failure = sm.take_consensus_failure().unwrap();
+		assert_eq!(failure.method, "test");
+		assert_eq!(failure.call_data, vec![1, 2, 3]);
+		assert_eq!(failure.native_result, Ok(vec![66]));
+		assert!(failure.wasm_result.is_err());
+		assert!(sm.take_consensus_failure().is_none());
+	}
+
 	#[test]
 	fn prove_execution_and_proof_check_works() {
 		let executor = DummyCodeExecutor {
@@ -1266,6 +1520,79 @@ mod tests {
 		assert_eq!(local_result2, None);
 	}
 
+	#[test]
+	fn prove_range_read_and_proof_check_works() {
+		let remote_backend = trie_backend::tests::test_trie();
+		let remote_root = remote_backend.storage_root(::std::iter::empty()).0;
+		let mut expected = remote_backend.pairs();
+		expected.sort();
+
+		let mut entries = Vec::new();
+		let mut start = Vec::new();
+		loop {
+			let proof = prove_range_read(
+				trie_backend::tests::test_trie(),
+				None,
+				&start,
+				64,
+			).unwrap();
+			let (chunk, complete) = read_range_proof_check::<Blake2Hasher>(
+				remote_root,
+				proof,
+				&start,
+			).unwrap();
+			assert!(!chunk.is_empty());
+			start = chunk.last().unwrap().0.clone();
+			start.push(0);
+			entries.extend(chunk);
+			if complete {
+				break;
+			}
+		}
+		assert_eq!(entries, expected);
+	}
+
+	#[test]
+	fn prove_read_keys_and_proof_check_works() {
+		let remote_backend = trie_backend::tests::test_trie();
+		let remote_root = remote_backend.storage_root(::std::iter::empty()).0;
+		let remote_proof = prove_read_keys(remote_backend, &[&b"value2"[..], &b"key"[..]]).unwrap();
+		assert_eq!(
+			read_proof_check::<Blake2Hasher>(remote_root, remote_proof.clone(), b"value2").unwrap(),
+			Some(vec![24]),
+		);
+		assert_eq!(
+			read_proof_check::<Blake2Hasher>(remote_root, remote_proof.clone(), b"key").unwrap(),
+			Some(b"value".to_vec()),
+		);
+
+		let remote_backend = trie_backend::tests::test_trie();
+		let remote_root = remote_backend.storage_root(::std::iter::empty()).0;
+		let remote_proof = prove_child_read_keys(
+			remote_backend,
+			b":child_storage:default:sub1",
+			&[&b"value3"[..], &b"value2"[..]],
+		).unwrap();
+		assert_eq!(
+			read_child_proof_check::<Blake2Hasher>(
+				remote_root,
+				remote_proof.clone(),
+				b":child_storage:default:sub1",
+				b"value3",
+			).unwrap(),
+			Some(vec![142]),
+		);
+		assert_eq!(
+			read_child_proof_check::<Blake2Hasher>(
+				remote_root,
+				remote_proof.clone(),
+				b":child_storage:default:sub1",
+				b"value2",
+			).unwrap(),
+			None,
+		);
+	}
+
 	#[test]
 	fn cannot_change_changes_trie_config() {
 		assert!(
@@ -56,6 +56,8 @@ pub struct OverlayedChangeSet {
 	pub top: HashMap<Vec<u8>, OverlayedValue>,
 	/// Child storage changes.
 	pub children: HashMap<Vec<u8>, (Option<BTreeSet<u32>>, HashMap<Vec<u8>, Option<Vec<u8>>>)>,
+	/// Offchain storage changes. These are not part of the state.
+	pub offchain: HashMap<Vec<u8>, Option<Vec<u8>>>,
 }
 
 #[cfg(test)]
@@ -64,6 +66,7 @@ impl FromIterator<(Vec<u8>, OverlayedValue)> for OverlayedChangeSet {
 		Self {
 			top: iter.into_iter().collect(),
 			children: Default::default(),
+			offchain: Default::default(),
 		}
 	}
 }
@@ -71,13 +74,14 @@ impl FromIterator<(Vec<u8>, OverlayedValue)> for OverlayedChangeSet {
 impl OverlayedChangeSet {
 	/// Whether the change set is empty.
 	pub fn is_empty(&self) -> bool {
-		self.top.is_empty() && self.children.is_empty()
+		self.top.is_empty() && self.children.is_empty() && self.offchain.is_empty()
 	}
 
 	/// Clear the change set.
 	pub fn clear(&mut self) {
 		self.top.clear();
 		self.children.clear();
+		self.offchain.clear();
 	}
 }
 
@@ -159,6 +163,13 @@ impl OverlayedChanges {
 		}
 	}
 
+	/// Inserts the given key-value pair into the prospective offchain change set.
+	///
+	/// `None` can be used to delete a value specified by the given key.
+	pub(crate) fn set_offchain_storage(&mut self, key: &[u8], val: Option<&[u8]>) {
+		self.prospective.offchain.insert(key.to_vec(), val.map(|v| v.to_vec()));
+	}
+
 	/// Clear child storage of given storage key.
 	///
 	/// NOTE that this doesn't take place immediately but written into the prospective
@@ -280,6 +291,7 @@ impl OverlayedChanges {
 						.extend(prospective_extrinsics);
 				}
 			}
+			self.committed.offchain.extend(self.prospective.offchain.drain());
 		}
 	}
 
@@ -296,6 +308,13 @@ impl OverlayedChanges {
 			self.committed.children.into_iter().map(|(sk, v)| (sk, v.1.into_iter())))
 	}
 
+	/// Returns committed offchain storage changes.
+	///
+	/// Values are `None` if the entry should be deleted.
+	pub fn offchain_storage_changes(&self) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+		self.committed.offchain.iter().map(|(k, v)| (k.clone(), v.clone())).collect()
+	}
+
 	/// Inserts storage entry responsible for current extrinsic index.
 	#[cfg(test)]
 	pub(crate) fn set_extrinsic_index(&mut self, extrinsic_index: u32) {
@@ -375,6 +394,25 @@ mod tests {
 		assert!(overlayed.storage(&key).unwrap().is_none());
 	}
 
+	#[test]
+	fn offchain_storage_changes_follow_prospective_commits() {
+		let mut overlayed = OverlayedChanges::default();
+
+		overlayed.set_offchain_storage(b"kept", Some(b"value"));
+		overlayed.commit_prospective();
+		overlayed.set_offchain_storage(b"discarded", Some(b"value"));
+		overlayed.discard_prospective();
+		overlayed.set_offchain_storage(b"removed", None);
+		overlayed.commit_prospective();
+
+		let mut changes = overlayed.offchain_storage_changes();
+		changes.sort();
+		assert_eq!(changes, vec![
+			(b"kept".to_vec(), Some(b"value".to_vec())),
+			(b"removed".to_vec(), None),
+		]);
+	}
+
 	#[test]
 	fn overlayed_storage_root_works() {
 		let initial: HashMap<_, _> = vec![
@@ -21,7 +21,7 @@ use log::debug;
 use hash_db::{Hasher, HashDB, EMPTY_PREFIX};
 use trie::{
 	MemoryDB, PrefixedMemoryDB, default_child_trie_root,
-	read_trie_value_with, read_child_trie_value_with, record_all_keys
+	read_trie_value_with, read_child_trie_value_with, record_all_keys, record_range
 };
 pub use trie::Recorder;
 pub use trie::trie_types::{Layout, TrieError};
@@ -99,6 +99,36 @@ impl<'a, S, H> ProvingBackendEssence<'a, S, H>
 			debug!(target: "trie", "Error while recording all keys: {}", e);
 		}
 	}
+
+	/// Record the entries of the top trie, or of the given child trie, from `start` on.
+	///
+	/// Returns `true` if the end of the trie has been reached.
+	pub fn record_range(
+		&mut self,
+		child_storage_key: Option<&[u8]>,
+		start: &[u8],
+		max_bytes: usize,
+	) -> Result<bool, String> {
+		let root = match child_storage_key {
+			Some(storage_key) => {
+				let child_root = self.backend.storage(storage_key)?
+					.unwrap_or(default_child_trie_root::<Layout<H>>(storage_key));
+				let mut root = H::Out::default();
+				root.as_mut().copy_from_slice(&child_root);
+				root
+			},
+			None => self.backend.root().clone(),
+		};
+
+		let mut read_overlay = S::Overlay::default();
+		let eph = Ephemeral::new(
+			self.backend.backend_storage(),
+			&mut read_overlay,
+		);
+
+		record_range::<Layout<H>, _>(&eph, &root, start, max_bytes, &mut *self.proof_recorder)
+			.map_err(|e| format!("Trie lookup error: {}", e))
+	}
 }
 
 /// Patricia trie-based backend which also tracks all touched storage trie values.
@@ -170,6 +200,14 @@ impl<'a, S, H> Backend<H> for ProvingBackend<'a, S, H>
 		self.backend.for_keys_in_child_storage(storage_key, f)
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.backend.next_storage_key(key)
+	}
+
+	fn next_child_storage_key(&self, storage_key: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.backend.next_child_storage_key(storage_key, key)
+	}
+
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
 		self.backend.for_keys_with_prefix(prefix, f)
 	}
@@ -225,6 +225,10 @@ impl<H, N> Externalities<H> for TestExternalities<H, N>
 		});
 	}
 
+	fn set_offchain_storage(&mut self, key: &[u8], value: Option<&[u8]>) {
+		self.overlay.set_offchain_storage(key, value);
+	}
+
 	fn chain_id(&self) -> u64 { 42 }
 
 	fn storage_root(&mut self) -> H::Out {
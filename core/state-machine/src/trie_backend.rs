@@ -74,6 +74,14 @@ impl<S: TrieBackendStorage<H>, H: Hasher> Backend<H> for TrieBackend<S, H> where
 		self.essence.child_storage(storage_key, key)
 	}
 
+	fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.essence.next_storage_key(key)
+	}
+
+	fn next_child_storage_key(&self, storage_key: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+		self.essence.next_child_storage_key(storage_key, key)
+	}
+
 	fn for_keys_with_prefix<F: FnMut(&[u8])>(&self, prefix: &[u8], f: F) {
 		self.essence.for_keys_with_prefix(prefix, f)
 	}
@@ -287,4 +295,21 @@ pub mod tests {
 		expected.insert(b"value2".to_vec());
 		assert_eq!(seen, expected);
 	}
+
+	#[test]
+	fn next_storage_key_works() {
+		let trie = test_trie();
+
+		assert_eq!(trie.next_storage_key(b"").unwrap(), Some(b":child_storage:default:sub1".to_vec()));
+		assert_eq!(trie.next_storage_key(b"key").unwrap(), Some(b"value1".to_vec()));
+		assert_eq!(trie.next_storage_key(b"value").unwrap(), Some(b"value1".to_vec()));
+		assert_eq!(trie.next_storage_key(b"value2").unwrap(), Some(vec![128]));
+		assert_eq!(trie.next_storage_key(&[254]).unwrap(), None);
+
+		let child = b":child_storage:default:sub1";
+		assert_eq!(trie.next_child_storage_key(child, b"").unwrap(), Some(b"value3".to_vec()));
+		assert_eq!(trie.next_child_storage_key(child, b"value3").unwrap(), Some(b"value4".to_vec()));
+		assert_eq!(trie.next_child_storage_key(child, b"value4").unwrap(), None);
+		assert_eq!(trie.next_child_storage_key(b":child_storage:default:sub2", b"").unwrap(), None);
+	}
 }
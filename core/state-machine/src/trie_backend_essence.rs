@@ -92,6 +92,49 @@ impl<S: TrieBackendStorage<H>, H: Hasher> TrieBackendEssence<S, H> {
 		read_child_trie_value::<Layout<H>, _>(storage_key, &eph, &root, key).map_err(map_e)
 	}
 
+	/// Return the smallest key in storage that is strictly greater than `key`, if any.
+	pub fn next_storage_key(&self, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+		self.next_storage_key_inner(&self.root, key)
+	}
+
+	/// Return the smallest key in child storage that is strictly greater than `key`, if any.
+	pub fn next_child_storage_key(&self, storage_key: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+		let root_vec = match self.storage(storage_key)? {
+			Some(root) => root,
+			None => return Ok(None),
+		};
+		let mut root = H::Out::default();
+		root.as_mut().copy_from_slice(&root_vec);
+
+		self.next_storage_key_inner(&root, key)
+	}
+
+	fn next_storage_key_inner(&self, root: &H::Out, key: &[u8]) -> Result<Option<Vec<u8>>, String> {
+		let mut read_overlay = S::Overlay::default();
+		let eph = Ephemeral {
+			storage: &self.storage,
+			overlay: &mut read_overlay,
+		};
+
+		let next = || -> Result<_, Box<TrieError<H::Out>>> {
+			let trie = TrieDB::<H>::new(&eph, root)?;
+			let mut iter = trie.iter()?;
+
+			// `key ++ 0` is the smallest key strictly greater than `key`.
+			let mut seek_key = Vec::with_capacity(key.len() + 1);
+			seek_key.extend_from_slice(key);
+			seek_key.push(0);
+			iter.seek(&seek_key)?;
+
+			Ok(match iter.next() {
+				Some(next) => Some(next?.0),
+				None => None,
+			})
+		};
+
+		next().map_err(|e| format!("Trie lookup error: {}", e))
+	}
+
 	/// Retrieve all entries keys of child storage and call `f` for each of those keys.
 	pub fn for_keys_in_child_storage<F: FnMut(&[u8])>(&self, storage_key: &[u8], f: F) {
 		let root = match self.storage(storage_key) {
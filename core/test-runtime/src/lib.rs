@@ -141,6 +141,7 @@ impl BlindCheckable for Extrinsic {
 
 impl ExtrinsicT for Extrinsic {
 	type Call = Extrinsic;
+	type SignaturePayload = ();
 
 	fn is_signed(&self) -> Option<bool> {
 		if let Extrinsic::IncludeData(_) = *self {
@@ -635,6 +636,12 @@ cfg_if! {
 					SessionKeys::generate(None)
 				}
 			}
+
+			impl system_rpc_runtime_api::AccountNonceApi<Block, AccountId, u64> for Runtime {
+				fn account_nonce(account: AccountId) -> u64 {
+					system::nonce_of(account)
+				}
+			}
 		}
 	} else {
 		impl_runtime_apis! {
@@ -850,6 +857,12 @@ cfg_if! {
 					SessionKeys::generate(None)
 				}
 			}
+
+			impl system_rpc_runtime_api::AccountNonceApi<Block, AccountId, u64> for Runtime {
+				fn account_nonce(account: AccountId) -> u64 {
+					system::nonce_of(account)
+				}
+			}
 		}
 	}
 }
@@ -171,9 +171,8 @@ pub fn validate_transaction(utx: Extrinsic) -> TransactionValidity {
 		return TransactionValidity::Unknown(ApplyError::Future as i8);
 	}
 
-	let hash = |from: &AccountId, nonce: u64| {
-		twox_128(&nonce.to_keyed_vec(&from.encode())).to_vec()
-	};
+	// tagged like the transactions of `srml-system`, which the account nonce RPC relies on
+	let hash = |from: &AccountId, nonce: u64| (from, nonce).encode();
 	let requires = if tx.nonce != expected_nonce && tx.nonce > 0 {
 		let mut deps = Vec::new();
 		deps.push(hash(&tx.from, tx.nonce - 1));
@@ -34,7 +34,7 @@ pub use trie_stream::TrieStream;
 pub use node_codec::NodeCodec;
 /// Various re-exports from the `trie-db` crate.
 pub use trie_db::{Trie, TrieMut, DBValue, Recorder, CError,
-	Query, TrieLayout, TrieConfiguration, nibble_ops};
+	Query, TrieLayout, TrieConfiguration, nibble_ops, NibbleSlice, NodeCodec as NodeCodecT, node::Node};
 /// Various re-exports from the `memory-db` crate.
 pub use memory_db::KeyFunction;
 pub use memory_db::prefixed_key;
@@ -283,6 +283,69 @@ pub fn record_all_keys<L: TrieConfiguration, DB>(
 	Ok(())
 }
 
+/// Record the nodes needed to iterate the trie from `start` (inclusive) on, until the
+/// recorded keys and values exceed `max_bytes`.
+///
+/// Returns `true` if the end of the trie has been reached.
+pub fn record_range<L: TrieConfiguration, DB>(
+	db: &DB,
+	root: &TrieHash<L>,
+	start: &[u8],
+	max_bytes: usize,
+	recorder: &mut Recorder<TrieHash<L>>
+) -> Result<bool, Box<TrieError<L>>> where
+	DB: hash_db::HashDBRef<L::Hash, trie_db::DBValue>
+{
+	let trie = TrieDB::<L>::new(&*db, root)?;
+	let mut iter = trie.iter()?;
+	iter.seek(start)?;
+
+	// the lookup records the nodes on the path to `start`, even if there is no such key.
+	trie.get_with(start, &mut *recorder)?;
+
+	let mut recorded_bytes = 0;
+	for x in iter {
+		if recorded_bytes >= max_bytes {
+			return Ok(false);
+		}
+
+		let (key, value) = x?;
+		trie.get_with(&key, &mut *recorder)?;
+		recorded_bytes += key.len() + value.len();
+	}
+
+	Ok(true)
+}
+
+/// Read the key-value pairs from `start` (inclusive) on, stopping at the first node
+/// missing from `db`.
+///
+/// Returns the pairs along with `true` if the end of the trie has been reached.
+pub fn read_range<L: TrieConfiguration, DB>(
+	db: &DB,
+	root: &TrieHash<L>,
+	start: &[u8],
+) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Box<TrieError<L>>> where
+	DB: hash_db::HashDBRef<L::Hash, trie_db::DBValue>
+{
+	let trie = TrieDB::<L>::new(&*db, root)?;
+	let mut iter = trie.iter()?;
+	iter.seek(start)?;
+
+	let mut pairs = Vec::new();
+	for x in iter {
+		match x {
+			Ok((key, value)) => pairs.push((key, value.to_vec())),
+			Err(e) => match *e {
+				trie_db::TrieError::IncompleteDatabase(_) => return Ok((pairs, false)),
+				_ => return Err(e),
+			},
+		}
+	}
+
+	Ok((pairs, true))
+}
+
 /// Read a value from the child trie.
 pub fn read_child_trie_value<L: TrieConfiguration, DB>(
 	_storage_key: &[u8],
@@ -635,4 +698,42 @@ mod tests {
 
 		assert_eq!(pairs, iter_pairs);
 	}
+
+	#[test]
+	fn range_is_recorded_and_read() {
+		let pairs: Vec<_> = (0u8..64).map(|i| (vec![i, i], vec![i; 32])).collect();
+
+		let mut mdb = MemoryDB::default();
+		let mut root = Default::default();
+		let _ = populate_trie::<Layout>(&mut mdb, &mut root, &pairs);
+
+		let read_chunk = |start: &[u8], max_bytes| {
+			let mut recorder = Recorder::new();
+			let complete = record_range::<Layout, _>(&mdb, &root, start, max_bytes, &mut recorder).unwrap();
+			let mut proof = MemoryDB::<Blake2Hasher>::default();
+			for node in recorder.drain() {
+				proof.insert(EMPTY_PREFIX, &node.data);
+			}
+			let (chunk, proof_complete) = read_range::<Layout, _>(&proof, &root, start).unwrap();
+			assert_eq!(complete, proof_complete);
+			(chunk, complete)
+		};
+
+		let mut read = Vec::new();
+		let mut start = Vec::new();
+		loop {
+			let (chunk, complete) = read_chunk(&start, 100);
+			assert!(!chunk.is_empty());
+			start = chunk.last().unwrap().0.clone();
+			start.push(0);
+			read.extend(chunk);
+			if complete {
+				break;
+			}
+		}
+		assert_eq!(read, pairs);
+
+		// the whole trie fits in a single large enough chunk.
+		assert_eq!(read_chunk(&[], 1024 * 1024), (pairs, true));
+	}
 }
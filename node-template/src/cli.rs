@@ -42,6 +42,11 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 			Ok(new_full_start!(config).0), load_spec, exit),
 		ParseAndPrepare::ImportBlocks(cmd) => cmd.run_with_builder::<(), _, _, _, _, _>(|config|
 			Ok(new_full_start!(config).0), load_spec, exit),
+		ParseAndPrepare::ExportState(cmd) => cmd.run_with_builder::<(), _, _, _, _>(|config|
+			Ok(new_full_start!(config).0), load_spec),
+		ParseAndPrepare::ImportState(cmd) => cmd.run_with_builder::<(), _, _, _, _>(|config|
+			Ok(new_full_start!(config).0), load_spec),
+		ParseAndPrepare::CheckDb(cmd) => cmd.run::<node_template_runtime::opaque::Block, _, _>(load_spec),
 		ParseAndPrepare::PurgeChain(cmd) => cmd.run(load_spec),
 		ParseAndPrepare::RevertChain(cmd) => cmd.run_with_builder::<(), _, _, _, _>(|config|
 			Ok(new_full_start!(config).0), load_spec),
@@ -33,6 +33,7 @@ construct_simple_protocol! {
 macro_rules! new_full_start {
 	($config:expr) => {{
 		let mut import_setup = None;
+		let mut warp_sync_verifier = None;
 		let inherent_data_providers = inherents::InherentDataProviders::new();
 		let mut tasks_to_spawn = None;
 
@@ -54,6 +55,7 @@ macro_rules! new_full_start {
 						client.clone(), client.clone(), select_chain
 					)?;
 				let justification_import = block_import.clone();
+				warp_sync_verifier = Some(block_import.warp_sync_verifier());
 
 				let (import_queue, babe_link, babe_block_import, pruning_task) = babe::import_queue(
 					babe::Config::get_or_compute(&*client)?,
@@ -70,7 +72,11 @@ macro_rules! new_full_start {
 				tasks_to_spawn = Some(vec![Box::new(pruning_task)]);
 
 				Ok(import_queue)
-			})?;
+			})?
+			.with_finality_proof_provider(|client|
+				Ok(std::sync::Arc::new(grandpa::FinalityProofProvider::new(client.clone(), client)) as _)
+			)?
+			.with_warp_sync_verifier(|_| Ok(warp_sync_verifier.take()))?;
 
 		(builder, import_setup, inherent_data_providers, tasks_to_spawn)
 	}}
@@ -84,9 +90,6 @@ pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisCon
 	let (builder, mut import_setup, inherent_data_providers, mut tasks_to_spawn) = new_full_start!(config);
 
 	let service = builder.with_network_protocol(|_| Ok(NodeProtocol::new()))?
-		.with_finality_proof_provider(|client|
-			Ok(Arc::new(GrandpaFinalityProofProvider::new(client.clone(), client)) as _)
-		)?
 		.build()?;
 
 	let (block_import, link_half, babe_link) =
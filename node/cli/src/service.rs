@@ -24,7 +24,7 @@ use babe::{import_queue, Config};
 use client::{self, LongestChain};
 use grandpa::{self, FinalityProofProvider as GrandpaFinalityProofProvider};
 use node_executor;
-use node_primitives::Block;
+use node_primitives::{AccountId, Block, Index};
 use node_runtime::{GenesisConfig, RuntimeApi};
 use substrate_service::{
 	AbstractService, ServiceBuilder, config::Configuration, error::{Error as ServiceError},
@@ -44,13 +44,16 @@ construct_simple_protocol! {
 /// be able to perform chain operations.
 macro_rules! new_full_start {
 	($config:expr) => {{
+		let mut config = $config;
+		config.rpc_event_types = node_rpc::event_types();
 		let mut import_setup = None;
+		let mut warp_sync_verifier = None;
 		let inherent_data_providers = inherents::InherentDataProviders::new();
 		let mut tasks_to_spawn = None;
 
 		let builder = substrate_service::ServiceBuilder::new_full::<
 			node_primitives::Block, node_runtime::RuntimeApi, node_executor::Executor
-		>($config)?
+		>(config)?
 			.with_select_chain(|_config, client| {
 				#[allow(deprecated)]
 				Ok(client::LongestChain::new(client.backend().clone()))
@@ -66,6 +69,7 @@ macro_rules! new_full_start {
 						client.clone(), client.clone(), select_chain
 					)?;
 				let justification_import = block_import.clone();
+				warp_sync_verifier = Some(block_import.warp_sync_verifier());
 
 				let (import_queue, babe_link, babe_block_import, pruning_task) = babe::import_queue(
 					babe::Config::get_or_compute(&*client)?,
@@ -83,14 +87,32 @@ macro_rules! new_full_start {
 
 				Ok(import_queue)
 			})?
-			.with_rpc_extensions(|client, pool| {
-				use node_rpc::accounts::{Accounts, AccountsApi};
-
-				let mut io = jsonrpc_core::IoHandler::<substrate_service::RpcMetadata>::default();
-				io.extend_with(
-					AccountsApi::to_delegate(Accounts::new(client, pool))
-				);
-				io
+			.with_finality_proof_provider(|client|
+				Ok(std::sync::Arc::new(grandpa::FinalityProofProvider::new(client.clone(), client)) as _)
+			)?
+			.with_warp_sync_verifier(|_| Ok(warp_sync_verifier.take()))?
+			.with_rpc_extensions_builder(|client, pool| {
+				use substrate_rpc::system::accounts::{Accounts, AccountsApi};
+				use node_rpc::contracts::{Contracts, ContractsApi};
+				use node_rpc::payment::{Payment, PaymentApi};
+				use node_rpc::system::{System, SystemApi};
+
+				move |deny_unsafe: substrate_service::DenyUnsafe| {
+					let mut io = jsonrpc_core::IoHandler::<substrate_service::RpcMetadata>::default();
+					io.extend_with(
+						AccountsApi::<AccountId, Index>::to_delegate(Accounts::new(client.clone(), pool.clone()))
+					);
+					io.extend_with(
+						ContractsApi::to_delegate(Contracts::new(client.clone()))
+					);
+					io.extend_with(
+						PaymentApi::to_delegate(Payment::new(client.clone()))
+					);
+					io.extend_with(
+						SystemApi::to_delegate(System::new(client.clone(), deny_unsafe))
+					);
+					io
+				}
 			})?;
 		
 		(builder, import_setup, inherent_data_providers, tasks_to_spawn)
@@ -108,9 +130,6 @@ macro_rules! new_full {
 		let (builder, mut import_setup, inherent_data_providers, mut tasks_to_spawn) = new_full_start!($config);
 
 		let service = builder.with_network_protocol(|_| Ok(crate::service::NodeProtocol::new()))?
-			.with_finality_proof_provider(|client|
-				Ok(Arc::new(grandpa::FinalityProofProvider::new(client.clone(), client)) as _)
-			)?
 			.build()?;
 
 		let (block_import, link_half, babe_link) = import_setup.take()
@@ -205,9 +224,10 @@ pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisCon
 }
 
 /// Builds a new service for a light client.
-pub fn new_light<C: Send + Default + 'static>(config: Configuration<C, GenesisConfig>)
+pub fn new_light<C: Send + Default + 'static>(mut config: Configuration<C, GenesisConfig>)
 -> Result<impl AbstractService, ServiceError> {
 	let inherent_data_providers = InherentDataProviders::new();
+	config.rpc_event_types = node_rpc::event_types();
 
 	ServiceBuilder::new_light::<Block, RuntimeApi, node_executor::Executor>(config)?
 		.with_select_chain(|_config, client| {
@@ -250,11 +270,19 @@ pub fn new_light<C: Send + Default + 'static>(config: Configuration<C, GenesisCo
 			Ok(Arc::new(GrandpaFinalityProofProvider::new(client.clone(), client)) as _)
 		)?
 		.with_rpc_extensions(|client, pool| {
-			use node_rpc::accounts::{Accounts, AccountsApi};
+			use substrate_rpc::system::accounts::{Accounts, AccountsApi};
+			use node_rpc::contracts::{Contracts, ContractsApi};
+			use node_rpc::payment::{Payment, PaymentApi};
 
 			let mut io = jsonrpc_core::IoHandler::default();
 			io.extend_with(
-				AccountsApi::to_delegate(Accounts::new(client, pool))
+				AccountsApi::<AccountId, Index>::to_delegate(Accounts::new(client.clone(), pool))
+			);
+			io.extend_with(
+				ContractsApi::to_delegate(Contracts::new(client.clone()))
+			);
+			io.extend_with(
+				PaymentApi::to_delegate(Payment::new(client))
 			);
 			io
 		})?
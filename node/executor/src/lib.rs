@@ -993,6 +993,54 @@ mod tests {
 		});
 	}
 
+	#[test]
+	fn offchain_transactions_are_signed_and_submitted() {
+		use primitives::{crypto::key_types, testing::KeyStore, traits::BareCryptoStore};
+		use sr_primitives::{app_crypto::sr25519, traits::Checkable, transaction_validity::TransactionValidity};
+		use system::offchain::{SubmitSignedTransaction, TransactionSubmitter};
+
+		type SubmitTransaction = TransactionSubmitter<sr25519::AppPublic, Runtime, UncheckedExtrinsic>;
+
+		let keystore = KeyStore::new();
+		keystore.write().sr25519_generate_new(key_types::SR25519, Some("//Alice")).unwrap();
+		let (offchain, state) = primitives::offchain::testing::TestOffchainExt::new();
+		let mut t = new_test_ext(COMPACT_CODE, false);
+		t.set_keystore(keystore);
+		t.set_offchain_externalities(offchain);
+
+		runtime_io::with_externalities(&mut t, || {
+			assert_eq!(
+				<SubmitTransaction as SubmitSignedTransaction<Runtime, Call>>::sign_and_submit(
+					Call::Balances(default_transfer_call()),
+					alice(),
+				),
+				Ok(()),
+			);
+			assert_eq!(
+				<SubmitTransaction as SubmitSignedTransaction<Runtime, Call>>::sign_and_submit(
+					Call::Balances(default_transfer_call()),
+					bob(),
+				),
+				Err(()),
+			);
+
+			let transactions = &state.read().transactions;
+			assert_eq!(transactions.len(), 1);
+			let xt = UncheckedExtrinsic::decode(&mut &transactions[0][..]).unwrap();
+
+			let checked = xt.clone().check(&system::ChainContext::<Runtime>::default()).unwrap();
+			let (signer, extra) = checked.signed.unwrap();
+			assert_eq!(signer, alice());
+			assert_eq!(extra.encode(), signed_extra(0, 0).encode());
+			assert_eq!(checked.function, Call::Balances(default_transfer_call()));
+
+			match node_runtime::Executive::validate_transaction(xt) {
+				TransactionValidity::Valid(_) => (),
+				other => panic!("Unexpected validity of the submitted transaction: {:?}", other),
+			}
+		});
+	}
+
 	#[test]
 	#[should_panic]
 	#[cfg(feature = "stress-test")]
@@ -20,8 +20,12 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+use codec::{Encode, Decode};
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
 use sr_primitives::{
-	generic, traits::{Verify, BlakeTwo256}, OpaqueExtrinsic, AnySignature
+	generic, traits::{Verify, BlakeTwo256, Block as BlockT}, OpaqueExtrinsic, AnySignature,
+	weights::{Weight, DispatchClass},
 };
 
 /// An index to a block.
@@ -67,10 +71,43 @@ pub type BlockId = generic::BlockId<Block>;
 /// Opaque, encoded, unchecked extrinsic.
 pub type UncheckedExtrinsic = OpaqueExtrinsic;
 
+/// Dispatch information and fee of an extrinsic, as reported by the runtime.
+#[derive(Eq, PartialEq, Encode, Decode, Default)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub struct RuntimeDispatchInfo {
+	/// Weight of the dispatch.
+	pub weight: Weight,
+	/// Class of the dispatch.
+	pub class: DispatchClass,
+	/// Whether the dispatch pays the length-related fee.
+	pub pays_fee: bool,
+	/// Fee that would be charged for the extrinsic, excluding any tip.
+	///
+	/// Serialized as a hex number, since it might not fit into a JavaScript number.
+	#[cfg_attr(feature = "std", serde(
+		serialize_with = "serialize_balance",
+		deserialize_with = "deserialize_balance"))]
+	pub fee: Balance,
+}
+
+#[cfg(feature = "std")]
+fn serialize_balance<S>(val: &Balance, s: S) -> Result<S::Ok, S::Error> where S: serde::Serializer {
+	let u256: primitives::U256 = (*val).into();
+	serde::Serialize::serialize(&u256, s)
+}
+
+#[cfg(feature = "std")]
+fn deserialize_balance<'a, D>(d: D) -> Result<Balance, D::Error> where D: serde::Deserializer<'a> {
+	let u256: primitives::U256 = serde::Deserialize::deserialize(d)?;
+	core::convert::TryFrom::try_from(u256).map_err(|_| serde::de::Error::custom("Balance overflow"))
+}
+
 client::decl_runtime_apis! {
-	/// The API to query account account nonce (aka index).
-	pub trait AccountNonceApi {
-		/// Get current account nonce of given `AccountId`.
-		fn account_nonce(account: AccountId) -> Index;
+	/// The API to query dispatch information and fees of extrinsics.
+	pub trait TransactionPaymentApi {
+		/// Get the dispatch information and the fee of the given extrinsic,
+		/// assuming it is `len` bytes long once encoded.
+		fn query_info(uxt: <Block as BlockT>::Extrinsic, len: u32) -> RuntimeDispatchInfo;
 	}
 }
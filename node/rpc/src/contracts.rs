@@ -0,0 +1,268 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Node-specific RPC methods for interaction with contracts.
+
+use std::sync::Arc;
+
+use client::blockchain::HeaderBackend;
+use jsonrpc_core::{Result, Error, ErrorCode};
+use jsonrpc_derive::rpc;
+use contracts_rpc_runtime_api::{
+	ContractAccessError, ContractExecResult, RentProjection, ContractsApi as ContractsRuntimeApi,
+};
+use node_primitives::{AccountId, Balance, Block, BlockId, BlockNumber, Hash};
+use serde::{Serialize, Deserialize};
+use sr_primitives::traits;
+use substrate_primitives::{Bytes, H256};
+
+pub use self::gen_client::Client as ContractsClient;
+
+const RUNTIME_ERROR: i64 = 1;
+const GAS_LIMIT_TOO_HIGH: i64 = 2;
+const CONTRACT_DOESNT_EXIST: i64 = 3;
+const CONTRACT_IS_A_TOMBSTONE: i64 = 4;
+
+/// The maximum amount of gas a single read-only call may use.
+///
+/// Calls are executed by the node for free, so the limit is bounded to keep
+/// a single query from stalling the RPC server.
+pub const MAX_GAS_LIMIT: u64 = 10_000_000;
+
+/// Parameters of a read-only call to a contract.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[serde(deny_unknown_fields)]
+pub struct CallRequest {
+	/// Account on behalf of which the call is made.
+	pub origin: AccountId,
+	/// Address of the contract.
+	pub dest: AccountId,
+	/// Value transferred to the contract with the call.
+	pub value: Balance,
+	/// Maximum amount of gas the call may use.
+	pub gas_limit: u64,
+	/// Input data passed to the contract.
+	pub input_data: Bytes,
+}
+
+/// Outcome of a read-only call to a contract.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RpcContractExecResult {
+	/// The contract ran to completion.
+	Success {
+		/// Status code returned by the contract.
+		status: u8,
+		/// Output data returned by the contract.
+		data: Bytes,
+		/// Amount of gas consumed by the call.
+		gas_consumed: u64,
+		/// SCALE-encoded runtime events deposited during the call.
+		events: Vec<Bytes>,
+	},
+	/// The call failed.
+	Error {
+		/// Reason of the failure.
+		reason: String,
+		/// Amount of gas consumed by the call.
+		gas_consumed: u64,
+	},
+}
+
+impl From<ContractExecResult> for RpcContractExecResult {
+	fn from(r: ContractExecResult) -> Self {
+		match r {
+			ContractExecResult::Success { status, data, gas_consumed, events } =>
+				RpcContractExecResult::Success {
+					status,
+					data: data.into(),
+					gas_consumed,
+					events: events.into_iter().map(Into::into).collect(),
+				},
+			ContractExecResult::Error { reason, gas_consumed } =>
+				RpcContractExecResult::Error {
+					reason: String::from_utf8_lossy(&reason).into_owned(),
+					gas_consumed,
+				},
+		}
+	}
+}
+
+/// Contracts RPC methods.
+#[rpc]
+pub trait ContractsApi {
+	/// Executes a call to a contract at block `at` (best block if not given).
+	///
+	/// This call is performed locally without submitting any transactions. Thus executing it
+	/// doesn't require any tokens to be held and doesn't change the state.
+	///
+	/// This method is useful for calling getter-like methods on contracts.
+	#[rpc(name = "contracts_call")]
+	fn call(&self, call_request: CallRequest, at: Option<Hash>) -> Result<RpcContractExecResult>;
+
+	/// Returns the value under a specified storage `key` in a contract given by `address` param,
+	/// or `None` if it is not set.
+	#[rpc(name = "contracts_getStorage")]
+	fn get_storage(&self, address: AccountId, key: H256, at: Option<Hash>) -> Result<Option<Bytes>>;
+
+	/// Returns the projected block number at which the contract given by `address` will be
+	/// evicted to a tombstone under the current rent parameters, or `None` if it is not
+	/// going to be evicted.
+	///
+	/// The projection assumes that the contract balance, rent allowance and storage size
+	/// stay as they are.
+	#[rpc(name = "contracts_rentProjection")]
+	fn rent_projection(&self, address: AccountId, at: Option<Hash>) -> Result<Option<BlockNumber>>;
+}
+
+/// An implementation of contract specific RPC methods.
+pub struct Contracts<C> {
+	client: Arc<C>,
+}
+
+impl<C> Contracts<C> {
+	/// Create new `Contracts` given client.
+	pub fn new(client: Arc<C>) -> Self {
+		Contracts {
+			client,
+		}
+	}
+}
+
+impl<C> ContractsApi for Contracts<C>
+where
+	C: traits::ProvideRuntimeApi,
+	C: HeaderBackend<Block>,
+	C: Send + Sync + 'static,
+	C::Api: ContractsRuntimeApi<Block, AccountId, Balance, BlockNumber>,
+{
+	fn call(&self, call_request: CallRequest, at: Option<Hash>) -> Result<RpcContractExecResult> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		let CallRequest { origin, dest, value, gas_limit, input_data } = call_request;
+		if gas_limit > MAX_GAS_LIMIT {
+			return Err(Error {
+				code: ErrorCode::ServerError(GAS_LIMIT_TOO_HIGH),
+				message: format!("Requested gas limit is greater than maximum allowed: {}", MAX_GAS_LIMIT),
+				data: None,
+			});
+		}
+
+		let result = api.call(&at, origin, dest, value, gas_limit, input_data.to_vec())
+			.map_err(|e| Error {
+				code: ErrorCode::ServerError(RUNTIME_ERROR),
+				message: "Runtime trapped while executing a contract.".into(),
+				data: Some(format!("{:?}", e).into()),
+			})?;
+
+		Ok(result.into())
+	}
+
+	fn get_storage(&self, address: AccountId, key: H256, at: Option<Hash>) -> Result<Option<Bytes>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		let result = api.get_storage(&at, address, key.into())
+			.map_err(runtime_error)?
+			.map_err(contract_access_error)?
+			.map(Bytes);
+
+		Ok(result)
+	}
+
+	fn rent_projection(&self, address: AccountId, at: Option<Hash>) -> Result<Option<BlockNumber>> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		let result = api.rent_projection(&at, address)
+			.map_err(runtime_error)?
+			.map_err(contract_access_error)?;
+
+		Ok(match result {
+			RentProjection::EvictionAt(block_num) => Some(block_num),
+			RentProjection::NoEviction => None,
+		})
+	}
+}
+
+fn runtime_error(e: impl std::fmt::Debug) -> Error {
+	Error {
+		code: ErrorCode::ServerError(RUNTIME_ERROR),
+		message: "Runtime trapped while querying a contract.".into(),
+		data: Some(format!("{:?}", e).into()),
+	}
+}
+
+fn contract_access_error(e: ContractAccessError) -> Error {
+	match e {
+		ContractAccessError::DoesntExist => Error {
+			code: ErrorCode::ServerError(CONTRACT_DOESNT_EXIST),
+			message: "The specified contract doesn't exist.".into(),
+			data: None,
+		},
+		ContractAccessError::IsTombstone => Error {
+			code: ErrorCode::ServerError(CONTRACT_IS_A_TOMBSTONE),
+			message: "The contract is a tombstone and doesn't have any storage.".into(),
+			data: None,
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use node_testing::{
+		client::{TestClientBuilder, TestClientBuilderExt},
+		keyring::{alice, bob},
+	};
+
+	#[test]
+	fn should_reject_too_high_gas_limit() {
+		// given
+		let client = Arc::new(TestClientBuilder::new().build());
+		let contracts = Contracts::new(client);
+
+		// when
+		let res = contracts.call(CallRequest {
+			origin: alice(),
+			dest: bob(),
+			value: 0,
+			gas_limit: MAX_GAS_LIMIT + 1,
+			input_data: vec![].into(),
+		}, None);
+
+		// then
+		assert_eq!(res.unwrap_err().code, ErrorCode::ServerError(GAS_LIMIT_TOO_HIGH));
+	}
+
+	#[test]
+	fn should_report_missing_contract() {
+		// given
+		let client = Arc::new(TestClientBuilder::new().build());
+		let contracts = Contracts::new(client);
+
+		// when
+		let storage = contracts.get_storage(alice(), H256::zero(), None);
+		let projection = contracts.rent_projection(alice(), None);
+
+		// then
+		assert_eq!(storage.unwrap_err().code, ErrorCode::ServerError(CONTRACT_DOESNT_EXIST));
+		assert_eq!(projection.unwrap_err().code, ErrorCode::ServerError(CONTRACT_DOESNT_EXIST));
+	}
+}
@@ -29,4 +29,26 @@
 
 #![warn(missing_docs)]
 
-pub mod accounts;
+pub mod contracts;
+pub mod payment;
+pub mod system;
+
+use substrate_rpc::state::events::TypeRegistry;
+
+/// Returns the registry of types the node runtime uses in its events.
+pub fn event_types() -> TypeRegistry {
+	let mut types = TypeRegistry::default();
+	for (name, definition) in &[
+		("AccountId", "[u8; 32]"),
+		("AuthorityId", "[u8; 32]"),
+		("Hash", "[u8; 32]"),
+		("Balance", "u128"),
+		("BlockNumber", "u32"),
+		("AccountIndex", "u32"),
+		("Index", "u32"),
+		("Moment", "u64"),
+	] {
+		types.register(name, definition);
+	}
+	types
+}
@@ -0,0 +1,129 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Node-specific RPC methods for transaction payment.
+
+use std::sync::Arc;
+
+use client::blockchain::HeaderBackend;
+use codec::Decode;
+use jsonrpc_core::{Result, Error, ErrorCode};
+use jsonrpc_derive::rpc;
+use node_primitives::{
+	Block, BlockId, Hash, RuntimeDispatchInfo, TransactionPaymentApi,
+};
+use sr_primitives::traits::{self, Block as BlockT};
+use substrate_primitives::Bytes;
+
+pub use self::gen_client::Client as PaymentClient;
+
+const RUNTIME_ERROR: i64 = 1;
+const DECODE_ERROR: i64 = 2;
+
+/// Payment RPC methods.
+#[rpc]
+pub trait PaymentApi {
+	/// Returns the dispatch information and the fee of the given encoded extrinsic
+	/// at block `at` (best block if not given).
+	///
+	/// The returned fee does not include any tip.
+	#[rpc(name = "payment_queryInfo")]
+	fn query_info(&self, encoded_xt: Bytes, at: Option<Hash>) -> Result<RuntimeDispatchInfo>;
+}
+
+/// An implementation of payment specific RPC methods.
+pub struct Payment<C> {
+	client: Arc<C>,
+}
+
+impl<C> Payment<C> {
+	/// Create new `Payment` given client.
+	pub fn new(client: Arc<C>) -> Self {
+		Payment {
+			client,
+		}
+	}
+}
+
+impl<C> PaymentApi for Payment<C>
+where
+	C: traits::ProvideRuntimeApi,
+	C: HeaderBackend<Block>,
+	C: Send + Sync + 'static,
+	C::Api: TransactionPaymentApi<Block>,
+{
+	fn query_info(&self, encoded_xt: Bytes, at: Option<Hash>) -> Result<RuntimeDispatchInfo> {
+		let api = self.client.runtime_api();
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().best_hash));
+
+		let encoded_len = encoded_xt.len() as u32;
+		let uxt = <Block as BlockT>::Extrinsic::decode(&mut &*encoded_xt).map_err(|e| Error {
+			code: ErrorCode::ServerError(DECODE_ERROR),
+			message: "Unable to decode extrinsic.".into(),
+			data: Some(format!("{:?}", e).into()),
+		})?;
+
+		api.query_info(&at, uxt, encoded_len).map_err(|e| Error {
+			code: ErrorCode::ServerError(RUNTIME_ERROR),
+			message: "Unable to query dispatch info.".into(),
+			data: Some(format!("{:?}", e).into()),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use codec::Encode;
+	use node_primitives::Balance;
+	use node_runtime::{CheckedExtrinsic, Call, BalancesCall, constants::currency::*};
+	use node_testing::{
+		client::{ClientExt, TestClientBuilder, TestClientBuilderExt},
+		keyring::{self, alice, bob, signed_extra},
+	};
+	use sr_primitives::weights::DispatchClass;
+
+	const VERSION: u32 = node_runtime::VERSION.spec_version;
+
+	#[test]
+	fn should_return_dispatch_info_and_fee() {
+		// given
+		let client = Arc::new(TestClientBuilder::new().build());
+		let xt = keyring::sign(CheckedExtrinsic {
+			signed: Some((alice().into(), signed_extra(0, 0))),
+			function: Call::Balances(BalancesCall::transfer(bob().into(), 69)),
+		}, VERSION, client.genesis_hash().into()).encode();
+		let payment = Payment::new(client);
+
+		// when
+		let info = payment.query_info(xt.clone().into(), None).unwrap();
+
+		// then
+		// base fee, length fee and the weight of a transfer at the genesis weight multiplier.
+		let fee = CENTS + 10 * MILLICENTS * xt.len() as Balance + 1_000_000 * 1_000;
+		assert_eq!(info, RuntimeDispatchInfo {
+			weight: 1_000_000,
+			class: DispatchClass::Normal,
+			pays_fee: true,
+			fee,
+		});
+		assert_eq!(
+			jsonrpc_core::serde_json::to_value(&info).unwrap()["fee"],
+			format!("0x{:x}", fee),
+		);
+	}
+}
@@ -0,0 +1,192 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Node-specific RPC methods for the System module.
+
+use std::sync::Arc;
+
+use client::{
+	Client, CallExecutor, backend::Backend, runtime_api::Metadata,
+	block_builder::api::BlockBuilder as BlockBuilderApi,
+};
+use codec::{Decode, Encode};
+use jsonrpc_core::{Result, Error, ErrorCode};
+use jsonrpc_derive::rpc;
+use node_primitives::{Block, BlockId, Hash};
+use serde::{Serialize, Deserialize};
+use sr_primitives::traits::{Block as BlockT, ProvideRuntimeApi};
+use srml_metadata::RuntimeMetadataPrefixed;
+use substrate_primitives::{Blake2Hasher, Bytes};
+use substrate_rpc::{DenyUnsafe, state::events::events_key};
+
+pub use self::gen_client::Client as SystemClient;
+
+const RUNTIME_ERROR: i64 = 1;
+const DECODE_ERROR: i64 = 2;
+
+/// Outcome of a dry run of an extrinsic.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunResult {
+	/// SCALE-encoded `ApplyResult` of the extrinsic.
+	pub result: Bytes,
+	/// SCALE-encoded `Vec<EventRecord>` deposited while initializing the block
+	/// and applying the extrinsic.
+	pub events: Option<Bytes>,
+}
+
+/// System RPC methods.
+#[rpc]
+pub trait SystemApi {
+	/// Applies the given extrinsic on top of the state of block `at` (best block
+	/// if not given) as if it was included in the next block, without importing
+	/// anything. Returns the `ApplyResult` and the emitted events.
+	///
+	/// This method is unsafe and is denied on RPC servers exposed to untrusted clients.
+	#[rpc(name = "system_dryRun")]
+	fn dry_run(&self, extrinsic: Bytes, at: Option<Hash>) -> Result<DryRunResult>;
+}
+
+/// An implementation of System specific RPC methods.
+pub struct System<B, E, RA> {
+	client: Arc<Client<B, E, Block, RA>>,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl<B, E, RA> System<B, E, RA> {
+	/// Create new `System` given client and whether unsafe calls should be denied.
+	pub fn new(client: Arc<Client<B, E, Block, RA>>, deny_unsafe: DenyUnsafe) -> Self {
+		System {
+			client,
+			deny_unsafe,
+		}
+	}
+}
+
+impl<B, E, RA> SystemApi for System<B, E, RA>
+where
+	B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+	Client<B, E, Block, RA>: ProvideRuntimeApi,
+	<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api: Metadata<Block> + BlockBuilderApi<Block>,
+{
+	fn dry_run(&self, extrinsic: Bytes, at: Option<Hash>) -> Result<DryRunResult> {
+		self.deny_unsafe.check_if_safe()?;
+
+		let at = BlockId::hash(at.unwrap_or_else(|| self.client.info().chain.best_hash));
+		let extrinsic = <Block as BlockT>::Extrinsic::decode(&mut &*extrinsic).map_err(|e| Error {
+			code: ErrorCode::ServerError(DECODE_ERROR),
+			message: "Unable to decode extrinsic.".into(),
+			data: Some(format!("{:?}", e).into()),
+		})?;
+
+		let (result, overlay) = self.client.dry_run_extrinsic(&at, extrinsic).map_err(|e| Error {
+			code: ErrorCode::ServerError(RUNTIME_ERROR),
+			message: "Unable to dry run extrinsic.".into(),
+			data: Some(format!("{:?}", e).into()),
+		})?;
+
+		let metadata = self.client.runtime_api().metadata(&at).map_err(|e| Error {
+			code: ErrorCode::ServerError(RUNTIME_ERROR),
+			message: "Unable to query runtime metadata.".into(),
+			data: Some(format!("{:?}", e).into()),
+		})?;
+		let events_key = RuntimeMetadataPrefixed::decode(&mut &metadata[..])
+			.map_err(|e| e.what().to_string())
+			.and_then(|metadata| events_key(&metadata))
+			.map_err(|e| Error {
+				code: ErrorCode::ServerError(DECODE_ERROR),
+				message: "Unable to find the events storage key in the runtime metadata.".into(),
+				data: Some(e.into()),
+			})?;
+
+		// Events are killed when the block is initialized, so anything in the overlay
+		// has been deposited during the dry run.
+		let events = overlay.storage(&events_key)
+			.and_then(|events| events.map(|events| events.to_vec()));
+
+		Ok(DryRunResult {
+			result: result.encode().into(),
+			events: events.map(Into::into),
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use codec::Encode;
+	use node_runtime::{CheckedExtrinsic, Call, BalancesCall, Event, UncheckedExtrinsic};
+	use node_testing::{
+		client::{ClientExt, TestClientBuilder, TestClientBuilderExt},
+		keyring::{self, alice, bob, signed_extra},
+	};
+	use sr_primitives::{ApplyResult, ApplyOutcome};
+	use system::{EventRecord, Phase};
+
+	const VERSION: u32 = node_runtime::VERSION.spec_version;
+
+	fn transfer(genesis_hash: Hash) -> UncheckedExtrinsic {
+		keyring::sign(CheckedExtrinsic {
+			signed: Some((alice().into(), signed_extra(0, 0))),
+			function: Call::Balances(BalancesCall::transfer(bob().into(), 69)),
+		}, VERSION, genesis_hash.into())
+	}
+
+	#[test]
+	fn should_dry_run_extrinsic_without_importing() {
+		// given
+		let client = Arc::new(TestClientBuilder::new().build());
+		let xt = transfer(client.genesis_hash());
+		let system = System::new(client.clone(), DenyUnsafe::No);
+
+		// when
+		let res = system.dry_run(xt.encode().into(), None).unwrap();
+
+		// then
+		assert_eq!(ApplyResult::decode(&mut &*res.result).unwrap(), Ok(ApplyOutcome::Success));
+		let events = Vec::<EventRecord<Event, Hash>>::decode(&mut &*res.events.unwrap()).unwrap();
+		assert!(events.iter().any(|record| match record.event {
+			Event::balances(balances::RawEvent::Transfer(ref from, ref to, value, _)) =>
+				*from == alice() && *to == bob() && value == 69,
+			_ => false,
+		}));
+		assert_eq!(events.last(), Some(&EventRecord {
+			phase: Phase::ApplyExtrinsic(0),
+			event: Event::system(system::Event::ExtrinsicSuccess),
+			topics: vec![],
+		}));
+		assert_eq!(client.info().chain.best_number, 0);
+	}
+
+	#[test]
+	fn should_deny_dry_run_when_unsafe_calls_are_denied() {
+		// given
+		let client = Arc::new(TestClientBuilder::new().build());
+		let xt = transfer(client.genesis_hash());
+		let system = System::new(client, DenyUnsafe::Yes);
+
+		// when
+		let res = system.dry_run(xt.encode().into(), None);
+
+		// then
+		let err = res.unwrap_err();
+		assert_eq!(err.code, ErrorCode::MethodNotFound);
+		assert_eq!(err.message, "RPC call is unsafe to be called externally");
+	}
+}
@@ -27,19 +27,21 @@ use support::{
 use primitives::u32_trait::{_1, _2, _3, _4};
 use node_primitives::{
 	AccountId, AccountIndex, Balance, BlockNumber, Hash, Index,
-	Moment, Signature,
+	Moment, Signature, RuntimeDispatchInfo,
 };
+use contracts_rpc_runtime_api::{ContractExecResult, ContractAccessError, RentProjection};
 use babe::{AuthorityId as BabeId};
 use grandpa::fg_primitives::{self, ScheduledChange};
 use client::{
 	block_builder::api::{self as block_builder_api, InherentData, CheckInherentsResult},
 	runtime_api as client_api, impl_runtime_apis
 };
+use codec::Encode;
 use sr_primitives::{ApplyResult, impl_opaque_keys, generic, create_runtime_str, key_types};
 use sr_primitives::transaction_validity::TransactionValidity;
-use sr_primitives::weights::Weight;
+use sr_primitives::weights::{Weight, GetDispatchInfo};
 use sr_primitives::traits::{
-	BlakeTwo256, Block as BlockT, DigestFor, NumberFor, StaticLookup,
+	self, BlakeTwo256, Block as BlockT, DigestFor, NumberFor, StaticLookup, SignedExtension, Hash as HashT,
 };
 use version::RuntimeVersion;
 use elections::VoteIndex;
@@ -79,8 +81,8 @@ pub const VERSION: RuntimeVersion = RuntimeVersion {
 	// and set impl_version to equal spec_version. If only runtime
 	// implementation changes and behavior does not, then leave spec_version as
 	// is and increment impl_version.
-	spec_version: 154,
-	impl_version: 154,
+	spec_version: 155,
+	impl_version: 155,
 	apis: RUNTIME_API_VERSIONS,
 };
 
@@ -424,6 +426,40 @@ impl finality_tracker::Trait for Runtime {
 	type ReportLatency = ReportLatency;
 }
 
+impl system::offchain::CreateTransaction<Runtime, UncheckedExtrinsic> for Runtime {
+	type Public = AccountId;
+	type Signature = Signature;
+
+	fn create_transaction<F: system::offchain::Signer<Self::Public, Self::Signature>>(
+		call: Call,
+		public: Self::Public,
+		account: AccountId,
+		index: Index,
+	) -> Option<(Call, <UncheckedExtrinsic as traits::Extrinsic>::SignaturePayload)> {
+		let period = 1 << 8;
+		let current_block = System::block_number().into();
+		let tip = 0;
+		let extra: SignedExtra = (
+			system::CheckVersion::<Runtime>::new(),
+			system::CheckGenesis::<Runtime>::new(),
+			system::CheckEra::<Runtime>::from(generic::Era::mortal(period, current_block)),
+			system::CheckNonce::<Runtime>::from(index),
+			system::CheckWeight::<Runtime>::new(),
+			balances::TakeFees::<Runtime>::from(tip),
+		);
+		let additional_signed = extra.additional_signed().ok()?;
+		let raw_payload = (call, extra, additional_signed);
+		let signature = raw_payload.using_encoded(|payload| if payload.len() > 256 {
+			F::sign(public, <BlakeTwo256 as HashT>::hash(payload).as_ref())
+		} else {
+			F::sign(public, payload)
+		})?;
+		let (call, extra, _) = raw_payload;
+		let address = Indices::unlookup(account);
+		Some((call, (address, signature, extra)))
+	}
+}
+
 construct_runtime!(
 	pub enum Runtime where
 		Block = Block,
@@ -596,12 +632,61 @@ impl_runtime_apis! {
 		}
 	}
 
-	impl node_primitives::AccountNonceApi<Block> for Runtime {
+	impl system_rpc_runtime_api::AccountNonceApi<Block, AccountId, Index> for Runtime {
 		fn account_nonce(account: AccountId) -> Index {
 			System::account_nonce(account)
 		}
 	}
 
+	impl node_primitives::TransactionPaymentApi<Block> for Runtime {
+		fn query_info(uxt: <Block as BlockT>::Extrinsic, len: u32) -> RuntimeDispatchInfo {
+			let dispatch_info = uxt.function.get_dispatch_info();
+			RuntimeDispatchInfo {
+				weight: dispatch_info.weight,
+				class: dispatch_info.class,
+				pays_fee: dispatch_info.pay_length_fee(),
+				fee: balances::TakeFees::<Runtime>::compute_fee(len as usize, dispatch_info, 0),
+			}
+		}
+	}
+
+	impl contracts_rpc_runtime_api::ContractsApi<Block, AccountId, Balance, BlockNumber> for Runtime {
+		fn call(
+			origin: AccountId,
+			dest: AccountId,
+			value: Balance,
+			gas_limit: u64,
+			input_data: Vec<u8>,
+		) -> ContractExecResult {
+			let outcome = Contracts::bare_call(origin, dest, value, gas_limit, input_data);
+			match outcome.result {
+				Ok(output) => ContractExecResult::Success {
+					status: output.status,
+					data: output.data,
+					gas_consumed: outcome.gas_consumed,
+					events: outcome.events.iter().map(Encode::encode).collect(),
+				},
+				Err(reason) => ContractExecResult::Error {
+					reason: reason.as_bytes().to_vec(),
+					gas_consumed: outcome.gas_consumed,
+				},
+			}
+		}
+
+		fn get_storage(
+			address: AccountId,
+			key: [u8; 32],
+		) -> Result<Option<Vec<u8>>, ContractAccessError> {
+			Contracts::get_storage(address, key)
+		}
+
+		fn rent_projection(
+			address: AccountId,
+		) -> Result<RentProjection<BlockNumber>, ContractAccessError> {
+			Contracts::rent_projection(address)
+		}
+	}
+
 	impl substrate_session::SessionKeys<Block> for Runtime {
 		fn generate_session_keys(seed: Option<Vec<u8>>) -> Vec<u8> {
 			let seed = seed.as_ref().map(|s| rstd::str::from_utf8(&s).expect("Seed is an utf8 string"));
@@ -1201,7 +1201,7 @@ impl<T: Trait<I>, I: Instance> TakeFees<T, I> {
 	///      and the time it consumes.
 	///   - (optional) _tip_: if included in the transaction, it will be added on top. Only signed
 	///      transactions can have a tip.
-	fn compute_fee(len: usize, info: DispatchInfo, tip: T::Balance) -> T::Balance {
+	pub fn compute_fee(len: usize, info: DispatchInfo, tip: T::Balance) -> T::Balance {
 		let len_fee = if info.pay_length_fee() {
 			let len = T::Balance::from(len as u32);
 			let base = T::TransactionBaseFee::get();
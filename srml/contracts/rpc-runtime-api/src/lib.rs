@@ -0,0 +1,104 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Runtime APIs backing the RPC methods of `srml-contracts`, together with the
+//! types they share with the module.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use rstd::prelude::*;
+use codec::{Codec, Encode, Decode};
+#[cfg(feature = "std")]
+use serde::{Serialize, Deserialize};
+
+/// Result of a contract call performed through the `ContractsApi`.
+#[derive(Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug, Serialize, Deserialize))]
+#[cfg_attr(feature = "std", serde(rename_all = "camelCase"))]
+pub enum ContractExecResult {
+	/// The contract ran to completion.
+	Success {
+		/// Status code returned by the contract.
+		status: u8,
+		/// Output data returned by the contract.
+		data: Vec<u8>,
+		/// Amount of gas consumed by the call.
+		gas_consumed: u64,
+		/// SCALE-encoded runtime events deposited during the call.
+		events: Vec<Vec<u8>>,
+	},
+	/// The call failed, e.g. the contract trapped or ran out of gas.
+	Error {
+		/// Reason of the failure.
+		reason: Vec<u8>,
+		/// Amount of gas consumed by the call.
+		gas_consumed: u64,
+	},
+}
+
+/// The possible errors that can happen querying the storage of a contract.
+#[derive(Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum ContractAccessError {
+	/// The given address doesn't point to a contract.
+	DoesntExist,
+	/// The specified contract is a tombstone and thus cannot have any storage.
+	IsTombstone,
+}
+
+/// A projected eviction of a contract under the current rent parameters.
+#[derive(Eq, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "std", derive(Debug))]
+pub enum RentProjection<BlockNumber> {
+	/// Eviction is projected to happen at the specified block number.
+	EvictionAt(BlockNumber),
+	/// No eviction is scheduled.
+	///
+	/// E.g. because the contract accumulated enough funds to offset the rent storage costs.
+	NoEviction,
+}
+
+client::decl_runtime_apis! {
+	/// The API to query contracts without submitting transactions.
+	pub trait ContractsApi<AccountId: Codec, Balance: Codec, BlockNumber: Codec> {
+		/// Perform a call from `origin` to the contract at `dest`.
+		///
+		/// Works like the contracts' `call` dispatchable function, but doesn't charge for
+		/// the gas and doesn't commit any changes to the storage.
+		fn call(
+			origin: AccountId,
+			dest: AccountId,
+			value: Balance,
+			gas_limit: u64,
+			input_data: Vec<u8>,
+		) -> ContractExecResult;
+
+		/// Query a given storage key in a given contract.
+		///
+		/// Returns `Ok(Some(Vec<u8>))` if the storage value exists under the given key in the
+		/// specified account and `Ok(None)` if it doesn't. If the account specified by the address
+		/// doesn't exist, or doesn't have a contract, or if the contract is a tombstone, then `Err`
+		/// is returned.
+		fn get_storage(
+			address: AccountId,
+			key: [u8; 32],
+		) -> Result<Option<Vec<u8>>, ContractAccessError>;
+
+		/// Returns the projected time at which the given contract will be evicted for not being
+		/// able to pay its rent.
+		fn rent_projection(address: AccountId) -> Result<RentProjection<BlockNumber>, ContractAccessError>;
+	}
+}
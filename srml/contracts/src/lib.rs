@@ -90,6 +90,7 @@ mod rent;
 mod tests;
 
 use crate::exec::{ExecutionContext, ExecResult};
+pub use crate::exec::{ExecReturnValue, StatusCode};
 use crate::account_db::{AccountDb, DirectAccountDb};
 pub use crate::gas::{Gas, GasMeter};
 use crate::wasm::{WasmLoader, WasmVm};
@@ -629,7 +630,87 @@ decl_module! {
 	}
 }
 
+pub use rpc_runtime_api::{ContractAccessError, RentProjection};
+
+/// The outcome of a contract call performed by [`Module::bare_call`].
+pub struct BareCallOutcome<T: Trait> {
+	/// The value returned by the contract, or the reason why the call failed.
+	pub result: rstd::result::Result<ExecReturnValue, &'static str>,
+	/// The amount of gas consumed by the call.
+	pub gas_consumed: Gas,
+	/// Events deposited during the call.
+	pub events: Vec<<T as Trait>::Event>,
+}
+
 impl<T: Trait> Module<T> {
+	/// Perform a call to a specified contract.
+	///
+	/// This function is similar to `Self::call`, but doesn't perform any address lookups, doesn't
+	/// charge for the gas and doesn't commit any changes to the storage. Events are returned to
+	/// the caller instead of being deposited, and deferred runtime calls are not dispatched.
+	///
+	/// It is intended to be used from outside of the runtime (e.g. through a runtime API) to
+	/// query contracts for free.
+	pub fn bare_call(
+		origin: T::AccountId,
+		dest: T::AccountId,
+		value: BalanceOf<T>,
+		gas_limit: Gas,
+		input_data: Vec<u8>,
+	) -> BareCallOutcome<T> {
+		let mut gas_meter = GasMeter::with_limit(gas_limit, Self::gas_price());
+
+		let cfg = Config::preload();
+		let vm = WasmVm::new(&cfg.schedule);
+		let loader = WasmLoader::new(&cfg.schedule);
+		let mut ctx = ExecutionContext::top_level(origin, &cfg, &vm, &loader);
+
+		let result = ctx.call(dest, value, &mut gas_meter, input_data)
+			.map_err(|e| e.reason);
+
+		let events = ctx.deferred.into_iter()
+			.filter_map(|deferred| match deferred {
+				exec::DeferredAction::DepositEvent { event, .. } =>
+					Some(<T as Trait>::Event::from(event)),
+				_ => None,
+			})
+			.collect();
+
+		BareCallOutcome {
+			result,
+			gas_consumed: gas_meter.spent(),
+			events,
+		}
+	}
+
+	/// Query storage of a specified contract under a specified key.
+	pub fn get_storage(
+		address: T::AccountId,
+		key: [u8; 32],
+	) -> rstd::result::Result<Option<Vec<u8>>, ContractAccessError> {
+		let contract_info = <ContractInfoOf<T>>::get(&address)
+			.ok_or(ContractAccessError::DoesntExist)?
+			.get_alive()
+			.ok_or(ContractAccessError::IsTombstone)?;
+
+		let maybe_value = AccountDb::<T>::get_storage(
+			&DirectAccountDb,
+			&address,
+			Some(&contract_info.trie_id),
+			&key,
+		);
+		Ok(maybe_value)
+	}
+
+	/// Query the projected eviction of a specified contract.
+	///
+	/// See `rent::compute_rent_projection` for the details and caveats.
+	pub fn rent_projection(
+		address: T::AccountId,
+	) -> rstd::result::Result<RentProjection<T::BlockNumber>, ContractAccessError> {
+		rent::compute_rent_projection::<T>(&address)
+	}
+
 	fn execute_wasm(
 		origin: T::AccountId,
 		gas_limit: Gas,
@@ -14,8 +14,11 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate. If not, see <http://www.gnu.org/licenses/>.
 
-use crate::{BalanceOf, ContractInfo, ContractInfoOf, TombstoneContractInfo, Trait, AliveContractInfo};
-use sr_primitives::traits::{Bounded, CheckedDiv, CheckedMul, Saturating, Zero,
+use crate::{
+	BalanceOf, ContractInfo, ContractInfoOf, TombstoneContractInfo, Trait, AliveContractInfo,
+	ContractAccessError, RentProjection,
+};
+use sr_primitives::traits::{Bounded, CheckedDiv, CheckedMul, Saturating, Zero, One,
 	SaturatedConversion};
 use srml_support::traits::{Currency, ExistenceRequirement, Get, WithdrawReason};
 use srml_support::StorageMap;
@@ -38,6 +41,34 @@ pub enum RentOutcome {
 	Ok,
 }
 
+/// Returns a fee charged per block from the contract.
+///
+/// This function accounts for the storage rent deposit. I.e. if the contract possesses enough funds
+/// then the fee can drop to zero.
+fn compute_fee_per_block<T: Trait>(
+	balance: &BalanceOf<T>,
+	contract: &AliveContractInfo<T>,
+) -> BalanceOf<T> {
+	let free_storage = balance
+		.checked_div(&T::RentDepositOffset::get())
+		.unwrap_or_else(Zero::zero);
+
+	let effective_storage_size =
+		<BalanceOf<T>>::from(contract.storage_size).saturating_sub(free_storage);
+
+	effective_storage_size
+		.checked_mul(&T::RentByteFee::get())
+		.unwrap_or(<BalanceOf<T>>::max_value())
+}
+
+/// The minimal amount of funds required for a contract not to be evicted.
+///
+/// This is the minimum balance (aka existential deposit) extended by the tombstone deposit,
+/// required for leaving a tombstone.
+fn subsistence_threshold<T: Trait>() -> BalanceOf<T> {
+	T::Currency::minimum_balance() + T::TombstoneDeposit::get()
+}
+
 /// Evict and optionally pay dues (or check account can pay them otherwise) at the current
 /// block number (modulo `handicap`, read on).
 ///
@@ -74,18 +105,7 @@ fn try_evict_or_and_pay_rent<T: Trait>(
 	let balance = T::Currency::free_balance(account);
 
 	// An amount of funds to charge per block for storage taken up by the contract.
-	let fee_per_block = {
-		let free_storage = balance
-			.checked_div(&T::RentDepositOffset::get())
-			.unwrap_or_else(Zero::zero);
-
-		let effective_storage_size =
-			<BalanceOf<T>>::from(contract.storage_size).saturating_sub(free_storage);
-
-		effective_storage_size
-			.checked_mul(&T::RentByteFee::get())
-			.unwrap_or(<BalanceOf<T>>::max_value())
-	};
+	let fee_per_block = compute_fee_per_block::<T>(&balance, &contract);
 
 	if fee_per_block.is_zero() {
 		// The rent deposit offset reduced the fee to 0. This means that the contract
@@ -93,8 +113,7 @@ fn try_evict_or_and_pay_rent<T: Trait>(
 		return (RentOutcome::Exempted, Some(ContractInfo::Alive(contract)));
 	}
 
-	// The minimal amount of funds required for a contract not to be evicted.
-	let subsistence_threshold = T::Currency::minimum_balance() + T::TombstoneDeposit::get();
+	let subsistence_threshold = subsistence_threshold::<T>();
 
 	if balance < subsistence_threshold {
 		// The contract cannot afford to leave a tombstone, so remove the contract info altogether.
@@ -190,3 +209,43 @@ pub fn pay_rent<T: Trait>(account: &T::AccountId) -> Option<ContractInfo<T>> {
 pub fn try_evict<T: Trait>(account: &T::AccountId, handicap: T::BlockNumber) -> RentOutcome {
 	try_evict_or_and_pay_rent::<T>(account, handicap, false).0
 }
+
+/// Returns the projected time a given contract will be able to sustain paying its rent. The
+/// returned projection is relevant for the current block, i.e. it is as if the contract was
+/// accessed at the beginning of the current block. Returns `None` in case if the contract was
+/// evicted before or as a result of the rent collection.
+///
+/// The returned value is only an estimation. It doesn't take into account any top ups, changing the
+/// rent allowance, or any problems coming from withdrawing the dues.
+///
+/// NOTE that this is not a side-effect free function! It will actually collect rent and then
+/// compute the projection. This function is only used for implementation of an RPC method through
+/// `RuntimeApi` meaning that the changes will be discarded anyway.
+pub fn compute_rent_projection<T: Trait>(
+	account: &T::AccountId,
+) -> Result<RentProjection<T::BlockNumber>, ContractAccessError> {
+	let contract = match pay_rent::<T>(account) {
+		None => return Err(ContractAccessError::DoesntExist),
+		Some(ContractInfo::Tombstone(_)) => return Err(ContractAccessError::IsTombstone),
+		Some(ContractInfo::Alive(contract)) => contract,
+	};
+
+	let balance = T::Currency::free_balance(account);
+	let fee_per_block = compute_fee_per_block::<T>(&balance, &contract);
+	if fee_per_block.is_zero() {
+		return Ok(RentProjection::NoEviction);
+	}
+
+	let rent_budget = contract.rent_allowance.min(balance.saturating_sub(subsistence_threshold::<T>()));
+	let blocks_left = match rent_budget.checked_div(&fee_per_block) {
+		Some(blocks_left) => blocks_left,
+		None => return Ok(RentProjection::NoEviction),
+	};
+
+	// The contract is evicted as soon as the dues exceed the rent budget, i.e. one block after
+	// the last block fully covered by the budget.
+	let blocks_left: T::BlockNumber = blocks_left.saturated_into::<u32>().into();
+	Ok(RentProjection::EvictionAt(
+		contract.deduct_block.saturating_add(blocks_left).saturating_add(One::one())
+	))
+}
@@ -21,8 +21,9 @@
 
 use crate::account_db::{AccountDb, DirectAccountDb, OverlayAccountDb};
 use crate::{
-	BalanceOf, ComputeDispatchFee, ContractAddressFor, ContractInfo, ContractInfoOf, GenesisConfig,
-	Module, RawAliveContractInfo, RawEvent, Trait, TrieId, TrieIdFromParentCounter,
+	BalanceOf, ComputeDispatchFee, ContractAccessError, ContractAddressFor, ContractInfo,
+	ContractInfoOf, GenesisConfig, ExecReturnValue, Module, RawAliveContractInfo, RawEvent,
+	RentProjection, Trait, TrieId, TrieIdFromParentCounter,
 	TrieIdGenerator, Schedule,
 };
 use assert_matches::assert_matches;
@@ -941,6 +942,38 @@ fn deduct_blocks() {
 	);
 }
 
+#[test]
+fn get_storage_and_rent_projection() {
+	let (wasm, code_hash) = compile_module::<Test>(CODE_SET_RENT).unwrap();
+
+	with_externalities(
+		&mut ExtBuilder::default().existential_deposit(50).build(),
+		|| {
+			// Create
+			Balances::deposit_creating(&ALICE, 1_000_000);
+			assert_ok!(Contract::put_code(Origin::signed(ALICE), 100_000, wasm));
+			assert_ok!(Contract::create(
+				Origin::signed(ALICE),
+				30_000,
+				100_000, code_hash.into(),
+				<Test as balances::Trait>::Balance::from(1_000u32).encode() // rent allowance
+			));
+
+			// The deploy routine stores 4 bytes under a key starting with the encoding of 10.
+			let mut key = [0u8; 32];
+			key[0] = 0x28;
+			assert_eq!(Contract::get_storage(BOB, key), Ok(Some(vec![0x28, 0, 0, 0])));
+			assert_eq!(Contract::get_storage(BOB, [1u8; 32]), Ok(None));
+			assert_eq!(Contract::get_storage(ALICE, key), Err(ContractAccessError::DoesntExist));
+
+			// fee per block = (8 + 4 - 3) * 4 = 36 and the rent allowance of 1_000 covers 27
+			// blocks after the creation at block 1.
+			assert_eq!(Contract::rent_projection(BOB), Ok(RentProjection::EvictionAt(29)));
+			assert_eq!(Contract::rent_projection(ALICE), Err(ContractAccessError::DoesntExist));
+		}
+	);
+}
+
 #[test]
 fn call_contract_removals() {
 	removals(|| {
@@ -1927,6 +1960,40 @@ fn deploy_and_call_other_contract() {
 	);
 }
 
+#[test]
+fn bare_call_returns_output_without_charging_or_committing() {
+	let (wasm, code_hash) = compile_module::<Test>(CODE_RETURN_WITH_DATA).unwrap();
+
+	with_externalities(
+		&mut ExtBuilder::default().existential_deposit(50).build(),
+		|| {
+			Balances::deposit_creating(&ALICE, 1_000_000);
+			assert_ok!(Contract::put_code(Origin::signed(ALICE), 100_000, wasm));
+			assert_ok!(Contract::create(
+				Origin::signed(ALICE),
+				100_000,
+				100_000,
+				code_hash.into(),
+				vec![0, 0, 0, 0],
+			));
+
+			let alice_balance = Balances::free_balance(&ALICE);
+			let bob_balance = Balances::free_balance(&BOB);
+
+			let outcome = Contract::bare_call(ALICE, BOB, 100, 100_000, vec![0, 0, 0, 0, 1, 2, 3]);
+			assert_eq!(outcome.result.unwrap(), ExecReturnValue { status: 0, data: vec![1, 2, 3] });
+			assert!(outcome.gas_consumed > 0);
+			assert_eq!(outcome.events, vec![
+				MetaEvent::contract(RawEvent::Transfer(ALICE, BOB, 100)),
+			]);
+
+			// Neither the gas nor the transferred value has been taken.
+			assert_eq!(Balances::free_balance(&ALICE), alice_balance);
+			assert_eq!(Balances::free_balance(&BOB), bob_balance);
+		}
+	);
+}
+
 const CODE_SELF_DESTRUCT: &str = r#"
 (module
 	(import "env" "ext_scratch_size" (func $ext_scratch_size (result i32)))
@@ -0,0 +1,32 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Runtime APIs backing the RPC methods of `srml-system`.
+//!
+//! Any runtime built on `srml-system` can implement these to get the
+//! corresponding generic RPC methods of `substrate-rpc` for free.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+
+client::decl_runtime_apis! {
+	/// The API to query account nonce (aka transaction index).
+	pub trait AccountNonceApi<AccountId: Codec, Index: Codec> {
+		/// Get current account nonce of given `AccountId`.
+		fn account_nonce(account: AccountId) -> Index;
+	}
+}
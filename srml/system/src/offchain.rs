@@ -0,0 +1,316 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Module helpers for offchain calls.
+//!
+//! Offchain workers can use [`SubmitSignedTransaction`] to sign a call with a key
+//! from the node keystore and submit it to the transaction pool as a signed extrinsic
+//! of the corresponding local account, or [`SubmitUnsignedTransaction`] to submit
+//! an unsigned one.
+
+use codec::Encode;
+use rstd::marker::PhantomData;
+use sr_primitives::app_crypto::{self, RuntimeAppPublic};
+use sr_primitives::traits::Extrinsic as ExtrinsicT;
+
+/// A trait responsible for signing a payload using given account.
+pub trait Signer<Public, Signature> {
+	/// Sign a message with given account.
+	///
+	/// Returns `None` if the keystore doesn't hold the key of the `public` account.
+	fn sign(public: Public, message: &[u8]) -> Option<Signature>;
+}
+
+/// Sign messages with application-specific keys from the node keystore.
+///
+/// The key is looked up under the `KeyTypeId` of the `AppPublic` application crypto.
+impl<Public, Signature, AppPublic> Signer<Public, Signature> for AppPublic where
+	AppPublic: RuntimeAppPublic + app_crypto::AppPublic,
+	<AppPublic as RuntimeAppPublic>::Signature: app_crypto::AppSignature,
+	Signature: From<
+		<<AppPublic as RuntimeAppPublic>::Signature as app_crypto::AppSignature>::Generic
+	>,
+	Public: Into<<AppPublic as app_crypto::AppPublic>::Generic>,
+{
+	fn sign(public: Public, message: &[u8]) -> Option<Signature> {
+		let generic: <AppPublic as app_crypto::AppPublic>::Generic = public.into();
+		let public: AppPublic = generic.into();
+		RuntimeAppPublic::sign(&public, &message).map(|signature| {
+			let generic: <<AppPublic as RuntimeAppPublic>::Signature as app_crypto::AppSignature>::Generic =
+				signature.into();
+			generic.into()
+		})
+	}
+}
+
+/// Creates runtime-specific signed transaction.
+pub trait CreateTransaction<T: crate::Trait, Extrinsic: ExtrinsicT> {
+	/// A public key of the account signing the transaction.
+	type Public: Clone + Into<T::AccountId>;
+	/// A signature produced by the `Signer`.
+	type Signature;
+
+	/// Attempt to create signed extrinsic data that encodes `call` from given account.
+	///
+	/// The runtime is free to construct the payload to sign in any way it wants.
+	/// Returns `None` if the signed extrinsic could not be created, either because
+	/// signing failed or for any other runtime-specific reason.
+	fn create_transaction<F: Signer<Self::Public, Self::Signature>>(
+		call: Extrinsic::Call,
+		public: Self::Public,
+		account: T::AccountId,
+		nonce: T::Index,
+	) -> Option<(Extrinsic::Call, Extrinsic::SignaturePayload)>;
+}
+
+type PublicOf<T, Call, X> = <
+	<X as SubmitSignedTransaction<T, Call>>::CreateTransaction as CreateTransaction<
+		T,
+		<X as SubmitSignedTransaction<T, Call>>::Extrinsic,
+	>
+>::Public;
+
+type SignatureOf<T, Call, X> = <
+	<X as SubmitSignedTransaction<T, Call>>::CreateTransaction as CreateTransaction<
+		T,
+		<X as SubmitSignedTransaction<T, Call>>::Extrinsic,
+	>
+>::Signature;
+
+/// A trait to sign and submit transactions in offchain calls.
+pub trait SubmitSignedTransaction<T: crate::Trait, Call> {
+	/// Unchecked extrinsic type.
+	type Extrinsic: ExtrinsicT<Call=Call> + Encode;
+
+	/// A runtime-specific type to produce signed data for the extrinsic.
+	type CreateTransaction: CreateTransaction<T, Self::Extrinsic>;
+
+	/// A type used to sign transactions created using `CreateTransaction`.
+	type Signer: Signer<PublicOf<T, Call, Self>, SignatureOf<T, Call, Self>>;
+
+	/// Sign given call and submit it to the transaction pool.
+	///
+	/// The transaction is signed by the account of given `public` key, using its
+	/// current nonce. Returns `Err` if the transaction could not be created or
+	/// was not accepted by the pool.
+	fn sign_and_submit(call: impl Into<Call>, public: PublicOf<T, Call, Self>) -> Result<(), ()> {
+		let call = call.into();
+		let account = public.clone().into();
+		let nonce = <crate::Module<T>>::account_nonce(&account);
+		let (call, signed_data) = Self::CreateTransaction
+			::create_transaction::<Self::Signer>(call, public, account, nonce)
+			.ok_or(())?;
+		let xt = Self::Extrinsic::new_signed(call, signed_data).ok_or(())?;
+		runtime_io::submit_transaction(&xt)
+	}
+}
+
+/// A trait to submit unsigned transactions in offchain calls.
+pub trait SubmitUnsignedTransaction<T: crate::Trait, Call> {
+	/// Unchecked extrinsic type.
+	type Extrinsic: ExtrinsicT<Call=Call> + Encode;
+
+	/// Submit given call to the transaction pool as unsigned transaction.
+	///
+	/// Returns `Err` if the transaction could not be created or was not
+	/// accepted by the pool.
+	fn submit_unsigned(call: impl Into<Call>) -> Result<(), ()> {
+		let xt = Self::Extrinsic::new_unsigned(call.into()).ok_or(())?;
+		runtime_io::submit_transaction(&xt)
+	}
+}
+
+/// A default type used to submit transactions to the pool.
+///
+/// `Signer` is the type signing the transactions, `CreateTransaction` the runtime-specific
+/// type producing the signed data and `Extrinsic` the unchecked extrinsic type.
+pub struct TransactionSubmitter<Signer, CreateTransaction, Extrinsic> {
+	_signer: PhantomData<(Signer, CreateTransaction, Extrinsic)>,
+}
+
+impl<S, C, E> Default for TransactionSubmitter<S, C, E> {
+	fn default() -> Self {
+		Self { _signer: Default::default() }
+	}
+}
+
+impl<T, E, S, C, Call> SubmitSignedTransaction<T, Call> for TransactionSubmitter<S, C, E> where
+	T: crate::Trait,
+	C: CreateTransaction<T, E>,
+	S: Signer<<C as CreateTransaction<T, E>>::Public, <C as CreateTransaction<T, E>>::Signature>,
+	E: ExtrinsicT<Call=Call> + Encode,
+{
+	type Extrinsic = E;
+	type CreateTransaction = C;
+	type Signer = S;
+}
+
+impl<T, E, S, C, Call> SubmitUnsignedTransaction<T, Call> for TransactionSubmitter<S, C, E> where
+	T: crate::Trait,
+	E: ExtrinsicT<Call=Call> + Encode,
+{
+	type Extrinsic = E;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Decode;
+	use primitives::{Pair, sr25519, crypto::key_types, testing::KeyStore, traits::BareCryptoStore};
+	use runtime_io::{with_externalities, Blake2Hasher, TestExternalities};
+	use sr_primitives::{app_crypto, testing::TestXt};
+	use primitives::offchain::testing::{State, TestOffchainExt};
+	use crate::{AccountNonce, GenesisConfig, tests::Test};
+
+	type Extrinsic = TestXt<(), (u64, Vec<u8>)>;
+
+	/// Signs with keys of accounts `1` and `2`, the signature being the key and the message.
+	struct TestSigner;
+
+	impl Signer<u64, (u64, Vec<u8>)> for TestSigner {
+		fn sign(public: u64, message: &[u8]) -> Option<(u64, Vec<u8>)> {
+			if public == 1 || public == 2 {
+				Some((public, message.to_vec()))
+			} else {
+				None
+			}
+		}
+	}
+
+	/// Signs the call with the nonce, transactions are sent from the account of the key.
+	struct TestCreateTransaction;
+
+	impl CreateTransaction<Test, Extrinsic> for TestCreateTransaction {
+		type Public = u64;
+		type Signature = (u64, Vec<u8>);
+
+		fn create_transaction<F: Signer<u64, Self::Signature>>(
+			call: (),
+			public: u64,
+			account: u64,
+			nonce: u64,
+		) -> Option<((), (u64, (u64, Vec<u8>)))> {
+			let signature = F::sign(public, &(call, nonce).encode())?;
+			Some((call, (account, signature)))
+		}
+	}
+
+	type SubmitTransaction = TransactionSubmitter<TestSigner, TestCreateTransaction, Extrinsic>;
+
+	/// `TestXt` refuses to be created unsigned, this wraps it to accept unsigned transactions.
+	#[derive(Encode)]
+	struct UnsignedExtrinsic(Extrinsic);
+
+	impl ExtrinsicT for UnsignedExtrinsic {
+		type Call = ();
+		type SignaturePayload = (u64, (u64, Vec<u8>));
+
+		fn is_signed(&self) -> Option<bool> {
+			self.0.is_signed()
+		}
+
+		fn new_unsigned(call: ()) -> Option<Self> {
+			Some(UnsignedExtrinsic(TestXt::unsigned(call)))
+		}
+	}
+
+	type SubmitUnsigned = TransactionSubmitter<TestSigner, TestCreateTransaction, UnsignedExtrinsic>;
+
+	fn new_test_ext(offchain: TestOffchainExt) -> TestExternalities<Blake2Hasher> {
+		let mut ext: TestExternalities<_> = GenesisConfig::default().build_storage::<Test>().unwrap().into();
+		ext.set_offchain_externalities(offchain);
+		ext
+	}
+
+	fn submitted(state: &State) -> Vec<Extrinsic> {
+		state.transactions.iter()
+			.map(|tx| Extrinsic::decode(&mut &tx[..]).expect("Submitted transactions are valid extrinsics"))
+			.collect()
+	}
+
+	#[test]
+	fn should_sign_and_submit_with_account_nonce() {
+		let (offchain, state) = TestOffchainExt::new();
+		let mut ext = new_test_ext(offchain);
+
+		with_externalities(&mut ext, || {
+			<AccountNonce<Test>>::insert(1, 5);
+
+			assert_eq!(
+				<SubmitTransaction as SubmitSignedTransaction<Test, ()>>::sign_and_submit((), 1),
+				Ok(()),
+			);
+		});
+
+		assert_eq!(
+			submitted(&state.read()),
+			vec![TestXt(Some((1, (1, ((), 5u64).encode()))), ())],
+		);
+	}
+
+	#[test]
+	fn should_not_submit_if_signing_fails() {
+		let (offchain, state) = TestOffchainExt::new();
+		let mut ext = new_test_ext(offchain);
+
+		with_externalities(&mut ext, || {
+			assert_eq!(
+				<SubmitTransaction as SubmitSignedTransaction<Test, ()>>::sign_and_submit((), 3),
+				Err(()),
+			);
+		});
+
+		assert!(state.read().transactions.is_empty());
+	}
+
+	#[test]
+	fn should_submit_unsigned() {
+		let (offchain, state) = TestOffchainExt::new();
+		let mut ext = new_test_ext(offchain);
+
+		with_externalities(&mut ext, || {
+			assert_eq!(
+				<SubmitUnsigned as SubmitUnsignedTransaction<Test, ()>>::submit_unsigned(()),
+				Ok(()),
+			);
+			assert_eq!(
+				<SubmitTransaction as SubmitUnsignedTransaction<Test, ()>>::submit_unsigned(()),
+				Err(()),
+			);
+		});
+
+		assert_eq!(submitted(&state.read()), vec![TestXt::unsigned(())]);
+	}
+
+	#[test]
+	fn should_sign_with_application_keys_from_keystore() {
+		type AppSigner = app_crypto::sr25519::AppPublic;
+
+		let keystore = KeyStore::new();
+		let alice = keystore.write().sr25519_generate_new(key_types::SR25519, Some("//Alice")).unwrap();
+		let bob = sr25519::Pair::from_string("//Bob", None).unwrap().public();
+		let mut ext = TestExternalities::<Blake2Hasher>::default();
+		ext.set_keystore(keystore);
+
+		with_externalities(&mut ext, || {
+			let signature: sr25519::Signature = <AppSigner as Signer<_, _>>::sign(alice.clone(), b"message")
+				.expect("Alice's key is in the keystore");
+			assert!(sr25519::Pair::verify(&signature, b"message", &alice));
+
+			assert!(<AppSigner as Signer<_, sr25519::Signature>>::sign(bob, b"message").is_none());
+		});
+	}
+}